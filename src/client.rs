@@ -0,0 +1,411 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use tokio::sync::{mpsc, Notify};
+
+use crate::config::{OutputBufferLimit, OutputBufferLimits};
+
+// A connected RESP client, shared between its connection task and the registry
+pub struct Client {
+    pub id: u64,
+    pub addr: String,
+    pub laddr: String,
+    pub user: String,
+    pub created: Instant,
+    state: Mutex<ClientState>,
+    killed: AtomicBool,
+    kill_notify: Notify,
+    // Set when a client kills itself, so its reply still goes out first
+    close_after_reply: AtomicBool,
+    // Replies waiting for the connection's writer task, i.e. the output buffer
+    output: Mutex<Option<mpsc::UnboundedSender<Vec<u8>>>>,
+    output_bytes: AtomicUsize,
+    output_limits: OutputBufferLimits,
+    output_limit_exceeded: AtomicBool,
+}
+
+struct ClientState {
+    name: String,
+    last_interaction: Instant,
+    last_cmd: String,
+    reply: ReplyMode,
+    no_evict: bool,
+    class: ClientClass,
+    soft_limit_since: Option<Instant>,
+    // Port a replica announced with REPLCONF listening-port
+    listening_port: Option<u16>,
+    // Set by ASKING, lets the next command into a slot being imported
+    asking: bool,
+    // RESP version, 2 until HELLO 3
+    protocol: u8,
+    // Streams every command after MONITOR
+    monitor: bool,
+}
+
+// Output buffer limit class, see client-output-buffer-limit
+#[derive(Clone, Copy, PartialEq)]
+pub enum ClientClass {
+    Normal,
+    Replica,
+    Pubsub,
+    // Our link to the master when this server is a replica
+    Master,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ReplyMode {
+    On,
+    Off,
+    // Suppresses the reply of the next command only
+    Skip,
+    // The SKIP itself has been answered (silently), the next reply is dropped
+    SkipNext,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PauseMode {
+    All,
+    Write,
+}
+
+impl Client {
+    pub fn name(&self) -> String {
+        self.state.lock().unwrap().name.clone()
+    }
+
+    pub fn set_name(&self, name: String) {
+        self.state.lock().unwrap().name = name;
+    }
+
+    // Called before each command is executed
+    pub fn touch(&self, cmd: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.last_interaction = Instant::now();
+        state.last_cmd = cmd.to_lowercase();
+    }
+
+    pub fn set_reply_mode(&self, mode: ReplyMode) {
+        self.state.lock().unwrap().reply = mode;
+    }
+
+    // Decides whether the reply to the command just executed is sent,
+    // consuming a pending SKIP
+    pub fn should_reply(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        match state.reply {
+            ReplyMode::On => true,
+            ReplyMode::Off => false,
+            ReplyMode::Skip => {
+                state.reply = ReplyMode::SkipNext;
+                false
+            }
+            ReplyMode::SkipNext => {
+                state.reply = ReplyMode::On;
+                false
+            }
+        }
+    }
+
+    pub fn set_no_evict(&self, no_evict: bool) {
+        self.state.lock().unwrap().no_evict = no_evict;
+    }
+
+    pub fn class(&self) -> ClientClass {
+        self.state.lock().unwrap().class
+    }
+
+    pub fn set_class(&self, class: ClientClass) {
+        self.state.lock().unwrap().class = class;
+    }
+
+    pub fn listening_port(&self) -> Option<u16> {
+        self.state.lock().unwrap().listening_port
+    }
+
+    pub fn set_listening_port(&self, port: u16) {
+        self.state.lock().unwrap().listening_port = Some(port);
+    }
+
+    pub fn set_asking(&self) {
+        self.state.lock().unwrap().asking = true;
+    }
+
+    // ASKING only applies to the command right after it
+    pub fn take_asking(&self) -> bool {
+        std::mem::take(&mut self.state.lock().unwrap().asking)
+    }
+
+    pub fn is_monitor(&self) -> bool {
+        self.state.lock().unwrap().monitor
+    }
+
+    pub fn protocol(&self) -> u8 {
+        self.state.lock().unwrap().protocol
+    }
+
+    pub fn set_protocol(&self, protocol: u8) {
+        self.state.lock().unwrap().protocol = protocol;
+    }
+
+    // Queues a reply for the writer task. A client that goes over its output buffer
+    // limits is killed and the reply is dropped.
+    pub fn send(&self, reply: impl Into<Vec<u8>>) -> bool {
+        let reply = reply.into();
+        let output = self.output.lock().unwrap();
+        let sender = match output.as_ref() {
+            Some(sender) if !self.is_killed() => sender,
+            _ => return false,
+        };
+
+        let size = self.output_bytes.fetch_add(reply.len(), Ordering::SeqCst) + reply.len();
+        if self.over_output_limit(size) {
+            drop(output);
+            self.output_limit_exceeded.store(true, Ordering::SeqCst);
+            self.kill();
+            return false;
+        }
+
+        sender.send(reply).is_ok()
+    }
+
+    fn over_output_limit(&self, size: usize) -> bool {
+        let mut state = self.state.lock().unwrap();
+        // Monitors take the replica limits, like in Redis
+        let limit: OutputBufferLimit = match state.class {
+            _ if state.monitor => self.output_limits.replica,
+            ClientClass::Normal | ClientClass::Master => self.output_limits.normal,
+            ClientClass::Replica => self.output_limits.replica,
+            ClientClass::Pubsub => self.output_limits.pubsub,
+        };
+
+        if limit.hard > 0 && size >= limit.hard {
+            return true;
+        }
+        if limit.soft > 0 && size >= limit.soft {
+            // The soft limit only applies once it has been exceeded for soft_seconds in a row
+            let since = *state.soft_limit_since.get_or_insert_with(Instant::now);
+            return since.elapsed() >= Duration::from_secs(limit.soft_seconds);
+        }
+        state.soft_limit_since = None;
+        false
+    }
+
+    // Called by the writer task once a reply has been written to the socket
+    pub fn output_written(&self, bytes: usize) {
+        self.output_bytes.fetch_sub(bytes, Ordering::SeqCst);
+    }
+
+    // Lets the writer task finish once the queued replies are written
+    pub fn close_output(&self) {
+        self.output.lock().unwrap().take();
+    }
+
+    pub fn output_limit_exceeded(&self) -> bool {
+        self.output_limit_exceeded.load(Ordering::SeqCst)
+    }
+
+    pub fn kill(&self) {
+        self.killed.store(true, Ordering::SeqCst);
+        self.kill_notify.notify_waiters();
+    }
+
+    pub fn is_killed(&self) -> bool {
+        self.killed.load(Ordering::SeqCst)
+    }
+
+    // Closes the connection once the current command has replied
+    pub fn close_after_reply(&self) {
+        self.close_after_reply.store(true, Ordering::SeqCst);
+    }
+
+    pub fn closes_after_reply(&self) -> bool {
+        self.close_after_reply.load(Ordering::SeqCst)
+    }
+
+    // Resolves once the client has been killed with CLIENT KILL
+    pub async fn killed(&self) {
+        let notified = self.kill_notify.notified();
+        if self.is_killed() {
+            return;
+        }
+        notified.await;
+    }
+
+    // One line of CLIENT LIST / CLIENT INFO output
+    pub fn info_line(&self) -> String {
+        let state = self.state.lock().unwrap();
+        let mut flags = match state.class {
+            _ if state.monitor => String::from("O"),
+            ClientClass::Normal => String::from("N"),
+            ClientClass::Pubsub => String::from("P"),
+            ClientClass::Replica => String::from("S"),
+            ClientClass::Master => String::from("M"),
+        };
+        if state.no_evict {
+            flags.push('e');
+        }
+
+        format!(
+            "id={} addr={} laddr={} name={} age={} idle={} flags={} db=0 omem={} cmd={} user={} resp={}",
+            self.id,
+            self.addr,
+            self.laddr,
+            state.name,
+            self.created.elapsed().as_secs(),
+            state.last_interaction.elapsed().as_secs(),
+            flags,
+            self.output_bytes.load(Ordering::SeqCst),
+            if state.last_cmd.is_empty() { "NULL" } else { &state.last_cmd },
+            self.user,
+            state.protocol,
+        )
+    }
+}
+
+// All live connections, keyed by client id
+pub struct ClientRegistry {
+    clients: RwLock<HashMap<u64, Arc<Client>>>,
+    next_id: AtomicU64,
+    // Clients that ran MONITOR
+    monitors: RwLock<HashMap<u64, Arc<Client>>>,
+    pause: Mutex<Option<(Instant, PauseMode)>>,
+    unpause_notify: Notify,
+    // Signalled whenever a client goes away, for shutdown to wait on
+    unregister_notify: Notify,
+}
+
+impl ClientRegistry {
+    pub fn new() -> Self {
+        ClientRegistry {
+            clients: RwLock::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+            monitors: RwLock::new(HashMap::new()),
+            pause: Mutex::new(None),
+            unpause_notify: Notify::new(),
+            unregister_notify: Notify::new(),
+        }
+    }
+
+    // Returns the client and the receiving end of its output buffer
+    pub fn register(
+        &self,
+        addr: String,
+        laddr: String,
+        user: Option<String>,
+        output_limits: OutputBufferLimits,
+    ) -> (Arc<Client>, mpsc::UnboundedReceiver<Vec<u8>>) {
+        let now = Instant::now();
+        let (output, receiver) = mpsc::unbounded_channel();
+        let client = Arc::new(Client {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            addr,
+            laddr,
+            user: user.unwrap_or_else(|| "default".to_string()),
+            created: now,
+            state: Mutex::new(ClientState {
+                name: String::new(),
+                last_interaction: now,
+                last_cmd: String::new(),
+                reply: ReplyMode::On,
+                no_evict: false,
+                class: ClientClass::Normal,
+                soft_limit_since: None,
+                listening_port: None,
+                asking: false,
+                protocol: 2,
+                monitor: false,
+            }),
+            killed: AtomicBool::new(false),
+            kill_notify: Notify::new(),
+            close_after_reply: AtomicBool::new(false),
+            output: Mutex::new(Some(output)),
+            output_bytes: AtomicUsize::new(0),
+            output_limits,
+            output_limit_exceeded: AtomicBool::new(false),
+        });
+
+        self.clients.write().unwrap().insert(client.id, client.clone());
+        (client, receiver)
+    }
+
+    pub fn get(&self, id: u64) -> Option<Arc<Client>> {
+        self.clients.read().unwrap().get(&id).cloned()
+    }
+
+    pub fn unregister(&self, id: u64) {
+        self.clients.write().unwrap().remove(&id);
+        self.monitors.write().unwrap().remove(&id);
+        self.unregister_notify.notify_waiters();
+    }
+
+    // Completes once every client has disconnected
+    pub async fn wait_until_empty(&self) {
+        loop {
+            let notified = self.unregister_notify.notified();
+            if self.clients.read().unwrap().is_empty() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    // MONITOR: from now on the client gets a line for every command
+    pub fn add_monitor(&self, client: &Arc<Client>) {
+        client.state.lock().unwrap().monitor = true;
+        self.monitors.write().unwrap().insert(client.id, client.clone());
+    }
+
+    pub fn has_monitors(&self) -> bool {
+        !self.monitors.read().unwrap().is_empty()
+    }
+
+    pub fn feed_monitors(&self, line: &str) {
+        for monitor in self.monitors.read().unwrap().values() {
+            monitor.send(line.to_string());
+        }
+    }
+
+    // Snapshot of all clients, ordered by id
+    pub fn list(&self) -> Vec<Arc<Client>> {
+        let mut clients: Vec<_> = self.clients.read().unwrap().values().cloned().collect();
+        clients.sort_by_key(|client| client.id);
+        clients
+    }
+
+    pub fn pause(&self, timeout: Duration, mode: PauseMode) {
+        let mut pause = self.pause.lock().unwrap();
+        let deadline = Instant::now() + timeout;
+
+        // A new pause can extend the current one or widen WRITE to ALL, never shrink it
+        *pause = match *pause {
+            Some((end, current)) if end > Instant::now() => {
+                let mode = if current == PauseMode::All { current } else { mode };
+                Some((end.max(deadline), mode))
+            }
+            _ => Some((deadline, mode)),
+        };
+    }
+
+    pub fn unpause(&self) {
+        *self.pause.lock().unwrap() = None;
+        self.unpause_notify.notify_waiters();
+    }
+
+    // Blocks the caller while clients are paused for this kind of command
+    pub async fn wait_if_paused(&self, is_write: bool) {
+        loop {
+            let notified = self.unpause_notify.notified();
+            let deadline = match *self.pause.lock().unwrap() {
+                Some((end, mode)) if end > Instant::now() && (is_write || mode == PauseMode::All) => end,
+                _ => return,
+            };
+
+            tokio::select! {
+                _ = tokio::time::sleep_until(deadline.into()) => {}
+                _ = notified => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use std::io;
+use std::time::Duration;
+
+use tokio::io::{AsyncBufRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+use crate::command::resp;
+use crate::database::Database;
+use crate::server::protocol::{read_line, ConnectionError, INLINE_MAX_SIZE};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const POLL_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Keeps the topology up to date: every second each known node is told about us
+// with CLUSTER MEET and asked for its CLUSTER NODES, which also introduces the
+// nodes it knows. Nodes that don't answer are flagged `fail?` until they do.
+pub async fn run(db: Database) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        let myself = db.cluster().myself();
+        let port = myself.port.to_string();
+
+        for (host, peer_port) in db.cluster().peers() {
+            let poll = async {
+                let stream = TcpStream::connect((host.as_str(), peer_port)).await?;
+                let mut stream = BufReader::new(stream);
+                call(&mut stream, &["CLUSTER", "MEET", &myself.host, &port]).await?;
+                call(&mut stream, &["CLUSTER", "NODES"]).await
+            };
+            let result = tokio::time::timeout(POLL_TIMEOUT, poll)
+                .await
+                .unwrap_or_else(|_| Err(ConnectionError::Io(io::ErrorKind::TimedOut.into())));
+
+            match result {
+                Ok(nodes) => db.cluster().update_from_peer(&host, peer_port, &nodes),
+                Err(e) => {
+                    if db.cluster().mark_unreachable(&host, peer_port) {
+                        println!("Cluster node {}:{} is unreachable: {}", host, peer_port, e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Sends one command and reads its reply: the line of a status or integer reply,
+// or the payload of a bulk reply. Error replies become Protocol errors.
+pub async fn call<S, A>(stream: &mut S, args: &[A]) -> Result<String, ConnectionError>
+where
+    S: AsyncBufRead + AsyncWrite + Unpin,
+    A: AsRef<[u8]>,
+{
+    stream.write_all(&resp::command_bytes(args)).await?;
+    let line = read_line(stream, INLINE_MAX_SIZE)
+        .await?
+        .ok_or_else(|| ConnectionError::Io(io::ErrorKind::UnexpectedEof.into()))?;
+    let line = String::from_utf8(line).map_err(|_| ConnectionError::Protocol("invalid UTF-8 in reply".to_string()))?;
+
+    if let Some(error) = line.strip_prefix('-') {
+        return Err(ConnectionError::Protocol(error.to_string()));
+    }
+    let len = match line.strip_prefix('$') {
+        Some(len) => len,
+        None => return Ok(line),
+    };
+    let len = match len.parse::<i64>() {
+        Ok(len) if len >= 0 => len as usize,
+        Ok(_) => return Ok(String::new()),
+        Err(_) => return Err(ConnectionError::Protocol(format!("bad bulk length '{}'", len))),
+    };
+
+    let mut data = vec![0; len + 2];
+    stream.read_exact(&mut data).await?;
+    data.truncate(len);
+    String::from_utf8(data).map_err(|_| ConnectionError::Protocol("invalid UTF-8 in reply".to_string()))
+}
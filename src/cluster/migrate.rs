@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+use tokio::io::BufReader;
+use tokio::net::TcpStream;
+
+use crate::cluster::bus::call;
+use crate::command::resp;
+use crate::database::Database;
+use crate::scripting::BUSY;
+use crate::server::protocol::{read_command, ConnectionError, RequestLimits};
+
+const DUMP_LIMITS: RequestLimits = RequestLimits {
+    query_buffer: usize::MAX,
+    max_bulk_len: usize::MAX,
+};
+
+// MIGRATE: copies the keys to host:port by replaying the commands that rebuild
+// them, each preceded by ASKING so an importing target accepts them, and then
+// deletes them here unless `copy` is set. Like in Redis other commands wait
+// meanwhile, so no write can land between the dump and the delete and get lost.
+pub async fn migrate(
+    db: &Database,
+    host: &str,
+    port: u16,
+    keys: &[&str],
+    timeout: Duration,
+    copy: bool,
+    replace: bool,
+) -> Result<String, String> {
+    let Some(_exec) = db.scripting().exclusive().await else {
+        return Ok(BUSY.to_string());
+    };
+    let dumps: Vec<(&str, Vec<u8>)> = keys
+        .iter()
+        .filter_map(|key| db.dump_key(key).map(|dump| (*key, dump)))
+        .collect();
+    if dumps.is_empty() {
+        return Ok("+NOKEY\r\n".to_string());
+    }
+
+    let transfer = async {
+        let stream = TcpStream::connect((host, port)).await?;
+        let mut stream = BufReader::new(stream);
+        for (key, dump) in &dumps {
+            if replace {
+                call(&mut stream, &["ASKING"]).await?;
+                call(&mut stream, &["DEL", key]).await?;
+            } else {
+                call(&mut stream, &["ASKING"]).await?;
+                if call(&mut stream, &["EXISTS", key]).await? != ":0" {
+                    return Ok(false);
+                }
+            }
+
+            let mut commands = dump.as_slice();
+            while let Some(args) = read_command(&mut commands, DUMP_LIMITS).await? {
+                call(&mut stream, &["ASKING"]).await?;
+                call(&mut stream, &args).await?;
+            }
+        }
+        Ok::<bool, ConnectionError>(true)
+    };
+
+    match tokio::time::timeout(timeout, transfer).await {
+        Ok(Ok(true)) => {}
+        Ok(Ok(false)) => return Ok("-BUSYKEY Target key name already exists.\r\n".to_string()),
+        Ok(Err(ConnectionError::Protocol(error))) => return Ok(format!("-{}\r\n", error)),
+        Ok(Err(e)) => return Err(format!("IOERR error or timeout connecting to {}:{}: {}", host, port, e)),
+        Err(_) => return Err("IOERR error or timeout reading to target instance".to_string()),
+    }
+
+    if !copy {
+        for (key, _) in &dumps {
+            db.delete(key).await;
+        }
+    }
+    Ok(resp::ok())
+}
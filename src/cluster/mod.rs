@@ -0,0 +1,432 @@
+pub mod bus;
+pub mod migrate;
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::id;
+
+pub const SLOTS: u16 = 16384;
+
+// CRC16/XMODEM, the checksum Redis Cluster maps keys to slots with
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+// Only the part between the first `{` and the next `}` is hashed when it isn't
+// empty, so `{user1}.name` and `{user1}.email` land in the same slot
+pub fn key_slot(key: &str) -> u16 {
+    let bytes = key.as_bytes();
+    let hashed = bytes
+        .iter()
+        .position(|b| *b == b'{')
+        .and_then(|open| {
+            let rest = &bytes[open + 1..];
+            match rest.iter().position(|b| *b == b'}') {
+                Some(len) if len > 0 => Some(&rest[..len]),
+                _ => None,
+            }
+        })
+        .unwrap_or(bytes);
+    crc16(hashed) % SLOTS
+}
+
+// Cluster topology as seen by this node. There is no separate cluster bus: nodes
+// poll each other's CLUSTER NODES over the normal port (see bus.rs) and every node
+// is the authority on which slots it serves itself.
+pub struct Cluster {
+    enabled: bool,
+    state: Mutex<ClusterState>,
+}
+
+struct ClusterState {
+    myself: String,
+    nodes: HashMap<String, Node>,
+    // Owning node id of every slot
+    owners: Vec<Option<String>>,
+    migrating: BTreeMap<u16, String>,
+    importing: BTreeMap<u16, String>,
+    // Addresses given to CLUSTER MEET whose node id isn't known yet
+    meet: Vec<(String, u16)>,
+}
+
+#[derive(Clone)]
+pub struct Node {
+    pub id: String,
+    pub host: String,
+    pub port: u16,
+    // Whether the last poll of this node succeeded; always true for ourselves
+    pub connected: bool,
+    // Unix time in ms of the last successful poll
+    pub last_pong: u64,
+}
+
+// CLUSTER SETSLOT <slot> ...
+pub enum SlotAction {
+    Importing(String),
+    Migrating(String),
+    Stable,
+    Node(String),
+}
+
+impl Cluster {
+    pub fn new(enabled: bool, host: String, port: u16) -> Self {
+        let myself = Node {
+            id: id::random_hex_id(),
+            host,
+            port,
+            connected: true,
+            last_pong: now_ms(),
+        };
+        Cluster {
+            enabled,
+            state: Mutex::new(ClusterState {
+                myself: myself.id.clone(),
+                nodes: HashMap::from([(myself.id.clone(), myself)]),
+                owners: vec![None; SLOTS as usize],
+                migrating: BTreeMap::new(),
+                importing: BTreeMap::new(),
+                meet: Vec::new(),
+            }),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn myself(&self) -> Node {
+        let state = self.state.lock().unwrap();
+        state.nodes[&state.myself].clone()
+    }
+
+    pub fn add_slots(&self, slots: &[u16]) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(slot) = slots.iter().find(|slot| state.owners[**slot as usize].is_some()) {
+            return Err(format!("Slot {} is already busy", slot));
+        }
+        let myself = state.myself.clone();
+        for slot in slots {
+            state.owners[*slot as usize] = Some(myself.clone());
+            state.importing.remove(slot);
+        }
+        Ok(())
+    }
+
+    pub fn del_slots(&self, slots: &[u16]) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(slot) = slots.iter().find(|slot| state.owners[**slot as usize].is_none()) {
+            return Err(format!("Slot {} is already unassigned", slot));
+        }
+        for slot in slots {
+            state.owners[*slot as usize] = None;
+            state.migrating.remove(slot);
+            state.importing.remove(slot);
+        }
+        Ok(())
+    }
+
+    pub fn meet(&self, host: String, port: u16) {
+        let mut state = self.state.lock().unwrap();
+        let known = state.nodes.values().any(|node| node.host == host && node.port == port)
+            || state.meet.iter().any(|(h, p)| *h == host && *p == port);
+        if !known {
+            state.meet.push((host, port));
+        }
+    }
+
+    pub fn set_slot(&self, slot: u16, action: SlotAction) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        let ours = state.owners[slot as usize].as_ref() == Some(&state.myself);
+        let known = |state: &ClusterState, id: &str| {
+            if state.nodes.contains_key(id) {
+                Ok(())
+            } else {
+                Err(format!("I don't know about node {}", id))
+            }
+        };
+
+        match action {
+            SlotAction::Migrating(id) => {
+                if !ours {
+                    return Err(format!("I'm not the owner of hash slot {}", slot));
+                }
+                known(&state, &id)?;
+                state.migrating.insert(slot, id);
+            }
+            SlotAction::Importing(id) => {
+                if ours {
+                    return Err(format!("I'm already the owner of hash slot {}", slot));
+                }
+                known(&state, &id)?;
+                state.importing.insert(slot, id);
+            }
+            SlotAction::Stable => {
+                state.migrating.remove(&slot);
+                state.importing.remove(&slot);
+            }
+            SlotAction::Node(id) => {
+                known(&state, &id)?;
+                state.migrating.remove(&slot);
+                if id == state.myself {
+                    state.importing.remove(&slot);
+                }
+                state.owners[slot as usize] = Some(id);
+            }
+        }
+        Ok(())
+    }
+
+    // Decides whether a command on `keys` runs here. Returns the error reply that
+    // sends the client elsewhere (MOVED/ASK) or refuses it, or None to execute it.
+    pub fn route(&self, keys: &[&str], asking: bool, exists: impl Fn(&str) -> bool) -> Option<String> {
+        let slot = key_slot(keys.first()?);
+        if keys.iter().any(|key| key_slot(key) != slot) {
+            return Some("-CROSSSLOT Keys in request don't hash to the same slot\r\n".to_string());
+        }
+
+        let (migrating_to, moved_to) = {
+            let state = self.state.lock().unwrap();
+            let owner = match &state.owners[slot as usize] {
+                Some(owner) => owner,
+                None => return Some("-CLUSTERDOWN Hash slot not served\r\n".to_string()),
+            };
+            let addr = |id: &str| state.nodes.get(id).map(|n| format!("{}:{}", n.host, n.port));
+
+            if *owner == state.myself {
+                (state.migrating.get(&slot).and_then(|id| addr(id)), None)
+            } else if asking && state.importing.contains_key(&slot) {
+                return None;
+            } else {
+                (None, addr(owner))
+            }
+        };
+
+        if let Some(addr) = moved_to {
+            return Some(format!("-MOVED {} {}\r\n", slot, addr));
+        }
+        // Keys of a migrating slot that are already gone have moved to the target
+        let target = migrating_to?;
+        let missing = keys.iter().filter(|key| !exists(key)).count();
+        match missing {
+            0 => None,
+            n if n == keys.len() => Some(format!("-ASK {} {}\r\n", slot, target)),
+            _ => Some("-TRYAGAIN Multiple keys request during rehashing of slot\r\n".to_string()),
+        }
+    }
+
+    // Contiguous slot ranges and the node serving each, ordered by slot
+    pub fn slot_ranges(&self) -> Vec<(u16, u16, Node)> {
+        let state = self.state.lock().unwrap();
+        let mut ranges: Vec<(u16, u16, Node)> = Vec::new();
+        for (slot, owner) in state.owners.iter().enumerate() {
+            let (slot, node) = match owner.as_ref().and_then(|id| state.nodes.get(id)) {
+                Some(node) => (slot as u16, node),
+                None => continue,
+            };
+            match ranges.last_mut() {
+                Some((_, end, last)) if *end + 1 == slot && last.id == node.id => *end = slot,
+                _ => ranges.push((slot, slot, node.clone())),
+            }
+        }
+        ranges
+    }
+
+    pub fn nodes(&self) -> Vec<Node> {
+        let state = self.state.lock().unwrap();
+        let mut nodes: Vec<Node> = state.nodes.values().cloned().collect();
+        nodes.sort_by(|a, b| a.id.cmp(&b.id));
+        nodes
+    }
+
+    // CLUSTER NODES: one line per node, ours carrying the migration state
+    pub fn nodes_text(&self) -> String {
+        let ranges = self.slot_ranges();
+        let nodes = self.nodes();
+        let state = self.state.lock().unwrap();
+        let mut out = String::new();
+
+        for node in nodes {
+            let myself = node.id == state.myself;
+            let mut flags = if myself { "myself,master".to_string() } else { "master".to_string() };
+            if !node.connected {
+                flags.push_str(",fail?");
+            }
+            out.push_str(&format!(
+                "{} {}:{}@{} {} - 0 {} 0 {}",
+                node.id,
+                node.host,
+                node.port,
+                node.port as u32 + 10000,
+                flags,
+                node.last_pong,
+                if node.connected { "connected" } else { "disconnected" },
+            ));
+
+            for (start, end, owner) in &ranges {
+                if owner.id == node.id {
+                    if start == end {
+                        out.push_str(&format!(" {}", start));
+                    } else {
+                        out.push_str(&format!(" {}-{}", start, end));
+                    }
+                }
+            }
+            if myself {
+                for (slot, id) in &state.migrating {
+                    out.push_str(&format!(" [{}->-{}]", slot, id));
+                }
+                for (slot, id) in &state.importing {
+                    out.push_str(&format!(" [{}-<-{}]", slot, id));
+                }
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    // CLUSTER INFO fields
+    pub fn info(&self) -> Vec<(&'static str, String)> {
+        let ranges = self.slot_ranges();
+        let nodes = self.nodes();
+        let count = |ok: bool| -> usize {
+            ranges
+                .iter()
+                .filter(|(_, _, node)| node.connected == ok)
+                .map(|(start, end, _)| (end - start + 1) as usize)
+                .sum()
+        };
+        let (slots_ok, slots_fail) = (count(true), count(false));
+        let mut masters: Vec<&str> = ranges.iter().map(|(_, _, node)| node.id.as_str()).collect();
+        masters.sort();
+        masters.dedup();
+
+        let cluster_ok = slots_ok == SLOTS as usize;
+        vec![
+            ("cluster_state", if cluster_ok { "ok" } else { "fail" }.to_string()),
+            ("cluster_slots_assigned", (slots_ok + slots_fail).to_string()),
+            ("cluster_slots_ok", slots_ok.to_string()),
+            ("cluster_slots_pfail", "0".to_string()),
+            ("cluster_slots_fail", slots_fail.to_string()),
+            ("cluster_known_nodes", nodes.len().to_string()),
+            ("cluster_size", masters.len().to_string()),
+            ("cluster_current_epoch", "0".to_string()),
+            ("cluster_my_epoch", "0".to_string()),
+        ]
+    }
+
+    // Addresses the bus polls: every other known node and pending MEETs
+    pub fn peers(&self) -> Vec<(String, u16)> {
+        let state = self.state.lock().unwrap();
+        let mut peers: Vec<(String, u16)> = state
+            .nodes
+            .values()
+            .filter(|node| node.id != state.myself)
+            .map(|node| (node.host.clone(), node.port))
+            .collect();
+        peers.extend(state.meet.iter().cloned());
+        peers
+    }
+
+    // Merges the CLUSTER NODES output of the node polled at host:port. Its own
+    // line decides which slots it serves; other lines only introduce new nodes.
+    pub fn update_from_peer(&self, host: &str, port: u16, nodes_text: &str) {
+        let mut state = self.state.lock().unwrap();
+
+        for line in nodes_text.lines() {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let (id, addr, flags) = match parts.as_slice() {
+                [id, addr, flags, ..] => (*id, *addr, *flags),
+                _ => continue,
+            };
+
+            if !flags.split(',').any(|flag| flag == "myself") {
+                let known = state.nodes.contains_key(id);
+                if let (false, Some((node_host, node_port))) = (known, parse_addr(addr)) {
+                    state.nodes.insert(id.to_string(), Node {
+                        id: id.to_string(),
+                        host: node_host,
+                        port: node_port,
+                        connected: false,
+                        last_pong: 0,
+                    });
+                }
+                continue;
+            }
+
+            if id == state.myself {
+                continue;
+            }
+            state.meet.retain(|(h, p)| !(h == host && *p == port));
+            // A node reached at a new address replaces the old entry for that address
+            state.nodes.retain(|other, node| other == id || node.host != host || node.port != port);
+            state.nodes.insert(id.to_string(), Node {
+                id: id.to_string(),
+                host: host.to_string(),
+                port,
+                connected: true,
+                last_pong: now_ms(),
+            });
+
+            let claimed = parse_slots(&parts[8.min(parts.len())..]);
+            let myself = state.myself.clone();
+            for (slot, owner) in state.owners.iter_mut().enumerate() {
+                let claims = claimed[slot];
+                if claims && owner.as_ref() != Some(&myself) {
+                    *owner = Some(id.to_string());
+                } else if !claims && owner.as_deref() == Some(id) {
+                    *owner = None;
+                }
+            }
+        }
+    }
+
+    // Returns true if the node was reachable before
+    pub fn mark_unreachable(&self, host: &str, port: u16) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let mut was_connected = false;
+        for node in state.nodes.values_mut() {
+            if node.host == host && node.port == port {
+                was_connected |= node.connected;
+                node.connected = false;
+            }
+        }
+        was_connected
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+// `host:port@cport` as written in CLUSTER NODES
+fn parse_addr(addr: &str) -> Option<(String, u16)> {
+    let addr = addr.split('@').next()?;
+    let (host, port) = addr.rsplit_once(':')?;
+    Some((host.to_string(), port.parse().ok()?))
+}
+
+// Slot fields of a CLUSTER NODES line (`5`, `0-5460`); migration entries in brackets are skipped
+fn parse_slots(fields: &[&str]) -> Vec<bool> {
+    let mut slots = vec![false; SLOTS as usize];
+    for field in fields.iter().filter(|field| !field.starts_with('[')) {
+        let (start, end) = field.split_once('-').unwrap_or((field, field));
+        if let (Ok(start), Ok(end)) = (start.parse::<u16>(), end.parse::<u16>()) {
+            for slot in start..=end.min(SLOTS - 1) {
+                slots[slot as usize] = true;
+            }
+        }
+    }
+    slots
+}
@@ -0,0 +1,272 @@
+use crate::command::resp;
+use crate::database::bitmap::{self, BitOp, FieldType, Overflow, Unit};
+use crate::database::Database;
+
+const SYNTAX_ERROR: &str = "syntax error";
+const NOT_INTEGER: &str = "value is not an integer or out of range";
+const BAD_OFFSET: &str = "bit offset is not an integer or out of range";
+const BAD_TYPE: &str = "Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is.";
+
+fn wrong_args(command: &str) -> String {
+    format!("wrong number of arguments for '{}' command", command)
+}
+
+fn parse_integer(value: &str) -> Result<i64, String> {
+    value.parse::<i64>().map_err(|_| NOT_INTEGER.to_string())
+}
+
+// A bit offset; BITFIELD also takes #N, meaning N fields of the given width in.
+// Like Redis, offsets stop where the string would outgrow proto-max-bulk-len.
+fn parse_offset(db: &Database, value: &str, field: Option<FieldType>) -> Result<u64, String> {
+    let (number, width) = match (value.strip_prefix('#'), field) {
+        (Some(number), Some(field)) => (number, field.bits as i64),
+        _ => (value, 1),
+    };
+    let offset = number
+        .parse::<i64>()
+        .ok()
+        .and_then(|n| n.checked_mul(width))
+        .filter(|n| *n >= 0)
+        .ok_or(BAD_OFFSET)?;
+    if (offset as u64 >> 3) >= db.config().proto_max_bulk_len as u64 {
+        return Err(BAD_OFFSET.to_string());
+    }
+    Ok(offset as u64)
+}
+
+fn parse_unit(value: &str) -> Result<Unit, String> {
+    match value.to_uppercase().as_str() {
+        "BYTE" => Ok(Unit::Byte),
+        "BIT" => Ok(Unit::Bit),
+        _ => Err(SYNTAX_ERROR.to_string()),
+    }
+}
+
+fn command<'a>(name: &'a str, args: &[&'a str]) -> Vec<&'a str> {
+    let mut command = vec![name];
+    command.extend(args);
+    command
+}
+
+// SETBIT key offset value. Replicated only when the string changes.
+pub fn setbit(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key, offset, value] = args else {
+        return Err(wrong_args("setbit"));
+    };
+    let offset = parse_offset(db, offset, None)?;
+    let bit = match value.parse::<i64>() {
+        Ok(bit @ (0 | 1)) => bit as u8,
+        _ => return Err("bit is not an integer or out of range".to_string()),
+    };
+
+    let old = db.update_string(key, &command("SETBIT", args), |value| {
+        let mut bytes = value.map(<[u8]>::to_vec);
+        let created = bytes.is_none();
+        let bytes_mut = bytes.get_or_insert_with(Vec::new);
+        let len = bytes_mut.len();
+        let old = bitmap::set_bit(bytes_mut, offset, bit);
+        let changed = created || bytes_mut.len() != len || old != bit;
+        Ok((bytes.filter(|_| changed), old))
+    })?;
+    Ok(resp::integer(old as i64))
+}
+
+// GETBIT key offset
+pub async fn getbit(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key, offset] = args else {
+        return Err(wrong_args("getbit"));
+    };
+    let offset = parse_offset(db, offset, None)?;
+    let bit = db.get(key).await.map_or(0, |bytes| bitmap::get_bit(&bytes, offset));
+    Ok(resp::integer(bit as i64))
+}
+
+// BITCOUNT key [start end [BYTE|BIT]]
+pub async fn bitcount(db: &Database, args: &[&str]) -> Result<String, String> {
+    let (key, range) = match args {
+        [key] => (key, None),
+        [key, start, end, unit @ ..] if unit.len() <= 1 => {
+            let unit = unit.first().map_or(Ok(Unit::Byte), |unit| parse_unit(unit))?;
+            (key, Some((parse_integer(start)?, parse_integer(end)?, unit)))
+        }
+        [_, ..] => return Err(SYNTAX_ERROR.to_string()),
+        [] => return Err(wrong_args("bitcount")),
+    };
+
+    let Some(bytes) = db.get(key).await else {
+        return Ok(resp::integer(0));
+    };
+    let (start, end, unit) = range.unwrap_or((0, -1, Unit::Byte));
+    let count = bitmap::bit_range(bytes.len(), start, end, unit).map_or(0, |range| bitmap::count(&bytes, range));
+    Ok(resp::integer(count as i64))
+}
+
+// BITPOS key bit [start [end [BYTE|BIT]]]
+pub async fn bitpos(db: &Database, args: &[&str]) -> Result<String, String> {
+    let (key, bit, options) = match args {
+        [key, bit, options @ ..] if options.len() <= 3 => (key, bit, options),
+        [_, _, ..] => return Err(SYNTAX_ERROR.to_string()),
+        _ => return Err(wrong_args("bitpos")),
+    };
+    let bit = match parse_integer(bit)? {
+        bit @ (0 | 1) => bit as u8,
+        _ => return Err("The bit argument must be 1 or 0.".to_string()),
+    };
+    let start = options.first().map_or(Ok(0), |start| parse_integer(start))?;
+    let end = options.get(1).map_or(Ok(-1), |end| parse_integer(end))?;
+    let unit = options.get(2).map_or(Ok(Unit::Byte), |unit| parse_unit(unit))?;
+    let end_given = options.len() > 1;
+
+    // A missing key is an empty string, which has all the clear bits you want
+    let Some(bytes) = db.get(key).await else {
+        return Ok(resp::integer(if bit == 1 { -1 } else { 0 }));
+    };
+    let position = match bitmap::bit_range(bytes.len(), start, end, unit) {
+        Some(range) => bitmap::position(&bytes, bit, range, end_given),
+        None => -1,
+    };
+    Ok(resp::integer(position))
+}
+
+// BITOP AND|OR|XOR|NOT destkey key [key ...]. An empty result deletes the destination.
+pub async fn bitop(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [op, dest, sources @ ..] = args else {
+        return Err(wrong_args("bitop"));
+    };
+    if sources.is_empty() {
+        return Err(wrong_args("bitop"));
+    }
+    let op = match op.to_uppercase().as_str() {
+        "AND" => BitOp::And,
+        "OR" => BitOp::Or,
+        "XOR" => BitOp::Xor,
+        "NOT" if sources.len() == 1 => BitOp::Not,
+        "NOT" => return Err("BITOP NOT must be called with a single source key.".to_string()),
+        _ => return Err(SYNTAX_ERROR.to_string()),
+    };
+
+    let mut values = Vec::with_capacity(sources.len());
+    for key in sources {
+        values.push(db.get(key).await.unwrap_or_default());
+    }
+    let result = bitmap::bitop(op, &values);
+    let len = result.len();
+    if result.is_empty() {
+        db.delete(dest).await;
+    } else {
+        db.set(dest.to_string(), result, None).await;
+    }
+    Ok(resp::integer(len as i64))
+}
+
+enum FieldOp {
+    Get(FieldType, u64),
+    Set(FieldType, u64, i64, Overflow),
+    IncrBy(FieldType, u64, i64, Overflow),
+}
+
+fn parse_field_ops(db: &Database, args: &[&str], read_only: bool) -> Result<Vec<FieldOp>, String> {
+    let mut ops = Vec::new();
+    let mut overflow = Overflow::Wrap;
+    let mut rest = args;
+    while let [sub, tail @ ..] = rest {
+        let sub = sub.to_uppercase();
+        let arity = match (sub.as_str(), tail.len()) {
+            ("GET", 2..) => 2,
+            ("SET" | "INCRBY", 3..) => 3,
+            ("OVERFLOW", 1..) => {
+                overflow = match tail[0].to_uppercase().as_str() {
+                    "WRAP" => Overflow::Wrap,
+                    "SAT" => Overflow::Sat,
+                    "FAIL" => Overflow::Fail,
+                    _ => return Err("Invalid OVERFLOW type specified".to_string()),
+                };
+                rest = &tail[1..];
+                continue;
+            }
+            _ => return Err(SYNTAX_ERROR.to_string()),
+        };
+
+        let field = FieldType::parse(tail[0]).ok_or(BAD_TYPE)?;
+        let offset = parse_offset(db, tail[1], Some(field))?;
+        let op = match sub.as_str() {
+            "GET" => FieldOp::Get(field, offset),
+            "SET" => FieldOp::Set(field, offset, parse_integer(tail[2])?, overflow),
+            _ => FieldOp::IncrBy(field, offset, parse_integer(tail[2])?, overflow),
+        };
+        if read_only && !matches!(op, FieldOp::Get(..)) {
+            return Err("BITFIELD_RO only supports the GET subcommand".to_string());
+        }
+        ops.push(op);
+        rest = &tail[arity..];
+    }
+    Ok(ops)
+}
+
+// Runs the operations in order. Returns the replies and whether anything was written.
+fn run_field_ops(bytes: &mut Vec<u8>, ops: &[FieldOp]) -> (Vec<String>, bool) {
+    let mut replies = Vec::with_capacity(ops.len());
+    let mut changed = false;
+    for op in ops {
+        let reply = match *op {
+            FieldOp::Get(field, offset) => Some(bitmap::get_field(bytes, offset, field)),
+            FieldOp::Set(field, offset, value, overflow) => {
+                let old = bitmap::get_field(bytes, offset, field);
+                // Unsigned fields take the value as an unsigned 64 bit number, like Redis
+                let value = if field.signed { value as i128 } else { value as u64 as i128 };
+                field.fit(value, overflow).map(|value| {
+                    bitmap::set_field(bytes, offset, field, value);
+                    changed = true;
+                    old
+                })
+            }
+            FieldOp::IncrBy(field, offset, increment, overflow) => {
+                let old = bitmap::get_field(bytes, offset, field);
+                let old = if field.signed { old as i128 } else { old as u64 as i128 };
+                field.fit(old + increment as i128, overflow).inspect(|&value| {
+                    bitmap::set_field(bytes, offset, field, value);
+                    changed = true;
+                })
+            }
+        };
+        replies.push(reply.map_or_else(resp::null_bulk, resp::integer));
+    }
+    (replies, changed)
+}
+
+// BITFIELD key [GET type offset] [SET type offset value] [INCRBY type offset
+// increment] [OVERFLOW WRAP|SAT|FAIL] ...; BITFIELD_RO only takes GET.
+// Writes are replicated as sent.
+pub async fn bitfield(db: &Database, args: &[&str], read_only: bool) -> Result<String, String> {
+    let name = if read_only { "BITFIELD_RO" } else { "BITFIELD" };
+    let [key, ops @ ..] = args else {
+        return Err(wrong_args(&name.to_lowercase()));
+    };
+    let ops = parse_field_ops(db, ops, read_only)?;
+
+    if ops.iter().all(|op| matches!(op, FieldOp::Get(..))) {
+        let mut bytes = db.get(key).await.unwrap_or_default();
+        let (replies, _) = run_field_ops(&mut bytes, &ops);
+        return Ok(resp::array(&replies));
+    }
+
+    // Like Redis, the string grows to fit every write up front, even ones that fail
+    let write_end = ops
+        .iter()
+        .filter_map(|op| match *op {
+            FieldOp::Set(field, offset, ..) | FieldOp::IncrBy(field, offset, ..) => Some(offset + field.bits as u64),
+            FieldOp::Get(..) => None,
+        })
+        .max()
+        .unwrap_or(0);
+    let replies = db.update_string(key, &command(name, args), |value| {
+        let created = value.is_none();
+        let mut bytes = value.map(<[u8]>::to_vec).unwrap_or_default();
+        let len = bytes.len();
+        bytes.resize(len.max(write_end.div_ceil(8) as usize), 0);
+        let (replies, changed) = run_field_ops(&mut bytes, &ops);
+        let changed = created || changed || bytes.len() != len;
+        Ok((changed.then_some(bytes), replies))
+    })?;
+    Ok(resp::array(&replies))
+}
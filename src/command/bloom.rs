@@ -0,0 +1,369 @@
+use crate::command::resp;
+use crate::database::bloom::{self, ScalableBloom};
+use crate::database::cuckoo::{self, CuckooFilter};
+use crate::database::sketch::{Sketch, FILTER_TOO_LARGE, WRONG_TYPE};
+use crate::database::Database;
+
+const SYNTAX_ERROR: &str = "syntax error";
+const ITEM_EXISTS: &str = "item exists";
+const NOT_FOUND: &str = "not found";
+
+fn wrong_args(command: &str) -> String {
+    format!("wrong number of arguments for '{}' command", command)
+}
+
+// WRONGTYPE goes out as is, the rest get -ERR from the connection
+fn prefixed(result: Result<String, String>) -> Result<String, String> {
+    match result {
+        Err(e) if e.starts_with("WRONGTYPE ") => Ok(format!("-{}\r\n", e)),
+        other => other,
+    }
+}
+
+// An error in place of one item's reply
+fn item_error(error: &str) -> String {
+    format!("-ERR {}\r\n", error)
+}
+
+fn command<'a>(name: &'a str, args: &[&'a str]) -> Vec<&'a str> {
+    let mut command = vec![name];
+    command.extend(args);
+    command
+}
+
+fn parse_count(text: &str) -> Result<u64, String> {
+    text.parse::<u64>().map_err(|_| "value is not an integer or out of range".to_string())
+}
+
+fn bloom(sketch: &Sketch) -> Result<&ScalableBloom, String> {
+    match sketch {
+        Sketch::Bloom(bloom) => Ok(bloom),
+        _ => Err(WRONG_TYPE.to_string()),
+    }
+}
+
+// The key's filter, created with the defaults if it doesn't exist
+fn bloom_or_default(sketch: &mut Option<Sketch>) -> Result<&mut ScalableBloom, String> {
+    if sketch.is_none() {
+        let bloom = ScalableBloom::new(bloom::DEFAULT_ERROR_RATE, bloom::DEFAULT_CAPACITY, bloom::DEFAULT_EXPANSION);
+        *sketch = Some(Sketch::Bloom(bloom.ok_or(FILTER_TOO_LARGE)?));
+    }
+    let sketch = sketch.as_mut().unwrap();
+    match sketch {
+        Sketch::Bloom(bloom) => Ok(bloom),
+        _ => Err(WRONG_TYPE.to_string()),
+    }
+}
+
+fn cuckoo(sketch: &Sketch) -> Result<&CuckooFilter, String> {
+    match sketch {
+        Sketch::Cuckoo(cuckoo) => Ok(cuckoo),
+        _ => Err(WRONG_TYPE.to_string()),
+    }
+}
+
+fn cuckoo_mut(sketch: &mut Sketch) -> Result<&mut CuckooFilter, String> {
+    match sketch {
+        Sketch::Cuckoo(cuckoo) => Ok(cuckoo),
+        _ => Err(WRONG_TYPE.to_string()),
+    }
+}
+
+fn cuckoo_or_default(sketch: &mut Option<Sketch>) -> Result<&mut CuckooFilter, String> {
+    if sketch.is_none() {
+        let cuckoo = CuckooFilter::new(
+            cuckoo::DEFAULT_CAPACITY,
+            cuckoo::DEFAULT_BUCKET_SIZE,
+            cuckoo::DEFAULT_MAX_ITERATIONS,
+            cuckoo::DEFAULT_EXPANSION,
+        );
+        *sketch = Some(Sketch::Cuckoo(cuckoo.ok_or(FILTER_TOO_LARGE)?));
+    }
+    cuckoo_mut(sketch.as_mut().unwrap())
+}
+
+// BF.RESERVE key error_rate capacity [EXPANSION expansion] [NONSCALING]
+pub fn bf_reserve(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key, error_rate, capacity, options @ ..] = args else {
+        return Err(wrong_args("bf.reserve"));
+    };
+    let error_rate = error_rate
+        .parse::<f64>()
+        .ok()
+        .filter(|rate| *rate > 0.0 && *rate < 1.0)
+        .ok_or("(0 < error rate range < 1)")?;
+    let capacity = capacity
+        .parse::<u64>()
+        .ok()
+        .filter(|capacity| *capacity > 0)
+        .ok_or("(capacity should be larger than 0)")?;
+    let mut expansion = bloom::DEFAULT_EXPANSION;
+    let mut non_scaling = false;
+    let mut options = options;
+    loop {
+        match options {
+            [option, value, tail @ ..] if option.eq_ignore_ascii_case("EXPANSION") => {
+                expansion = parse_count(value)?;
+                if expansion == 0 {
+                    return Err("expansion should be greater or equal to 1".to_string());
+                }
+                options = tail;
+            }
+            [option, tail @ ..] if option.eq_ignore_ascii_case("NONSCALING") => {
+                non_scaling = true;
+                options = tail;
+            }
+            [] => break,
+            _ => return Err(SYNTAX_ERROR.to_string()),
+        }
+    }
+    let expansion = if non_scaling { 0 } else { expansion };
+    let bloom = ScalableBloom::new(error_rate, capacity, expansion).ok_or(FILTER_TOO_LARGE)?;
+
+    db.sketch_write(key, &command("BF.RESERVE", args), |sketch| {
+        if sketch.is_some() {
+            return Err(ITEM_EXISTS.to_string());
+        }
+        *sketch = Some(Sketch::Bloom(bloom));
+        Ok((resp::ok(), true))
+    })
+}
+
+// BF.ADD key item
+pub fn bf_add(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key, item] = args else {
+        return Err(wrong_args("bf.add"));
+    };
+    prefixed(db.sketch_write(key, &command("BF.ADD", args), |sketch| {
+        let added = bloom_or_default(sketch)?.add(item)?;
+        Ok((resp::integer(added as i64), added))
+    }))
+}
+
+// BF.MADD key item [item ...]. A full NONSCALING filter fails the items that
+// don't fit, each with its own error.
+pub fn bf_madd(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key, items @ ..] = args else {
+        return Err(wrong_args("bf.madd"));
+    };
+    if items.is_empty() {
+        return Err(wrong_args("bf.madd"));
+    }
+    prefixed(db.sketch_write(key, &command("BF.MADD", args), |sketch| {
+        let bloom = bloom_or_default(sketch)?;
+        let mut changed = false;
+        let replies: Vec<String> = items
+            .iter()
+            .map(|item| match bloom.add(item) {
+                Ok(added) => {
+                    changed |= added;
+                    resp::integer(added as i64)
+                }
+                Err(e) => item_error(&e),
+            })
+            .collect();
+        Ok((resp::array(&replies), changed))
+    }))
+}
+
+// BF.EXISTS key item
+pub fn bf_exists(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key, item] = args else {
+        return Err(wrong_args("bf.exists"));
+    };
+    let found = db.sketch_read(key, |sketch| bloom(sketch).map(|bloom| bloom.contains(item)))?;
+    prefixed(found.unwrap_or(Ok(false)).map(|found| resp::integer(found as i64)))
+}
+
+// BF.MEXISTS key item [item ...]
+pub fn bf_mexists(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key, items @ ..] = args else {
+        return Err(wrong_args("bf.mexists"));
+    };
+    if items.is_empty() {
+        return Err(wrong_args("bf.mexists"));
+    }
+    let found = db.sketch_read(key, |sketch| {
+        bloom(sketch).map(|bloom| items.iter().map(|item| bloom.contains(item)).collect::<Vec<bool>>())
+    })?;
+    prefixed(found.unwrap_or_else(|| Ok(vec![false; items.len()])).map(|found| {
+        let replies: Vec<String> = found.into_iter().map(|found| resp::integer(found as i64)).collect();
+        resp::array(&replies)
+    }))
+}
+
+// BF.INFO key [CAPACITY | SIZE | FILTERS | ITEMS | EXPANSION]
+pub fn bf_info(db: &Database, args: &[&str]) -> Result<String, String> {
+    let (key, field) = match args {
+        [key] => (key, None),
+        [key, field] => (key, Some(field.to_uppercase())),
+        _ => return Err(wrong_args("bf.info")),
+    };
+    let info = db
+        .sketch_read(key, |sketch| {
+            let bloom = bloom(sketch)?;
+            let expansion = match bloom.expansion() {
+                0 => resp::null_bulk(),
+                expansion => resp::integer(expansion as i64),
+            };
+            let info = [
+                ("CAPACITY", "Capacity", resp::integer(bloom.capacity() as i64)),
+                ("SIZE", "Size", resp::integer(bloom.bytes_len() as i64)),
+                ("FILTERS", "Number of filters", resp::integer(bloom.filters() as i64)),
+                ("ITEMS", "Number of items inserted", resp::integer(bloom.count() as i64)),
+                ("EXPANSION", "Expansion rate", expansion),
+            ];
+            match &field {
+                None => {
+                    let replies: Vec<String> =
+                        info.into_iter().flat_map(|(_, name, value)| [resp::bulk(name), value]).collect();
+                    Ok(resp::array(&replies))
+                }
+                Some(field) => {
+                    let (_, _, value) =
+                        info.into_iter().find(|(option, _, _)| option == field).ok_or(SYNTAX_ERROR)?;
+                    Ok(resp::array(&[value]))
+                }
+            }
+        })?
+        .ok_or(NOT_FOUND)?;
+    prefixed(info)
+}
+
+// CF.RESERVE key capacity [BUCKETSIZE size] [MAXITERATIONS iterations]
+// [EXPANSION expansion]
+pub fn cf_reserve(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key, capacity, options @ ..] = args else {
+        return Err(wrong_args("cf.reserve"));
+    };
+    let capacity = capacity
+        .parse::<u64>()
+        .ok()
+        .filter(|capacity| *capacity > 0)
+        .ok_or("(capacity should be larger than 0)")?;
+    let mut bucket_size = cuckoo::DEFAULT_BUCKET_SIZE;
+    let mut max_iterations = cuckoo::DEFAULT_MAX_ITERATIONS;
+    let mut expansion = cuckoo::DEFAULT_EXPANSION;
+    let mut options = options;
+    while let [option, value, tail @ ..] = options {
+        let value = parse_count(value)?;
+        match option.to_uppercase().as_str() {
+            "BUCKETSIZE" if (1..=255).contains(&value) => bucket_size = value as usize,
+            "BUCKETSIZE" => return Err("Bucket size must be between 1 and 255".to_string()),
+            "MAXITERATIONS" if (1..=65535).contains(&value) => max_iterations = value as usize,
+            "MAXITERATIONS" => return Err("Max iterations must be between 1 and 65535".to_string()),
+            "EXPANSION" if value <= 32768 => expansion = value,
+            "EXPANSION" => return Err("Expansion must be between 0 and 32768".to_string()),
+            _ => return Err(SYNTAX_ERROR.to_string()),
+        }
+        options = tail;
+    }
+    if !options.is_empty() {
+        return Err(SYNTAX_ERROR.to_string());
+    }
+
+    let cuckoo = CuckooFilter::new(capacity, bucket_size, max_iterations, expansion).ok_or(FILTER_TOO_LARGE)?;
+
+    db.sketch_write(key, &command("CF.RESERVE", args), |sketch| {
+        if sketch.is_some() {
+            return Err(ITEM_EXISTS.to_string());
+        }
+        *sketch = Some(Sketch::Cuckoo(cuckoo));
+        Ok((resp::ok(), true))
+    })
+}
+
+// CF.ADD key item adds the item even if it's there already, CF.ADDNX only if
+// it isn't
+pub fn cf_add(db: &Database, args: &[&str], nx: bool) -> Result<String, String> {
+    let (name, replicated) = if nx { ("cf.addnx", "CF.ADDNX") } else { ("cf.add", "CF.ADD") };
+    let [key, item] = args else {
+        return Err(wrong_args(name));
+    };
+    prefixed(db.sketch_write(key, &command(replicated, args), |sketch| {
+        let cuckoo = cuckoo_or_default(sketch)?;
+        if nx && cuckoo.contains(item) {
+            return Ok((resp::integer(0), false));
+        }
+        cuckoo.add(item)?;
+        Ok((resp::integer(1), true))
+    }))
+}
+
+// CF.DEL key item removes one occurrence of the item
+pub fn cf_del(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key, item] = args else {
+        return Err(wrong_args("cf.del"));
+    };
+    prefixed(db.sketch_write(key, &command("CF.DEL", args), |sketch| {
+        let cuckoo = cuckoo_mut(sketch.as_mut().ok_or("Not found")?)?;
+        let deleted = cuckoo.delete(item);
+        Ok((resp::integer(deleted as i64), deleted))
+    }))
+}
+
+// CF.EXISTS key item
+pub fn cf_exists(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key, item] = args else {
+        return Err(wrong_args("cf.exists"));
+    };
+    let found = db.sketch_read(key, |sketch| cuckoo(sketch).map(|cuckoo| cuckoo.contains(item)))?;
+    prefixed(found.unwrap_or(Ok(false)).map(|found| resp::integer(found as i64)))
+}
+
+// CF.MEXISTS key item [item ...]
+pub fn cf_mexists(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key, items @ ..] = args else {
+        return Err(wrong_args("cf.mexists"));
+    };
+    if items.is_empty() {
+        return Err(wrong_args("cf.mexists"));
+    }
+    let found = db.sketch_read(key, |sketch| {
+        cuckoo(sketch).map(|cuckoo| items.iter().map(|item| cuckoo.contains(item)).collect::<Vec<bool>>())
+    })?;
+    prefixed(found.unwrap_or_else(|| Ok(vec![false; items.len()])).map(|found| {
+        let replies: Vec<String> = found.into_iter().map(|found| resp::integer(found as i64)).collect();
+        resp::array(&replies)
+    }))
+}
+
+// CF.COUNT key item: how many times the item may have been added
+pub fn cf_count(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key, item] = args else {
+        return Err(wrong_args("cf.count"));
+    };
+    let count = db.sketch_read(key, |sketch| cuckoo(sketch).map(|cuckoo| cuckoo.count(item)))?;
+    prefixed(count.unwrap_or(Ok(0)).map(|count| resp::integer(count as i64)))
+}
+
+// CF.INFO key
+pub fn cf_info(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key] = args else {
+        return Err(wrong_args("cf.info"));
+    };
+    let info = db
+        .sketch_read(key, |sketch| {
+            let cuckoo = cuckoo(sketch)?;
+            Ok(resp::array(&[
+                resp::bulk("Size"),
+                resp::integer(cuckoo.bytes_len() as i64),
+                resp::bulk("Number of buckets"),
+                resp::integer(cuckoo.buckets() as i64),
+                resp::bulk("Number of filters"),
+                resp::integer(cuckoo.filters() as i64),
+                resp::bulk("Number of items inserted"),
+                resp::integer(cuckoo.items() as i64),
+                resp::bulk("Number of items deleted"),
+                resp::integer(cuckoo.deleted() as i64),
+                resp::bulk("Bucket size"),
+                resp::integer(cuckoo.bucket_size() as i64),
+                resp::bulk("Expansion rate"),
+                resp::integer(cuckoo.expansion() as i64),
+                resp::bulk("Max iterations"),
+                resp::integer(cuckoo.max_iterations() as i64),
+            ]))
+        })?
+        .ok_or(NOT_FOUND)?;
+    prefixed(info)
+}
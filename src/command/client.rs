@@ -0,0 +1,291 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::client::{Client, ClientClass, PauseMode, ReplyMode};
+use crate::command::resp;
+use crate::database::Database;
+use crate::tracking::TrackingOptions;
+
+// CLIENT subcommands. `args` excludes the CLIENT keyword itself.
+pub async fn client_command(db: &Database, client: &Arc<Client>, args: &[&str]) -> Result<String, String> {
+    let subcommand = args.first().map(|s| s.to_uppercase()).unwrap_or_default();
+
+    match (subcommand.as_str(), &args[1..]) {
+        ("ID", []) => Ok(resp::integer(client.id as i64)),
+        ("INFO", []) => Ok(resp::bulk(&format!("{}\n", client.info_line()))),
+        ("LIST", filters) => client_list(db, filters),
+        ("GETNAME", []) => {
+            let name = client.name();
+            if name.is_empty() {
+                Ok(resp::null_bulk())
+            } else {
+                Ok(resp::bulk(&name))
+            }
+        }
+        ("SETNAME", [name]) => {
+            if name.chars().any(|c| c.is_whitespace() || c.is_control()) {
+                return Err("Client names cannot contain spaces, newlines or special characters.".to_string());
+            }
+            client.set_name(name.to_string());
+            Ok(resp::ok())
+        }
+        ("KILL", [addr]) => {
+            // Old form: CLIENT KILL ip:port
+            let target = db.clients().list().into_iter().find(|c| c.addr == *addr);
+            match target {
+                Some(target) if target.id == client.id => {
+                    client.close_after_reply();
+                    Ok(resp::ok())
+                }
+                Some(target) => {
+                    target.kill();
+                    Ok(resp::ok())
+                }
+                None => Err("No such client".to_string()),
+            }
+        }
+        ("KILL", filters) if !filters.is_empty() => client_kill(db, client, filters),
+        ("PAUSE", [timeout]) => client_pause(db, timeout, PauseMode::All),
+        ("PAUSE", [timeout, mode]) => match mode.to_uppercase().as_str() {
+            "ALL" => client_pause(db, timeout, PauseMode::All),
+            "WRITE" => client_pause(db, timeout, PauseMode::Write),
+            _ => Err("syntax error".to_string()),
+        },
+        ("UNPAUSE", []) => {
+            db.clients().unpause();
+            Ok(resp::ok())
+        }
+        ("NO-EVICT", [flag]) => {
+            client.set_no_evict(parse_on_off(flag)?);
+            Ok(resp::ok())
+        }
+        ("TRACKING", [mode, options @ ..]) => client_tracking(db, client, mode, options),
+        ("CACHING", [flag]) => {
+            db.tracking().set_caching(client.id, parse_yes_no(flag)?)?;
+            Ok(resp::ok())
+        }
+        ("GETREDIR", []) => Ok(resp::integer(db.tracking().redirect(client.id))),
+        ("TRACKINGINFO", []) => {
+            let info = db.tracking().info(client.id);
+            let flags: Vec<String> = info.flags.iter().map(|flag| resp::bulk(flag)).collect();
+            let prefixes: Vec<String> = info.prefixes.iter().map(|prefix| resp::bulk(prefix)).collect();
+            Ok(resp::array(&[
+                resp::bulk("flags"),
+                resp::array(&flags),
+                resp::bulk("redirect"),
+                resp::integer(info.redirect),
+                resp::bulk("prefixes"),
+                resp::array(&prefixes),
+            ]))
+        }
+        ("REPLY", [mode]) => {
+            // OFF and SKIP replies are dropped by the connection, see Client::should_reply
+            let mode = match mode.to_uppercase().as_str() {
+                "ON" => ReplyMode::On,
+                "OFF" => ReplyMode::Off,
+                "SKIP" => ReplyMode::Skip,
+                _ => return Err("syntax error".to_string()),
+            };
+            client.set_reply_mode(mode);
+            Ok(resp::ok())
+        }
+        _ => Err(format!(
+            "unknown subcommand or wrong number of arguments for 'CLIENT|{}'",
+            subcommand
+        )),
+    }
+}
+
+fn client_list(db: &Database, filters: &[&str]) -> Result<String, String> {
+    let mut clients = db.clients().list();
+
+    match filters {
+        [] => {}
+        [opt, kind] if opt.eq_ignore_ascii_case("TYPE") => {
+            let class = parse_client_type(kind)?;
+            clients.retain(|c| c.class() == class);
+        }
+        [opt, ids @ ..] if opt.eq_ignore_ascii_case("ID") && !ids.is_empty() => {
+            let ids = ids
+                .iter()
+                .map(|id| id.parse::<u64>().map_err(|_| "Invalid client ID".to_string()))
+                .collect::<Result<Vec<_>, _>>()?;
+            clients.retain(|c| ids.contains(&c.id));
+        }
+        _ => return Err("syntax error".to_string()),
+    }
+
+    let mut out = String::new();
+    for c in clients {
+        out.push_str(&c.info_line());
+        out.push('\n');
+    }
+    Ok(resp::bulk(&out))
+}
+
+// New form: CLIENT KILL <filter> <value> ... returning the number of killed clients
+fn client_kill(db: &Database, me: &Arc<Client>, filters: &[&str]) -> Result<String, String> {
+    if !filters.len().is_multiple_of(2) {
+        return Err("syntax error".to_string());
+    }
+
+    let mut clients = db.clients().list();
+    let mut skip_me = true;
+
+    for pair in filters.chunks(2) {
+        let (name, value) = (pair[0].to_uppercase(), pair[1]);
+        match name.as_str() {
+            "ID" => {
+                let id = value.parse::<u64>().map_err(|_| "client-id should be greater than 0")?;
+                clients.retain(|c| c.id == id);
+            }
+            "ADDR" => clients.retain(|c| c.addr == value),
+            "LADDR" => clients.retain(|c| c.laddr == value),
+            "USER" => clients.retain(|c| c.user == value),
+            "TYPE" => {
+                let class = parse_client_type(value)?;
+                clients.retain(|c| c.class() == class);
+            }
+            "SKIPME" => skip_me = parse_yes_no(value)?,
+            "MAXAGE" => {
+                let max_age = value.parse::<u64>().map_err(|_| "syntax error")?;
+                clients.retain(|c| c.created.elapsed().as_secs() >= max_age);
+            }
+            _ => return Err("syntax error".to_string()),
+        }
+    }
+
+    if skip_me {
+        clients.retain(|c| c.id != me.id);
+    }
+    for c in &clients {
+        // Killing ourselves outright would drop this reply
+        if c.id == me.id {
+            me.close_after_reply();
+        } else {
+            c.kill();
+        }
+    }
+    Ok(resp::integer(clients.len() as i64))
+}
+
+// CLIENT TRACKING ON|OFF [REDIRECT id] [PREFIX prefix ...] [BCAST] [OPTIN] [OPTOUT] [NOLOOP]
+fn client_tracking(db: &Database, client: &Arc<Client>, mode: &str, args: &[&str]) -> Result<String, String> {
+    if !parse_on_off(mode)? {
+        db.tracking().disable(client.id);
+        return Ok(resp::ok());
+    }
+
+    let mut options = TrackingOptions::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.to_uppercase().as_str() {
+            "REDIRECT" => {
+                let id = args.next().ok_or("syntax error")?;
+                options.redirect = Some(id.parse::<u64>().map_err(|_| "Invalid client ID")?);
+            }
+            "PREFIX" => options.prefixes.push(args.next().ok_or("syntax error")?.to_string()),
+            "BCAST" => options.bcast = true,
+            "OPTIN" => options.optin = true,
+            "OPTOUT" => options.optout = true,
+            "NOLOOP" => options.noloop = true,
+            _ => return Err("syntax error".to_string()),
+        }
+    }
+    db.tracking().enable(client, options)?;
+    Ok(resp::ok())
+}
+
+// HELLO [protover [AUTH username password] [SETNAME name]] switches the
+// protocol and describes the server
+pub fn hello(db: &Database, client: &Client, args: &[&str]) -> Result<String, String> {
+    let (protocol, mut options) = match args {
+        [] => (client.protocol(), [].iter()),
+        [version, options @ ..] => match version.parse::<u8>() {
+            Ok(version @ (2 | 3)) => (version, options.iter()),
+            Ok(_) => return Ok("-NOPROTO unsupported protocol version\r\n".to_string()),
+            Err(_) => return Err("Protocol version is not an integer or out of range".to_string()),
+        },
+    };
+    let mut name = None;
+    while let Some(option) = options.next() {
+        match option.to_uppercase().as_str() {
+            "AUTH" => {
+                let (Some(user), Some(_password)) = (options.next(), options.next()) else {
+                    return Err("syntax error".to_string());
+                };
+                // Users have no passwords, the connection just has to be the one named
+                if *user != client.user {
+                    return Ok("-WRONGPASS invalid username-password pair or user is disabled.\r\n".to_string());
+                }
+            }
+            "SETNAME" => name = Some(options.next().ok_or("syntax error")?),
+            _ => return Err(format!("Syntax error in HELLO option '{}'", option)),
+        }
+    }
+    if let Some(name) = name {
+        if name.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            return Err("Client names cannot contain spaces, newlines or special characters.".to_string());
+        }
+        client.set_name(name.to_string());
+    }
+    client.set_protocol(protocol);
+
+    let mode = if db.cluster().enabled() { "cluster" } else { "standalone" };
+    let role = if db.replication().is_replica() { "replica" } else { "master" };
+    let fields = [
+        resp::bulk("server"),
+        resp::bulk("redis"),
+        resp::bulk("version"),
+        resp::bulk(env!("CARGO_PKG_VERSION")),
+        resp::bulk("proto"),
+        resp::integer(protocol as i64),
+        resp::bulk("id"),
+        resp::integer(client.id as i64),
+        resp::bulk("mode"),
+        resp::bulk(mode),
+        resp::bulk("role"),
+        resp::bulk(role),
+        resp::bulk("modules"),
+        resp::array(&[]),
+    ];
+    Ok(match protocol {
+        2 => resp::array(&fields),
+        _ => resp::map(&fields),
+    })
+}
+
+fn client_pause(db: &Database, timeout: &str, mode: PauseMode) -> Result<String, String> {
+    let ms = timeout
+        .parse::<u64>()
+        .map_err(|_| "timeout is not an integer or out of range")?;
+    db.clients().pause(Duration::from_millis(ms), mode);
+    Ok(resp::ok())
+}
+
+fn parse_on_off(value: &str) -> Result<bool, String> {
+    match value.to_uppercase().as_str() {
+        "ON" => Ok(true),
+        "OFF" => Ok(false),
+        _ => Err("syntax error".to_string()),
+    }
+}
+
+fn parse_yes_no(value: &str) -> Result<bool, String> {
+    match value.to_uppercase().as_str() {
+        "YES" => Ok(true),
+        "NO" => Ok(false),
+        _ => Err("syntax error".to_string()),
+    }
+}
+
+// Client types accepted by LIST and KILL
+fn parse_client_type(kind: &str) -> Result<ClientClass, String> {
+    match kind.to_lowercase().as_str() {
+        "normal" => Ok(ClientClass::Normal),
+        "replica" | "slave" => Ok(ClientClass::Replica),
+        "pubsub" => Ok(ClientClass::Pubsub),
+        "master" => Ok(ClientClass::Master),
+        _ => Err(format!("Unknown client type '{}'", kind)),
+    }
+}
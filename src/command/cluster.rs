@@ -0,0 +1,224 @@
+use std::time::Duration;
+
+use crate::cluster::{self, key_slot, migrate, SlotAction, SLOTS};
+use crate::command::resp;
+use crate::database::Database;
+
+pub const CLUSTER_DISABLED: &str = "This instance has cluster support disabled";
+
+pub async fn cluster_command(db: &Database, args: &[&str]) -> Result<String, String> {
+    if !db.cluster().enabled() {
+        return Err(CLUSTER_DISABLED.to_string());
+    }
+    let cluster = db.cluster();
+    let subcommand = args.first().map(|s| s.to_uppercase()).unwrap_or_default();
+
+    match (subcommand.as_str(), &args[1..]) {
+        ("INFO", []) => {
+            let text: String = cluster
+                .info()
+                .iter()
+                .map(|(name, value)| format!("{}:{}\r\n", name, value))
+                .collect();
+            Ok(resp::bulk(&text))
+        }
+        ("MYID", []) => Ok(resp::bulk(&cluster.myself().id)),
+        ("NODES", []) => Ok(resp::bulk(&cluster.nodes_text())),
+        ("SLOTS", []) => Ok(slots(db)),
+        ("SHARDS", []) => Ok(shards(db)),
+        ("KEYSLOT", [key]) => Ok(resp::integer(key_slot(key) as i64)),
+        ("COUNTKEYSINSLOT", [slot]) => {
+            let slot = parse_slot(slot)?;
+            let count = db.keys().iter().filter(|key| key_slot(key) == slot).count();
+            Ok(resp::integer(count as i64))
+        }
+        ("GETKEYSINSLOT", [slot, count]) => {
+            let slot = parse_slot(slot)?;
+            let count = count
+                .parse::<usize>()
+                .map_err(|_| "Invalid number of keys")?;
+            let mut keys: Vec<String> = db.keys().into_iter().filter(|key| key_slot(key) == slot).collect();
+            keys.sort();
+            let keys: Vec<String> = keys.iter().take(count).map(|key| resp::bulk(key)).collect();
+            Ok(resp::array(&keys))
+        }
+        ("ADDSLOTS", slots) if !slots.is_empty() => {
+            cluster.add_slots(&parse_slots(slots)?)?;
+            Ok(resp::ok())
+        }
+        ("DELSLOTS", slots) if !slots.is_empty() => {
+            cluster.del_slots(&parse_slots(slots)?)?;
+            Ok(resp::ok())
+        }
+        ("ADDSLOTSRANGE", ranges) if !ranges.is_empty() && ranges.len() % 2 == 0 => {
+            cluster.add_slots(&parse_ranges(ranges)?)?;
+            Ok(resp::ok())
+        }
+        ("DELSLOTSRANGE", ranges) if !ranges.is_empty() && ranges.len() % 2 == 0 => {
+            cluster.del_slots(&parse_ranges(ranges)?)?;
+            Ok(resp::ok())
+        }
+        // The cluster bus port is accepted but unused, nodes talk over the client port
+        ("MEET", [host, port] | [host, port, _]) => {
+            let port = port
+                .parse::<u16>()
+                .map_err(|_| format!("Invalid node address specified: {}:{}", host, port))?;
+            cluster.meet(host.to_string(), port);
+            Ok(resp::ok())
+        }
+        ("SETSLOT", [slot, action, rest @ ..]) => {
+            let slot = parse_slot(slot)?;
+            let action = match (action.to_uppercase().as_str(), rest) {
+                ("IMPORTING", [node]) => SlotAction::Importing(node.to_string()),
+                ("MIGRATING", [node]) => SlotAction::Migrating(node.to_string()),
+                ("STABLE", []) => SlotAction::Stable,
+                ("NODE", [node]) => SlotAction::Node(node.to_string()),
+                _ => return Err("Invalid CLUSTER SETSLOT action or number of arguments".to_string()),
+            };
+            cluster.set_slot(slot, action)?;
+            Ok(resp::ok())
+        }
+        _ => Err(format!(
+            "unknown subcommand or wrong number of arguments for '{}'. Try CLUSTER HELP.",
+            args.first().unwrap_or(&"")
+        )),
+    }
+}
+
+fn parse_slot(slot: &str) -> Result<u16, String> {
+    match slot.parse::<u16>() {
+        Ok(slot) if slot < SLOTS => Ok(slot),
+        _ => Err("Invalid or out of range slot".to_string()),
+    }
+}
+
+fn parse_slots(slots: &[&str]) -> Result<Vec<u16>, String> {
+    let slots = slots.iter().map(|slot| parse_slot(slot)).collect::<Result<Vec<u16>, String>>()?;
+    unique(slots)
+}
+
+fn unique(slots: Vec<u16>) -> Result<Vec<u16>, String> {
+    let mut seen = vec![false; SLOTS as usize];
+    for slot in &slots {
+        if std::mem::replace(&mut seen[*slot as usize], true) {
+            return Err(format!("Slot {} specified multiple times", slot));
+        }
+    }
+    Ok(slots)
+}
+
+fn parse_ranges(ranges: &[&str]) -> Result<Vec<u16>, String> {
+    let mut slots = Vec::new();
+    for range in ranges.chunks(2) {
+        let (start, end) = (parse_slot(range[0])?, parse_slot(range[1])?);
+        if start > end {
+            return Err(format!("start slot number {} is greater than end slot number {}", start, end));
+        }
+        slots.extend(start..=end);
+    }
+    unique(slots)
+}
+
+fn node_entry(node: &cluster::Node) -> String {
+    resp::array(&[
+        resp::bulk(&node.host),
+        resp::integer(node.port as i64),
+        resp::bulk(&node.id),
+    ])
+}
+
+// CLUSTER SLOTS: [start, end, [host, port, id]] per slot range
+fn slots(db: &Database) -> String {
+    let ranges: Vec<String> = db
+        .cluster()
+        .slot_ranges()
+        .iter()
+        .map(|(start, end, node)| {
+            resp::array(&[
+                resp::integer(*start as i64),
+                resp::integer(*end as i64),
+                node_entry(node),
+            ])
+        })
+        .collect();
+    resp::array(&ranges)
+}
+
+// CLUSTER SHARDS: every node is a master without replicas, so one shard per node
+fn shards(db: &Database) -> String {
+    let ranges = db.cluster().slot_ranges();
+    let myself = db.cluster().myself().id;
+    let shards: Vec<String> = db
+        .cluster()
+        .nodes()
+        .iter()
+        .map(|node| {
+            let slots: Vec<String> = ranges
+                .iter()
+                .filter(|(_, _, owner)| owner.id == node.id)
+                .flat_map(|(start, end, _)| [resp::integer(*start as i64), resp::integer(*end as i64)])
+                .collect();
+            let info = resp::array(&[
+                resp::bulk("id"),
+                resp::bulk(&node.id),
+                resp::bulk("port"),
+                resp::integer(node.port as i64),
+                resp::bulk("ip"),
+                resp::bulk(&node.host),
+                resp::bulk("endpoint"),
+                resp::bulk(&node.host),
+                resp::bulk("role"),
+                resp::bulk("master"),
+                resp::bulk("replication-offset"),
+                // Other nodes' offsets aren't known here
+                resp::integer(if node.id == myself { db.replication().offset() as i64 } else { 0 }),
+                resp::bulk("health"),
+                resp::bulk(if node.connected { "online" } else { "fail" }),
+            ]);
+            resp::array(&[
+                resp::bulk("slots"),
+                resp::array(&slots),
+                resp::bulk("nodes"),
+                resp::array(&[info]),
+            ])
+        })
+        .collect();
+    resp::array(&shards)
+}
+
+// MIGRATE host port key|"" destination-db timeout [COPY] [REPLACE] [KEYS key [key ...]]
+pub async fn migrate_command(db: &Database, args: &[&str]) -> Result<String, String> {
+    let (host, port, key, dest_db, timeout, options) = match args {
+        [host, port, key, dest_db, timeout, options @ ..] => (*host, *port, *key, *dest_db, *timeout, options),
+        _ => return Err("wrong number of arguments for 'migrate' command".to_string()),
+    };
+    let port = port.parse::<u16>().map_err(|_| "value is not an integer or out of range")?;
+    // Only database 0 exists
+    if dest_db != "0" {
+        return Err("target database must be 0".to_string());
+    }
+    let timeout = match timeout.parse::<u64>() {
+        Ok(0) => Duration::from_secs(1),
+        Ok(ms) => Duration::from_millis(ms),
+        Err(_) => return Err("value is not an integer or out of range".to_string()),
+    };
+
+    let (mut copy, mut replace) = (false, false);
+    let mut keys = vec![key];
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match option.to_uppercase().as_str() {
+            "COPY" => copy = true,
+            "REPLACE" => replace = true,
+            "KEYS" => {
+                if !key.is_empty() {
+                    return Err("When using MIGRATE KEYS option, the key argument must be set to the empty string".to_string());
+                }
+                keys = options.by_ref().copied().collect();
+            }
+            _ => return Err("syntax error".to_string()),
+        }
+    }
+
+    migrate::migrate(db, host, port, &keys, timeout, copy, replace).await
+}
@@ -0,0 +1,173 @@
+use crate::command::resp;
+use crate::database::countmin::CountMinSketch;
+use crate::database::sketch::{Sketch, WRONG_TYPE};
+use crate::database::Database;
+
+const SYNTAX_ERROR: &str = "syntax error";
+const KEY_EXISTS: &str = "CMS: key already exists";
+const NO_KEY: &str = "CMS: key does not exist";
+const BAD_NUMBER: &str = "CMS: Cannot parse number";
+const TOO_LARGE: &str = "CMS: sketch is too large";
+
+fn wrong_args(command: &str) -> String {
+    format!("wrong number of arguments for '{}' command", command)
+}
+
+// WRONGTYPE goes out as is, the rest get -ERR from the connection
+fn prefixed(result: Result<String, String>) -> Result<String, String> {
+    match result {
+        Err(e) if e.starts_with("WRONGTYPE ") => Ok(format!("-{}\r\n", e)),
+        other => other,
+    }
+}
+
+fn command<'a>(name: &'a str, args: &[&'a str]) -> Vec<&'a str> {
+    let mut command = vec![name];
+    command.extend(args);
+    command
+}
+
+fn cms(sketch: &Sketch) -> Result<&CountMinSketch, String> {
+    match sketch {
+        Sketch::CountMin(cms) => Ok(cms),
+        _ => Err(WRONG_TYPE.to_string()),
+    }
+}
+
+// Creates the sketch unless the key exists
+fn init(db: &Database, key: &str, command: &[&str], sketch: CountMinSketch) -> Result<String, String> {
+    db.sketch_write(key, command, |existing| {
+        if existing.is_some() {
+            return Err(KEY_EXISTS.to_string());
+        }
+        *existing = Some(Sketch::CountMin(sketch));
+        Ok((resp::ok(), true))
+    })
+}
+
+// CMS.INITBYDIM key width depth
+pub fn cms_initbydim(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key, width, depth] = args else {
+        return Err(wrong_args("cms.initbydim"));
+    };
+    let width = width.parse::<usize>().ok().filter(|width| *width > 0).ok_or("CMS: invalid width")?;
+    let depth = depth.parse::<usize>().ok().filter(|depth| *depth > 0).ok_or("CMS: invalid depth")?;
+    let sketch = CountMinSketch::new(width, depth).ok_or(TOO_LARGE)?;
+    init(db, key, &command("CMS.INITBYDIM", args), sketch)
+}
+
+// CMS.INITBYPROB key error probability: estimates overcount by at most `error`
+// times the total, except with `probability`
+pub fn cms_initbyprob(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key, error, probability] = args else {
+        return Err(wrong_args("cms.initbyprob"));
+    };
+    let in_range = |value: &f64| *value > 0.0 && *value < 1.0;
+    let error = error.parse::<f64>().ok().filter(in_range).ok_or("CMS: invalid overestimation value")?;
+    let probability = probability.parse::<f64>().ok().filter(in_range).ok_or("CMS: invalid prob value")?;
+    let sketch = CountMinSketch::by_probability(error, probability).ok_or(TOO_LARGE)?;
+    init(db, key, &command("CMS.INITBYPROB", args), sketch)
+}
+
+// CMS.INCRBY key item increment [item increment ...], replying with the new
+// estimates
+pub fn cms_incrby(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key, pairs @ ..] = args else {
+        return Err(wrong_args("cms.incrby"));
+    };
+    if pairs.is_empty() || !pairs.len().is_multiple_of(2) {
+        return Err(wrong_args("cms.incrby"));
+    }
+    let increments = pairs
+        .chunks(2)
+        .map(|pair| Ok((pair[0], pair[1].parse::<u64>().map_err(|_| BAD_NUMBER)?)))
+        .collect::<Result<Vec<(&str, u64)>, String>>()?;
+
+    prefixed(db.sketch_write(key, &command("CMS.INCRBY", args), |sketch| {
+        let Some(Sketch::CountMin(cms)) = sketch else {
+            return Err(sketch.as_ref().map_or(NO_KEY, |_| WRONG_TYPE).to_string());
+        };
+        let estimates: Vec<String> = increments
+            .iter()
+            .map(|(item, by)| resp::integer(cms.increment(item, *by) as i64))
+            .collect();
+        Ok((resp::array(&estimates), true))
+    }))
+}
+
+// CMS.QUERY key item [item ...]
+pub fn cms_query(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key, items @ ..] = args else {
+        return Err(wrong_args("cms.query"));
+    };
+    if items.is_empty() {
+        return Err(wrong_args("cms.query"));
+    }
+    let estimates = db
+        .sketch_read(key, |sketch| {
+            let cms = cms(sketch)?;
+            let estimates: Vec<String> = items.iter().map(|item| resp::integer(cms.query(item) as i64)).collect();
+            Ok(resp::array(&estimates))
+        })?
+        .ok_or(NO_KEY)?;
+    prefixed(estimates)
+}
+
+// CMS.MERGE destination numkeys source [source ...] [WEIGHTS weight ...]. The
+// destination must exist and have the sources' dimensions; it's replaced by
+// their weighted sum.
+pub fn cms_merge(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [destination, count, rest @ ..] = args else {
+        return Err(wrong_args("cms.merge"));
+    };
+    let count = count.parse::<usize>().ok().filter(|count| *count > 0).ok_or("CMS: invalid numkeys")?;
+    if rest.len() < count {
+        return Err(wrong_args("cms.merge"));
+    }
+    let (sources, rest) = rest.split_at(count);
+    let weights = match rest {
+        [] => vec![1; count],
+        [option, weights @ ..] if option.eq_ignore_ascii_case("WEIGHTS") && weights.len() == count => weights
+            .iter()
+            .map(|weight| weight.parse::<i64>().map_err(|_| BAD_NUMBER.to_string()))
+            .collect::<Result<Vec<i64>, String>>()?,
+        _ => return Err(SYNTAX_ERROR.to_string()),
+    };
+
+    prefixed(db.sketches_write(destination, sources, &command("CMS.MERGE", args), |sketches| {
+        let dimensions = cms(sketches.get(*destination).ok_or(NO_KEY)?)?.dimensions();
+        let mut weighted = Vec::new();
+        for (source, weight) in sources.iter().zip(&weights) {
+            let source = cms(sketches.get(*source).ok_or(NO_KEY)?)?;
+            if source.dimensions() != dimensions {
+                return Err("CMS: width/depth is not equal".to_string());
+            }
+            weighted.push((source, *weight));
+        }
+        let merged = CountMinSketch::merge(&weighted);
+        sketches.insert(destination.to_string(), Sketch::CountMin(merged));
+        Ok((resp::ok(), true))
+    }))
+}
+
+// CMS.INFO key
+pub fn cms_info(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key] = args else {
+        return Err(wrong_args("cms.info"));
+    };
+    let info = db
+        .sketch_read(key, |sketch| {
+            let cms = cms(sketch)?;
+            let (width, depth) = cms.dimensions();
+            Ok(resp::array(&[
+                resp::bulk("width"),
+                resp::integer(width as i64),
+                resp::bulk("depth"),
+                resp::integer(depth as i64),
+                resp::bulk("count"),
+                resp::integer(cms.count() as i64),
+            ]))
+        })?
+        .ok_or(NO_KEY)?;
+    prefixed(info)
+}
@@ -0,0 +1,138 @@
+// DUMP and RESTORE for string values, HyperLogLogs included. The payload is the
+// one Redis produces: the value in RDB encoding, followed by the RDB version and a
+// CRC64 of everything before it, so values move between this server and Redis.
+
+use std::time::Duration;
+
+use crate::command::resp;
+use crate::database::Database;
+
+const RDB_TYPE_STRING: u8 = 0;
+const RDB_VERSION: u16 = 11;
+const BAD_PAYLOAD: &str = "DUMP payload version or checksum are wrong";
+const BAD_FORMAT: &str = "Bad data format";
+
+// DUMP key
+pub async fn dump(db: &Database, key: &str) -> Result<Vec<u8>, String> {
+    match db.get(key).await {
+        Some(value) => Ok(resp::bulk_bytes(&payload(&value))),
+        None if db.exists(key) => Err("DUMP is only supported for string values".to_string()),
+        None => Ok(resp::null_bulk().into_bytes()),
+    }
+}
+
+// RESTORE key ttl serialized-value [REPLACE]. The TTL is in milliseconds, 0 for none.
+pub async fn restore(db: &Database, args: &[&[u8]], splitted_command: &[&str]) -> Result<Vec<u8>, String> {
+    let [_, key, ttl, _, options @ ..] = splitted_command else {
+        return Err("wrong number of arguments for 'restore' command".to_string());
+    };
+    let mut replace = false;
+    for option in options {
+        match option.to_uppercase().as_str() {
+            "REPLACE" => replace = true,
+            _ => return Err("syntax error".to_string()),
+        }
+    }
+    let ttl = match ttl.parse::<i64>() {
+        Ok(0) => None,
+        Ok(ttl) if ttl > 0 => Some(Duration::from_millis(ttl as u64)),
+        _ => return Err("Invalid TTL value, must be >= 0".to_string()),
+    };
+    let value = load(args[3])?;
+
+    if db.exists(key) {
+        if !replace {
+            return Ok(b"-BUSYKEY Target key name already exists.\r\n".to_vec());
+        }
+        db.delete(key).await;
+    }
+    db.restore_string(key, value, ttl, args).await;
+    Ok(resp::ok().into_bytes())
+}
+
+fn payload(value: &[u8]) -> Vec<u8> {
+    let mut out = vec![RDB_TYPE_STRING];
+    match value.len() {
+        len if len < 1 << 6 => out.push(len as u8),
+        len if len < 1 << 14 => out.extend([0x40 | (len >> 8) as u8, len as u8]),
+        len => match u32::try_from(len) {
+            Ok(len) => {
+                out.push(0x80);
+                out.extend(len.to_be_bytes());
+            }
+            Err(_) => {
+                out.push(0x81);
+                out.extend((len as u64).to_be_bytes());
+            }
+        },
+    }
+    out.extend_from_slice(value);
+    out.extend(RDB_VERSION.to_le_bytes());
+    out.extend(crc64(&out).to_le_bytes());
+    out
+}
+
+// The string value of a payload, after checking its version and checksum
+fn load(payload: &[u8]) -> Result<Vec<u8>, String> {
+    if payload.len() < 10 {
+        return Err(BAD_PAYLOAD.to_string());
+    }
+    let (data, crc) = payload.split_at(payload.len() - 8);
+    let version = u16::from_le_bytes([data[data.len() - 2], data[data.len() - 1]]);
+    if version > RDB_VERSION || crc64(data).to_le_bytes() != crc {
+        return Err(BAD_PAYLOAD.to_string());
+    }
+
+    match &data[..data.len() - 2] {
+        [RDB_TYPE_STRING, value @ ..] => rdb_string(value).ok_or_else(|| BAD_FORMAT.to_string()),
+        _ => Err(BAD_FORMAT.to_string()),
+    }
+}
+
+// An RDB string that makes up all of `data`: a length and the bytes, or one of the
+// integer encodings. LZF compressed strings aren't supported.
+fn rdb_string(data: &[u8]) -> Option<Vec<u8>> {
+    let (&first, rest) = data.split_first()?;
+    let exact = |rest: &[u8], len: u64| (rest.len() as u64 == len).then(|| rest.to_vec());
+    match (first >> 6, first & 0x3f) {
+        (0, len) => exact(rest, len as u64),
+        (1, high) => {
+            let (&low, rest) = rest.split_first()?;
+            exact(rest, (high as u64) << 8 | low as u64)
+        }
+        (2, 0) => {
+            let (len, rest) = rest.split_first_chunk::<4>()?;
+            exact(rest, u32::from_be_bytes(*len) as u64)
+        }
+        (2, 1) => {
+            let (len, rest) = rest.split_first_chunk::<8>()?;
+            exact(rest, u64::from_be_bytes(*len))
+        }
+        (3, 0) => match rest {
+            [n] => Some((*n as i8).to_string().into_bytes()),
+            _ => None,
+        },
+        (3, 1) => match rest {
+            [a, b] => Some(i16::from_le_bytes([*a, *b]).to_string().into_bytes()),
+            _ => None,
+        },
+        (3, 2) => match rest {
+            [a, b, c, d] => Some(i32::from_le_bytes([*a, *b, *c, *d]).to_string().into_bytes()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// CRC-64/Jones as Redis computes it: reflected, no initial or final XOR
+fn crc64(data: &[u8]) -> u64 {
+    const POLY: u64 = 0x95ac_9329_ac4b_c9b5;
+    let mut crc = 0u64;
+    for &byte in data {
+        crc ^= byte as u64;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ POLY } else { crc >> 1 };
+        }
+    }
+    crc
+}
@@ -0,0 +1,368 @@
+use crate::command::resp;
+use crate::database::geo::{self, Shape};
+use crate::database::Database;
+
+const SYNTAX_ERROR: &str = "syntax error";
+const NOT_INTEGER: &str = "value is not an integer or out of range";
+const NOT_FLOAT: &str = "value is not a valid float";
+const BAD_UNIT: &str = "unsupported unit provided. please use M, KM, FT, MI";
+const ONE_CENTER: &str = "exactly one of FROMMEMBER or FROMLONLAT can be specified for GEOSEARCH";
+const ONE_SHAPE: &str = "exactly one of BYRADIUS and BYBOX can be specified for GEOSEARCH";
+
+fn wrong_args(command: &str) -> String {
+    format!("wrong number of arguments for '{}' command", command)
+}
+
+fn parse_float(value: &str) -> Result<f64, String> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|value| !value.is_nan())
+        .ok_or_else(|| NOT_FLOAT.to_string())
+}
+
+fn parse_position(long: &str, lat: &str) -> Result<(f64, f64), String> {
+    let (long, lat) = (parse_float(long)?, parse_float(lat)?);
+    if !geo::valid(long, lat) {
+        return Err(format!("invalid longitude,latitude pair {:.6},{:.6}", long, lat));
+    }
+    Ok((long, lat))
+}
+
+// Meters per unit
+fn parse_unit(unit: &str) -> Result<f64, String> {
+    match unit.to_lowercase().as_str() {
+        "m" => Ok(1.0),
+        "km" => Ok(1000.0),
+        "ft" => Ok(0.3048),
+        "mi" => Ok(1609.34),
+        _ => Err(BAD_UNIT.to_string()),
+    }
+}
+
+// Coordinates are printed with up to 17 decimals, distances with 4, like Redis
+fn coordinate(value: f64) -> String {
+    let text = format!("{:.17}", value);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+fn position_reply((long, lat): (f64, f64)) -> String {
+    resp::array(&[resp::bulk(&coordinate(long)), resp::bulk(&coordinate(lat))])
+}
+
+fn distance_reply(meters: f64, unit: f64) -> String {
+    resp::bulk(&format!("{:.4}", meters / unit))
+}
+
+// GEOADD key [NX|XX] [CH] longitude latitude member [...]. Stored as ZADDs.
+pub fn geoadd(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key, rest @ ..] = args else {
+        return Err(wrong_args("geoadd"));
+    };
+    if rest.len() < 3 {
+        return Err(wrong_args("geoadd"));
+    }
+    let (mut nx, mut xx, mut ch) = (false, false, false);
+    let mut rest = rest;
+    while let [option, tail @ ..] = rest {
+        match option.to_uppercase().as_str() {
+            "NX" => nx = true,
+            "XX" => xx = true,
+            "CH" => ch = true,
+            _ => break,
+        }
+        rest = tail;
+    }
+    if nx && xx {
+        return Err("XX and NX options at the same time are not compatible".to_string());
+    }
+    if rest.is_empty() || rest.len() % 3 != 0 {
+        return Err("syntax error. Try GEOADD key [x1] [y1] [name1] [x2] [y2] [name2] ... ".to_string());
+    }
+
+    let mut members = Vec::with_capacity(rest.len() / 3);
+    for triple in rest.chunks(3) {
+        let (long, lat) = parse_position(triple[0], triple[1])?;
+        let score = geo::encode(long, lat).expect("position was validated");
+        members.push((score as f64, triple[2].to_string()));
+    }
+    let (added, changed) = db.zadd_members(key, &members, nx, xx)?;
+    Ok(resp::integer(if ch { changed } else { added } as i64))
+}
+
+// GEOPOS key [member ...]
+pub fn geopos(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key, members @ ..] = args else {
+        return Err(wrong_args("geopos"));
+    };
+    let positions = db
+        .sorted_set_read(key, |zset| members.iter().map(|member| zset.zscore(member)).collect())
+        .unwrap_or_else(|| vec![None; members.len()]);
+    let replies: Vec<String> = positions
+        .into_iter()
+        .map(|score| score.map_or_else(resp::null_array, |score| position_reply(geo::decode(score as u64))))
+        .collect();
+    Ok(resp::array(&replies))
+}
+
+// GEODIST key member1 member2 [M|KM|FT|MI]
+pub fn geodist(db: &Database, args: &[&str]) -> Result<String, String> {
+    let (key, first, second, unit) = match args {
+        [key, first, second] => (key, first, second, 1.0),
+        [key, first, second, unit] => (key, first, second, parse_unit(unit)?),
+        [_, _, _, ..] => return Err(SYNTAX_ERROR.to_string()),
+        _ => return Err(wrong_args("geodist")),
+    };
+    let scores = db.sorted_set_read(key, |zset| zset.zscore(first).zip(zset.zscore(second)));
+    match scores.flatten() {
+        Some((first, second)) => {
+            let (long1, lat1) = geo::decode(first as u64);
+            let (long2, lat2) = geo::decode(second as u64);
+            Ok(distance_reply(geo::distance(long1, lat1, long2, lat2), unit))
+        }
+        None => Ok(resp::null_bulk()),
+    }
+}
+
+// GEOHASH key [member ...]
+pub fn geohash(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key, members @ ..] = args else {
+        return Err(wrong_args("geohash"));
+    };
+    let scores = db
+        .sorted_set_read(key, |zset| members.iter().map(|member| zset.zscore(member)).collect())
+        .unwrap_or_else(|| vec![None; members.len()]);
+    let replies: Vec<String> = scores
+        .into_iter()
+        .map(|score| score.map_or_else(resp::null_bulk, |score| resp::bulk(&geo::hash_string(score as u64))))
+        .collect();
+    Ok(resp::array(&replies))
+}
+
+enum Center<'a> {
+    Member(&'a str),
+    Position(f64, f64),
+}
+
+#[derive(Default)]
+struct SearchOptions<'a> {
+    center: Option<Center<'a>>,
+    shape: Option<(Shape, f64)>,
+    descending: Option<bool>,
+    count: Option<usize>,
+    any: bool,
+    with_coord: bool,
+    with_dist: bool,
+    with_hash: bool,
+    store_dist: bool,
+}
+
+fn parse_search<'a>(args: &[&'a str], store: bool) -> Result<SearchOptions<'a>, String> {
+    let mut options = SearchOptions::default();
+    let mut rest = args;
+    while let [option, tail @ ..] = rest {
+        let option = option.to_uppercase();
+        let used = match (option.as_str(), tail) {
+            ("FROMMEMBER", [member, ..]) => {
+                if options.center.is_some() {
+                    return Err(ONE_CENTER.to_string());
+                }
+                options.center = Some(Center::Member(member));
+                1
+            }
+            ("FROMLONLAT", [long, lat, ..]) => {
+                if options.center.is_some() {
+                    return Err(ONE_CENTER.to_string());
+                }
+                let (long, lat) = parse_position(long, lat)?;
+                options.center = Some(Center::Position(long, lat));
+                2
+            }
+            ("BYRADIUS", [radius, unit, ..]) => {
+                if options.shape.is_some() {
+                    return Err(ONE_SHAPE.to_string());
+                }
+                let radius = parse_float(radius)?;
+                if radius < 0.0 {
+                    return Err("radius cannot be negative".to_string());
+                }
+                let unit = parse_unit(unit)?;
+                options.shape = Some((Shape::Radius(radius * unit), unit));
+                2
+            }
+            ("BYBOX", [width, height, unit, ..]) => {
+                if options.shape.is_some() {
+                    return Err(ONE_SHAPE.to_string());
+                }
+                let (width, height) = (parse_float(width)?, parse_float(height)?);
+                if width < 0.0 || height < 0.0 {
+                    return Err("height or width cannot be negative".to_string());
+                }
+                let unit = parse_unit(unit)?;
+                options.shape = Some((Shape::Box { width: width * unit, height: height * unit }, unit));
+                3
+            }
+            ("ASC", _) => {
+                options.descending = Some(false);
+                0
+            }
+            ("DESC", _) => {
+                options.descending = Some(true);
+                0
+            }
+            ("COUNT", [count, more @ ..]) => {
+                let count = count.parse::<i64>().map_err(|_| NOT_INTEGER)?;
+                if count <= 0 {
+                    return Err("COUNT must be > 0".to_string());
+                }
+                options.count = Some(count as usize);
+                options.any = more.first().is_some_and(|any| any.eq_ignore_ascii_case("ANY"));
+                1 + options.any as usize
+            }
+            ("WITHCOORD", _) => {
+                options.with_coord = true;
+                0
+            }
+            ("WITHDIST", _) => {
+                options.with_dist = true;
+                0
+            }
+            ("WITHHASH", _) => {
+                options.with_hash = true;
+                0
+            }
+            ("STOREDIST", _) if store => {
+                options.store_dist = true;
+                0
+            }
+            _ => return Err(SYNTAX_ERROR.to_string()),
+        };
+        rest = &tail[used..];
+    }
+
+    if options.center.is_none() {
+        return Err(ONE_CENTER.to_string());
+    }
+    if options.shape.is_none() {
+        return Err(ONE_SHAPE.to_string());
+    }
+    if options.any && options.count.is_none() {
+        return Err("the ANY argument requires COUNT argument".to_string());
+    }
+    if store && (options.with_coord || options.with_dist || options.with_hash) {
+        return Err("GEOSEARCHSTORE is not compatible with WITHDIST, WITHHASH and WITHCOORD options".to_string());
+    }
+    Ok(options)
+}
+
+struct Found {
+    member: String,
+    score: u64,
+    distance: f64,
+    position: (f64, f64),
+}
+
+// Members inside the search area, nearest first when sorted. Every member is
+// checked, which finds the same members as Redis' scan of neighbouring geohash
+// cells. With COUNT but no order the nearest ones are returned, unless ANY asks
+// for the first ones found.
+fn search(db: &Database, key: &str, options: &SearchOptions) -> Result<Option<Vec<Found>>, String> {
+    let (shape, _) = options.shape.expect("checked when parsing");
+    let found = db.sorted_set_read(key, |zset| {
+        let center = match options.center {
+            Some(Center::Member(member)) => {
+                let score = zset.zscore(member).ok_or("could not decode requested zset member")?;
+                geo::decode(score as u64)
+            }
+            Some(Center::Position(long, lat)) => (long, lat),
+            None => unreachable!("checked when parsing"),
+        };
+
+        let mut found = Vec::new();
+        for entry in &zset.sorted {
+            if options.any && Some(found.len()) == options.count {
+                break;
+            }
+            let score = entry.score.0 as u64;
+            let position = geo::decode(score);
+            if let Some(distance) = shape.contains(center, position) {
+                found.push(Found { member: entry.member.clone(), score, distance, position });
+            }
+        }
+        Ok::<_, String>(found)
+    });
+    let Some(mut found) = found.transpose()? else {
+        return Ok(None);
+    };
+
+    let descending = match options.descending {
+        None if options.count.is_some() && !options.any => Some(false),
+        descending => descending,
+    };
+    if let Some(descending) = descending {
+        found.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        if descending {
+            found.reverse();
+        }
+    }
+    if let Some(count) = options.count {
+        found.truncate(count);
+    }
+    Ok(Some(found))
+}
+
+// GEOSEARCH key FROMMEMBER member|FROMLONLAT longitude latitude
+// BYRADIUS radius unit|BYBOX width height unit [ASC|DESC] [COUNT count [ANY]]
+// [WITHCOORD] [WITHDIST] [WITHHASH]
+pub fn geosearch(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key, rest @ ..] = args else {
+        return Err(wrong_args("geosearch"));
+    };
+    let options = parse_search(rest, false)?;
+    let Some(found) = search(db, key, &options)? else {
+        return Ok(resp::array(&[]));
+    };
+
+    let unit = options.shape.map_or(1.0, |(_, unit)| unit);
+    let plain = !(options.with_coord || options.with_dist || options.with_hash);
+    let replies: Vec<String> = found
+        .iter()
+        .map(|found| {
+            if plain {
+                return resp::bulk(&found.member);
+            }
+            let mut item = vec![resp::bulk(&found.member)];
+            if options.with_dist {
+                item.push(distance_reply(found.distance, unit));
+            }
+            if options.with_hash {
+                item.push(resp::integer(found.score as i64));
+            }
+            if options.with_coord {
+                item.push(position_reply(found.position));
+            }
+            resp::array(&item)
+        })
+        .collect();
+    Ok(resp::array(&replies))
+}
+
+// GEOSEARCHSTORE destination source <GEOSEARCH options> [STOREDIST]. The
+// destination becomes a geo set, or with STOREDIST a sorted set scored by distance.
+pub fn geosearchstore(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [dest, source, rest @ ..] = args else {
+        return Err(wrong_args("geosearchstore"));
+    };
+    let options = parse_search(rest, true)?;
+    let found = search(db, source, &options)?.unwrap_or_default();
+
+    let unit = options.shape.map_or(1.0, |(_, unit)| unit);
+    let members: Vec<(f64, String)> = found
+        .into_iter()
+        .map(|found| {
+            let score = if options.store_dist { found.distance / unit } else { found.score as f64 };
+            (score, found.member)
+        })
+        .collect();
+    Ok(resp::integer(db.zstore(dest, members) as i64))
+}
@@ -0,0 +1,88 @@
+use crate::command::resp;
+use crate::database::hyperloglog::{count_registers, empty_registers, HyperLogLog};
+use crate::database::Database;
+
+// WRONGTYPE and INVALIDOBJ go out as is, the rest get -ERR from the connection
+fn prefixed(result: Result<String, String>) -> Result<String, String> {
+    match result {
+        Err(e) if e.starts_with("WRONGTYPE ") || e.starts_with("INVALIDOBJ ") => Ok(format!("-{}\r\n", e)),
+        other => other,
+    }
+}
+
+// PFADD key [element ...]. Creating the key counts as a change even without elements.
+pub fn pfadd(db: &Database, args: &[&str]) -> Result<String, String> {
+    let (key, elements) = args
+        .split_first()
+        .ok_or("wrong number of arguments for 'pfadd' command")?;
+    let sparse_max_bytes = db.config().hll_sparse_max_bytes;
+    let mut command = vec!["PFADD"];
+    command.extend(args);
+
+    let changed = db.update_string(key, &command, |value| {
+        let (mut hll, mut changed) = match value {
+            Some(value) => (HyperLogLog::from_bytes(value)?, false),
+            None => (HyperLogLog::new(), true),
+        };
+        for element in elements {
+            changed |= hll.add(element, sparse_max_bytes)?;
+        }
+        Ok((changed.then(|| hll.into_bytes()), changed))
+    });
+    prefixed(changed.map(|changed| resp::integer(changed as i64)))
+}
+
+// PFCOUNT key [key ...]; several keys count their union
+pub async fn pfcount(db: &Database, keys: &[&str]) -> Result<String, String> {
+    let count = async {
+        if let [key] = keys {
+            return match db.get(key).await {
+                Some(value) => HyperLogLog::from_bytes(&value)?.count(),
+                None => Ok(0),
+            };
+        }
+        let mut registers = empty_registers();
+        for key in keys {
+            if let Some(value) = db.get(key).await {
+                HyperLogLog::from_bytes(&value)?.merge_into(&mut registers)?;
+            }
+        }
+        Ok(count_registers(&registers))
+    };
+    prefixed(count.await.map(|count| resp::integer(count as i64)))
+}
+
+// PFMERGE destkey [sourcekey ...]; the destination is part of the union
+pub async fn pfmerge(db: &Database, args: &[&str]) -> Result<String, String> {
+    let (dest, sources) = args
+        .split_first()
+        .ok_or("wrong number of arguments for 'pfmerge' command")?;
+    let sparse_max_bytes = db.config().hll_sparse_max_bytes;
+    let mut command = vec!["PFMERGE"];
+    command.extend(args);
+
+    let merged = async {
+        // Like Redis, the result is dense as soon as one input is
+        let mut registers = empty_registers();
+        let mut dense = false;
+        for key in sources {
+            if let Some(value) = db.get(key).await {
+                let hll = HyperLogLog::from_bytes(&value)?;
+                dense |= hll.is_dense();
+                hll.merge_into(&mut registers)?;
+            }
+        }
+
+        db.update_string(dest, &command, |value| {
+            let mut hll = match value {
+                Some(value) => HyperLogLog::from_bytes(value)?,
+                None => HyperLogLog::new(),
+            };
+            dense |= hll.is_dense();
+            hll.merge_into(&mut registers)?;
+            hll.merge_from(&registers, dense, sparse_max_bytes)?;
+            Ok((Some(hll.into_bytes()), resp::ok()))
+        })
+    };
+    prefixed(merged.await)
+}
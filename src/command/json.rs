@@ -0,0 +1,355 @@
+use serde_json::{Number, Value};
+
+use crate::command::resp;
+use crate::database::json::{self, Format, Location, Path};
+use crate::database::Database;
+
+const SYNTAX_ERROR: &str = "syntax error";
+const NO_KEY: &str = "could not perform this operation on a key that doesn't exist";
+
+fn wrong_args(command: &str) -> String {
+    format!("wrong number of arguments for '{}' command", command)
+}
+
+// Errors with a prefix of their own go out as is, the rest get -ERR from the connection
+fn prefixed(result: Result<String, String>) -> Result<String, String> {
+    match result {
+        Err(e) if e.starts_with("WRONGTYPE ") => Ok(format!("-{}\r\n", e)),
+        other => other,
+    }
+}
+
+fn wrong_type(expected: &str, found: &Value) -> String {
+    format!(
+        "WRONGTYPE wrong type of path value - expected {} but found {}",
+        expected,
+        json::type_name(found)
+    )
+}
+
+fn missing_path(path: &str) -> String {
+    format!("Path '{}' does not exist", path)
+}
+
+fn parse_value(text: &str) -> Result<Value, String> {
+    serde_json::from_str(text).map_err(|e| e.to_string())
+}
+
+fn command<'a>(name: &'a str, args: &[&'a str]) -> Vec<&'a str> {
+    let mut command = vec![name];
+    command.extend(args);
+    command
+}
+
+// The reply for one result per match: JSONPath gets them all as an array, a
+// legacy path the first one, or an error when it matched nothing
+fn per_match(path: &Path, raw: &str, replies: Vec<String>) -> Result<String, String> {
+    if !path.is_legacy() {
+        return Ok(resp::array(&replies));
+    }
+    replies.into_iter().next().ok_or_else(|| missing_path(raw))
+}
+
+// JSON.SET key path value [NX|XX]. New keys must start at the root; a path
+// ending in a member name also adds the member to objects that lack it.
+pub fn json_set(db: &Database, args: &[&str]) -> Result<String, String> {
+    let (key, raw, text, condition) = match args {
+        [key, path, value] => (key, path, value, None),
+        [key, path, value, condition] => match condition.to_uppercase().as_str() {
+            "NX" => (key, path, value, Some(true)),
+            "XX" => (key, path, value, Some(false)),
+            _ => return Err(SYNTAX_ERROR.to_string()),
+        },
+        _ => return Err(wrong_args("json.set")),
+    };
+    let path = Path::parse(raw)?;
+    let value = parse_value(text)?;
+
+    db.json_write(key, &command("JSON.SET", args), |doc| {
+        let Some(doc) = doc.as_mut() else {
+            if !path.is_root() {
+                return Err("new objects must be created at the root".to_string());
+            }
+            if condition == Some(false) {
+                return Ok((resp::null_bulk(), false));
+            }
+            *doc = Some(value);
+            return Ok((resp::ok(), true));
+        };
+
+        let locations = path.find(doc);
+        if !locations.is_empty() {
+            if condition == Some(true) {
+                return Ok((resp::null_bulk(), false));
+            }
+            for location in &locations {
+                if let Some(target) = json::get_mut(doc, location) {
+                    *target = value.clone();
+                }
+            }
+            return Ok((resp::ok(), true));
+        }
+
+        let Some((parent, name)) = path.parent_and_name().filter(|_| condition != Some(false)) else {
+            return Ok((resp::null_bulk(), false));
+        };
+        let mut added = false;
+        for location in parent.find(doc) {
+            if let Some(Value::Object(map)) = json::get_mut(doc, &location) {
+                map.insert(name.to_string(), value.clone());
+                added = true;
+            }
+        }
+        Ok((if added { resp::ok() } else { resp::null_bulk() }, added))
+    })
+}
+
+// JSON.GET key [INDENT indent] [NEWLINE newline] [SPACE space] [path ...]
+pub fn json_get(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key, rest @ ..] = args else {
+        return Err(wrong_args("json.get"));
+    };
+    let mut format = Format { indent: "", newline: "", space: "" };
+    let mut rest = rest;
+    loop {
+        match rest {
+            [option, value, ..] if option.eq_ignore_ascii_case("INDENT") => format.indent = value,
+            [option, value, ..] if option.eq_ignore_ascii_case("NEWLINE") => format.newline = value,
+            [option, value, ..] if option.eq_ignore_ascii_case("SPACE") => format.space = value,
+            _ => break,
+        }
+        rest = &rest[2..];
+    }
+    let raws = if rest.is_empty() { vec!["."] } else { rest.to_vec() };
+    let paths = raws.iter().map(|raw| Path::parse(raw)).collect::<Result<Vec<_>, _>>()?;
+
+    let result = db.json_read(key, |doc| {
+        if let [path] = paths.as_slice() {
+            return json::matches(doc, path).ok_or_else(|| missing_path(raws[0]));
+        }
+        // Several paths give an object keyed by path, with arrays for every path as
+        // soon as one of them is JSONPath
+        let legacy = paths.iter().all(Path::is_legacy);
+        let mut object = serde_json::Map::new();
+        for (raw, path) in raws.iter().zip(&paths) {
+            let path = if legacy { path.clone() } else { path.to_jsonpath() };
+            let value = json::matches(doc, &path).ok_or_else(|| missing_path(raw))?;
+            object.insert(raw.to_string(), value);
+        }
+        Ok(Value::Object(object))
+    });
+    match result.transpose()? {
+        Some(value) => Ok(resp::bulk(&format.write(&value))),
+        None => Ok(resp::null_bulk()),
+    }
+}
+
+// JSON.DEL key [path] (and JSON.FORGET). Deleting the root deletes the key.
+pub async fn json_del(db: &Database, args: &[&str]) -> Result<String, String> {
+    let (key, path) = match args {
+        [key] => (key, Path::root()),
+        [key, path] => (key, Path::parse(path)?),
+        _ => return Err(wrong_args("json.del")),
+    };
+    if path.is_root() {
+        let exists = db.json_read(key, |_| ()).is_some();
+        return Ok(resp::integer((exists && db.delete(key).await) as i64));
+    }
+    let removed = db.json_write(key, &command("JSON.DEL", args), |doc| {
+        let removed = doc.as_mut().map_or(0, |doc| json::remove(doc, path.find(doc)));
+        Ok((removed, removed > 0))
+    })?;
+    Ok(resp::integer(removed as i64))
+}
+
+fn add_numbers(a: &Number, b: &Number) -> Option<Number> {
+    if let (Some(a), Some(b)) = (a.as_i64(), b.as_i64()) {
+        if let Some(sum) = a.checked_add(b) {
+            return Some(sum.into());
+        }
+    }
+    Number::from_f64(a.as_f64()? + b.as_f64()?)
+}
+
+// JSON.NUMINCRBY key path number. Replies with the new numbers as JSON, null
+// where a match isn't a number.
+pub fn json_numincrby(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key, raw, increment] = args else {
+        return Err(wrong_args("json.numincrby"));
+    };
+    let path = Path::parse(raw)?;
+    let Value::Number(increment) = parse_value(increment)? else {
+        return Err("expected a number as the increment".to_string());
+    };
+
+    let result = db.json_write(key, &command("JSON.NUMINCRBY", args), |doc| {
+        let doc = doc.as_mut().ok_or(NO_KEY)?;
+        let mut results = Vec::new();
+        for location in path.find(doc) {
+            let Some(target) = json::get_mut(doc, &location) else { continue };
+            match target {
+                Value::Number(number) => {
+                    let sum = add_numbers(number, &increment).ok_or("result is not a number or infinity")?;
+                    *target = Value::Number(sum.clone());
+                    results.push(Value::Number(sum));
+                }
+                other if path.is_legacy() => return Err(wrong_type("a number", other)),
+                _ => results.push(Value::Null),
+            }
+        }
+        let changed = results.iter().any(Value::is_number);
+        let reply = if path.is_legacy() {
+            results.first().map(Value::to_string).ok_or_else(|| missing_path(raw))?
+        } else {
+            Value::Array(results).to_string()
+        };
+        Ok((resp::bulk(&reply), changed))
+    });
+    prefixed(result)
+}
+
+// Runs `f` on every array the path matches, collecting the replies. Matches that
+// aren't arrays get a null reply, or an error for a legacy path.
+fn on_arrays(
+    doc: &mut Value,
+    path: &Path,
+    raw: &str,
+    mut f: impl FnMut(&mut Vec<Value>) -> String,
+) -> Result<String, String> {
+    let locations: Vec<Location> = path.find(doc);
+    let mut replies = Vec::with_capacity(locations.len());
+    for location in locations {
+        match json::get_mut(doc, &location) {
+            Some(Value::Array(items)) => replies.push(f(items)),
+            Some(other) if path.is_legacy() => return Err(wrong_type("array", other)),
+            _ => replies.push(resp::null_bulk()),
+        }
+    }
+    per_match(path, raw, replies)
+}
+
+// JSON.ARRAPPEND key path value [value ...]. Replies with the new lengths.
+pub fn json_arrappend(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key, raw, values @ ..] = args else {
+        return Err(wrong_args("json.arrappend"));
+    };
+    if values.is_empty() {
+        return Err(wrong_args("json.arrappend"));
+    }
+    let path = Path::parse(raw)?;
+    let values = values.iter().map(|value| parse_value(value)).collect::<Result<Vec<_>, _>>()?;
+
+    let result = db.json_write(key, &command("JSON.ARRAPPEND", args), |doc| {
+        let doc = doc.as_mut().ok_or(NO_KEY)?;
+        let mut changed = false;
+        let reply = on_arrays(doc, &path, raw, |items| {
+            items.extend(values.iter().cloned());
+            changed = true;
+            resp::integer(items.len() as i64)
+        })?;
+        Ok((reply, changed))
+    });
+    prefixed(result)
+}
+
+// JSON.ARRPOP key [path [index]]. Out of range indexes pop the first or last
+// element; empty arrays give null.
+pub fn json_arrpop(db: &Database, args: &[&str]) -> Result<String, String> {
+    let (key, raw, index) = match args {
+        [key] => (key, ".", -1),
+        [key, path] => (key, *path, -1),
+        [key, path, index] => {
+            let index = index.parse::<i64>().map_err(|_| "value is not an integer or out of range")?;
+            (key, *path, index)
+        }
+        _ => return Err(wrong_args("json.arrpop")),
+    };
+    let path = Path::parse(raw)?;
+
+    let result = db.json_write(key, &command("JSON.ARRPOP", args), |doc| {
+        let doc = doc.as_mut().ok_or(NO_KEY)?;
+        let mut changed = false;
+        let reply = on_arrays(doc, &path, raw, |items| {
+            if items.is_empty() {
+                return resp::null_bulk();
+            }
+            let len = items.len() as i64;
+            let index = if index < 0 { len + index } else { index }.clamp(0, len - 1);
+            changed = true;
+            resp::bulk(&items.remove(index as usize).to_string())
+        })?;
+        Ok((reply, changed))
+    });
+    prefixed(result)
+}
+
+// JSON.OBJKEYS key [path]
+pub fn json_objkeys(db: &Database, args: &[&str]) -> Result<String, String> {
+    let (key, raw) = match args {
+        [key] => (key, "."),
+        [key, path] => (key, *path),
+        _ => return Err(wrong_args("json.objkeys")),
+    };
+    let path = Path::parse(raw)?;
+
+    let result = db.json_read(key, |doc| {
+        let mut replies = Vec::new();
+        for location in path.find(doc) {
+            match json::get(doc, &location) {
+                Some(Value::Object(map)) => {
+                    let keys: Vec<String> = map.keys().map(|key| resp::bulk(key)).collect();
+                    replies.push(resp::array(&keys));
+                }
+                Some(other) if path.is_legacy() => return Err(wrong_type("object", other)),
+                _ => replies.push(resp::null_bulk()),
+            }
+        }
+        per_match(&path, raw, replies)
+    });
+    prefixed(result.unwrap_or_else(|| Ok(resp::null_bulk())))
+}
+
+// JSON.TYPE key [path]
+pub fn json_type(db: &Database, args: &[&str]) -> Result<String, String> {
+    let (key, raw) = match args {
+        [key] => (key, "."),
+        [key, path] => (key, *path),
+        _ => return Err(wrong_args("json.type")),
+    };
+    let path = Path::parse(raw)?;
+
+    let reply = db.json_read(key, |doc| {
+        let replies: Vec<String> = path
+            .find(doc)
+            .iter()
+            .filter_map(|location| json::get(doc, location))
+            .map(|value| resp::bulk(json::type_name(value)))
+            .collect();
+        match path.is_legacy() {
+            true => replies.into_iter().next().unwrap_or_else(resp::null_bulk),
+            false => resp::array(&replies),
+        }
+    });
+    Ok(reply.unwrap_or_else(resp::null_bulk))
+}
+
+// JSON.MGET key [key ...] path. Missing keys, and legacy paths that match
+// nothing, give null.
+pub fn json_mget(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [keys @ .., raw] = args else {
+        return Err(wrong_args("json.mget"));
+    };
+    if keys.is_empty() {
+        return Err(wrong_args("json.mget"));
+    }
+    let path = Path::parse(raw)?;
+
+    let replies: Vec<String> = keys
+        .iter()
+        .map(|key| match db.json_read(key, |doc| json::matches(doc, &path)).flatten() {
+            Some(value) => resp::bulk(&value.to_string()),
+            None => resp::null_bulk(),
+        })
+        .collect();
+    Ok(resp::array(&replies))
+}
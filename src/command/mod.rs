@@ -1,41 +1,261 @@
+mod bitmap;
+mod bloom;
+mod client;
+mod cluster;
+mod countmin;
+mod dump;
+mod geo;
+mod hyperloglog;
+mod json;
+mod monitor;
+mod pubsub;
+mod replication;
+mod scripting;
+mod search;
+mod sketch;
+mod slowlog;
+mod stream;
+mod timeseries;
+mod topk;
+pub mod resp;
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use tokio::sync::RwLockReadGuard;
+
+use crate::client::{Client, ClientClass};
+use crate::database::stats::Stats;
 use crate::database::Database;
+use crate::info;
+use crate::scripting::BUSY;
+use crate::shutdown;
+use crate::tracking::CURRENT_CLIENT;
 
-pub async fn command_parser(db: &Database, command: &str) -> Result<String, String> {
-    let splitted_command: Vec<&str> = command.split_whitespace().collect();
-    
-    match splitted_command.as_slice() {
-        // String operations
-        ["SET", key, value, "EX", ttl] => {
-            let ttl = ttl.parse::<u64>().map_err(|_| "Invalid TTL value")?;
-            db.set(key.to_string(), value.to_string(), Some(ttl)).await;
-            Ok("+OK\r\n".to_string())
-        }
-        ["SET", key, value] => {
-            db.set(key.to_string(), value.to_string(), None).await;
-            Ok("+OK\r\n".to_string())
-        }
-        ["GET", key] => {
-            if let Some(value) = db.get(key).await {
-                Ok(format!("${}\r\n{}\r\n", value.len(), value))
-            } else {
-                Ok("$-1\r\n".to_string())
+const UNKNOWN_COMMAND: &str = "-ERR Unknown command\r\n";
+const READONLY: &str = "-READONLY You can't write against a read only replica.\r\n";
+
+// Commands that modify the keyspace, held back by CLIENT PAUSE WRITE
+const WRITE_COMMANDS: [&str; 58] = [
+    "SET", "DEL", "LPUSH", "RPUSH", "LPOP", "RPOP", "SADD", "SREM", "ZADD", "ZREM", "XADD", "XDEL", "XTRIM",
+    "XSETID", "XGROUP", "XREADGROUP", "XACK", "XCLAIM", "XAUTOCLAIM", "PFADD", "PFMERGE", "SETBIT", "BITOP",
+    "BITFIELD", "GEOADD", "GEOSEARCHSTORE", "JSON.SET", "JSON.DEL", "JSON.FORGET", "JSON.NUMINCRBY",
+    "JSON.ARRAPPEND", "JSON.ARRPOP", "FT.CREATE", "FT.DROPINDEX", "TS.CREATE", "RESTORE",
+    "TS.ADD", "TS.MADD", "TS.CREATERULE", "TS.DELETERULE", "BF.RESERVE", "BF.ADD", "BF.MADD", "BF.LOADCHUNK",
+    "CF.RESERVE", "CF.ADD", "CF.ADDNX", "CF.DEL", "CF.LOADCHUNK", "CMS.INITBYDIM", "CMS.INITBYPROB", "CMS.INCRBY",
+    "CMS.MERGE", "CMS.LOADCHUNK", "TOPK.RESERVE", "TOPK.ADD", "TOPK.INCRBY", "TOPK.LOADCHUNK",
+];
+
+pub fn is_write_command(name: &str) -> bool {
+    WRITE_COMMANDS.iter().any(|cmd| cmd.eq_ignore_ascii_case(name))
+}
+
+// Whether CLIENT PAUSE WRITE holds the command back. Besides the write commands this
+// covers scripts that may write and PUBLISH, as in Redis.
+pub fn is_paused_by_write(name: &str) -> bool {
+    is_write_command(name) || ["EVAL", "EVALSHA", "PUBLISH"].iter().any(|cmd| cmd.eq_ignore_ascii_case(name))
+}
+
+// The checks a write from the HTTP API goes through, the same ones RESP writes
+// get: it waits out CLIENT PAUSE WRITE, is refused on a read-only replica and for
+// keys another cluster node serves, and waits while a script runs. The guard keeps
+// scripts out until the write is done. Errors are RESP error lines.
+pub async fn admit_write<'a>(db: &'a Database, key: &str) -> Result<RwLockReadGuard<'a, ()>, String> {
+    db.clients().wait_if_paused(true).await;
+    if db.replication().is_replica() && db.config().replica_read_only {
+        return Err(READONLY.to_string());
+    }
+    if db.cluster().enabled() {
+        if let Some(redirect) = db.cluster().route(&[key], false, |key| db.exists(key)) {
+            return Err(redirect);
+        }
+    }
+    db.scripting().enter().await.ok_or_else(|| BUSY.to_string())
+}
+
+pub async fn command_parser(db: &Database, client: &Arc<Client>, args: &[Vec<u8>]) -> Result<Vec<u8>, String> {
+    let args: Vec<&[u8]> = args.iter().map(Vec::as_slice).collect();
+    Stats::incr(&db.stats().total_commands_processed);
+    monitor::feed(db, &client.addr, &args);
+    let splitted_command = text_args(&args)?;
+
+    // Subscribed clients only manage their subscriptions
+    if let Some(error) = splitted_command.first().and_then(|name| pubsub::check_subscribed(db, client, name)) {
+        return Err(error);
+    }
+
+    // Only the master may write to a read-only replica
+    if let Some(name) = splitted_command.first() {
+        if is_write_command(name)
+            && client.class() != ClientClass::Master
+            && db.replication().is_replica()
+            && db.config().replica_read_only
+        {
+            return Ok(READONLY.as_bytes().to_vec());
+        }
+    }
+
+    // In cluster mode keys in slots served elsewhere are redirected. The master link
+    // and ASKING'd commands for importing slots are let through.
+    if db.cluster().enabled() && splitted_command.first() != Some(&"ASKING") {
+        let asking = client.take_asking();
+        let keys = command_keys(&splitted_command);
+        if !keys.is_empty() && client.class() != ClientClass::Master {
+            if let Some(redirect) = db.cluster().route(&keys, asking, |key| db.exists(key)) {
+                return Ok(redirect.into_bytes());
             }
         }
-        ["DEL", key] => {
-            if db.delete(key).await {
-                Ok(":1\r\n".to_string()) 
-            } else {
-                Ok(":0\r\n".to_string()) 
+    }
+
+    // Commands wait while a script runs, or get -BUSY once it runs too long.
+    // Scripts, MIGRATE and blocking reads take the keyspace themselves when they need it.
+    // WAIT parks on replicas, so holding the keyspace would stall a script behind it.
+    let runs_script = splitted_command.first().is_some_and(|name| scripting::is_script_command(name));
+    let migrates = splitted_command.first() == Some(&"MIGRATE");
+    let waits = splitted_command.first() == Some(&"WAIT");
+    let skip_exec = runs_script || migrates || waits || stream::is_blocking(&splitted_command);
+    let _exec = if skip_exec || scripting::allowed_while_busy(&splitted_command) {
+        None
+    } else {
+        match db.scripting().enter().await {
+            Some(exec) => Some(exec),
+            None => return Ok(BUSY.as_bytes().to_vec()),
+        }
+    };
+
+    // CLIENT CACHING sets up the command after it, everything else is tracked.
+    // Reads are tracked before they run, so a write racing with one still
+    // invalidates the key.
+    if !matches!(splitted_command[..], ["CLIENT", subcommand, ..] if subcommand.eq_ignore_ascii_case("CACHING")) {
+        let read_keys = match splitted_command.first() {
+            Some(name) if !is_write_command(name) => command_keys(&splitted_command),
+            _ => Vec::new(),
+        };
+        db.tracking().track_reads(client.id, &read_keys);
+    }
+
+    let start = Instant::now();
+    let result = call(db, client, &args, &splitted_command).await;
+    // Time spent blocked waiting for data isn't slowness
+    if !stream::is_blocking(&splitted_command) {
+        slowlog::record(db, client, &args, start.elapsed());
+    }
+    result
+}
+
+// redis.call from a script, which already holds the keyspace
+pub async fn script_call(db: &Database, client: &Arc<Client>, args: &[Vec<u8>]) -> Result<Vec<u8>, String> {
+    let args: Vec<&[u8]> = args.iter().map(Vec::as_slice).collect();
+    Stats::incr(&db.stats().total_commands_processed);
+    monitor::feed(db, "lua", &args);
+    let splitted_command = text_args(&args)?;
+    let splitted_command = stream::without_block(&splitted_command);
+    call(db, client, &args, &splitted_command).await
+}
+
+// Commands take text arguments, except for string values: SET's value and
+// RESTORE's payload may hold any bytes and are read from the raw arguments
+fn text_args<'a>(args: &[&'a [u8]]) -> Result<Vec<&'a str>, String> {
+    let binary_value = match args {
+        [b"SET", _, _, ..] => Some(2),
+        [b"RESTORE", _, _, _, ..] => Some(3),
+        _ => None,
+    };
+    args.iter()
+        .enumerate()
+        .map(|(i, arg)| match std::str::from_utf8(arg) {
+            Ok(arg) => Ok(arg),
+            Err(_) if binary_value == Some(i) => Ok(""),
+            Err(_) => Err("invalid UTF-8 in command arguments".to_string()),
+        })
+        .collect()
+}
+
+async fn call(
+    db: &Database,
+    client: &Arc<Client>,
+    args: &[&[u8]],
+    splitted_command: &[&str],
+) -> Result<Vec<u8>, String> {
+    let start = Instant::now();
+    let result = CURRENT_CLIENT
+        .scope(client.id, async {
+            match execute_binary(db, client, args, splitted_command).await {
+                Some(result) => result,
+                None => execute(db, client, splitted_command).await.map(String::into_bytes),
+            }
+        })
+        .await;
+
+    // Only known commands get per-command stats, so arbitrary input can't add labels
+    let known = !matches!(&result, Ok(response) if response == UNKNOWN_COMMAND.as_bytes());
+    if let (true, Some(name)) = (known, splitted_command.first()) {
+        db.stats().record_command(name, start.elapsed());
+    }
+    // WRONGTYPE goes out as is, the rest get -ERR from the connection
+    match result {
+        Err(e) if e.starts_with("WRONGTYPE ") => Ok(format!("-{}\r\n", e).into_bytes()),
+        result => result,
+    }
+}
+
+// String operations and scripts, the only commands whose replies may be binary
+async fn execute_binary(
+    db: &Database,
+    client: &Arc<Client>,
+    args: &[&[u8]],
+    splitted_command: &[&str],
+) -> Option<Result<Vec<u8>, String>> {
+    match splitted_command {
+        ["SET", key, _, "EX", ttl] => {
+            let ttl = match ttl.parse::<u64>() {
+                Ok(ttl) => ttl,
+                Err(_) => return Some(Err("Invalid TTL value".to_string())),
+            };
+            db.set(key.to_string(), args[2].to_vec(), Some(ttl)).await;
+            Some(Ok(b"+OK\r\n".to_vec()))
+        }
+        ["SET", key, _] => {
+            db.set(key.to_string(), args[2].to_vec(), None).await;
+            Some(Ok(b"+OK\r\n".to_vec()))
+        }
+        ["GET", key] => match db.get(key).await {
+            Some(value) => Some(Ok(resp::bulk_bytes(&value))),
+            None => Some(Ok(b"$-1\r\n".to_vec())),
+        },
+        ["DUMP", key] => Some(dump::dump(db, key).await),
+        ["RESTORE", ..] => Some(dump::restore(db, args, splitted_command).await),
+        ["EVAL", args @ ..] => Some(scripting::eval(db, client, args, false).await),
+        ["EVAL_RO", args @ ..] => Some(scripting::eval(db, client, args, true).await),
+        ["EVALSHA", args @ ..] => Some(scripting::evalsha(db, client, args, false).await),
+        ["EVALSHA_RO", args @ ..] => Some(scripting::evalsha(db, client, args, true).await),
+        _ => None,
+    }
+}
+
+async fn execute(db: &Database, client: &Arc<Client>, splitted_command: &[&str]) -> Result<String, String> {
+    match splitted_command {
+        ["DEL", keys @ ..] if !keys.is_empty() => {
+            let mut deleted = 0;
+            for key in keys {
+                if db.delete(key).await {
+                    deleted += 1;
+                }
             }
+            Ok(format!(":{}\r\n", deleted))
+        }
+        ["EXISTS", keys @ ..] if !keys.is_empty() => {
+            let count = keys.iter().filter(|key| db.exists(key)).count();
+            Ok(format!(":{}\r\n", count))
         }
 
         // List operations
         ["LPUSH", key, value] => {
-            let len = db.lpush(key.to_string(), value.to_string()).await;
+            let len = db.lpush(key.to_string(), value.to_string()).await?;
             Ok(format!(":{}\r\n", len))
         }
         ["RPUSH", key, value] => {
-            let len = db.rpush(key.to_string(), value.to_string()).await;
+            let len = db.rpush(key.to_string(), value.to_string()).await?;
             Ok(format!(":{}\r\n", len))
         }
         ["LPOP", key] => {
@@ -68,7 +288,7 @@ pub async fn command_parser(db: &Database, command: &str) -> Result<String, Stri
 
         // Set operations
         ["SADD", key, value] => {
-            let added = db.sadd(key.to_string(), value.to_string()).await;
+            let added = db.sadd(key.to_string(), value.to_string()).await?;
             Ok(format!(":{}\r\n", if added { 1 } else { 0 }))
         }
         ["SREM", key, value] => {
@@ -94,7 +314,7 @@ pub async fn command_parser(db: &Database, command: &str) -> Result<String, Stri
         // Sorted Set operations
         ["ZADD", key, score, member] => {
             let score = score.parse::<f64>().map_err(|_| "Invalid score")?;
-            let added = db.zadd(key.to_string(), score, member.to_string()).await;
+            let added = db.zadd(key.to_string(), score, member.to_string()).await?;
             Ok(format!(":{}\r\n", if added { 1 } else { 0 }))
         }
         ["ZREM", key, member] => {
@@ -122,9 +342,222 @@ pub async fn command_parser(db: &Database, command: &str) -> Result<String, Stri
             }
         }
 
+        // Geo operations
+        ["GEOADD", args @ ..] => geo::geoadd(db, args),
+        ["GEOPOS", args @ ..] => geo::geopos(db, args),
+        ["GEODIST", args @ ..] => geo::geodist(db, args),
+        ["GEOHASH", args @ ..] => geo::geohash(db, args),
+        ["GEOSEARCH", args @ ..] => geo::geosearch(db, args),
+        ["GEOSEARCHSTORE", args @ ..] => geo::geosearchstore(db, args),
+
+        // JSON operations
+        ["JSON.SET", args @ ..] => json::json_set(db, args),
+        ["JSON.GET", args @ ..] => json::json_get(db, args),
+        ["JSON.DEL" | "JSON.FORGET", args @ ..] => json::json_del(db, args).await,
+        ["JSON.NUMINCRBY", args @ ..] => json::json_numincrby(db, args),
+        ["JSON.ARRAPPEND", args @ ..] => json::json_arrappend(db, args),
+        ["JSON.ARRPOP", args @ ..] => json::json_arrpop(db, args),
+        ["JSON.OBJKEYS", args @ ..] => json::json_objkeys(db, args),
+        ["JSON.TYPE", args @ ..] => json::json_type(db, args),
+        ["JSON.MGET", args @ ..] => json::json_mget(db, args),
+
+        // Time series operations
+        ["TS.CREATE", args @ ..] => timeseries::ts_create(db, args),
+        ["TS.ADD", args @ ..] => timeseries::ts_add(db, args),
+        ["TS.MADD", args @ ..] => timeseries::ts_madd(db, args),
+        ["TS.GET", args @ ..] => timeseries::ts_get(db, args),
+        ["TS.RANGE", args @ ..] => timeseries::ts_range(db, args, false),
+        ["TS.REVRANGE", args @ ..] => timeseries::ts_range(db, args, true),
+        ["TS.MRANGE", args @ ..] => timeseries::ts_mrange(db, args, false),
+        ["TS.MREVRANGE", args @ ..] => timeseries::ts_mrange(db, args, true),
+        ["TS.CREATERULE", args @ ..] => timeseries::ts_createrule(db, args),
+        ["TS.DELETERULE", args @ ..] => timeseries::ts_deleterule(db, args),
+        ["TS.INFO", args @ ..] => timeseries::ts_info(db, args),
+
+        // Bloom and Cuckoo filters
+        ["BF.RESERVE", args @ ..] => bloom::bf_reserve(db, args),
+        ["BF.ADD", args @ ..] => bloom::bf_add(db, args),
+        ["BF.MADD", args @ ..] => bloom::bf_madd(db, args),
+        ["BF.EXISTS", args @ ..] => bloom::bf_exists(db, args),
+        ["BF.MEXISTS", args @ ..] => bloom::bf_mexists(db, args),
+        ["BF.INFO", args @ ..] => bloom::bf_info(db, args),
+        ["BF.SCANDUMP", args @ ..] => sketch::scandump(db, args, "BF"),
+        ["BF.LOADCHUNK", args @ ..] => sketch::loadchunk(db, args, "BF"),
+        ["CF.RESERVE", args @ ..] => bloom::cf_reserve(db, args),
+        ["CF.ADD", args @ ..] => bloom::cf_add(db, args, false),
+        ["CF.ADDNX", args @ ..] => bloom::cf_add(db, args, true),
+        ["CF.DEL", args @ ..] => bloom::cf_del(db, args),
+        ["CF.EXISTS", args @ ..] => bloom::cf_exists(db, args),
+        ["CF.MEXISTS", args @ ..] => bloom::cf_mexists(db, args),
+        ["CF.COUNT", args @ ..] => bloom::cf_count(db, args),
+        ["CF.INFO", args @ ..] => bloom::cf_info(db, args),
+        ["CF.SCANDUMP", args @ ..] => sketch::scandump(db, args, "CF"),
+        ["CF.LOADCHUNK", args @ ..] => sketch::loadchunk(db, args, "CF"),
+
+        // Count-Min sketches and Top-K
+        ["CMS.INITBYDIM", args @ ..] => countmin::cms_initbydim(db, args),
+        ["CMS.INITBYPROB", args @ ..] => countmin::cms_initbyprob(db, args),
+        ["CMS.INCRBY", args @ ..] => countmin::cms_incrby(db, args),
+        ["CMS.QUERY", args @ ..] => countmin::cms_query(db, args),
+        ["CMS.MERGE", args @ ..] => countmin::cms_merge(db, args),
+        ["CMS.INFO", args @ ..] => countmin::cms_info(db, args),
+        ["CMS.SCANDUMP", args @ ..] => sketch::scandump(db, args, "CMS"),
+        ["CMS.LOADCHUNK", args @ ..] => sketch::loadchunk(db, args, "CMS"),
+        ["TOPK.RESERVE", args @ ..] => topk::topk_reserve(db, args),
+        ["TOPK.ADD", args @ ..] => topk::topk_add(db, args),
+        ["TOPK.INCRBY", args @ ..] => topk::topk_incrby(db, args),
+        ["TOPK.QUERY", args @ ..] => topk::topk_query(db, args),
+        ["TOPK.LIST", args @ ..] => topk::topk_list(db, args),
+        ["TOPK.INFO", args @ ..] => topk::topk_info(db, args),
+        ["TOPK.SCANDUMP", args @ ..] => sketch::scandump(db, args, "TOPK"),
+        ["TOPK.LOADCHUNK", args @ ..] => sketch::loadchunk(db, args, "TOPK"),
+
+        // Search
+        ["FT.CREATE", args @ ..] => search::ft_create(db, args),
+        ["FT.SEARCH", args @ ..] => search::ft_search(db, args),
+        ["FT.DROPINDEX", args @ ..] => search::ft_dropindex(db, args).await,
+        ["FT.INFO", args @ ..] => search::ft_info(db, args),
+        ["FT._LIST"] => Ok(search::ft_list(db)),
+
+        // Bitmap operations
+        ["SETBIT", args @ ..] => bitmap::setbit(db, args),
+        ["GETBIT", args @ ..] => bitmap::getbit(db, args).await,
+        ["BITCOUNT", args @ ..] => bitmap::bitcount(db, args).await,
+        ["BITPOS", args @ ..] => bitmap::bitpos(db, args).await,
+        ["BITOP", args @ ..] => bitmap::bitop(db, args).await,
+        ["BITFIELD", args @ ..] => bitmap::bitfield(db, args, false).await,
+        ["BITFIELD_RO", args @ ..] => bitmap::bitfield(db, args, true).await,
+
+        // HyperLogLog operations
+        ["PFADD", args @ ..] => hyperloglog::pfadd(db, args),
+        ["PFCOUNT", keys @ ..] if !keys.is_empty() => hyperloglog::pfcount(db, keys).await,
+        ["PFMERGE", args @ ..] => hyperloglog::pfmerge(db, args).await,
+
+        // Stream operations
+        ["XADD", args @ ..] => stream::xadd(db, args),
+        ["XTRIM", args @ ..] => stream::xtrim(db, args),
+        ["XDEL", args @ ..] => stream::xdel(db, args),
+        ["XLEN", key] => Ok(stream::xlen(db, key)),
+        ["XRANGE", args @ ..] => stream::xrange(db, args, false),
+        ["XREVRANGE", args @ ..] => stream::xrange(db, args, true),
+        ["XREAD", args @ ..] => stream::xread(db, client, args).await,
+        ["XREADGROUP", args @ ..] => stream::xreadgroup(db, client, args).await,
+        ["XGROUP", args @ ..] => stream::xgroup(db, args),
+        ["XACK", args @ ..] => stream::xack(db, args),
+        ["XPENDING", args @ ..] => stream::xpending(db, args),
+        ["XCLAIM", args @ ..] => stream::xclaim(db, args),
+        ["XAUTOCLAIM", args @ ..] => stream::xautoclaim(db, args),
+        ["XSETID", args @ ..] => stream::xsetid(db, args),
+        ["XINFO", args @ ..] => stream::xinfo(db, args),
+
         // Ping/Pong for testing
         ["PING"] => Ok("+PONG\r\n".to_string()),
+
+        // Connection management
+        ["CLIENT", args @ ..] => client::client_command(db, client, args).await,
+        ["HELLO", args @ ..] => client::hello(db, client, args),
+
+        // Pub/sub
+        ["SUBSCRIBE", channels @ ..] => pubsub::subscribe(db, client, channels),
+        ["UNSUBSCRIBE", channels @ ..] => pubsub::unsubscribe(db, client, channels),
+        ["PSUBSCRIBE", patterns @ ..] => pubsub::psubscribe(db, client, patterns),
+        ["PUNSUBSCRIBE", patterns @ ..] => pubsub::punsubscribe(db, client, patterns),
+        ["PUBLISH", args @ ..] => pubsub::publish(db, args),
+        ["PUBSUB", args @ ..] => pubsub::pubsub_command(db, args),
+
+        // Server management
+        ["SHUTDOWN", args @ ..] => shutdown_command(db, args).await,
+        ["MONITOR"] => monitor::monitor(db, client),
+        ["SLOWLOG", args @ ..] => slowlog::slowlog_command(db, args),
+        ["LATENCY", args @ ..] => slowlog::latency_command(db, client, args),
+
+        // Replication
+        ["REPLICAOF" | "SLAVEOF", args @ ..] => replication::replicaof(db, args),
+        ["ROLE"] => Ok(replication::role(db)),
+        ["REPLCONF", args @ ..] => replication::replconf(db, client, args),
+        ["PSYNC", replid, offset] => replication::psync(db, client, replid, offset).await,
+        ["WAIT", numreplicas, timeout] => replication::wait(db, numreplicas, timeout).await,
+
+        // Cluster
+        ["CLUSTER", args @ ..] => cluster::cluster_command(db, args).await,
+        ["ASKING"] => {
+            if !db.cluster().enabled() {
+                return Err(cluster::CLUSTER_DISABLED.to_string());
+            }
+            client.set_asking();
+            Ok(resp::ok())
+        }
+        ["MIGRATE", args @ ..] => cluster::migrate_command(db, args).await,
+
+        // Scripting
+        ["SCRIPT", args @ ..] => scripting::script_command(db, args),
+
+        // Server introspection
+        ["INFO"] => {
+            let text = info::render(&info::collect(db, None));
+            Ok(format!("${}\r\n{}\r\n", text.len(), text))
+        }
+        ["INFO", section] => {
+            let text = info::render(&info::collect(db, Some(section)));
+            Ok(format!("${}\r\n{}\r\n", text.len(), text))
+        }
         
-        _ => Ok("-ERR Unknown command\r\n".to_string()),
+        _ => Ok(UNKNOWN_COMMAND.to_string()),
     }   
-}
\ No newline at end of file
+}
+
+// The keys a command reads or writes, for cluster routing. Commands without keys
+// return none and always run locally.
+pub fn command_keys<'a>(args: &[&'a str]) -> Vec<&'a str> {
+    match args {
+        ["DEL" | "EXISTS" | "PFCOUNT" | "PFMERGE", keys @ ..] => keys.to_vec(),
+        ["EVAL" | "EVALSHA" | "EVAL_RO" | "EVALSHA_RO", _, args @ ..] => {
+            scripting::split_keys(args).map(|(keys, _)| keys.to_vec()).unwrap_or_default()
+        }
+        ["BITOP", _, keys @ ..] => keys.to_vec(),
+        ["GEOSEARCHSTORE", dest, source, ..] => vec![dest, source],
+        ["JSON.MGET", keys @ .., _] => keys.to_vec(),
+        ["TS.MADD", args @ ..] => args.iter().step_by(3).copied().collect(),
+        ["TS.CREATERULE" | "TS.DELETERULE", source, dest, ..] => vec![source, dest],
+        ["CMS.MERGE", dest, count, rest @ ..] => {
+            let count = count.parse::<usize>().unwrap_or(0).min(rest.len());
+            std::iter::once(*dest).chain(rest[..count].iter().copied()).collect()
+        }
+        [
+            "SET" | "GET" | "DUMP" | "RESTORE" | "SETBIT" | "GETBIT" | "BITCOUNT" | "BITPOS" | "BITFIELD" | "BITFIELD_RO" | "PFADD"
+            | "LPUSH" | "RPUSH" | "LPOP" | "RPOP" | "LRANGE" | "SADD" | "SREM" | "SISMEMBER" | "SMEMBERS" | "ZADD"
+            | "ZREM" | "ZRANGE" | "ZSCORE" | "GEOADD" | "GEOPOS" | "GEODIST" | "GEOHASH" | "GEOSEARCH" | "XADD"
+            | "XTRIM" | "XDEL" | "XLEN" | "XRANGE" | "XREVRANGE" | "XACK" | "XPENDING" | "XCLAIM" | "XAUTOCLAIM"
+            | "XSETID" | "JSON.SET" | "JSON.GET" | "JSON.DEL" | "JSON.FORGET" | "JSON.NUMINCRBY" | "JSON.ARRAPPEND"
+            | "JSON.ARRPOP" | "JSON.OBJKEYS" | "JSON.TYPE" | "TS.CREATE" | "TS.ADD" | "TS.GET" | "TS.RANGE"
+            | "TS.REVRANGE" | "TS.INFO" | "BF.RESERVE" | "BF.ADD" | "BF.MADD" | "BF.EXISTS" | "BF.MEXISTS"
+            | "BF.INFO" | "BF.SCANDUMP" | "BF.LOADCHUNK" | "CF.RESERVE" | "CF.ADD" | "CF.ADDNX" | "CF.DEL"
+            | "CF.EXISTS" | "CF.MEXISTS" | "CF.COUNT" | "CF.INFO" | "CF.SCANDUMP" | "CF.LOADCHUNK" | "CMS.INITBYDIM"
+            | "CMS.INITBYPROB" | "CMS.INCRBY" | "CMS.QUERY" | "CMS.INFO" | "CMS.SCANDUMP" | "CMS.LOADCHUNK"
+            | "TOPK.RESERVE" | "TOPK.ADD" | "TOPK.INCRBY" | "TOPK.QUERY" | "TOPK.LIST" | "TOPK.INFO"
+            | "TOPK.SCANDUMP" | "TOPK.LOADCHUNK",
+            key,
+            ..,
+        ] => vec![key],
+        ["XGROUP" | "XINFO", _, key, ..] => vec![key],
+        ["XREAD" | "XREADGROUP", ..] => stream::read_keys(args),
+        _ => Vec::new(),
+    }
+}
+
+// SHUTDOWN [NOSAVE] [NOW]. On success there is no reply, the connection closes
+// along with the others. SAVE and FORCE are refused since nothing is persisted.
+async fn shutdown_command(db: &Database, args: &[&str]) -> Result<String, String> {
+    let mut now = false;
+    for arg in args {
+        match arg.to_uppercase().as_str() {
+            "NOSAVE" => {}
+            "NOW" => now = true,
+            "SAVE" | "FORCE" => return Err(format!("{} is not supported without persistence", arg.to_uppercase())),
+            _ => return Err("syntax error".to_string()),
+        }
+    }
+
+    shutdown::request(db, now).await;
+    Ok(String::new())
+}
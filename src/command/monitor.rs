@@ -0,0 +1,68 @@
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::client::Client;
+use crate::command::resp;
+use crate::database::Database;
+
+const REDACTED: &[u8] = b"(redacted)";
+
+// MONITOR: the client only listens from now on
+pub fn monitor(db: &Database, client: &Arc<Client>) -> Result<String, String> {
+    db.clients().add_monitor(client);
+    Ok(resp::ok())
+}
+
+// Sends monitors a line for the command, such as
+// `+1700000000.123456 [0 127.0.0.1:50000] "SET" "key" "value"`. `source` is
+// the client address, or "lua" for commands run by scripts.
+pub fn feed(db: &Database, source: &str, args: &[&[u8]]) {
+    if !db.clients().has_monitors() {
+        return;
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let mut line = format!("+{}.{:06} [0 {}]", now.as_secs(), now.subsec_micros(), source);
+    for arg in redacted(args) {
+        line.push(' ');
+        line.push_str(&quoted(arg));
+    }
+    line.push_str("\r\n");
+    db.clients().feed_monitors(&line);
+}
+
+// Credentials never reach monitors: every AUTH argument, and the user name and
+// password of HELLO's AUTH option
+pub fn redacted<'a>(args: &[&'a [u8]]) -> Vec<&'a [u8]> {
+    let mut args = args.to_vec();
+    match args.first() {
+        Some(name) if name.eq_ignore_ascii_case(b"AUTH") => args[1..].fill(REDACTED),
+        Some(name) if name.eq_ignore_ascii_case(b"HELLO") => {
+            if let Some(auth) = args.iter().position(|arg| arg.eq_ignore_ascii_case(b"AUTH")) {
+                let end = (auth + 3).min(args.len());
+                args[auth + 1..end].fill(REDACTED);
+            }
+        }
+        _ => {}
+    }
+    args
+}
+
+// Double quoted with escapes, so every argument fits on the one line
+fn quoted(arg: &[u8]) -> String {
+    let mut out = String::from("\"");
+    for &byte in arg {
+        match byte {
+            b'\\' => out.push_str("\\\\"),
+            b'"' => out.push_str("\\\""),
+            b'\n' => out.push_str("\\n"),
+            b'\r' => out.push_str("\\r"),
+            b'\t' => out.push_str("\\t"),
+            0x07 => out.push_str("\\a"),
+            0x08 => out.push_str("\\b"),
+            0x20..=0x7e => out.push(byte as char),
+            _ => out.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,142 @@
+use std::sync::Arc;
+
+use crate::client::{Client, ClientClass};
+use crate::command::resp;
+use crate::database::Database;
+use crate::pubsub::out_of_band;
+
+// Commands a client may still send once it has subscribed to something
+const SUBSCRIBED_COMMANDS: [&str; 5] = ["SUBSCRIBE", "UNSUBSCRIBE", "PSUBSCRIBE", "PUNSUBSCRIBE", "PING"];
+
+fn wrong_args(command: &str) -> String {
+    format!("wrong number of arguments for '{}' command", command)
+}
+
+// The error for commands a subscribed client can't run, if this is one. RESP3
+// tells messages from replies, so those clients may run anything.
+pub fn check_subscribed(db: &Database, client: &Client, name: &str) -> Option<String> {
+    if client.protocol() != 2 || client.class() != ClientClass::Pubsub || db.pubsub().count(client.id) == 0 {
+        return None;
+    }
+    if SUBSCRIBED_COMMANDS.iter().any(|allowed| allowed.eq_ignore_ascii_case(name)) {
+        return None;
+    }
+    Some(format!(
+        "Can't execute '{}': only (P)SUBSCRIBE / (P)UNSUBSCRIBE / PING are allowed in this context",
+        name.to_lowercase()
+    ))
+}
+
+// One reply per channel, each carrying the client's subscription count so far
+fn confirmation(client: &Client, kind: &str, channel: Option<&str>, count: usize) -> String {
+    let items = [resp::bulk(kind), channel.map_or_else(resp::null_bulk, resp::bulk), resp::integer(count as i64)];
+    out_of_band(client, &items)
+}
+
+// Subscribers get the pubsub output buffer limits and no idle timeout
+fn update_class(client: &Client, count: usize) {
+    match (client.class(), count) {
+        (ClientClass::Normal, 1..) => client.set_class(ClientClass::Pubsub),
+        (ClientClass::Pubsub, 0) => client.set_class(ClientClass::Normal),
+        _ => {}
+    }
+}
+
+// SUBSCRIBE channel [channel ...]
+pub fn subscribe(db: &Database, client: &Arc<Client>, channels: &[&str]) -> Result<String, String> {
+    if channels.is_empty() {
+        return Err(wrong_args("subscribe"));
+    }
+    let mut replies = String::new();
+    for channel in channels {
+        let count = db.pubsub().subscribe(client, channel);
+        update_class(client, count);
+        replies.push_str(&confirmation(client, "subscribe", Some(channel), count));
+    }
+    Ok(replies)
+}
+
+// PSUBSCRIBE pattern [pattern ...]
+pub fn psubscribe(db: &Database, client: &Arc<Client>, patterns: &[&str]) -> Result<String, String> {
+    if patterns.is_empty() {
+        return Err(wrong_args("psubscribe"));
+    }
+    let mut replies = String::new();
+    for pattern in patterns {
+        let count = db.pubsub().psubscribe(client, pattern);
+        update_class(client, count);
+        replies.push_str(&confirmation(client, "psubscribe", Some(pattern), count));
+    }
+    Ok(replies)
+}
+
+// UNSUBSCRIBE [channel ...], all of them without arguments
+pub fn unsubscribe(db: &Database, client: &Client, channels: &[&str]) -> Result<String, String> {
+    let channels = match channels {
+        [] => db.pubsub().channels_of(client.id),
+        channels => channels.iter().map(|channel| channel.to_string()).collect(),
+    };
+    if channels.is_empty() {
+        return Ok(confirmation(client, "unsubscribe", None, db.pubsub().count(client.id)));
+    }
+    let mut replies = String::new();
+    for channel in &channels {
+        let count = db.pubsub().unsubscribe(client.id, channel);
+        update_class(client, count);
+        replies.push_str(&confirmation(client, "unsubscribe", Some(channel), count));
+    }
+    Ok(replies)
+}
+
+// PUNSUBSCRIBE [pattern ...], all of them without arguments
+pub fn punsubscribe(db: &Database, client: &Client, patterns: &[&str]) -> Result<String, String> {
+    let patterns = match patterns {
+        [] => db.pubsub().patterns_of(client.id),
+        patterns => patterns.iter().map(|pattern| pattern.to_string()).collect(),
+    };
+    if patterns.is_empty() {
+        return Ok(confirmation(client, "punsubscribe", None, db.pubsub().count(client.id)));
+    }
+    let mut replies = String::new();
+    for pattern in &patterns {
+        let count = db.pubsub().punsubscribe(client.id, pattern);
+        update_class(client, count);
+        replies.push_str(&confirmation(client, "punsubscribe", Some(pattern), count));
+    }
+    Ok(replies)
+}
+
+// PUBLISH channel message, replying with the number of receivers. Messages
+// stay on this node, they aren't replicated or sent over the cluster bus.
+pub fn publish(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [channel, message] = args else {
+        return Err(wrong_args("publish"));
+    };
+    Ok(resp::integer(db.pubsub().publish(channel, message) as i64))
+}
+
+// PUBSUB CHANNELS [pattern] | NUMSUB [channel ...] | NUMPAT
+pub fn pubsub_command(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [subcommand, rest @ ..] = args else {
+        return Err(wrong_args("pubsub"));
+    };
+    match (subcommand.to_uppercase().as_str(), rest) {
+        ("CHANNELS", [] | [_]) => {
+            let channels: Vec<String> =
+                db.pubsub().channels(rest.first().copied()).iter().map(|channel| resp::bulk(channel)).collect();
+            Ok(resp::array(&channels))
+        }
+        ("NUMSUB", channels) => {
+            let counts: Vec<String> = channels
+                .iter()
+                .flat_map(|channel| [resp::bulk(channel), resp::integer(db.pubsub().subscribers(channel) as i64)])
+                .collect();
+            Ok(resp::array(&counts))
+        }
+        ("NUMPAT", []) => Ok(resp::integer(db.pubsub().pattern_count() as i64)),
+        _ => Err(format!(
+            "unknown subcommand or wrong number of arguments for 'pubsub|{}'",
+            subcommand.to_lowercase()
+        )),
+    }
+}
@@ -0,0 +1,115 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::client::Client;
+use crate::command::resp;
+use crate::database::Database;
+use crate::replication::replica;
+
+// REPLICAOF host port | REPLICAOF NO ONE (also SLAVEOF)
+pub fn replicaof(db: &Database, args: &[&str]) -> Result<String, String> {
+    match args {
+        [no, one] if no.eq_ignore_ascii_case("NO") && one.eq_ignore_ascii_case("ONE") => {
+            if db.replication().is_replica() {
+                db.replication().promote();
+                println!("Promoted to master, replication id {}", db.replication().replid());
+            }
+            Ok(resp::ok())
+        }
+        [host, port] => {
+            let port = port.parse::<u16>().map_err(|_| "Invalid master port")?;
+            if let Some(master) = db.replication().master() {
+                if master.host == *host && master.port == port {
+                    return Ok("+OK Already connected to specified master\r\n".to_string());
+                }
+            }
+            println!("Replicating from {}:{}", host, port);
+            replica::replicate(db, host.to_string(), port);
+            Ok(resp::ok())
+        }
+        _ => Err("wrong number of arguments for 'replicaof' command".to_string()),
+    }
+}
+
+pub fn role(db: &Database) -> String {
+    let replication = db.replication();
+    match replication.master() {
+        Some(master) => resp::array(&[
+            resp::bulk("slave"),
+            resp::bulk(&master.host),
+            resp::integer(master.port as i64),
+            resp::bulk(master.status.as_str()),
+            resp::integer(replication.offset() as i64),
+        ]),
+        None => {
+            let replicas: Vec<String> = replication
+                .replicas()
+                .iter()
+                .map(|replica| {
+                    resp::array(&[
+                        resp::bulk(&replica.ip),
+                        resp::bulk(&replica.port.to_string()),
+                        resp::bulk(&replica.offset.to_string()),
+                    ])
+                })
+                .collect();
+            resp::array(&[
+                resp::bulk("master"),
+                resp::integer(replication.offset() as i64),
+                resp::array(&replicas),
+            ])
+        }
+    }
+}
+
+// REPLCONF, sent by replicas during the handshake and to acknowledge the stream
+pub fn replconf(db: &Database, client: &Arc<Client>, args: &[&str]) -> Result<String, String> {
+    let option = args.first().map(|s| s.to_lowercase()).unwrap_or_default();
+    match (option.as_str(), &args[1..]) {
+        ("listening-port", [port]) => {
+            let port = port.parse::<u16>().map_err(|_| "Invalid listening port")?;
+            client.set_listening_port(port);
+            Ok(resp::ok())
+        }
+        ("capa", capabilities) if !capabilities.is_empty() => Ok(resp::ok()),
+        ("ack", [offset]) => {
+            let offset = offset.parse::<u64>().map_err(|_| "Invalid ACK offset")?;
+            db.replication().ack(client.id, offset);
+            // ACKs are never answered
+            Ok(String::new())
+        }
+        ("getack", [_]) => {
+            let offset = db.replication().offset().to_string();
+            Ok(resp::command(&["REPLCONF", "ACK", &offset]))
+        }
+        _ => Err(format!("Unrecognized REPLCONF option: {}", option)),
+    }
+}
+
+// PSYNC replid offset. The reply (+FULLRESYNC or +CONTINUE and the data) is
+// queued by Database::psync, after which the connection carries the stream.
+pub async fn psync(db: &Database, client: &Arc<Client>, replid: &str, offset: &str) -> Result<String, String> {
+    let offset = offset
+        .parse::<i64>()
+        .map_err(|_| "value is not an integer or out of range")?;
+    db.psync(client, replid, offset).await;
+    Ok(String::new())
+}
+
+// WAIT numreplicas timeout
+pub async fn wait(db: &Database, numreplicas: &str, timeout: &str) -> Result<String, String> {
+    if db.replication().is_replica() {
+        return Err("WAIT cannot be used with replica instances".to_string());
+    }
+    let numreplicas = numreplicas
+        .parse::<usize>()
+        .map_err(|_| "value is not an integer or out of range")?;
+    let timeout = match timeout.parse::<u64>() {
+        Ok(0) => None,
+        Ok(ms) => Some(Duration::from_millis(ms)),
+        Err(_) => return Err("timeout is not an integer or out of range".to_string()),
+    };
+
+    let acked = db.replication().wait(numreplicas, timeout).await;
+    Ok(resp::integer(acked as i64))
+}
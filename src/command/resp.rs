@@ -0,0 +1,65 @@
+// Helpers for encoding RESP replies
+
+pub fn ok() -> String {
+    "+OK\r\n".to_string()
+}
+
+pub fn integer(value: i64) -> String {
+    format!(":{}\r\n", value)
+}
+
+pub fn bulk(value: &str) -> String {
+    format!("${}\r\n{}\r\n", value.len(), value)
+}
+
+// A bulk string that may hold any bytes, like string values
+pub fn bulk_bytes(value: &[u8]) -> Vec<u8> {
+    let mut out = format!("${}\r\n", value.len()).into_bytes();
+    out.extend_from_slice(value);
+    out.extend_from_slice(b"\r\n");
+    out
+}
+
+pub fn null_bulk() -> String {
+    "$-1\r\n".to_string()
+}
+
+pub fn null_array() -> String {
+    "*-1\r\n".to_string()
+}
+
+// An array of already encoded elements
+pub fn array(items: &[String]) -> String {
+    format!("*{}\r\n{}", items.len(), items.concat())
+}
+
+// A command as clients (and masters) send it: an array of bulk strings
+pub fn command(args: &[&str]) -> String {
+    let items: Vec<String> = args.iter().map(|arg| bulk(arg)).collect();
+    array(&items)
+}
+
+// The same for commands with binary arguments
+pub fn command_bytes<A: AsRef<[u8]>>(args: &[A]) -> Vec<u8> {
+    let mut out = format!("*{}\r\n", args.len()).into_bytes();
+    for arg in args {
+        out.extend(bulk_bytes(arg.as_ref()));
+    }
+    out
+}
+
+// RESP3 types, only sent to clients that switched protocols with HELLO 3
+
+pub fn null() -> String {
+    "_\r\n".to_string()
+}
+
+// Alternating keys and values, already encoded
+pub fn map(items: &[String]) -> String {
+    format!("%{}\r\n{}", items.len() / 2, items.concat())
+}
+
+// Out-of-band data such as pub/sub messages and invalidations
+pub fn push(items: &[String]) -> String {
+    format!(">{}\r\n{}", items.len(), items.concat())
+}
@@ -0,0 +1,104 @@
+use std::sync::Arc;
+
+use crate::client::Client;
+use crate::command::resp;
+use crate::database::Database;
+use crate::scripting::{Invocation, Scripting};
+
+const NOSCRIPT: &str = "-NOSCRIPT No matching script. Please use EVAL.\r\n";
+
+// EVAL script numkeys [key ...] [arg ...] (EVAL_RO when read_only)
+pub async fn eval(db: &Database, client: &Arc<Client>, args: &[&str], read_only: bool) -> Result<Vec<u8>, String> {
+    let (body, rest) = args
+        .split_first()
+        .ok_or("wrong number of arguments for 'eval' command")?;
+    let sha = db.scripting().load(body);
+    run(db, client, sha, body.to_string(), rest, read_only).await
+}
+
+// EVALSHA sha1 numkeys [key ...] [arg ...] (EVALSHA_RO when read_only)
+pub async fn evalsha(db: &Database, client: &Arc<Client>, args: &[&str], read_only: bool) -> Result<Vec<u8>, String> {
+    let (sha, rest) = args
+        .split_first()
+        .ok_or("wrong number of arguments for 'evalsha' command")?;
+    match db.scripting().get(sha) {
+        Some(body) => run(db, client, sha.to_lowercase(), body, rest, read_only).await,
+        None => Ok(NOSCRIPT.as_bytes().to_vec()),
+    }
+}
+
+async fn run(
+    db: &Database,
+    client: &Arc<Client>,
+    sha: String,
+    body: String,
+    args: &[&str],
+    read_only: bool,
+) -> Result<Vec<u8>, String> {
+    let (keys, argv) = split_keys(args)?;
+    let invocation = Invocation {
+        sha,
+        body,
+        keys: keys.iter().map(|key| key.to_string()).collect(),
+        argv: argv.iter().map(|arg| arg.to_string()).collect(),
+        read_only,
+    };
+    Scripting::run(db, client, invocation).await
+}
+
+// Splits `numkeys [key ...] [arg ...]` into keys and args
+pub fn split_keys<'a, 'b>(args: &'b [&'a str]) -> Result<(&'b [&'a str], &'b [&'a str]), String> {
+    let (numkeys, rest) = args.split_first().ok_or("wrong number of arguments for 'eval' command")?;
+    let numkeys = numkeys
+        .parse::<i64>()
+        .map_err(|_| "value is not an integer or out of range")?;
+    if numkeys < 0 {
+        return Err("Number of keys can't be negative".to_string());
+    }
+    if numkeys as usize > rest.len() {
+        return Err("Number of keys can't be greater than number of args".to_string());
+    }
+    Ok(rest.split_at(numkeys as usize))
+}
+
+// SCRIPT LOAD|EXISTS|FLUSH|KILL
+pub fn script_command(db: &Database, args: &[&str]) -> Result<String, String> {
+    let subcommand = args.first().map(|s| s.to_uppercase()).unwrap_or_default();
+    match (subcommand.as_str(), &args[1..]) {
+        ("LOAD", [body]) => Ok(resp::bulk(&db.scripting().load(body))),
+        ("EXISTS", shas) if !shas.is_empty() => {
+            let found: Vec<String> = shas
+                .iter()
+                .map(|sha| resp::integer(db.scripting().exists(sha) as i64))
+                .collect();
+            Ok(resp::array(&found))
+        }
+        // Flushing is instant either way
+        ("FLUSH", mode)
+            if mode.len() <= 1
+                && mode.iter().all(|m| m.eq_ignore_ascii_case("ASYNC") || m.eq_ignore_ascii_case("SYNC")) =>
+        {
+            db.scripting().flush();
+            Ok(resp::ok())
+        }
+        ("KILL", []) => Ok(db.scripting().kill()),
+        _ => Err(format!(
+            "unknown subcommand or wrong number of arguments for '{}'. Try SCRIPT HELP.",
+            args.first().unwrap_or(&"")
+        )),
+    }
+}
+
+// Whether a command can run while a script is busy
+pub fn allowed_while_busy(args: &[&str]) -> bool {
+    match args {
+        ["SCRIPT", subcommand] => subcommand.eq_ignore_ascii_case("KILL"),
+        ["SHUTDOWN", options @ ..] => options.iter().any(|option| option.eq_ignore_ascii_case("NOSAVE")),
+        _ => false,
+    }
+}
+
+// Whether the command runs a script, which takes the keyspace for itself
+pub fn is_script_command(name: &str) -> bool {
+    matches!(name, "EVAL" | "EVALSHA" | "EVAL_RO" | "EVALSHA_RO")
+}
@@ -0,0 +1,301 @@
+use serde_json::Value;
+
+use crate::command::resp;
+use crate::database::json::{self, Path};
+use crate::database::Database;
+use crate::search::{query, Field, FieldType, Index, UNKNOWN_INDEX};
+
+const SYNTAX_ERROR: &str = "syntax error";
+
+fn wrong_args(command: &str) -> String {
+    format!("wrong number of arguments for '{}' command", command)
+}
+
+fn parse_count(text: &str) -> Result<usize, String> {
+    text.parse::<usize>().map_err(|_| "value is not an integer or out of range".to_string())
+}
+
+// FT.CREATE index [ON JSON] [PREFIX count prefix ...] SCHEMA field [field ...]
+// where each field is `path [AS name] TEXT [WEIGHT w] | TAG [SEPARATOR c] |
+// NUMERIC`, optionally SORTABLE. There is no hash type, so only JSON documents
+// can be indexed.
+pub fn ft_create(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [name, rest @ ..] = args else {
+        return Err(wrong_args("ft.create"));
+    };
+    let mut rest = rest;
+    let mut prefixes = Vec::new();
+    loop {
+        match rest {
+            [option, on, tail @ ..] if option.eq_ignore_ascii_case("ON") => {
+                if !on.eq_ignore_ascii_case("JSON") {
+                    return Err("only ON JSON indexes are supported".to_string());
+                }
+                rest = tail;
+            }
+            [option, count, tail @ ..] if option.eq_ignore_ascii_case("PREFIX") => {
+                let count = parse_count(count)?;
+                if tail.len() < count {
+                    return Err(SYNTAX_ERROR.to_string());
+                }
+                prefixes.extend(tail[..count].iter().map(|prefix| prefix.to_string()));
+                rest = &tail[count..];
+            }
+            [option, schema @ ..] if option.eq_ignore_ascii_case("SCHEMA") => {
+                rest = schema;
+                break;
+            }
+            _ => return Err(SYNTAX_ERROR.to_string()),
+        }
+    }
+
+    let mut fields: Vec<Field> = Vec::new();
+    while let [path, tail @ ..] = rest {
+        let (field_name, tail) = match tail {
+            [option, alias, tail @ ..] if option.eq_ignore_ascii_case("AS") => (*alias, tail),
+            _ => (*path, tail),
+        };
+        let [kind, tail @ ..] = tail else {
+            return Err(SYNTAX_ERROR.to_string());
+        };
+        let kind = match kind.to_uppercase().as_str() {
+            "TEXT" => FieldType::Text,
+            "TAG" => FieldType::Tag,
+            "NUMERIC" => FieldType::Numeric,
+            other => return Err(format!("Invalid field type for field `{}`: {}", field_name, other)),
+        };
+        if fields.iter().any(|field| field.name == field_name) {
+            return Err(format!("Duplicate field in schema - {}", field_name));
+        }
+        let mut field = Field::new(path, field_name, kind)?;
+        rest = tail;
+        loop {
+            match rest {
+                [option, weight, tail @ ..] if kind == FieldType::Text && option.eq_ignore_ascii_case("WEIGHT") => {
+                    field.weight = weight.parse::<f64>().map_err(|_| "Invalid weight".to_string())?;
+                    rest = tail;
+                }
+                [option, separator, tail @ ..]
+                    if kind == FieldType::Tag && option.eq_ignore_ascii_case("SEPARATOR") =>
+                {
+                    let mut chars = separator.chars();
+                    field.separator = match (chars.next(), chars.next()) {
+                        (Some(c), None) => c,
+                        _ => return Err("Tag separator must be a single character".to_string()),
+                    };
+                    rest = tail;
+                }
+                [option, tail @ ..] if option.eq_ignore_ascii_case("SORTABLE") => {
+                    field.sortable = true;
+                    rest = tail;
+                }
+                _ => break,
+            }
+        }
+        fields.push(field);
+    }
+    if fields.is_empty() {
+        return Err("Fields arguments are missing".to_string());
+    }
+
+    let mut definition = vec!["FT.CREATE".to_string()];
+    definition.extend(args.iter().map(|arg| arg.to_string()));
+    db.create_index(Index::new(name, prefixes, fields, definition))?;
+    Ok(resp::ok())
+}
+
+struct SearchOptions<'a> {
+    no_content: bool,
+    with_scores: bool,
+    returns: Option<Vec<&'a str>>,
+    sort_by: Option<(&'a str, bool)>,
+    offset: usize,
+    limit: usize,
+}
+
+fn parse_search_options<'a>(mut args: &[&'a str]) -> Result<SearchOptions<'a>, String> {
+    let mut options = SearchOptions {
+        no_content: false,
+        with_scores: false,
+        returns: None,
+        sort_by: None,
+        offset: 0,
+        limit: 10,
+    };
+    while let [option, tail @ ..] = args {
+        args = tail;
+        match option.to_uppercase().as_str() {
+            "NOCONTENT" => options.no_content = true,
+            "WITHSCORES" => options.with_scores = true,
+            "RETURN" => {
+                let [count, tail @ ..] = args else {
+                    return Err(SYNTAX_ERROR.to_string());
+                };
+                let count = parse_count(count)?;
+                if tail.len() < count {
+                    return Err(SYNTAX_ERROR.to_string());
+                }
+                options.returns = Some(tail[..count].to_vec());
+                args = &tail[count..];
+            }
+            "SORTBY" => {
+                let [field, tail @ ..] = args else {
+                    return Err(SYNTAX_ERROR.to_string());
+                };
+                let (ascending, tail) = match tail {
+                    [order, tail @ ..] if order.eq_ignore_ascii_case("ASC") => (true, tail),
+                    [order, tail @ ..] if order.eq_ignore_ascii_case("DESC") => (false, tail),
+                    _ => (true, tail),
+                };
+                options.sort_by = Some((field.trim_start_matches('@'), ascending));
+                args = tail;
+            }
+            "LIMIT" => {
+                let [offset, limit, tail @ ..] = args else {
+                    return Err(SYNTAX_ERROR.to_string());
+                };
+                options.offset = parse_count(offset)?;
+                options.limit = parse_count(limit)?;
+                args = tail;
+            }
+            _ => return Err(SYNTAX_ERROR.to_string()),
+        }
+    }
+    Ok(options)
+}
+
+// A returned value: strings as they are, anything else as JSON
+fn field_value(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+// FT.SEARCH index query [NOCONTENT] [WITHSCORES] [RETURN count field ...]
+// [SORTBY field [ASC|DESC]] [LIMIT offset num]. Replies with the total number
+// of matches and then, per document on the page, its key, score and fields.
+pub fn ft_search(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [name, text, rest @ ..] = args else {
+        return Err(wrong_args("ft.search"));
+    };
+    let options = parse_search_options(rest)?;
+    let query = query::parse(text)?;
+
+    // Matches are found under the index lock, documents read after it's released
+    let (total, page, paths) = db
+        .search()
+        .read(name, |index| {
+            let matches = index.sort(index.evaluate(&query)?, options.sort_by)?;
+            let page: Vec<(String, f64)> = matches.iter().skip(options.offset).take(options.limit).cloned().collect();
+            let paths = options.returns.as_ref().map(|returns| {
+                returns
+                    .iter()
+                    .map(|name| {
+                        let path = index.field(name).map_or(*name, |field| field.path.as_str());
+                        (name.to_string(), Path::parse(path).ok())
+                    })
+                    .collect::<Vec<_>>()
+            });
+            Ok::<_, String>((matches.len(), page, paths))
+        })
+        .ok_or(UNKNOWN_INDEX)??;
+
+    let mut replies = vec![resp::integer(total as i64)];
+    for (key, score) in page {
+        replies.push(resp::bulk(&key));
+        if options.with_scores {
+            replies.push(resp::bulk(&score.to_string()));
+        }
+        if options.no_content {
+            continue;
+        }
+        let fields = db.json_read(&key, |doc| match &paths {
+            None => vec![resp::bulk("$"), resp::bulk(&doc.to_string())],
+            Some(paths) => paths
+                .iter()
+                .filter_map(|(name, path)| {
+                    let location = path.as_ref()?.find(doc).into_iter().next()?;
+                    let value = json::get(doc, &location)?;
+                    Some([resp::bulk(name), resp::bulk(&field_value(value))])
+                })
+                .flatten()
+                .collect(),
+        });
+        replies.push(resp::array(&fields.unwrap_or_default()));
+    }
+    Ok(resp::array(&replies))
+}
+
+// FT.DROPINDEX index [DD]
+pub async fn ft_dropindex(db: &Database, args: &[&str]) -> Result<String, String> {
+    let (name, delete_docs) = match args {
+        [name] => (name, false),
+        [name, dd] if dd.eq_ignore_ascii_case("DD") => (name, true),
+        [_, _] => return Err(SYNTAX_ERROR.to_string()),
+        _ => return Err(wrong_args("ft.dropindex")),
+    };
+    db.drop_index(name, delete_docs).await?;
+    Ok(resp::ok())
+}
+
+// FT.INFO index
+pub fn ft_info(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [name] = args else {
+        return Err(wrong_args("ft.info"));
+    };
+    db.search()
+        .read(name, |index| {
+            let prefixes: Vec<String> = index.prefixes.iter().map(|prefix| resp::bulk(prefix)).collect();
+            let definition = resp::array(&[
+                resp::bulk("key_type"),
+                resp::bulk("JSON"),
+                resp::bulk("prefixes"),
+                resp::array(&prefixes),
+            ]);
+            let attributes: Vec<String> = index
+                .fields
+                .iter()
+                .map(|field| {
+                    let mut attribute = vec![
+                        resp::bulk("identifier"),
+                        resp::bulk(&field.path),
+                        resp::bulk("attribute"),
+                        resp::bulk(&field.name),
+                        resp::bulk("type"),
+                        resp::bulk(field.kind.name()),
+                    ];
+                    match field.kind {
+                        FieldType::Text => attribute.extend([resp::bulk("WEIGHT"), resp::bulk(&field.weight.to_string())]),
+                        FieldType::Tag => {
+                            attribute.extend([resp::bulk("SEPARATOR"), resp::bulk(&field.separator.to_string())])
+                        }
+                        FieldType::Numeric => {}
+                    }
+                    if field.sortable {
+                        attribute.push(resp::bulk("SORTABLE"));
+                    }
+                    resp::array(&attribute)
+                })
+                .collect();
+            resp::array(&[
+                resp::bulk("index_name"),
+                resp::bulk(&index.name),
+                resp::bulk("index_definition"),
+                definition,
+                resp::bulk("attributes"),
+                resp::array(&attributes),
+                resp::bulk("num_docs"),
+                resp::integer(index.num_docs() as i64),
+                resp::bulk("num_terms"),
+                resp::integer(index.num_terms() as i64),
+            ])
+        })
+        .ok_or_else(|| UNKNOWN_INDEX.to_string())
+}
+
+// FT._LIST
+pub fn ft_list(db: &Database) -> String {
+    let names: Vec<String> = db.search().names().iter().map(|name| resp::bulk(name)).collect();
+    resp::array(&names)
+}
@@ -0,0 +1,68 @@
+use crate::command::resp;
+use crate::database::sketch::{Sketch, WRONG_TYPE};
+use crate::database::Database;
+
+const NOT_FOUND: &str = "not found";
+
+fn wrong_args(command: &str) -> String {
+    format!("wrong number of arguments for '{}' command", command)
+}
+
+// WRONGTYPE goes out as is, the rest get -ERR from the connection
+fn prefixed(result: Result<String, String>) -> Result<String, String> {
+    match result {
+        Err(e) if e.starts_with("WRONGTYPE ") => Ok(format!("-{}\r\n", e)),
+        other => other,
+    }
+}
+
+fn parse_iterator(text: &str) -> Result<usize, String> {
+    text.parse::<usize>().map_err(|_| "value is not an integer or out of range".to_string())
+}
+
+// <prefix>.SCANDUMP key iterator, for BF, CF, CMS and TOPK: the next iterator
+// and a chunk of the sketch, to be handed to LOADCHUNK in the same order
+pub fn scandump(db: &Database, args: &[&str], prefix: &str) -> Result<String, String> {
+    let [key, iter] = args else {
+        return Err(wrong_args(&format!("{}.scandump", prefix.to_lowercase())));
+    };
+    let iter = parse_iterator(iter)?;
+    let chunk = db
+        .sketch_read(key, |sketch| match sketch.prefix() == prefix {
+            true => Ok(sketch.scandump(iter)),
+            false => Err(WRONG_TYPE.to_string()),
+        })?
+        .ok_or(NOT_FOUND)?;
+    prefixed(chunk.map(|(next, data)| resp::array(&[resp::integer(next as i64), resp::bulk(&data)])))
+}
+
+// <prefix>.LOADCHUNK key iterator data. The first chunk recreates the sketch,
+// the others fill it in.
+pub fn loadchunk(db: &Database, args: &[&str], prefix: &str) -> Result<String, String> {
+    let name = format!("{}.LOADCHUNK", prefix);
+    let [key, iter, data] = args else {
+        return Err(wrong_args(&name.to_lowercase()));
+    };
+    let iter = parse_iterator(iter)?;
+    let mut command = vec![name.as_str()];
+    command.extend(args);
+    // The header is checked and the sketch allocated before the keyspace is locked
+    let created = match iter {
+        1 => Some(Sketch::from_header(prefix, data).ok_or("received bad data")?),
+        _ => None,
+    };
+    prefixed(db.sketch_write(key, &command, |sketch| {
+        match iter {
+            0 => return Err("invalid iterator".to_string()),
+            1 => *sketch = created,
+            _ => {
+                let target = sketch.as_mut().ok_or(NOT_FOUND)?;
+                if target.prefix() != prefix {
+                    return Err(WRONG_TYPE.to_string());
+                }
+                target.load_chunk(iter, data)?;
+            }
+        }
+        Ok((resp::ok(), true))
+    }))
+}
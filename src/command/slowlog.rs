@@ -0,0 +1,136 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::client::Client;
+use crate::command::{monitor, resp};
+use crate::database::stats::LATENCY_BUCKETS;
+use crate::database::Database;
+
+// Entries SLOWLOG GET returns without a count
+const DEFAULT_GET_COUNT: usize = 10;
+
+// Logs a command that ran from start to finish in `duration`, if it was slow
+// enough, and samples it for LATENCY. Credentials are redacted like for MONITOR.
+pub fn record(db: &Database, client: &Client, args: &[&[u8]], duration: Duration) {
+    let (slower_than, max_len) = {
+        let config = db.config();
+        (config.slowlog_log_slower_than, config.slowlog_max_len)
+    };
+    let micros = duration.as_micros() as u64;
+    if slower_than >= 0 && micros >= slower_than as u64 {
+        db.slowlog().push(max_len, &monitor::redacted(args), micros, client);
+    }
+    db.record_latency("command", duration);
+}
+
+// SLOWLOG GET [count] | LEN | RESET
+pub fn slowlog_command(db: &Database, args: &[&str]) -> Result<String, String> {
+    let subcommand = args.first().map(|s| s.to_uppercase()).unwrap_or_default();
+    match (subcommand.as_str(), args.get(1..).unwrap_or_default()) {
+        ("GET", []) => Ok(slowlog_get(db, DEFAULT_GET_COUNT)),
+        ("GET", [count]) => match count.parse::<i64>() {
+            Ok(-1) => Ok(slowlog_get(db, usize::MAX)),
+            Ok(count) if count >= 0 => Ok(slowlog_get(db, count as usize)),
+            _ => Err("count should be greater than or equal to -1".to_string()),
+        },
+        ("LEN", []) => Ok(resp::integer(db.slowlog().len() as i64)),
+        ("RESET", []) => {
+            db.slowlog().reset();
+            Ok(resp::ok())
+        }
+        _ => Err(format!(
+            "unknown subcommand or wrong number of arguments for '{}'. Try SLOWLOG HELP.",
+            args.first().unwrap_or(&"")
+        )),
+    }
+}
+
+// Newest first: id, unix time, duration in microseconds, arguments, client
+// address and client name
+fn slowlog_get(db: &Database, count: usize) -> String {
+    let entries: Vec<String> = db
+        .slowlog()
+        .get(count)
+        .iter()
+        .map(|entry| {
+            let args: Vec<&str> = entry.args.iter().map(String::as_str).collect();
+            resp::array(&[
+                resp::integer(entry.id as i64),
+                resp::integer(entry.timestamp as i64),
+                resp::integer(entry.micros as i64),
+                resp::command(&args),
+                resp::bulk(&entry.addr),
+                resp::bulk(&entry.name),
+            ])
+        })
+        .collect();
+    resp::array(&entries)
+}
+
+// LATENCY LATEST | HISTORY event | RESET [event ...] | HISTOGRAM [command ...]
+pub fn latency_command(db: &Database, client: &Arc<Client>, args: &[&str]) -> Result<String, String> {
+    let subcommand = args.first().map(|s| s.to_uppercase()).unwrap_or_default();
+    match (subcommand.as_str(), args.get(1..).unwrap_or_default()) {
+        ("LATEST", []) => {
+            let events: Vec<String> = db
+                .latency()
+                .latest()
+                .into_iter()
+                .map(|(event, time, latency, max)| {
+                    resp::array(&[
+                        resp::bulk(&event),
+                        resp::integer(time as i64),
+                        resp::integer(latency as i64),
+                        resp::integer(max as i64),
+                    ])
+                })
+                .collect();
+            Ok(resp::array(&events))
+        }
+        ("HISTORY", [event]) => {
+            let samples: Vec<String> = db
+                .latency()
+                .history(event)
+                .into_iter()
+                .map(|(time, latency)| resp::array(&[resp::integer(time as i64), resp::integer(latency as i64)]))
+                .collect();
+            Ok(resp::array(&samples))
+        }
+        ("RESET", events) => Ok(resp::integer(db.latency().reset(events) as i64)),
+        ("HISTOGRAM", commands) => Ok(latency_histogram(db, client, commands)),
+        _ => Err(format!(
+            "unknown subcommand or wrong number of arguments for '{}'. Try LATENCY HELP.",
+            args.first().unwrap_or(&"")
+        )),
+    }
+}
+
+// Per command: its calls and the cumulative count of calls at or under each
+// bucket bound, in microseconds. Calls over the last bound only count in `calls`.
+fn latency_histogram(db: &Database, client: &Client, commands: &[&str]) -> String {
+    let map = |items: &[String]| match client.protocol() {
+        2 => resp::array(items),
+        _ => resp::map(items),
+    };
+    let mut fields = Vec::new();
+    for (name, stats) in db.stats().command_stats() {
+        if !commands.is_empty() && !commands.iter().any(|command| command.eq_ignore_ascii_case(&name)) {
+            continue;
+        }
+        let mut buckets = Vec::new();
+        let mut total = 0;
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(stats.buckets) {
+            total += count;
+            buckets.push(resp::integer((bound * 1_000_000.0).round() as i64));
+            buckets.push(resp::integer(total as i64));
+        }
+        fields.push(resp::bulk(&name));
+        fields.push(map(&[
+            resp::bulk("calls"),
+            resp::integer(stats.calls as i64),
+            resp::bulk("histogram_usec"),
+            map(&buckets),
+        ]));
+    }
+    map(&fields)
+}
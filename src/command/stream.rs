@@ -0,0 +1,1057 @@
+use std::future::pending;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::client::Client;
+use crate::command::resp;
+use crate::database::stream::{now_ms, ClaimOptions, Fields, IdSpec, RStream, StreamId, Trim, INVALID_ID};
+use crate::database::Database;
+use crate::scripting::BUSY;
+
+const SYNTAX_ERROR: &str = "syntax error";
+const NOT_INTEGER: &str = "value is not an integer or out of range";
+const DOLLAR_IN_GROUP: &str = "The $ ID is meaningless in the context of XREADGROUP: you want to read the history of this consumer by specifying a proper ID, or use the > ID to get new messages. The $ ID would just return an empty result set.";
+const KEY_REQUIRED: &str = "The XGROUP subcommand requires the key to exist. Note that for CREATE you may want to use the MKSTREAM option to create an empty stream automatically.";
+
+// Group errors carry their own prefix instead of ERR
+fn no_group(key: &str, group: &str) -> String {
+    format!("NOGROUP No such key '{}' or consumer group '{}'", key, group)
+}
+
+fn wrong_args(command: &str) -> String {
+    format!("wrong number of arguments for '{}' command", command)
+}
+
+// Errors with a prefix of their own go out as is, the rest get -ERR from the connection
+fn prefixed(result: Result<String, String>) -> Result<String, String> {
+    match result {
+        Err(e) if e.starts_with("NOGROUP ") || e.starts_with("BUSYGROUP ") => Ok(format!("-{}\r\n", e)),
+        other => other,
+    }
+}
+
+fn command(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+fn parse_count(value: &str) -> Result<usize, String> {
+    // Negative counts are treated as zero, like in Redis
+    value
+        .parse::<i64>()
+        .map(|n| n.max(0) as usize)
+        .map_err(|_| NOT_INTEGER.to_string())
+}
+
+fn parse_ms(value: &str) -> Result<u64, String> {
+    value
+        .parse::<i64>()
+        .map(|n| n.max(0) as u64)
+        .map_err(|_| NOT_INTEGER.to_string())
+}
+
+fn parse_ids(ids: &[&str]) -> Result<Vec<StreamId>, String> {
+    ids.iter().map(|id| StreamId::parse(id, 0)).collect()
+}
+
+// Range bounds: `-`, `+`, full or partial IDs, and `(` for exclusive ones. None
+// means the bound excludes everything.
+fn parse_bound(value: &str, start: bool) -> Result<Option<StreamId>, String> {
+    let default_seq = if start { 0 } else { u64::MAX };
+    match value {
+        "-" => return Ok(Some(StreamId::MIN)),
+        "+" => return Ok(Some(StreamId::MAX)),
+        _ => {}
+    }
+    match value.strip_prefix('(') {
+        Some("-" | "+") => Err(format!(
+            "invalid {} ID for the interval",
+            if start { "start" } else { "end" }
+        )),
+        Some(id) => {
+            let id = StreamId::parse(id, default_seq)?;
+            Ok(if start { id.next() } else { id.prev() })
+        }
+        None => StreamId::parse(value, default_seq).map(Some),
+    }
+}
+
+// MAXLEN|MINID [=|~] threshold [LIMIT count]; returns the trim, its LIMIT and how
+// many arguments it took. Trimming is always exact, `~` only allows LIMIT.
+fn parse_trim(args: &[&str]) -> Result<(Trim, Option<usize>, usize), String> {
+    let strategy = args[0].to_uppercase();
+    let mut i = 1;
+    let mut approximate = false;
+    match args.get(i) {
+        Some(&"=") => i += 1,
+        Some(&"~") => {
+            approximate = true;
+            i += 1;
+        }
+        _ => {}
+    }
+
+    let threshold = args.get(i).ok_or(SYNTAX_ERROR)?;
+    i += 1;
+    let trim = if strategy == "MAXLEN" {
+        let max_len = threshold.parse::<i64>().map_err(|_| NOT_INTEGER)?;
+        if max_len < 0 {
+            return Err("The MAXLEN argument must be >= 0.".to_string());
+        }
+        Trim::MaxLen(max_len as usize)
+    } else {
+        Trim::MinId(StreamId::parse(threshold, 0)?)
+    };
+
+    let mut limit = None;
+    if args.get(i).is_some_and(|arg| arg.eq_ignore_ascii_case("LIMIT")) {
+        let count = args.get(i + 1).ok_or(SYNTAX_ERROR)?;
+        let count = count
+            .parse::<i64>()
+            .ok()
+            .filter(|count| *count >= 0)
+            .ok_or("The LIMIT argument must be >= 0.")?;
+        if !approximate {
+            return Err("syntax error, LIMIT cannot be used without the special ~ option".to_string());
+        }
+        // LIMIT 0 means no limit
+        limit = (count > 0).then_some(count as usize);
+        i += 2;
+    }
+    Ok((trim, limit, i))
+}
+
+// Trimming is replicated by the length it left, which gives the same result
+fn trim_command(key: &str, stream: &RStream) -> Vec<String> {
+    command(&["XTRIM", key, "MAXLEN", &stream.len().to_string()])
+}
+
+fn entry_reply(id: StreamId, fields: Option<&Fields>) -> String {
+    let fields = match fields {
+        Some(fields) => {
+            let items: Vec<String> = fields
+                .iter()
+                .flat_map(|(field, value)| [resp::bulk(field), resp::bulk(value)])
+                .collect();
+            resp::array(&items)
+        }
+        None => resp::null_array(),
+    };
+    resp::array(&[resp::bulk(&id.to_string()), fields])
+}
+
+fn entries_reply(entries: &[(StreamId, Fields)]) -> String {
+    let items: Vec<String> = entries
+        .iter()
+        .map(|(id, fields)| entry_reply(*id, Some(fields)))
+        .collect();
+    resp::array(&items)
+}
+
+fn ids_reply(ids: &[StreamId]) -> String {
+    let items: Vec<String> = ids.iter().map(|id| resp::bulk(&id.to_string())).collect();
+    resp::array(&items)
+}
+
+// A pending entry handed to a consumer, replicated as a forced claim
+fn claim_command(key: &str, group: &str, stream: &RStream, id: StreamId) -> Vec<String> {
+    let pending = &stream.groups[group].pending[&id];
+    command(&[
+        "XCLAIM",
+        key,
+        group,
+        &pending.consumer,
+        "0",
+        &id.to_string(),
+        "TIME",
+        &pending.delivered.to_string(),
+        "RETRYCOUNT",
+        &pending.deliveries.to_string(),
+        "FORCE",
+        "JUSTID",
+    ])
+}
+
+fn setid_command(key: &str, group: &str, stream: &RStream) -> Vec<String> {
+    let group_state = &stream.groups[group];
+    let mut args = command(&["XGROUP", "SETID", key, group, &group_state.last_id.to_string()]);
+    if let Some(entries_read) = group_state.entries_read {
+        args.extend(command(&["ENTRIESREAD", &entries_read.to_string()]));
+    }
+    args
+}
+
+// XADD key [NOMKSTREAM] [MAXLEN|MINID [=|~] threshold [LIMIT count]] id field value [field value ...]
+pub fn xadd(db: &Database, args: &[&str]) -> Result<String, String> {
+    let (key, rest) = args.split_first().ok_or_else(|| wrong_args("xadd"))?;
+    let mut i = 0;
+    let mut make_stream = true;
+    let mut trim = None;
+    loop {
+        match rest.get(i).map(|arg| arg.to_uppercase()).as_deref() {
+            Some("NOMKSTREAM") => {
+                make_stream = false;
+                i += 1;
+            }
+            Some("MAXLEN" | "MINID") => {
+                let (strategy, limit, used) = parse_trim(&rest[i..])?;
+                trim = Some((strategy, limit));
+                i += used;
+            }
+            _ => break,
+        }
+    }
+
+    let spec = IdSpec::parse(rest.get(i).ok_or_else(|| wrong_args("xadd"))?)?;
+    let fields = &rest[i + 1..];
+    if fields.is_empty() || !fields.len().is_multiple_of(2) {
+        return Err(wrong_args("xadd"));
+    }
+    let fields: Fields = fields
+        .chunks(2)
+        .map(|pair| (pair[0].to_string(), pair[1].to_string()))
+        .collect();
+
+    let added = db.stream_write(key, make_stream, |stream| {
+        let id = stream.next_id(&spec, now_ms())?;
+        // Replicas get the generated ID so they store the same entry
+        let mut commands = vec![command(&["XADD", key, &id.to_string()])];
+        commands[0].extend(fields.iter().flat_map(|(field, value)| [field.clone(), value.clone()]));
+        stream.add(id, fields);
+        if let Some((strategy, limit)) = trim {
+            if stream.trim(strategy, limit) > 0 {
+                commands.push(trim_command(key, stream));
+            }
+        }
+        Ok((id, commands))
+    })?;
+    Ok(added
+        .map(|id| resp::bulk(&id.to_string()))
+        .unwrap_or_else(resp::null_bulk))
+}
+
+// XTRIM key MAXLEN|MINID [=|~] threshold [LIMIT count]
+pub fn xtrim(db: &Database, args: &[&str]) -> Result<String, String> {
+    let (key, rest) = match args {
+        [key, rest @ ..] if !rest.is_empty() => (key, rest),
+        _ => return Err(wrong_args("xtrim")),
+    };
+    if !rest[0].eq_ignore_ascii_case("MAXLEN") && !rest[0].eq_ignore_ascii_case("MINID") {
+        return Err(SYNTAX_ERROR.to_string());
+    }
+    let (strategy, limit, used) = parse_trim(rest)?;
+    if used != rest.len() {
+        return Err(SYNTAX_ERROR.to_string());
+    }
+
+    let removed = db.stream_write(key, false, |stream| {
+        let removed = stream.trim(strategy, limit);
+        let commands = if removed > 0 {
+            vec![trim_command(key, stream)]
+        } else {
+            Vec::new()
+        };
+        Ok((removed, commands))
+    })?;
+    Ok(resp::integer(removed.unwrap_or(0) as i64))
+}
+
+// XDEL key id [id ...]
+pub fn xdel(db: &Database, args: &[&str]) -> Result<String, String> {
+    let (key, ids) = match args {
+        [key, ids @ ..] if !ids.is_empty() => (key, ids),
+        _ => return Err(wrong_args("xdel")),
+    };
+    let parsed = parse_ids(ids)?;
+    let deleted = db.stream_write(key, false, |stream| {
+        let deleted = stream.delete(&parsed);
+        let commands = if deleted > 0 {
+            let mut args = command(&["XDEL", key]);
+            args.extend(command(ids));
+            vec![args]
+        } else {
+            Vec::new()
+        };
+        Ok((deleted, commands))
+    })?;
+    Ok(resp::integer(deleted.unwrap_or(0) as i64))
+}
+
+pub fn xlen(db: &Database, key: &str) -> String {
+    resp::integer(db.stream_read(key, |stream| stream.len()).unwrap_or(0) as i64)
+}
+
+// XRANGE key start end [COUNT count], or XREVRANGE key end start [COUNT count]
+pub fn xrange(db: &Database, args: &[&str], rev: bool) -> Result<String, String> {
+    let name = if rev { "xrevrange" } else { "xrange" };
+    let (key, start, end, rest) = match args {
+        [key, first, second, rest @ ..] if rev => (key, second, first, rest),
+        [key, first, second, rest @ ..] => (key, first, second, rest),
+        _ => return Err(wrong_args(name)),
+    };
+    let count = match rest {
+        [] => None,
+        [option, count] if option.eq_ignore_ascii_case("COUNT") => Some(parse_count(count)?),
+        _ => return Err(SYNTAX_ERROR.to_string()),
+    };
+
+    let entries = match (parse_bound(start, true)?, parse_bound(end, false)?) {
+        (Some(start), Some(end)) => db
+            .stream_read(key, |stream| stream.range(start, end, count, rev))
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    };
+    Ok(entries_reply(&entries))
+}
+
+// Where BLOCK sits in XREAD or XREADGROUP, among the options between the command
+// (and GROUP group consumer) and STREAMS
+fn block_option(args: &[&str]) -> Option<usize> {
+    let start = match args.first() {
+        Some(&"XREAD") => 1,
+        Some(&"XREADGROUP") => 4,
+        _ => return None,
+    };
+    args.iter()
+        .enumerate()
+        .skip(start)
+        .take_while(|(_, arg)| !arg.eq_ignore_ascii_case("STREAMS"))
+        .find(|(_, arg)| arg.eq_ignore_ascii_case("BLOCK"))
+        .map(|(i, _)| i)
+}
+
+// Whether the command may block waiting for stream entries. Blocked clients must
+// not hold the keyspace, so they enter it themselves on every attempt.
+pub fn is_blocking(args: &[&str]) -> bool {
+    block_option(args).is_some()
+}
+
+// The command without its BLOCK option; scripts never block, like in Redis
+pub fn without_block<'a>(args: &[&'a str]) -> Vec<&'a str> {
+    let mut stripped = args.to_vec();
+    if let Some(block) = block_option(args) {
+        stripped.drain(block..(block + 2).min(args.len()));
+    }
+    stripped
+}
+
+// The keys of XREAD and XREADGROUP: the first half of what follows STREAMS
+pub fn read_keys<'a>(args: &[&'a str]) -> Vec<&'a str> {
+    match args.iter().position(|arg| arg.eq_ignore_ascii_case("STREAMS")) {
+        Some(i) => {
+            let streams = &args[i + 1..];
+            streams[..streams.len() / 2].to_vec()
+        }
+        None => Vec::new(),
+    }
+}
+
+// Runs `attempt` until it has a reply. Without BLOCK it runs once; otherwise the
+// client waits for stream changes in between for up to `block` ms, 0 meaning
+// forever. Running out of time or attempts replies with a nil array.
+async fn wait_for(
+    db: &Database,
+    client: &Arc<Client>,
+    block: Option<u64>,
+    mut attempt: impl FnMut() -> Result<Option<String>, String>,
+) -> Result<String, String> {
+    let block = match block {
+        Some(block) => block,
+        None => return Ok(attempt()?.unwrap_or_else(resp::null_array)),
+    };
+    let deadline = (block > 0).then(|| Instant::now() + Duration::from_millis(block));
+
+    loop {
+        // Registered before the attempt so a change right after it isn't missed
+        let changed = db.stream_changed().notified();
+        {
+            let _exec = match db.scripting().enter().await {
+                Some(exec) => exec,
+                None => return Ok(BUSY.to_string()),
+            };
+            if let Some(reply) = attempt()? {
+                return Ok(reply);
+            }
+        }
+
+        tokio::select! {
+            _ = changed => {}
+            _ = sleep_until(deadline) => return Ok(resp::null_array()),
+            _ = client.killed() => return Ok(resp::null_array()),
+            _ = db.shutdown().wait() => return Ok(resp::null_array()),
+        }
+    }
+}
+
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+        None => pending().await,
+    }
+}
+
+struct ReadOptions<'a> {
+    count: Option<usize>,
+    block: Option<u64>,
+    noack: bool,
+    keys: &'a [&'a str],
+    ids: &'a [&'a str],
+}
+
+// [COUNT count] [BLOCK milliseconds] [NOACK] STREAMS key [key ...] id [id ...]
+fn parse_read<'a>(args: &'a [&'a str], name: &str, group: bool) -> Result<ReadOptions<'a>, String> {
+    let mut options = ReadOptions {
+        count: None,
+        block: None,
+        noack: false,
+        keys: &[],
+        ids: &[],
+    };
+    let mut i = 0;
+    loop {
+        let option = args.get(i).ok_or(SYNTAX_ERROR)?.to_uppercase();
+        match option.as_str() {
+            "COUNT" => {
+                options.count = Some(parse_count(args.get(i + 1).ok_or(SYNTAX_ERROR)?)?);
+                i += 2;
+            }
+            "BLOCK" => {
+                let timeout = args.get(i + 1).ok_or(SYNTAX_ERROR)?;
+                let timeout = timeout
+                    .parse::<i64>()
+                    .map_err(|_| "timeout is not an integer or out of range")?;
+                if timeout < 0 {
+                    return Err("timeout is negative".to_string());
+                }
+                options.block = Some(timeout as u64);
+                i += 2;
+            }
+            "NOACK" if group => {
+                options.noack = true;
+                i += 1;
+            }
+            "STREAMS" => break,
+            _ => return Err(SYNTAX_ERROR.to_string()),
+        }
+    }
+
+    let streams = &args[i + 1..];
+    if streams.is_empty() || !streams.len().is_multiple_of(2) {
+        let id = if group { ">" } else { "$" };
+        return Err(format!(
+            "Unbalanced '{}' list of streams: for each stream key an ID or '{}' must be specified.",
+            name, id
+        ));
+    }
+    (options.keys, options.ids) = streams.split_at(streams.len() / 2);
+    Ok(options)
+}
+
+// XREAD [COUNT count] [BLOCK milliseconds] STREAMS key [key ...] id [id ...]
+pub async fn xread(db: &Database, client: &Arc<Client>, args: &[&str]) -> Result<String, String> {
+    let options = parse_read(args, "xread", false)?;
+
+    // `$` reads only entries added after the command, so it's resolved right away
+    let mut after = Vec::with_capacity(options.ids.len());
+    for (key, id) in options.keys.iter().zip(options.ids) {
+        after.push(match *id {
+            "$" => db.stream_read(key, |stream| stream.last_id).unwrap_or(StreamId::MIN),
+            id => StreamId::parse(id, 0)?,
+        });
+    }
+
+    wait_for(db, client, options.block, || {
+        let mut streams = Vec::new();
+        for (key, after) in options.keys.iter().zip(&after) {
+            let entries = match after.next() {
+                Some(start) => db
+                    .stream_read(key, |stream| stream.range(start, StreamId::MAX, options.count, false))
+                    .unwrap_or_default(),
+                None => Vec::new(),
+            };
+            if !entries.is_empty() {
+                streams.push(resp::array(&[resp::bulk(key), entries_reply(&entries)]));
+            }
+        }
+        Ok((!streams.is_empty()).then(|| resp::array(&streams)))
+    })
+    .await
+}
+
+// XREADGROUP GROUP group consumer [COUNT count] [BLOCK milliseconds] [NOACK]
+// STREAMS key [key ...] id [id ...]
+pub async fn xreadgroup(db: &Database, client: &Arc<Client>, args: &[&str]) -> Result<String, String> {
+    let (group, consumer, rest) = match args {
+        [option, group, consumer, rest @ ..] if option.eq_ignore_ascii_case("GROUP") => (group, consumer, rest),
+        _ => return Err(SYNTAX_ERROR.to_string()),
+    };
+    let mut options = parse_read(rest, "xreadgroup", true)?;
+
+    // None reads new entries (`>`), an ID rereads the consumer's pending entries
+    let mut after = Vec::with_capacity(options.ids.len());
+    for id in options.ids {
+        after.push(match *id {
+            ">" => None,
+            "$" => return Err(DOLLAR_IN_GROUP.to_string()),
+            id => Some(StreamId::parse(id, 0)?),
+        });
+    }
+    // Reading history never blocks
+    if after.iter().any(Option::is_some) {
+        options.block = None;
+    }
+
+    let result = wait_for(db, client, options.block, || {
+        let mut streams = Vec::new();
+        for (key, after) in options.keys.iter().zip(&after) {
+            let entries = db.stream_write(key, false, |stream| {
+                read_group(stream, key, group, consumer, *after, &options)
+            })?;
+            if let Some(entries) = entries.ok_or_else(|| no_group(key, group))? {
+                streams.push(resp::array(&[resp::bulk(key), entries]));
+            }
+        }
+        Ok((!streams.is_empty()).then(|| resp::array(&streams)))
+    })
+    .await;
+    prefixed(result)
+}
+
+// One stream of XREADGROUP; None when there is nothing new to deliver
+fn read_group(
+    stream: &mut RStream,
+    key: &str,
+    group: &str,
+    consumer: &str,
+    after: Option<StreamId>,
+    options: &ReadOptions,
+) -> Result<(Option<String>, Vec<Vec<String>>), String> {
+    let group_state = stream.groups.get(group).ok_or_else(|| no_group(key, group))?;
+    let mut commands = Vec::new();
+    if !group_state.consumers.contains_key(consumer) {
+        commands.push(command(&["XGROUP", "CREATECONSUMER", key, group, consumer]));
+    }
+
+    let now = now_ms();
+    match after {
+        None => {
+            let entries = stream.read_new(group, consumer, options.count, options.noack, now);
+            if entries.is_empty() {
+                return Ok((None, commands));
+            }
+            if !options.noack {
+                commands.extend(entries.iter().map(|(id, _)| claim_command(key, group, stream, *id)));
+            }
+            commands.push(setid_command(key, group, stream));
+            Ok((Some(entries_reply(&entries)), commands))
+        }
+        Some(after) => {
+            let entries = stream.read_pending(group, consumer, after, options.count, now);
+            // A claim would drop the pending entries whose message is gone on replicas
+            let delivered = entries.iter().filter(|(_, fields)| fields.is_some());
+            commands.extend(delivered.map(|(id, _)| claim_command(key, group, stream, *id)));
+            let items: Vec<String> = entries
+                .iter()
+                .map(|(id, fields)| entry_reply(*id, fields.as_ref()))
+                .collect();
+            Ok((Some(resp::array(&items)), commands))
+        }
+    }
+}
+
+// XACK key group id [id ...]
+pub fn xack(db: &Database, args: &[&str]) -> Result<String, String> {
+    let (key, group, ids) = match args {
+        [key, group, ids @ ..] if !ids.is_empty() => (key, group, ids),
+        _ => return Err(wrong_args("xack")),
+    };
+    let parsed = parse_ids(ids)?;
+    let acked = db.stream_write(key, false, |stream| {
+        let acked = stream.groups.get_mut(*group).map_or(0, |group| group.ack(&parsed));
+        let commands = if acked > 0 {
+            let mut args = command(&["XACK", key, group]);
+            args.extend(command(ids));
+            vec![args]
+        } else {
+            Vec::new()
+        };
+        Ok((acked, commands))
+    })?;
+    Ok(resp::integer(acked.unwrap_or(0) as i64))
+}
+
+// XGROUP CREATE|SETID|DESTROY|CREATECONSUMER|DELCONSUMER
+pub fn xgroup(db: &Database, args: &[&str]) -> Result<String, String> {
+    let subcommand = args.first().map(|s| s.to_uppercase()).unwrap_or_default();
+    let result = match (subcommand.as_str(), &args[1.min(args.len())..]) {
+        ("CREATE", [key, group, id, options @ ..]) => group_create(db, key, group, id, options),
+        ("SETID", [key, group, id, options @ ..]) => group_setid(db, key, group, id, options),
+        ("DESTROY", [key, group]) => group_write(db, key, |stream| {
+            let destroyed = stream.groups.remove(*group).is_some();
+            let commands = if destroyed {
+                vec![command(&["XGROUP", "DESTROY", key, group])]
+            } else {
+                Vec::new()
+            };
+            Ok((resp::integer(destroyed as i64), commands))
+        }),
+        ("CREATECONSUMER", [key, group, consumer]) => group_write(db, key, |stream| {
+            let group_state = stream.groups.get_mut(*group).ok_or_else(|| group_missing(key, group))?;
+            if group_state.consumers.contains_key(*consumer) {
+                return Ok((resp::integer(0), Vec::new()));
+            }
+            group_state.touch_consumer(consumer, now_ms(), false);
+            Ok((
+                resp::integer(1),
+                vec![command(&["XGROUP", "CREATECONSUMER", key, group, consumer])],
+            ))
+        }),
+        ("DELCONSUMER", [key, group, consumer]) => group_write(db, key, |stream| {
+            let group_state = stream.groups.get_mut(*group).ok_or_else(|| group_missing(key, group))?;
+            if group_state.consumers.remove(*consumer).is_none() {
+                return Ok((resp::integer(0), Vec::new()));
+            }
+            // Its pending entries go with it
+            let before = group_state.pending.len();
+            group_state.pending.retain(|_, pending| pending.consumer != *consumer);
+            let pending = before - group_state.pending.len();
+            Ok((
+                resp::integer(pending as i64),
+                vec![command(&["XGROUP", "DELCONSUMER", key, group, consumer])],
+            ))
+        }),
+        _ => Err(format!(
+            "unknown subcommand or wrong number of arguments for '{}'. Try XGROUP HELP.",
+            args.first().unwrap_or(&"")
+        )),
+    };
+    prefixed(result)
+}
+
+fn group_missing(key: &str, group: &str) -> String {
+    format!("NOGROUP No such consumer group '{}' for key name '{}'", group, key)
+}
+
+// XGROUP subcommands other than CREATE need the stream to exist
+fn group_write(
+    db: &Database,
+    key: &str,
+    f: impl FnOnce(&mut RStream) -> Result<(String, Vec<Vec<String>>), String>,
+) -> Result<String, String> {
+    db.stream_write(key, false, f)?.ok_or_else(|| KEY_REQUIRED.to_string())
+}
+
+// [ENTRIESREAD entries-read], where -1 means unknown
+fn parse_entries_read(options: &[&str]) -> Result<Option<Option<u64>>, String> {
+    match options {
+        [] => Ok(None),
+        [option, value] if option.eq_ignore_ascii_case("ENTRIESREAD") => {
+            let value = value.parse::<i64>().map_err(|_| NOT_INTEGER)?;
+            if value < -1 {
+                return Err("value for ENTRIESREAD must be positive or -1".to_string());
+            }
+            Ok(Some((value >= 0).then_some(value as u64)))
+        }
+        _ => Err(SYNTAX_ERROR.to_string()),
+    }
+}
+
+// `$` is the stream's last ID
+fn parse_group_id(stream: &RStream, id: &str) -> Result<StreamId, String> {
+    match id {
+        "$" => Ok(stream.last_id),
+        id => StreamId::parse(id, 0),
+    }
+}
+
+// XGROUP CREATE key group id|$ [MKSTREAM] [ENTRIESREAD entries-read]
+fn group_create(db: &Database, key: &str, group: &str, id: &str, options: &[&str]) -> Result<String, String> {
+    let make_stream = options
+        .first()
+        .is_some_and(|option| option.eq_ignore_ascii_case("MKSTREAM"));
+    let entries_read = parse_entries_read(&options[make_stream as usize..])?;
+
+    let created = db.stream_write(key, make_stream, |stream| {
+        let last_id = parse_group_id(stream, id)?;
+        let entries_read = entries_read.unwrap_or_else(|| stream.entries_read_at(last_id));
+        if !stream.create_group(group, last_id, entries_read) {
+            return Err("BUSYGROUP Consumer Group name already exists".to_string());
+        }
+
+        let mut args = command(&["XGROUP", "CREATE", key, group, &last_id.to_string()]);
+        if make_stream {
+            args.push("MKSTREAM".to_string());
+        }
+        if let Some(entries_read) = entries_read {
+            args.extend(command(&["ENTRIESREAD", &entries_read.to_string()]));
+        }
+        Ok(((), vec![args]))
+    })?;
+    created.map(|_| resp::ok()).ok_or_else(|| KEY_REQUIRED.to_string())
+}
+
+// XGROUP SETID key group id|$ [ENTRIESREAD entries-read]
+fn group_setid(db: &Database, key: &str, group: &str, id: &str, options: &[&str]) -> Result<String, String> {
+    let entries_read = parse_entries_read(options)?;
+    group_write(db, key, |stream| {
+        let last_id = parse_group_id(stream, id)?;
+        let known = stream.entries_read_at(last_id);
+        let group_state = stream.groups.get_mut(group).ok_or_else(|| group_missing(key, group))?;
+        group_state.last_id = last_id;
+        group_state.entries_read = entries_read.unwrap_or(known);
+        Ok((resp::ok(), vec![setid_command(key, group, stream)]))
+    })
+}
+
+// XSETID key last-id [ENTRIESADDED entries-added] [MAXDELETEDID max-deleted-id]
+pub fn xsetid(db: &Database, args: &[&str]) -> Result<String, String> {
+    let (key, id, options) = match args {
+        [key, id, options @ ..] if options.len().is_multiple_of(2) => (key, id, options),
+        _ => return Err(wrong_args("xsetid")),
+    };
+    let last_id = StreamId::parse(id, 0)?;
+    let mut entries_added = None;
+    let mut max_deleted_id = None;
+    for pair in options.chunks(2) {
+        match pair {
+            [option, value] if option.eq_ignore_ascii_case("ENTRIESADDED") => {
+                let value = value.parse::<i64>().map_err(|_| NOT_INTEGER)?;
+                if value < 0 {
+                    return Err("entries_added must be positive".to_string());
+                }
+                entries_added = Some(value as u64);
+            }
+            [option, value] if option.eq_ignore_ascii_case("MAXDELETEDID") => {
+                max_deleted_id = Some(StreamId::parse(value, 0)?);
+            }
+            _ => return Err(SYNTAX_ERROR.to_string()),
+        }
+    }
+
+    let set = db.stream_write(key, false, |stream| {
+        if stream.entries.keys().next_back().is_some_and(|top| last_id < *top) {
+            return Err("The ID specified in XSETID is smaller than the target stream top item".to_string());
+        }
+        if entries_added.is_some_and(|added| added < stream.len() as u64) {
+            return Err("The entries_added specified in XSETID is smaller than the target stream length".to_string());
+        }
+        if max_deleted_id.is_some_and(|max_deleted| last_id < max_deleted) {
+            return Err("The ID specified in XSETID is smaller than the provided max_deleted_entry_id".to_string());
+        }
+
+        stream.last_id = last_id;
+        stream.entries_added = entries_added.unwrap_or(stream.entries_added);
+        stream.max_deleted_id = max_deleted_id.unwrap_or(stream.max_deleted_id);
+        let mut replicated = command(&["XSETID"]);
+        replicated.extend(command(args));
+        Ok(((), vec![replicated]))
+    })?;
+    set.map(|_| resp::ok()).ok_or_else(|| "no such key".to_string())
+}
+
+// XPENDING key group [[IDLE min-idle-time] start end count [consumer]]
+pub fn xpending(db: &Database, args: &[&str]) -> Result<String, String> {
+    let (key, group, rest) = match args {
+        [key, group, rest @ ..] => (key, group, rest),
+        _ => return Err(wrong_args("xpending")),
+    };
+    let (min_idle, rest) = match rest {
+        [option, idle, rest @ ..] if option.eq_ignore_ascii_case("IDLE") => (parse_ms(idle)?, rest),
+        _ => (0, rest),
+    };
+    let extended = match rest {
+        [] if min_idle == 0 => None,
+        [start, end, count] => Some((start, end, count, None)),
+        [start, end, count, consumer] => Some((start, end, count, Some(*consumer))),
+        _ => return Err(SYNTAX_ERROR.to_string()),
+    };
+
+    let now = now_ms();
+    let reply = db.stream_read(key, |stream| {
+        let group_state = stream.groups.get(*group).ok_or_else(|| no_group(key, group))?;
+        let (start, end, count, consumer) = match extended {
+            Some(extended) => extended,
+            None => {
+                // Summary: count, lowest and highest ID, and entries per consumer
+                let pending = &group_state.pending;
+                let (first, last) = match (pending.keys().next(), pending.keys().next_back()) {
+                    (Some(first), Some(last)) => (resp::bulk(&first.to_string()), resp::bulk(&last.to_string())),
+                    _ => {
+                        return Ok(resp::array(&[
+                            resp::integer(0),
+                            resp::null_bulk(),
+                            resp::null_bulk(),
+                            resp::null_array(),
+                        ]))
+                    }
+                };
+                let consumers: Vec<String> = group_state
+                    .consumers
+                    .keys()
+                    .map(|name| (name, group_state.pending_for(name)))
+                    .filter(|(_, count)| *count > 0)
+                    .map(|(name, count)| resp::array(&[resp::bulk(name), resp::bulk(&count.to_string())]))
+                    .collect();
+                return Ok(resp::array(&[
+                    resp::integer(pending.len() as i64),
+                    first,
+                    last,
+                    resp::array(&consumers),
+                ]));
+            }
+        };
+
+        let (start, end) = match (parse_bound(start, true)?, parse_bound(end, false)?) {
+            (Some(start), Some(end)) if start <= end => (start, end),
+            _ => return Ok(resp::array(&[])),
+        };
+        let items: Vec<String> = group_state
+            .pending
+            .range(start..=end)
+            .filter(|(_, pending)| consumer.is_none_or(|consumer| pending.consumer == consumer))
+            .map(|(id, pending)| (id, pending, now.saturating_sub(pending.delivered)))
+            .filter(|(_, _, idle)| *idle >= min_idle)
+            .take(parse_count(count)?)
+            .map(|(id, pending, idle)| {
+                resp::array(&[
+                    resp::bulk(&id.to_string()),
+                    resp::bulk(&pending.consumer),
+                    resp::integer(idle as i64),
+                    resp::integer(pending.deliveries as i64),
+                ])
+            })
+            .collect();
+        Ok(resp::array(&items))
+    });
+    prefixed(reply.unwrap_or_else(|| Err(no_group(key, group))))
+}
+
+// The replies and replication of XCLAIM and XAUTOCLAIM: claimed entries become
+// forced claims and pending entries whose message is gone become acks
+fn claimed_commands(
+    key: &str,
+    group: &str,
+    stream: &RStream,
+    claimed: &[(StreamId, Fields)],
+    deleted: &[StreamId],
+) -> Vec<Vec<String>> {
+    let mut commands: Vec<Vec<String>> = claimed
+        .iter()
+        .map(|(id, _)| claim_command(key, group, stream, *id))
+        .collect();
+    if !deleted.is_empty() {
+        let mut ack = command(&["XACK", key, group]);
+        ack.extend(deleted.iter().map(StreamId::to_string));
+        commands.push(ack);
+    }
+    commands
+}
+
+fn claimed_reply(claimed: &[(StreamId, Fields)], just_id: bool) -> String {
+    if just_id {
+        let ids: Vec<StreamId> = claimed.iter().map(|(id, _)| *id).collect();
+        ids_reply(&ids)
+    } else {
+        entries_reply(claimed)
+    }
+}
+
+// XCLAIM key group consumer min-idle-time id [id ...] [IDLE ms] [TIME unix-time-ms]
+// [RETRYCOUNT count] [FORCE] [JUSTID] [LASTID lastid]
+pub fn xclaim(db: &Database, args: &[&str]) -> Result<String, String> {
+    let (key, group, consumer, min_idle, rest) = match args {
+        [key, group, consumer, min_idle, rest @ ..] if !rest.is_empty() => (key, group, consumer, min_idle, rest),
+        _ => return Err(wrong_args("xclaim")),
+    };
+    let min_idle = min_idle
+        .parse::<i64>()
+        .map_err(|_| "Invalid min-idle-time argument for XCLAIM")?
+        .max(0) as u64;
+
+    // IDs come first, the options start at the first argument that isn't one
+    let id_count = rest.iter().take_while(|arg| StreamId::parse(arg, 0).is_ok()).count();
+    if id_count == 0 {
+        return Err(INVALID_ID.to_string());
+    }
+    let ids = parse_ids(&rest[..id_count])?;
+
+    let now = now_ms();
+    let mut options = ClaimOptions {
+        delivered: None,
+        retry_count: None,
+        force: false,
+        just_id: false,
+        last_id: None,
+    };
+    let mut i = id_count;
+    while let Some(option) = rest.get(i) {
+        let value = rest.get(i + 1);
+        match option.to_uppercase().as_str() {
+            "FORCE" => options.force = true,
+            "JUSTID" => options.just_id = true,
+            "IDLE" => options.delivered = Some(now.saturating_sub(parse_ms(value.ok_or(SYNTAX_ERROR)?)?)),
+            "TIME" => options.delivered = Some(parse_ms(value.ok_or(SYNTAX_ERROR)?)?),
+            "RETRYCOUNT" => options.retry_count = Some(parse_ms(value.ok_or(SYNTAX_ERROR)?)?),
+            "LASTID" => options.last_id = Some(StreamId::parse(value.ok_or(SYNTAX_ERROR)?, 0)?),
+            _ => return Err(format!("Unrecognized XCLAIM option '{}'", option)),
+        }
+        i += if matches!(option.to_uppercase().as_str(), "FORCE" | "JUSTID") {
+            1
+        } else {
+            2
+        };
+    }
+
+    let result = db.stream_write(key, false, |stream| {
+        if !stream.groups.contains_key(*group) {
+            return Err(no_group(key, group));
+        }
+        let (claimed, deleted) = stream.claim(group, consumer, min_idle, &ids, &options, now);
+        let commands = claimed_commands(key, group, stream, &claimed, &deleted);
+        Ok((claimed_reply(&claimed, options.just_id), commands))
+    });
+    prefixed(result.and_then(|reply| reply.ok_or_else(|| no_group(key, group))))
+}
+
+// XAUTOCLAIM key group consumer min-idle-time start [COUNT count] [JUSTID]
+pub fn xautoclaim(db: &Database, args: &[&str]) -> Result<String, String> {
+    let (key, group, consumer, min_idle, start, rest) = match args {
+        [key, group, consumer, min_idle, start, rest @ ..] => (key, group, consumer, min_idle, start, rest),
+        _ => return Err(wrong_args("xautoclaim")),
+    };
+    let min_idle = min_idle
+        .parse::<i64>()
+        .map_err(|_| "Invalid min-idle-time argument for XAUTOCLAIM")?
+        .max(0) as u64;
+    let start = parse_bound(start, true)?.ok_or(INVALID_ID)?;
+
+    let mut count = 100;
+    let mut just_id = false;
+    let mut i = 0;
+    while let Some(option) = rest.get(i) {
+        match option.to_uppercase().as_str() {
+            "COUNT" => {
+                count = rest
+                    .get(i + 1)
+                    .ok_or(SYNTAX_ERROR)?
+                    .parse::<i64>()
+                    .map_err(|_| NOT_INTEGER)?
+                    .max(0) as usize;
+                if count == 0 {
+                    return Err("COUNT must be > 0".to_string());
+                }
+                i += 2;
+            }
+            "JUSTID" => {
+                just_id = true;
+                i += 1;
+            }
+            _ => return Err(SYNTAX_ERROR.to_string()),
+        }
+    }
+
+    let result = db.stream_write(key, false, |stream| {
+        if !stream.groups.contains_key(*group) {
+            return Err(no_group(key, group));
+        }
+        let (cursor, claimed, deleted) = stream.auto_claim(group, consumer, min_idle, start, count, just_id);
+        let commands = claimed_commands(key, group, stream, &claimed, &deleted);
+        let reply = resp::array(&[
+            resp::bulk(&cursor.to_string()),
+            claimed_reply(&claimed, just_id),
+            ids_reply(&deleted),
+        ]);
+        Ok((reply, commands))
+    });
+    prefixed(result.and_then(|reply| reply.ok_or_else(|| no_group(key, group))))
+}
+
+// XINFO STREAM|GROUPS|CONSUMERS
+pub fn xinfo(db: &Database, args: &[&str]) -> Result<String, String> {
+    let subcommand = args.first().map(|s| s.to_uppercase()).unwrap_or_default();
+    let now = now_ms();
+    let reply = match (subcommand.as_str(), &args[1.min(args.len())..]) {
+        ("STREAM", [key]) => db.stream_read(key, |stream| Ok(stream_info(stream))),
+        ("GROUPS", [key]) => db.stream_read(key, |stream| {
+            let groups: Vec<String> = stream
+                .groups
+                .iter()
+                .map(|(name, group)| {
+                    let entries_read = group.entries_read.map(|read| resp::integer(read as i64));
+                    let lag = group
+                        .entries_read
+                        .map(|read| resp::integer(stream.entries_added.saturating_sub(read) as i64));
+                    resp::array(&[
+                        resp::bulk("name"),
+                        resp::bulk(name),
+                        resp::bulk("consumers"),
+                        resp::integer(group.consumers.len() as i64),
+                        resp::bulk("pending"),
+                        resp::integer(group.pending.len() as i64),
+                        resp::bulk("last-delivered-id"),
+                        resp::bulk(&group.last_id.to_string()),
+                        resp::bulk("entries-read"),
+                        entries_read.unwrap_or_else(resp::null_bulk),
+                        resp::bulk("lag"),
+                        lag.unwrap_or_else(resp::null_bulk),
+                    ])
+                })
+                .collect();
+            Ok(resp::array(&groups))
+        }),
+        ("CONSUMERS", [key, group]) => db.stream_read(key, |stream| {
+            let group_state = stream.groups.get(*group).ok_or_else(|| group_missing(key, group))?;
+            let consumers: Vec<String> = group_state
+                .consumers
+                .iter()
+                .map(|(name, consumer)| {
+                    let inactive = consumer.active.map_or(-1, |active| now.saturating_sub(active) as i64);
+                    resp::array(&[
+                        resp::bulk("name"),
+                        resp::bulk(name),
+                        resp::bulk("pending"),
+                        resp::integer(group_state.pending_for(name) as i64),
+                        resp::bulk("idle"),
+                        resp::integer(now.saturating_sub(consumer.seen) as i64),
+                        resp::bulk("inactive"),
+                        resp::integer(inactive),
+                    ])
+                })
+                .collect();
+            Ok(resp::array(&consumers))
+        }),
+        _ => {
+            return Err(format!(
+                "unknown subcommand or wrong number of arguments for '{}'. Try XINFO HELP.",
+                args.first().unwrap_or(&"")
+            ))
+        }
+    };
+    prefixed(reply.unwrap_or_else(|| Err("no such key".to_string())))
+}
+
+fn stream_info(stream: &RStream) -> String {
+    let first = stream.entries.iter().next();
+    let last = stream.entries.iter().next_back();
+    let entry = |entry: Option<(&StreamId, &Fields)>| match entry {
+        Some((id, fields)) => entry_reply(*id, Some(fields)),
+        None => resp::null_bulk(),
+    };
+    resp::array(&[
+        resp::bulk("length"),
+        resp::integer(stream.len() as i64),
+        resp::bulk("last-generated-id"),
+        resp::bulk(&stream.last_id.to_string()),
+        resp::bulk("max-deleted-entry-id"),
+        resp::bulk(&stream.max_deleted_id.to_string()),
+        resp::bulk("entries-added"),
+        resp::integer(stream.entries_added as i64),
+        resp::bulk("recorded-first-entry-id"),
+        resp::bulk(&stream.first_id().unwrap_or(StreamId::MIN).to_string()),
+        resp::bulk("groups"),
+        resp::integer(stream.groups.len() as i64),
+        resp::bulk("first-entry"),
+        entry(first),
+        resp::bulk("last-entry"),
+        entry(last),
+    ])
+}
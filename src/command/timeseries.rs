@@ -0,0 +1,430 @@
+use crate::command::resp;
+use crate::database::stream::now_ms;
+use crate::database::timeseries::{
+    self, aggregate, format_value, Aggregation, Rule, TimeSeries, DEFAULT_CHUNK_SIZE, NO_KEY,
+};
+use crate::database::Database;
+
+const SYNTAX_ERROR: &str = "syntax error";
+const KEY_EXISTS: &str = "TSDB: key already exists";
+const INVALID_TIMESTAMP: &str = "TSDB: invalid timestamp";
+const INVALID_VALUE: &str = "TSDB: invalid value";
+
+fn wrong_args(command: &str) -> String {
+    format!("wrong number of arguments for '{}' command", command)
+}
+
+fn command(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+fn parse_timestamp(value: &str) -> Result<i64, String> {
+    match value {
+        "*" => Ok(now_ms() as i64),
+        _ => value.parse::<u64>().map(|ts| ts as i64).map_err(|_| INVALID_TIMESTAMP.to_string()),
+    }
+}
+
+fn parse_value(value: &str) -> Result<f64, String> {
+    value.parse::<f64>().ok().filter(|v| v.is_finite()).ok_or_else(|| INVALID_VALUE.to_string())
+}
+
+// Range bounds: `-` and `+` for the oldest and newest samples
+fn parse_bound(value: &str) -> Result<i64, String> {
+    match value {
+        "-" => Ok(0),
+        "+" => Ok(i64::MAX),
+        _ => value.parse::<u64>().map(|ts| ts as i64).map_err(|_| INVALID_TIMESTAMP.to_string()),
+    }
+}
+
+fn sample(timestamp: i64, value: f64) -> String {
+    resp::array(&[resp::integer(timestamp), resp::bulk(&format_value(value))])
+}
+
+fn labels_reply(labels: &[(String, String)]) -> String {
+    let pairs: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| resp::array(&[resp::bulk(name), resp::bulk(value)]))
+        .collect();
+    resp::array(&pairs)
+}
+
+// Options a new series takes, from TS.CREATE or a TS.ADD that creates the key
+struct CreateOptions {
+    retention: u64,
+    chunk_size: usize,
+    labels: Vec<(String, String)>,
+}
+
+impl CreateOptions {
+    fn parse(mut args: &[&str]) -> Result<CreateOptions, String> {
+        let mut options = CreateOptions { retention: 0, chunk_size: DEFAULT_CHUNK_SIZE, labels: Vec::new() };
+        while let [option, tail @ ..] = args {
+            match (option.to_uppercase().as_str(), tail) {
+                ("RETENTION", [retention, tail @ ..]) => {
+                    options.retention = retention.parse().map_err(|_| "TSDB: Couldn't parse RETENTION")?;
+                    args = tail;
+                }
+                ("CHUNK_SIZE", [size, tail @ ..]) => {
+                    options.chunk_size = size
+                        .parse()
+                        .ok()
+                        .filter(|size| (48..=1048576).contains(size))
+                        .ok_or("TSDB: CHUNK_SIZE value must be between 48 and 1048576")?;
+                    args = tail;
+                }
+                // Labels take the rest of the arguments
+                ("LABELS", pairs) => {
+                    if !pairs.len().is_multiple_of(2) {
+                        return Err(SYNTAX_ERROR.to_string());
+                    }
+                    options.labels = pairs.chunks(2).map(|pair| (pair[0].to_string(), pair[1].to_string())).collect();
+                    args = &[];
+                }
+                _ => return Err(SYNTAX_ERROR.to_string()),
+            }
+        }
+        Ok(options)
+    }
+
+    fn series(self) -> TimeSeries {
+        TimeSeries::new(self.retention, self.chunk_size, self.labels)
+    }
+}
+
+// TS.CREATE key [RETENTION ms] [CHUNK_SIZE bytes] [LABELS label value ...]
+pub fn ts_create(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key, rest @ ..] = args else {
+        return Err(wrong_args("ts.create"));
+    };
+    let options = CreateOptions::parse(rest)?;
+    db.timeseries_write(&[key], |map| {
+        if map.contains_key(*key) {
+            return Err(KEY_EXISTS.to_string());
+        }
+        map.insert(key.to_string(), options.series());
+        let mut replicated = vec!["TS.CREATE"];
+        replicated.extend(args);
+        Ok((resp::ok(), vec![command(&replicated)]))
+    })
+}
+
+// TS.ADD key timestamp value [RETENTION ms] [CHUNK_SIZE bytes] [LABELS ...]. A
+// missing key is created with the options, which are ignored otherwise. `*`
+// timestamps are replicated as the time they were given.
+pub fn ts_add(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key, timestamp, value, rest @ ..] = args else {
+        return Err(wrong_args("ts.add"));
+    };
+    let timestamp = parse_timestamp(timestamp)?;
+    let value = parse_value(value)?;
+    let options = CreateOptions::parse(rest)?;
+
+    db.timeseries_write(&[key], |map| {
+        let created = !map.contains_key(*key);
+        if created {
+            map.insert(key.to_string(), options.series());
+        }
+        if let Err(e) = timeseries::add_sample(map, key, timestamp, value) {
+            if created {
+                map.remove(*key);
+            }
+            return Err(e);
+        }
+        let resolved = timestamp.to_string();
+        let mut replicated = vec!["TS.ADD", key, &resolved];
+        replicated.extend(&args[2..]);
+        Ok((resp::integer(timestamp), vec![command(&replicated)]))
+    })
+}
+
+// TS.MADD key timestamp value [key timestamp value ...]. Each sample gets its
+// timestamp or an error.
+pub fn ts_madd(db: &Database, args: &[&str]) -> Result<String, String> {
+    if args.is_empty() || !args.len().is_multiple_of(3) {
+        return Err(wrong_args("ts.madd"));
+    }
+    let samples = args
+        .chunks(3)
+        .map(|sample| Ok((sample[0], parse_timestamp(sample[1])?, parse_value(sample[2])?)))
+        .collect::<Result<Vec<_>, String>>()?;
+
+    db.timeseries_write(&[], |map| {
+        let mut replies = Vec::new();
+        let mut replicated = vec!["TS.MADD".to_string()];
+        for (key, timestamp, value) in samples {
+            match timeseries::add_sample(map, key, timestamp, value) {
+                Ok(()) => {
+                    replies.push(resp::integer(timestamp));
+                    replicated.extend([key.to_string(), timestamp.to_string(), format_value(value)]);
+                }
+                Err(e) => replies.push(format!("-ERR {}\r\n", e)),
+            }
+        }
+        let commands = if replicated.len() > 1 { vec![replicated] } else { Vec::new() };
+        Ok((resp::array(&replies), commands))
+    })
+}
+
+// TS.GET key: the latest sample, or an empty array
+pub fn ts_get(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key] = args else {
+        return Err(wrong_args("ts.get"));
+    };
+    db.timeseries_read(key, |series| match series.last() {
+        Some((timestamp, value)) => sample(timestamp, value),
+        None => resp::array(&[]),
+    })
+    .ok_or_else(|| NO_KEY.to_string())
+}
+
+// A label matcher from a FILTER: `l=v`, `l!=v`, `l=(v1,v2)`, `l!=(v1,v2)`,
+// and with no value `l=` for series without the label, `l!=` for series with it
+struct Matcher {
+    label: String,
+    values: Vec<String>,
+    negated: bool,
+}
+
+impl Matcher {
+    fn parse(text: &str) -> Result<Matcher, String> {
+        let (label, value, negated) = match text.split_once("!=") {
+            Some((label, value)) => (label, value, true),
+            None => {
+                let (label, value) = text.split_once('=').ok_or("TSDB: failed parsing labels")?;
+                (label, value, false)
+            }
+        };
+        if label.is_empty() {
+            return Err("TSDB: failed parsing labels".to_string());
+        }
+        let values = match value.strip_prefix('(').and_then(|list| list.strip_suffix(')')) {
+            Some(list) => list.split(',').map(|value| value.trim().to_string()).collect(),
+            None => vec![value.to_string()],
+        };
+        Ok(Matcher { label: label.to_string(), values, negated })
+    }
+
+    fn absent(&self) -> bool {
+        self.values == [""]
+    }
+
+    fn matches(&self, series: &TimeSeries) -> bool {
+        let found = match series.label(&self.label) {
+            None => self.absent(),
+            Some(value) => self.values.iter().any(|candidate| candidate == value),
+        };
+        found != self.negated
+    }
+}
+
+struct RangeOptions {
+    filter_by_value: Option<(f64, f64)>,
+    count: Option<usize>,
+    aggregation: Option<(Aggregation, i64)>,
+    with_labels: bool,
+    filters: Vec<Matcher>,
+}
+
+// The options after the range. Only TS.MRANGE takes WITHLABELS and FILTER,
+// which comes last.
+fn parse_range_options(mut args: &[&str], multi: bool) -> Result<RangeOptions, String> {
+    let mut options =
+        RangeOptions { filter_by_value: None, count: None, aggregation: None, with_labels: false, filters: Vec::new() };
+    while let [option, tail @ ..] = args {
+        match (option.to_uppercase().as_str(), tail) {
+            ("FILTER_BY_VALUE", [min, max, tail @ ..]) => {
+                options.filter_by_value = Some((parse_value(min)?, parse_value(max)?));
+                args = tail;
+            }
+            ("COUNT", [count, tail @ ..]) => {
+                options.count = Some(count.parse().map_err(|_| "TSDB: Couldn't parse COUNT")?);
+                args = tail;
+            }
+            ("AGGREGATION", [kind, bucket, tail @ ..]) => {
+                options.aggregation = Some(parse_aggregation(kind, bucket)?);
+                args = tail;
+            }
+            ("WITHLABELS", tail) if multi => {
+                options.with_labels = true;
+                args = tail;
+            }
+            ("FILTER", matchers) if multi && !matchers.is_empty() => {
+                options.filters = matchers.iter().map(|matcher| Matcher::parse(matcher)).collect::<Result<_, _>>()?;
+                args = &[];
+            }
+            _ => return Err(SYNTAX_ERROR.to_string()),
+        }
+    }
+    if multi && !options.filters.iter().any(|matcher| !matcher.negated && !matcher.absent()) {
+        return Err("TSDB: please provide at least one matcher".to_string());
+    }
+    Ok(options)
+}
+
+fn parse_aggregation(kind: &str, bucket: &str) -> Result<(Aggregation, i64), String> {
+    let aggregation = Aggregation::parse(kind).ok_or("TSDB: Unknown aggregation type")?;
+    let bucket = bucket
+        .parse::<i64>()
+        .ok()
+        .filter(|bucket| *bucket > 0)
+        .ok_or("TSDB: bucketDuration must be greater than zero")?;
+    Ok((aggregation, bucket))
+}
+
+// The samples of one series as TS.RANGE returns them: filtered by value, then
+// aggregated, then reversed for REVRANGE and cut to COUNT
+fn range_reply(series: &TimeSeries, from: i64, to: i64, options: &RangeOptions, reverse: bool) -> String {
+    let mut samples = series.samples(from, to);
+    if let Some((min, max)) = options.filter_by_value {
+        samples.retain(|(_, value)| (min..=max).contains(value));
+    }
+    if let Some((aggregation, bucket)) = options.aggregation {
+        samples = aggregate(&samples, aggregation, bucket);
+    }
+    if reverse {
+        samples.reverse();
+    }
+    if let Some(count) = options.count {
+        samples.truncate(count);
+    }
+    let samples: Vec<String> = samples.into_iter().map(|(timestamp, value)| sample(timestamp, value)).collect();
+    resp::array(&samples)
+}
+
+// TS.RANGE / TS.REVRANGE key from to [FILTER_BY_VALUE min max] [COUNT n]
+// [AGGREGATION type bucket]
+pub fn ts_range(db: &Database, args: &[&str], reverse: bool) -> Result<String, String> {
+    let [key, from, to, rest @ ..] = args else {
+        return Err(wrong_args(if reverse { "ts.revrange" } else { "ts.range" }));
+    };
+    let (from, to) = (parse_bound(from)?, parse_bound(to)?);
+    let options = parse_range_options(rest, false)?;
+    db.timeseries_read(key, |series| range_reply(series, from, to, &options, reverse))
+        .ok_or_else(|| NO_KEY.to_string())
+}
+
+// TS.MRANGE / TS.MREVRANGE from to [WITHLABELS] [FILTER_BY_VALUE min max]
+// [COUNT n] [AGGREGATION type bucket] FILTER matcher ...
+pub fn ts_mrange(db: &Database, args: &[&str], reverse: bool) -> Result<String, String> {
+    let [from, to, rest @ ..] = args else {
+        return Err(wrong_args(if reverse { "ts.mrevrange" } else { "ts.mrange" }));
+    };
+    let (from, to) = (parse_bound(from)?, parse_bound(to)?);
+    let options = parse_range_options(rest, true)?;
+
+    Ok(db.timeseries_scan(|map| {
+        let mut keys: Vec<&String> = map
+            .iter()
+            .filter(|(_, series)| options.filters.iter().all(|matcher| matcher.matches(series)))
+            .map(|(key, _)| key)
+            .collect();
+        keys.sort();
+        let replies: Vec<String> = keys
+            .into_iter()
+            .map(|key| {
+                let series = &map[key];
+                let labels = if options.with_labels { labels_reply(&series.labels) } else { resp::array(&[]) };
+                resp::array(&[resp::bulk(key), labels, range_reply(series, from, to, &options, reverse)])
+            })
+            .collect();
+        resp::array(&replies)
+    }))
+}
+
+// TS.CREATERULE source dest AGGREGATION type bucket. Each time a sample
+// starts a new bucket in the source, the finished bucket is aggregated into dest.
+pub fn ts_createrule(db: &Database, args: &[&str]) -> Result<String, String> {
+    let (source, dest, kind, bucket) = match args {
+        [source, dest, option, kind, bucket] if option.eq_ignore_ascii_case("AGGREGATION") => {
+            (source, dest, kind, bucket)
+        }
+        [_, _, _, _, _] => return Err(SYNTAX_ERROR.to_string()),
+        _ => return Err(wrong_args("ts.createrule")),
+    };
+    let (aggregation, bucket) = parse_aggregation(kind, bucket)?;
+    if source == dest {
+        return Err("TSDB: the source key and destination key should be different".to_string());
+    }
+
+    db.timeseries_write(&[], |map| {
+        let (Some(source_series), Some(dest_series)) = (map.get(*source), map.get(*dest)) else {
+            return Err(NO_KEY.to_string());
+        };
+        if source_series.source.is_some() {
+            return Err("TSDB: the source key already has a source rule".to_string());
+        }
+        if dest_series.source.is_some() || !dest_series.rules.is_empty() {
+            return Err("TSDB: the destination key already has a src rule".to_string());
+        }
+        map.get_mut(*dest).unwrap().source = Some(source.to_string());
+        map.get_mut(*source).unwrap().rules.push(Rule { dest: dest.to_string(), aggregation, bucket });
+        let mut replicated = vec!["TS.CREATERULE"];
+        replicated.extend(args);
+        Ok((resp::ok(), vec![command(&replicated)]))
+    })
+}
+
+// TS.DELETERULE source dest
+pub fn ts_deleterule(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [source, dest] = args else {
+        return Err(wrong_args("ts.deleterule"));
+    };
+    db.timeseries_write(&[], |map| {
+        let series = map.get_mut(*source).ok_or(NO_KEY)?;
+        let before = series.rules.len();
+        series.rules.retain(|rule| rule.dest != *dest);
+        if series.rules.len() == before {
+            return Err("TSDB: compaction rule does not exist".to_string());
+        }
+        if let Some(dest_series) = map.get_mut(*dest) {
+            dest_series.source = None;
+        }
+        Ok((resp::ok(), vec![command(&["TS.DELETERULE", source, dest])]))
+    })
+}
+
+// TS.INFO key
+pub fn ts_info(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key] = args else {
+        return Err(wrong_args("ts.info"));
+    };
+    db.timeseries_read(key, |series| {
+        let rules: Vec<String> = series
+            .rules
+            .iter()
+            .map(|rule| {
+                resp::array(&[
+                    resp::bulk(&rule.dest),
+                    resp::integer(rule.bucket),
+                    resp::bulk(rule.aggregation.name()),
+                ])
+            })
+            .collect();
+        let last = series.last().map_or(0, |(timestamp, _)| timestamp);
+        resp::array(&[
+            resp::bulk("totalSamples"),
+            resp::integer(series.total_samples() as i64),
+            resp::bulk("memoryUsage"),
+            resp::integer(series.memory_usage() as i64),
+            resp::bulk("firstTimestamp"),
+            resp::integer(series.first_timestamp().unwrap_or(0)),
+            resp::bulk("lastTimestamp"),
+            resp::integer(last),
+            resp::bulk("retentionTime"),
+            resp::integer(series.retention as i64),
+            resp::bulk("chunkCount"),
+            resp::integer(series.chunk_count() as i64),
+            resp::bulk("chunkSize"),
+            resp::integer(series.chunk_size as i64),
+            resp::bulk("labels"),
+            labels_reply(&series.labels),
+            resp::bulk("sourceKey"),
+            series.source.as_deref().map_or_else(resp::null_bulk, resp::bulk),
+            resp::bulk("rules"),
+            resp::array(&rules),
+        ])
+    })
+    .ok_or_else(|| NO_KEY.to_string())
+}
@@ -0,0 +1,185 @@
+use crate::command::resp;
+use crate::database::sketch::{Sketch, WRONG_TYPE};
+use crate::database::topk::{self, TopK, MAX_INCREMENT};
+use crate::database::Database;
+
+const SYNTAX_ERROR: &str = "syntax error";
+const KEY_EXISTS: &str = "TopK: key already exists";
+const NO_KEY: &str = "TopK: key does not exist";
+
+fn wrong_args(command: &str) -> String {
+    format!("wrong number of arguments for '{}' command", command)
+}
+
+// WRONGTYPE goes out as is, the rest get -ERR from the connection
+fn prefixed(result: Result<String, String>) -> Result<String, String> {
+    match result {
+        Err(e) if e.starts_with("WRONGTYPE ") => Ok(format!("-{}\r\n", e)),
+        other => other,
+    }
+}
+
+fn command<'a>(name: &'a str, args: &[&'a str]) -> Vec<&'a str> {
+    let mut command = vec![name];
+    command.extend(args);
+    command
+}
+
+fn topk(sketch: &Sketch) -> Result<&TopK, String> {
+    match sketch {
+        Sketch::TopK(topk) => Ok(topk),
+        _ => Err(WRONG_TYPE.to_string()),
+    }
+}
+
+fn parse_size(text: &str, name: &str) -> Result<usize, String> {
+    text.parse::<usize>()
+        .ok()
+        .filter(|size| *size > 0)
+        .ok_or_else(|| format!("TopK: invalid {}", name))
+}
+
+// TOPK.RESERVE key topk [width depth decay]
+pub fn topk_reserve(db: &Database, args: &[&str]) -> Result<String, String> {
+    let (key, k, width, depth, decay) = match args {
+        [key, k] => (key, k, topk::DEFAULT_WIDTH, topk::DEFAULT_DEPTH, topk::DEFAULT_DECAY),
+        [key, k, width, depth, decay] => (
+            key,
+            k,
+            parse_size(width, "width")?,
+            parse_size(depth, "depth")?,
+            decay
+                .parse::<f64>()
+                .ok()
+                .filter(|decay| *decay > 0.0 && *decay <= 1.0)
+                .ok_or("TopK: invalid decay value. must be '<= 1' & '> 0'")?,
+        ),
+        _ => return Err(wrong_args("topk.reserve")),
+    };
+    let k = parse_size(k, "k")?;
+    let topk = TopK::new(k, width, depth, decay).ok_or("TopK: sketch is too large")?;
+
+    db.sketch_write(key, &command("TOPK.RESERVE", args), |sketch| {
+        if sketch.is_some() {
+            return Err(KEY_EXISTS.to_string());
+        }
+        *sketch = Some(Sketch::TopK(topk));
+        Ok((resp::ok(), true))
+    })
+}
+
+// Counts the items up, replying per item with the item it pushed out of the
+// top k, or nil
+fn increment(db: &Database, key: &str, command: &[&str], increments: &[(&str, u64)]) -> Result<String, String> {
+    prefixed(db.sketch_write(key, command, |sketch| {
+        let Some(Sketch::TopK(topk)) = sketch else {
+            return Err(sketch.as_ref().map_or(NO_KEY, |_| WRONG_TYPE).to_string());
+        };
+        let replies: Vec<String> = increments
+            .iter()
+            .map(|(item, by)| match topk.increment(item, *by) {
+                Some(expelled) => resp::bulk(&expelled),
+                None => resp::null_bulk(),
+            })
+            .collect();
+        Ok((resp::array(&replies), true))
+    }))
+}
+
+// TOPK.ADD key item [item ...]
+pub fn topk_add(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key, items @ ..] = args else {
+        return Err(wrong_args("topk.add"));
+    };
+    if items.is_empty() {
+        return Err(wrong_args("topk.add"));
+    }
+    let increments: Vec<(&str, u64)> = items.iter().map(|item| (*item, 1)).collect();
+    increment(db, key, &command("TOPK.ADD", args), &increments)
+}
+
+// TOPK.INCRBY key item increment [item increment ...]
+pub fn topk_incrby(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key, pairs @ ..] = args else {
+        return Err(wrong_args("topk.incrby"));
+    };
+    if pairs.is_empty() || !pairs.len().is_multiple_of(2) {
+        return Err(wrong_args("topk.incrby"));
+    }
+    let increments = pairs
+        .chunks(2)
+        .map(|pair| {
+            let by = pair[1].parse::<u64>().ok().filter(|by| (1..=MAX_INCREMENT).contains(by)).ok_or_else(|| {
+                format!("TopK: increment must be an integer between 1 and {}", MAX_INCREMENT)
+            })?;
+            Ok((pair[0], by))
+        })
+        .collect::<Result<Vec<(&str, u64)>, String>>()?;
+    increment(db, key, &command("TOPK.INCRBY", args), &increments)
+}
+
+// TOPK.QUERY key item [item ...]: whether each item is in the top k
+pub fn topk_query(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key, items @ ..] = args else {
+        return Err(wrong_args("topk.query"));
+    };
+    if items.is_empty() {
+        return Err(wrong_args("topk.query"));
+    }
+    let replies = db
+        .sketch_read(key, |sketch| {
+            let topk = topk(sketch)?;
+            let replies: Vec<String> =
+                items.iter().map(|item| resp::integer(topk.contains(item) as i64)).collect();
+            Ok(resp::array(&replies))
+        })?
+        .ok_or(NO_KEY)?;
+    prefixed(replies)
+}
+
+// TOPK.LIST key [WITHCOUNT], biggest count first
+pub fn topk_list(db: &Database, args: &[&str]) -> Result<String, String> {
+    let (key, with_count) = match args {
+        [key] => (key, false),
+        [key, option] if option.eq_ignore_ascii_case("WITHCOUNT") => (key, true),
+        [_, _] => return Err(SYNTAX_ERROR.to_string()),
+        _ => return Err(wrong_args("topk.list")),
+    };
+    let replies = db
+        .sketch_read(key, |sketch| {
+            let replies: Vec<String> = topk(sketch)?
+                .list()
+                .into_iter()
+                .flat_map(|(item, count)| {
+                    let count = with_count.then(|| resp::integer(count as i64));
+                    std::iter::once(resp::bulk(item)).chain(count)
+                })
+                .collect();
+            Ok(resp::array(&replies))
+        })?
+        .ok_or(NO_KEY)?;
+    prefixed(replies)
+}
+
+// TOPK.INFO key
+pub fn topk_info(db: &Database, args: &[&str]) -> Result<String, String> {
+    let [key] = args else {
+        return Err(wrong_args("topk.info"));
+    };
+    let info = db
+        .sketch_read(key, |sketch| {
+            let topk = topk(sketch)?;
+            Ok(resp::array(&[
+                resp::bulk("k"),
+                resp::integer(topk.k() as i64),
+                resp::bulk("width"),
+                resp::integer(topk.width() as i64),
+                resp::bulk("depth"),
+                resp::integer(topk.depth() as i64),
+                resp::bulk("decay"),
+                resp::bulk(&topk.decay().to_string()),
+            ]))
+        })?
+        .ok_or(NO_KEY)?;
+    prefixed(info)
+}
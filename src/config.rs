@@ -0,0 +1,324 @@
+use std::fs;
+
+use crate::pubsub::notify::NotifyFlags;
+
+// Server configuration. Values come from an optional redis.conf-style file
+// (`redis-rust /path/to/redis.conf`) followed by `--name value` overrides.
+#[derive(Clone)]
+pub struct Config {
+    pub bind: String,
+    pub port: u16,
+    pub http_port: u16,
+    pub tls_port: u16,
+    pub tls_http: bool,
+    pub tls_cert_file: Option<String>,
+    pub tls_key_file: Option<String>,
+    pub tls_ca_cert_file: Option<String>,
+    pub tls_auth_clients: TlsAuthClients,
+    pub tls_auth_clients_user: bool,
+    pub unixsocket: Option<String>,
+    pub unixsocketperm: Option<u32>,
+    pub client_query_buffer_limit: usize,
+    pub proto_max_bulk_len: usize,
+    pub client_output_buffer_limit: OutputBufferLimits,
+    // Seconds a client may stay idle before it is closed, 0 disables it
+    pub timeout: u64,
+    pub tcp_keepalive: u64,
+    pub maxclients: u64,
+    pub pidfile: Option<String>,
+    // Master to replicate from at startup, as (host, port)
+    pub replicaof: Option<(String, u16)>,
+    pub replica_read_only: bool,
+    pub repl_backlog_size: usize,
+    pub cluster_enabled: bool,
+    // Milliseconds a script may run before other clients get -BUSY
+    pub busy_reply_threshold: u64,
+    // Address other cluster nodes reach us at, defaults to `bind`
+    pub cluster_announce_ip: Option<String>,
+    // Size a sparse HyperLogLog may grow to before switching to dense
+    pub hll_sparse_max_bytes: usize,
+    // Which keyspace notifications get published, none by default
+    pub notify_keyspace_events: NotifyFlags,
+    // Microseconds a command must take to enter the slow log; negative disables it
+    pub slowlog_log_slower_than: i64,
+    pub slowlog_max_len: usize,
+    // Milliseconds an event must take to be sampled by LATENCY, 0 disables it
+    pub latency_monitor_threshold: u64,
+}
+
+// Limits for one client class; 0 disables a limit
+#[derive(Clone, Copy)]
+pub struct OutputBufferLimit {
+    pub hard: usize,
+    pub soft: usize,
+    pub soft_seconds: u64,
+}
+
+#[derive(Clone, Copy)]
+pub struct OutputBufferLimits {
+    pub normal: OutputBufferLimit,
+    pub replica: OutputBufferLimit,
+    pub pubsub: OutputBufferLimit,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TlsAuthClients {
+    Yes,
+    No,
+    Optional,
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Config {
+            bind: "127.0.0.1".to_string(),
+            port: 6379,
+            http_port: 3000,
+            tls_port: 0,
+            tls_http: false,
+            tls_cert_file: None,
+            tls_key_file: None,
+            tls_ca_cert_file: None,
+            tls_auth_clients: TlsAuthClients::Yes,
+            tls_auth_clients_user: false,
+            unixsocket: None,
+            unixsocketperm: None,
+            client_query_buffer_limit: 1024 * 1024 * 1024,
+            proto_max_bulk_len: 512 * 1024 * 1024,
+            client_output_buffer_limit: OutputBufferLimits {
+                normal: OutputBufferLimit { hard: 0, soft: 0, soft_seconds: 0 },
+                replica: OutputBufferLimit {
+                    hard: 256 * 1024 * 1024,
+                    soft: 64 * 1024 * 1024,
+                    soft_seconds: 60,
+                },
+                pubsub: OutputBufferLimit {
+                    hard: 32 * 1024 * 1024,
+                    soft: 8 * 1024 * 1024,
+                    soft_seconds: 60,
+                },
+            },
+            timeout: 0,
+            tcp_keepalive: 300,
+            maxclients: 10000,
+            pidfile: None,
+            replicaof: None,
+            replica_read_only: true,
+            repl_backlog_size: 1024 * 1024,
+            cluster_enabled: false,
+            busy_reply_threshold: 5000,
+            cluster_announce_ip: None,
+            hll_sparse_max_bytes: 3000,
+            notify_keyspace_events: NotifyFlags::default(),
+            slowlog_log_slower_than: 10000,
+            slowlog_max_len: 128,
+            latency_monitor_threshold: 0,
+        }
+    }
+
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut config = Config::new();
+        let mut args = args.iter().peekable();
+
+        // A leading argument that isn't an option is a config file path
+        if let Some(path) = args.next_if(|arg| !arg.starts_with("--")) {
+            config.load_file(path)?;
+        }
+
+        while let Some(arg) = args.next() {
+            let name = arg
+                .strip_prefix("--")
+                .ok_or_else(|| format!("Unexpected argument '{}'", arg))?;
+            let value = args
+                .next()
+                .ok_or_else(|| format!("Missing value for option '--{}'", name))?;
+            config.set(name, value)?;
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn load_file(&mut self, path: &str) -> Result<(), String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Can't read config file '{}': {}", path, e))?;
+
+        for (line_no, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            self.set(name, value.trim())
+                .map_err(|e| format!("{} (line {} of '{}')", e, line_no + 1, path))?;
+        }
+        Ok(())
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name.to_lowercase().as_str() {
+            "bind" => self.bind = value.to_string(),
+            "port" => self.port = parse_port(name, value)?,
+            "http-port" => self.http_port = parse_port(name, value)?,
+            "tls-port" => self.tls_port = parse_port(name, value)?,
+            "tls-http" => self.tls_http = parse_bool(name, value)?,
+            "tls-cert-file" => self.tls_cert_file = Some(value.to_string()),
+            "tls-key-file" => self.tls_key_file = Some(value.to_string()),
+            "tls-ca-cert-file" => self.tls_ca_cert_file = Some(value.to_string()),
+            "tls-auth-clients" => {
+                self.tls_auth_clients = match value.to_lowercase().as_str() {
+                    "yes" => TlsAuthClients::Yes,
+                    "no" => TlsAuthClients::No,
+                    "optional" => TlsAuthClients::Optional,
+                    _ => return Err(invalid_value(name, value)),
+                }
+            }
+            "tls-auth-clients-user" => {
+                self.tls_auth_clients_user = match value.to_lowercase().as_str() {
+                    "cn" => true,
+                    "off" => false,
+                    _ => return Err(invalid_value(name, value)),
+                }
+            }
+            "unixsocket" => self.unixsocket = Some(value.to_string()),
+            "unixsocketperm" => {
+                let perm = u32::from_str_radix(value, 8).map_err(|_| invalid_value(name, value))?;
+                if perm > 0o777 {
+                    return Err(invalid_value(name, value));
+                }
+                self.unixsocketperm = Some(perm);
+            }
+            "client-query-buffer-limit" => {
+                self.client_query_buffer_limit = parse_memory(name, value)?;
+            }
+            "proto-max-bulk-len" => self.proto_max_bulk_len = parse_memory(name, value)?,
+            "client-output-buffer-limit" => {
+                // <class> <hard> <soft> <soft-seconds>, possibly repeated for several classes
+                let parts: Vec<&str> = value.split_whitespace().collect();
+                if parts.is_empty() || !parts.len().is_multiple_of(4) {
+                    return Err(invalid_value(name, value));
+                }
+                for group in parts.chunks(4) {
+                    let limit = OutputBufferLimit {
+                        hard: parse_memory(name, group[1])?,
+                        soft: parse_memory(name, group[2])?,
+                        soft_seconds: group[3].parse().map_err(|_| invalid_value(name, value))?,
+                    };
+                    let limits = &mut self.client_output_buffer_limit;
+                    match group[0].to_lowercase().as_str() {
+                        "normal" => limits.normal = limit,
+                        "replica" | "slave" => limits.replica = limit,
+                        "pubsub" => limits.pubsub = limit,
+                        _ => return Err(invalid_value(name, value)),
+                    }
+                }
+            }
+            "timeout" => self.timeout = parse_number(name, value)?,
+            "tcp-keepalive" => self.tcp_keepalive = parse_number(name, value)?,
+            "maxclients" => {
+                self.maxclients = parse_number(name, value)?;
+                if self.maxclients == 0 {
+                    return Err(invalid_value(name, value));
+                }
+            }
+            "pidfile" => self.pidfile = Some(value.to_string()),
+            "replicaof" | "slaveof" => {
+                let (host, port) = value
+                    .split_once(char::is_whitespace)
+                    .ok_or_else(|| invalid_value(name, value))?;
+                self.replicaof = Some((host.to_string(), parse_port(name, port.trim())?));
+            }
+            "replica-read-only" | "slave-read-only" => {
+                self.replica_read_only = parse_bool(name, value)?;
+            }
+            "repl-backlog-size" => {
+                self.repl_backlog_size = parse_memory(name, value)?;
+                if self.repl_backlog_size == 0 {
+                    return Err(invalid_value(name, value));
+                }
+            }
+            "cluster-enabled" => self.cluster_enabled = parse_bool(name, value)?,
+            "busy-reply-threshold" | "lua-time-limit" => {
+                self.busy_reply_threshold = parse_number(name, value)?;
+            }
+            "cluster-announce-ip" => self.cluster_announce_ip = Some(value.to_string()),
+            "hll-sparse-max-bytes" => self.hll_sparse_max_bytes = parse_memory(name, value)?,
+            // Config files write the empty string as ""
+            "notify-keyspace-events" => {
+                self.notify_keyspace_events =
+                    NotifyFlags::parse(value.trim_matches('"')).ok_or_else(|| invalid_value(name, value))?;
+            }
+            "slowlog-log-slower-than" => {
+                self.slowlog_log_slower_than = value.parse().map_err(|_| invalid_value(name, value))?;
+            }
+            "slowlog-max-len" => self.slowlog_max_len = value.parse().map_err(|_| invalid_value(name, value))?,
+            "latency-monitor-threshold" => self.latency_monitor_threshold = parse_number(name, value)?,
+            _ => return Err(format!("Unknown option '{}'", name)),
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.tls_enabled() {
+            if self.tls_cert_file.is_none() || self.tls_key_file.is_none() {
+                return Err("TLS requires tls-cert-file and tls-key-file".to_string());
+            }
+            if self.tls_auth_clients != TlsAuthClients::No && self.tls_ca_cert_file.is_none() {
+                return Err(
+                    "tls-auth-clients requires tls-ca-cert-file (or set it to 'no')".to_string(),
+                );
+            }
+        }
+        // Cluster nodes find each other through the plaintext port
+        if self.cluster_enabled && self.port == 0 {
+            return Err("cluster-enabled requires a non-zero port".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn tls_enabled(&self) -> bool {
+        self.tls_port != 0 || self.tls_http
+    }
+}
+
+fn parse_port(name: &str, value: &str) -> Result<u16, String> {
+    value.parse::<u16>().map_err(|_| invalid_value(name, value))
+}
+
+fn parse_number(name: &str, value: &str) -> Result<u64, String> {
+    value.parse::<u64>().map_err(|_| invalid_value(name, value))
+}
+
+fn parse_bool(name: &str, value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "yes" => Ok(true),
+        "no" => Ok(false),
+        _ => Err(invalid_value(name, value)),
+    }
+}
+
+// Parses Redis memory units: 1k = 1000, 1kb = 1024, and likewise for m/mb and g/gb
+fn parse_memory(name: &str, value: &str) -> Result<usize, String> {
+    let lower = value.to_lowercase();
+    let split = lower.find(|c: char| !c.is_ascii_digit()).unwrap_or(lower.len());
+    let (digits, unit) = lower.split_at(split);
+    let multiplier = match unit {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return Err(invalid_value(name, value)),
+    };
+    digits
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| invalid_value(name, value))
+}
+
+fn invalid_value(name: &str, value: &str) -> String {
+    format!("Invalid value '{}' for option '{}'", value, name)
+}
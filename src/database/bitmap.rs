@@ -0,0 +1,184 @@
+// Bit operations on string values, which are binary safe. Bits are numbered from
+// the most significant bit of the first byte, like in Redis.
+
+pub fn get_bit(bytes: &[u8], offset: u64) -> u8 {
+    match bytes.get((offset / 8) as usize) {
+        Some(byte) => (byte >> (7 - offset % 8)) & 1,
+        None => 0,
+    }
+}
+
+// Sets a bit, growing the string with zero bytes as needed, and returns the old one
+pub fn set_bit(bytes: &mut Vec<u8>, offset: u64, bit: u8) -> u8 {
+    let index = (offset / 8) as usize;
+    if index >= bytes.len() {
+        bytes.resize(index + 1, 0);
+    }
+    let mask = 1 << (7 - offset % 8);
+    let old = (bytes[index] & mask != 0) as u8;
+    if bit == 1 {
+        bytes[index] |= mask;
+    } else {
+        bytes[index] &= !mask;
+    }
+    old
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Unit {
+    Byte,
+    Bit,
+}
+
+// Turns a BITCOUNT / BITPOS range into an inclusive range of bits. Negative
+// indexes count from the end and the range is clamped to the string; None when
+// nothing is left.
+pub fn bit_range(len: usize, start: i64, end: i64, unit: Unit) -> Option<(u64, u64)> {
+    let total = match unit {
+        Unit::Byte => len as i64,
+        Unit::Bit => len as i64 * 8,
+    };
+    let start = if start < 0 { (total + start).max(0) } else { start };
+    let end = if end < 0 { (total + end).max(0) } else { end }.min(total - 1);
+    if start > end {
+        return None;
+    }
+    match unit {
+        Unit::Byte => Some((start as u64 * 8, end as u64 * 8 + 7)),
+        Unit::Bit => Some((start as u64, end as u64)),
+    }
+}
+
+// Number of set bits in an inclusive bit range within the string
+pub fn count(bytes: &[u8], (first, last): (u64, u64)) -> u64 {
+    let (first_byte, last_byte) = ((first / 8) as usize, (last / 8) as usize);
+    let mut total: u64 = bytes[first_byte..=last_byte].iter().map(|byte| byte.count_ones() as u64).sum();
+    // Leave out the bits of the first and last byte that are outside the range
+    let before = !(0xFFu8 >> (first % 8));
+    let after = (0xFFu16 >> (last % 8 + 1)) as u8;
+    total -= (bytes[first_byte] & before).count_ones() as u64;
+    total -= (bytes[last_byte] & after).count_ones() as u64;
+    total
+}
+
+// First bit set to `bit` in an inclusive bit range within the string. When
+// looking for a clear bit without an explicit end, the zero bits right after the
+// string count as found.
+pub fn position(bytes: &[u8], bit: u8, (first, last): (u64, u64), end_given: bool) -> i64 {
+    let skip = if bit == 1 { 0x00 } else { 0xFF };
+    let mut offset = first;
+    while offset <= last {
+        let byte = bytes[(offset / 8) as usize];
+        if offset % 8 == 0 && offset + 7 <= last && byte == skip {
+            offset += 8;
+            continue;
+        }
+        if (byte >> (7 - offset % 8)) & 1 == bit {
+            return offset as i64;
+        }
+        offset += 1;
+    }
+    if bit == 0 && !end_given {
+        last as i64 + 1
+    } else {
+        -1
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum BitOp {
+    And,
+    Or,
+    Xor,
+    Not,
+}
+
+// Combines the sources byte by byte, shorter ones padded with zero bytes
+pub fn bitop(op: BitOp, sources: &[Vec<u8>]) -> Vec<u8> {
+    let len = sources.iter().map(Vec::len).max().unwrap_or(0);
+    let byte_at = |source: &Vec<u8>, i: usize| source.get(i).copied().unwrap_or(0);
+    (0..len)
+        .map(|i| {
+            let mut bytes = sources.iter().map(|source| byte_at(source, i));
+            let first = bytes.next().unwrap_or(0);
+            match op {
+                BitOp::And => bytes.fold(first, |acc, byte| acc & byte),
+                BitOp::Or => bytes.fold(first, |acc, byte| acc | byte),
+                BitOp::Xor => bytes.fold(first, |acc, byte| acc ^ byte),
+                BitOp::Not => !first,
+            }
+        })
+        .collect()
+}
+
+// A BITFIELD type: i1 to i64 or u1 to u63
+#[derive(Clone, Copy)]
+pub struct FieldType {
+    pub signed: bool,
+    pub bits: u32,
+}
+
+#[derive(Clone, Copy)]
+pub enum Overflow {
+    Wrap,
+    Sat,
+    Fail,
+}
+
+impl FieldType {
+    pub fn parse(arg: &str) -> Option<Self> {
+        let signed = match arg.chars().next()? {
+            'i' | 'I' => true,
+            'u' | 'U' => false,
+            _ => return None,
+        };
+        let bits = arg[1..].parse::<u32>().ok()?;
+        let max = if signed { 64 } else { 63 };
+        (1..=max).contains(&bits).then_some(FieldType { signed, bits })
+    }
+
+    fn bounds(self) -> (i128, i128) {
+        if self.signed {
+            (-(1 << (self.bits - 1)), (1 << (self.bits - 1)) - 1)
+        } else {
+            (0, (1 << self.bits) - 1)
+        }
+    }
+
+    // Brings a value into range the way the overflow mode says; None when it
+    // doesn't fit and the mode is FAIL
+    pub fn fit(self, value: i128, overflow: Overflow) -> Option<i64> {
+        let (min, max) = self.bounds();
+        if (min..=max).contains(&value) {
+            return Some(value as i64);
+        }
+        match overflow {
+            Overflow::Wrap => {
+                let wrapped = value.rem_euclid(1 << self.bits);
+                Some(if wrapped > max { wrapped - (1 << self.bits) } else { wrapped } as i64)
+            }
+            Overflow::Sat => Some(value.clamp(min, max) as i64),
+            Overflow::Fail => None,
+        }
+    }
+}
+
+// Reads a field; bits past the end of the string are zero
+pub fn get_field(bytes: &[u8], offset: u64, field: FieldType) -> i64 {
+    let mut value: u64 = 0;
+    for i in 0..field.bits as u64 {
+        value = (value << 1) | get_bit(bytes, offset + i) as u64;
+    }
+    if field.signed && field.bits < 64 && value >> (field.bits - 1) == 1 {
+        value |= u64::MAX << field.bits;
+    }
+    value as i64
+}
+
+// Writes the low bits of a value into a field, growing the string as needed
+pub fn set_field(bytes: &mut Vec<u8>, offset: u64, field: FieldType, value: i64) {
+    for i in 0..field.bits as u64 {
+        let bit = (value as u64 >> (field.bits as u64 - 1 - i)) & 1;
+        set_bit(bytes, offset + i, bit as u8);
+    }
+}
@@ -0,0 +1,186 @@
+// Scalable Bloom filters: a stack of fixed-size filters. When the newest one
+// holds its capacity, a bigger one is added with a tighter error rate, so the
+// combined false positive rate stays under the requested one.
+
+use crate::database::sketch::{murmur64, read_parts, write_parts, FILTER_TOO_LARGE, MAX_SKETCH_BYTES};
+
+pub const DEFAULT_ERROR_RATE: f64 = 0.01;
+pub const DEFAULT_CAPACITY: u64 = 100;
+pub const DEFAULT_EXPANSION: u64 = 2;
+
+// Each added filter's error rate is this times the previous one's
+const TIGHTENING: f64 = 0.5;
+
+struct Layer {
+    bits: Vec<u8>,
+    hashes: u32,
+    capacity: u64,
+    count: u64,
+}
+
+impl Layer {
+    // Bytes and hash count of a filter for `capacity` items, None past the size cap
+    fn size(capacity: u64, error_rate: f64) -> Option<(usize, u32)> {
+        let bits_per_item = -error_rate.ln() / (2f64.ln() * 2f64.ln());
+        let bytes = (capacity as f64 * bits_per_item / 8.0).ceil().max(8.0);
+        if bytes > MAX_SKETCH_BYTES as f64 {
+            return None;
+        }
+        Some((bytes as usize, (2f64.ln() * bits_per_item).ceil() as u32))
+    }
+
+    fn new(capacity: u64, error_rate: f64) -> Option<Self> {
+        let (bytes, hashes) = Layer::size(capacity, error_rate)?;
+        Some(Layer {
+            bits: vec![0; bytes],
+            hashes,
+            capacity,
+            count: 0,
+        })
+    }
+
+    // Bit positions by double hashing
+    fn positions(&self, hash: (u64, u64)) -> impl Iterator<Item = usize> + '_ {
+        let size = self.bits.len() as u64 * 8;
+        (0..self.hashes as u64).map(move |i| (hash.0.wrapping_add(i.wrapping_mul(hash.1)) % size) as usize)
+    }
+
+    fn contains(&self, hash: (u64, u64)) -> bool {
+        self.positions(hash).all(|bit| self.bits[bit / 8] & (1 << (bit % 8)) != 0)
+    }
+
+    fn insert(&mut self, hash: (u64, u64)) {
+        let positions: Vec<usize> = self.positions(hash).collect();
+        for bit in positions {
+            self.bits[bit / 8] |= 1 << (bit % 8);
+        }
+        self.count += 1;
+    }
+}
+
+fn hash(item: &str) -> (u64, u64) {
+    let first = murmur64(item.as_bytes(), 0xc6a4a7935bd1e995);
+    (first, murmur64(item.as_bytes(), first))
+}
+
+pub struct ScalableBloom {
+    error_rate: f64,
+    // 0 for a NONSCALING filter
+    expansion: u64,
+    layers: Vec<Layer>,
+}
+
+impl ScalableBloom {
+    // None if the filter would be too large
+    pub fn new(error_rate: f64, capacity: u64, expansion: u64) -> Option<Self> {
+        Some(ScalableBloom {
+            error_rate,
+            expansion,
+            layers: vec![Layer::new(capacity, error_rate)?],
+        })
+    }
+
+    // The error rate of the filter at `index`
+    fn layer_error_rate(&self, index: usize) -> f64 {
+        self.error_rate * TIGHTENING.powi(index as i32)
+    }
+
+    pub fn contains(&self, item: &str) -> bool {
+        let hash = hash(item);
+        self.layers.iter().any(|layer| layer.contains(hash))
+    }
+
+    // Whether the item was new. A full NONSCALING filter refuses new items.
+    pub fn add(&mut self, item: &str) -> Result<bool, String> {
+        let hash = hash(item);
+        if self.layers.iter().any(|layer| layer.contains(hash)) {
+            return Ok(false);
+        }
+        let last = self.layers.last().unwrap();
+        if last.count >= last.capacity {
+            if self.expansion == 0 {
+                return Err("non scaling filter is full".to_string());
+            }
+            let capacity = last.capacity.saturating_mul(self.expansion);
+            let layer = Layer::new(capacity, self.layer_error_rate(self.layers.len()))
+                .filter(|layer| self.bytes_len() + layer.bits.len() <= MAX_SKETCH_BYTES)
+                .ok_or(FILTER_TOO_LARGE)?;
+            self.layers.push(layer);
+        }
+        self.layers.last_mut().unwrap().insert(hash);
+        Ok(true)
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.layers.iter().map(|layer| layer.capacity).sum()
+    }
+
+    pub fn count(&self) -> u64 {
+        self.layers.iter().map(|layer| layer.count).sum()
+    }
+
+    pub fn filters(&self) -> usize {
+        self.layers.len()
+    }
+
+    pub fn expansion(&self) -> u64 {
+        self.expansion
+    }
+
+    pub fn bytes_len(&self) -> usize {
+        self.layers.iter().map(|layer| layer.bits.len()).sum()
+    }
+
+    // `error expansion capacity:bytes:hashes:count ...`, enough to allocate the
+    // filters that the data chunks then fill
+    pub fn header(&self) -> String {
+        let mut header = format!("{} {}", self.error_rate, self.expansion);
+        for layer in &self.layers {
+            header.push_str(&format!(" {}:{}:{}:{}", layer.capacity, layer.bits.len(), layer.hashes, layer.count));
+        }
+        header
+    }
+
+    // Every filter's size must be the one its capacity and error rate give, and
+    // all of them must fit the cap, before anything is allocated
+    pub fn from_header(header: &str) -> Option<Self> {
+        let mut parts = header.split(' ');
+        let error_rate: f64 = parts.next()?.parse().ok().filter(|rate| *rate > 0.0 && *rate < 1.0)?;
+        let expansion = parts.next()?.parse().ok()?;
+        let mut bloom = ScalableBloom {
+            error_rate,
+            expansion,
+            layers: Vec::new(),
+        };
+        let mut shapes = Vec::new();
+        let mut total = 0usize;
+        for (index, layer) in parts.enumerate() {
+            let fields: Vec<u64> = layer.split(':').map(|field| field.parse().ok()).collect::<Option<_>>()?;
+            let [capacity, bytes, hashes, count] = fields[..] else {
+                return None;
+            };
+            let (expected_bytes, expected_hashes) = Layer::size(capacity, bloom.layer_error_rate(index))?;
+            if bytes != expected_bytes as u64 || hashes != expected_hashes as u64 {
+                return None;
+            }
+            total = total.checked_add(expected_bytes).filter(|total| *total <= MAX_SKETCH_BYTES)?;
+            shapes.push((capacity, count));
+        }
+        for (index, (capacity, count)) in shapes.into_iter().enumerate() {
+            let mut layer = Layer::new(capacity, bloom.layer_error_rate(index))?;
+            layer.count = count;
+            bloom.layers.push(layer);
+        }
+        (!bloom.layers.is_empty()).then_some(bloom)
+    }
+
+    pub fn read_bytes(&self, offset: usize, len: usize) -> Vec<u8> {
+        let parts: Vec<&[u8]> = self.layers.iter().map(|layer| layer.bits.as_slice()).collect();
+        read_parts(&parts, offset, len)
+    }
+
+    pub fn write_bytes(&mut self, offset: usize, bytes: &[u8]) -> Result<(), String> {
+        let mut parts: Vec<&mut [u8]> = self.layers.iter_mut().map(|layer| layer.bits.as_mut_slice()).collect();
+        write_parts(&mut parts, offset, bytes)
+    }
+}
@@ -0,0 +1,128 @@
+// Count-Min sketches: `depth` rows of `width` counters. An item increments one
+// counter per row and its estimate is the smallest of them, which can only
+// overcount, by about 2 / width of the total with probability 0.5^depth.
+
+use crate::database::sketch::{murmur64, table_len, INVALID_CHUNK};
+
+pub struct CountMinSketch {
+    width: usize,
+    depth: usize,
+    // Sum of all increments
+    count: u64,
+    counters: Vec<u64>,
+}
+
+impl CountMinSketch {
+    // None if the sketch would be too large
+    pub fn new(width: usize, depth: usize) -> Option<Self> {
+        Some(CountMinSketch {
+            width,
+            depth,
+            count: 0,
+            counters: vec![0; table_len(depth, width, 8)?],
+        })
+    }
+
+    // Sized for estimates within `error` of the total, wrong with `probability`.
+    // A tiny `error` saturates the width, which `new` then turns down.
+    pub fn by_probability(error: f64, probability: f64) -> Option<Self> {
+        let width = (2.0 / error).ceil() as usize;
+        let depth = (probability.ln() / 0.5f64.ln()).ceil() as usize;
+        CountMinSketch::new(width, depth.max(1))
+    }
+
+    // The item's counter in each row
+    fn positions(&self, item: &str) -> Vec<usize> {
+        (0..self.depth)
+            .map(|row| row * self.width + (murmur64(item.as_bytes(), row as u64) % self.width as u64) as usize)
+            .collect()
+    }
+
+    // The item's estimate after the increment
+    pub fn increment(&mut self, item: &str, by: u64) -> u64 {
+        let mut estimate = u64::MAX;
+        for position in self.positions(item) {
+            let counter = &mut self.counters[position];
+            *counter = counter.saturating_add(by);
+            estimate = estimate.min(*counter);
+        }
+        self.count = self.count.saturating_add(by);
+        estimate
+    }
+
+    pub fn query(&self, item: &str) -> u64 {
+        self.positions(item).into_iter().map(|position| self.counters[position]).min().unwrap_or(0)
+    }
+
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.depth)
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    // The weighted sum of sketches that all have the same dimensions, clamped at
+    // zero since weights may be negative
+    pub fn merge(sources: &[(&CountMinSketch, i64)]) -> CountMinSketch {
+        let weighted = |value: &dyn Fn(&CountMinSketch) -> u64| {
+            let sum: i128 = sources
+                .iter()
+                .map(|(source, weight)| value(source) as i128 * *weight as i128)
+                .sum();
+            sum.clamp(0, u64::MAX as i128) as u64
+        };
+        let (first, _) = sources[0];
+        CountMinSketch {
+            width: first.width,
+            depth: first.depth,
+            count: weighted(&|source| source.count),
+            counters: (0..first.counters.len())
+                .map(|position| weighted(&|source| source.counters[position]))
+                .collect(),
+        }
+    }
+
+    pub fn bytes_len(&self) -> usize {
+        self.counters.len() * 8
+    }
+
+    // `width depth count`
+    pub fn header(&self) -> String {
+        format!("{} {} {}", self.width, self.depth, self.count)
+    }
+
+    pub fn from_header(header: &str) -> Option<Self> {
+        let fields: Vec<u64> = header.split(' ').map(|field| field.parse().ok()).collect::<Option<_>>()?;
+        let [width, depth, count] = fields[..] else {
+            return None;
+        };
+        if width == 0 || depth == 0 {
+            return None;
+        }
+        let mut sketch = CountMinSketch::new(width.try_into().ok()?, depth.try_into().ok()?)?;
+        sketch.count = count;
+        Some(sketch)
+    }
+
+    // Counters as little-endian bytes
+    pub fn read_bytes(&self, offset: usize, len: usize) -> Vec<u8> {
+        let end = (offset + len).min(self.bytes_len());
+        let bytes: Vec<u8> = self.counters[offset / 8..end.div_ceil(8)]
+            .iter()
+            .flat_map(|counter| counter.to_le_bytes())
+            .collect();
+        bytes[offset % 8..offset % 8 + (end - offset)].to_vec()
+    }
+
+    // Chunks hold whole counters
+    pub fn write_bytes(&mut self, offset: usize, bytes: &[u8]) -> Result<(), String> {
+        if !offset.is_multiple_of(8) || !bytes.len().is_multiple_of(8) || offset + bytes.len() > self.bytes_len() {
+            return Err(INVALID_CHUNK.to_string());
+        }
+        for (i, chunk) in bytes.chunks_exact(8).enumerate() {
+            self.counters[offset / 8 + i] = u64::from_le_bytes(chunk.try_into().unwrap());
+        }
+        Ok(())
+    }
+}
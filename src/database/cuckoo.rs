@@ -0,0 +1,255 @@
+// Cuckoo filters: one-byte fingerprints in buckets, each item having two
+// candidate buckets. Unlike a Bloom filter, items can be counted and deleted.
+// When no slot can be freed by kicking fingerprints to their other bucket, a
+// bigger filter is added.
+
+use crate::database::sketch::{murmur64, read_parts, table_len, write_parts, FILTER_TOO_LARGE, MAX_SKETCH_BYTES};
+
+pub const DEFAULT_CAPACITY: u64 = 1080;
+pub const DEFAULT_BUCKET_SIZE: usize = 2;
+pub const DEFAULT_MAX_ITERATIONS: usize = 20;
+pub const DEFAULT_EXPANSION: u64 = 1;
+
+// Empty slots hold 0, so fingerprints are 1..=255
+const EMPTY: u8 = 0;
+
+struct Layer {
+    buckets: usize,
+    slots: Vec<u8>,
+}
+
+impl Layer {
+    // None if the filter would be too large
+    fn new(buckets: usize, bucket_size: usize) -> Option<Self> {
+        Some(Layer {
+            buckets,
+            slots: vec![EMPTY; table_len(buckets, bucket_size, 1)?],
+        })
+    }
+
+    // Bucket counts are powers of two, so the alternate of the alternate is the
+    // original bucket
+    fn alternate(&self, bucket: usize, fingerprint: u8) -> usize {
+        (bucket ^ (fingerprint as usize).wrapping_mul(0x5bd1e995)) & (self.buckets - 1)
+    }
+
+    fn candidates(&self, hash: u64, fingerprint: u8) -> (usize, usize) {
+        let first = (hash >> 32) as usize & (self.buckets - 1);
+        (first, self.alternate(first, fingerprint))
+    }
+}
+
+fn fingerprint(item: &str) -> (u64, u8) {
+    let hash = murmur64(item.as_bytes(), 0);
+    (hash, (hash % 255 + 1) as u8)
+}
+
+pub struct CuckooFilter {
+    capacity: u64,
+    bucket_size: usize,
+    max_iterations: usize,
+    // 0 when the filter can't grow
+    expansion: u64,
+    items: u64,
+    deleted: u64,
+    layers: Vec<Layer>,
+}
+
+impl CuckooFilter {
+    // None if the filter would be too large
+    pub fn new(capacity: u64, bucket_size: usize, max_iterations: usize, expansion: u64) -> Option<Self> {
+        let buckets = usize::try_from(capacity).ok()?.div_ceil(bucket_size).checked_next_power_of_two()?;
+        Some(CuckooFilter {
+            capacity,
+            bucket_size,
+            max_iterations,
+            expansion,
+            items: 0,
+            deleted: 0,
+            layers: vec![Layer::new(buckets, bucket_size)?],
+        })
+    }
+
+    fn bucket(&self, bucket: usize) -> std::ops::Range<usize> {
+        bucket * self.bucket_size..(bucket + 1) * self.bucket_size
+    }
+
+    // Occurrences of the item's fingerprint in its candidate buckets
+    pub fn count(&self, item: &str) -> u64 {
+        let (hash, fingerprint) = fingerprint(item);
+        self.layers
+            .iter()
+            .map(|layer| {
+                let (first, second) = layer.candidates(hash, fingerprint);
+                let in_bucket = |bucket| {
+                    layer.slots[self.bucket(bucket)].iter().filter(|slot| **slot == fingerprint).count() as u64
+                };
+                in_bucket(first) + if second != first { in_bucket(second) } else { 0 }
+            })
+            .sum()
+    }
+
+    pub fn contains(&self, item: &str) -> bool {
+        self.count(item) > 0
+    }
+
+    // Adds the item even if it's already there. Fails when the filter is full
+    // and can't grow.
+    pub fn add(&mut self, item: &str) -> Result<(), String> {
+        let (hash, fingerprint) = fingerprint(item);
+
+        // A free slot in any filter
+        for index in 0..self.layers.len() {
+            let (first, second) = self.layers[index].candidates(hash, fingerprint);
+            for bucket in [first, second] {
+                let range = self.bucket(bucket);
+                if let Some(slot) = self.layers[index].slots[range].iter_mut().find(|slot| **slot == EMPTY) {
+                    *slot = fingerprint;
+                    self.items += 1;
+                    return Ok(());
+                }
+            }
+        }
+
+        // Kick fingerprints around the newest filter. If the last one still has
+        // nowhere to go, the kicks are undone and the item goes in a new filter.
+        let bucket_size = self.bucket_size;
+        let layer = self.layers.last_mut().unwrap();
+        let (mut bucket, _) = layer.candidates(hash, fingerprint);
+        let mut homeless = fingerprint;
+        let mut kicked = Vec::new();
+        for round in 0..self.max_iterations {
+            let slot = bucket * bucket_size + round % bucket_size;
+            std::mem::swap(&mut homeless, &mut layer.slots[slot]);
+            kicked.push(slot);
+            bucket = layer.alternate(bucket, homeless);
+            let range = bucket * bucket_size..(bucket + 1) * bucket_size;
+            if let Some(free) = layer.slots[range].iter_mut().find(|slot| **slot == EMPTY) {
+                *free = homeless;
+                self.items += 1;
+                return Ok(());
+            }
+        }
+        for slot in kicked.into_iter().rev() {
+            std::mem::swap(&mut homeless, &mut layer.slots[slot]);
+        }
+        if self.expansion == 0 {
+            return Err("Filter is full".to_string());
+        }
+
+        let total = self.bytes_len();
+        let layer = self.layers.last().unwrap();
+        let mut grown = layer
+            .buckets
+            .checked_mul(self.expansion as usize)
+            .and_then(usize::checked_next_power_of_two)
+            .and_then(|buckets| Layer::new(buckets, bucket_size))
+            .filter(|grown| total + grown.slots.len() <= MAX_SKETCH_BYTES)
+            .ok_or(FILTER_TOO_LARGE)?;
+        let (first, _) = grown.candidates(hash, fingerprint);
+        grown.slots[first * bucket_size] = fingerprint;
+        self.layers.push(grown);
+        self.items += 1;
+        Ok(())
+    }
+
+    // Removes one occurrence of the item, newest filter first
+    pub fn delete(&mut self, item: &str) -> bool {
+        let (hash, fingerprint) = fingerprint(item);
+        for index in (0..self.layers.len()).rev() {
+            let (first, second) = self.layers[index].candidates(hash, fingerprint);
+            for bucket in [first, second] {
+                let range = self.bucket(bucket);
+                if let Some(slot) = self.layers[index].slots[range].iter_mut().find(|slot| **slot == fingerprint) {
+                    *slot = EMPTY;
+                    self.items -= 1;
+                    self.deleted += 1;
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    pub fn items(&self) -> u64 {
+        self.items
+    }
+
+    pub fn deleted(&self) -> u64 {
+        self.deleted
+    }
+
+    pub fn buckets(&self) -> usize {
+        self.layers.iter().map(|layer| layer.buckets).sum()
+    }
+
+    pub fn filters(&self) -> usize {
+        self.layers.len()
+    }
+
+    pub fn bucket_size(&self) -> usize {
+        self.bucket_size
+    }
+
+    pub fn max_iterations(&self) -> usize {
+        self.max_iterations
+    }
+
+    pub fn expansion(&self) -> u64 {
+        self.expansion
+    }
+
+    pub fn bytes_len(&self) -> usize {
+        self.layers.iter().map(|layer| layer.slots.len()).sum()
+    }
+
+    // `capacity bucket_size max_iterations expansion items deleted buckets ...`
+    pub fn header(&self) -> String {
+        let mut header = format!(
+            "{} {} {} {} {} {}",
+            self.capacity, self.bucket_size, self.max_iterations, self.expansion, self.items, self.deleted
+        );
+        for layer in &self.layers {
+            header.push_str(&format!(" {}", layer.buckets));
+        }
+        header
+    }
+
+    pub fn from_header(header: &str) -> Option<Self> {
+        let fields: Vec<u64> = header.split(' ').map(|field| field.parse().ok()).collect::<Option<_>>()?;
+        let [capacity, bucket_size, max_iterations, expansion, items, deleted, ref buckets @ ..] = fields[..] else {
+            return None;
+        };
+        if buckets.is_empty() || !(1..=255).contains(&bucket_size) || buckets.iter().any(|count| !count.is_power_of_two()) {
+            return None;
+        }
+        // The whole filter must fit the cap before anything is allocated
+        let mut total = 0usize;
+        for count in buckets {
+            let len = table_len(usize::try_from(*count).ok()?, bucket_size as usize, 1)?;
+            total = total.checked_add(len).filter(|total| *total <= MAX_SKETCH_BYTES)?;
+        }
+        Some(CuckooFilter {
+            capacity,
+            bucket_size: bucket_size as usize,
+            max_iterations: max_iterations as usize,
+            expansion,
+            items,
+            deleted,
+            layers: buckets
+                .iter()
+                .map(|count| Layer::new(*count as usize, bucket_size as usize))
+                .collect::<Option<_>>()?,
+        })
+    }
+
+    pub fn read_bytes(&self, offset: usize, len: usize) -> Vec<u8> {
+        let parts: Vec<&[u8]> = self.layers.iter().map(|layer| layer.slots.as_slice()).collect();
+        read_parts(&parts, offset, len)
+    }
+
+    pub fn write_bytes(&mut self, offset: usize, bytes: &[u8]) -> Result<(), String> {
+        let mut parts: Vec<&mut [u8]> = self.layers.iter_mut().map(|layer| layer.slots.as_mut_slice()).collect();
+        write_parts(&mut parts, offset, bytes)
+    }
+}
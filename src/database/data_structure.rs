@@ -151,6 +151,9 @@ impl RSortedSet {
     }
 
     pub fn zrange(&self, start: usize, end: usize) -> Vec<String> {
+        if start > end {
+            return Vec::new();
+        }
         self.sorted
             .iter()
             .skip(start)
@@ -1,30 +1,225 @@
-use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard};
 use std::time::{Duration, Instant};
 
+use serde_json::Value;
+use tokio::sync::Notify;
+
+use crate::client::{Client, ClientRegistry};
+use crate::cluster::Cluster;
+use crate::command::resp;
+use crate::config::Config;
 use crate::database::data_structure::{RList, RSets, RSortedSet};
+use crate::database::expiry::Expiries;
+use crate::database::sketch::{Sketch, WRONG_TYPE};
+use crate::database::stats::Stats;
+use crate::database::stream::{RStream, StreamId};
+use crate::database::timeseries::{self, TimeSeries};
+use crate::latency::LatencyMonitor;
+use crate::pubsub::notify::{self, command_events};
+use crate::pubsub::PubSub;
+use crate::replication::Replication;
+use crate::scripting::Scripting;
+use crate::search::{Index, Search, UNKNOWN_INDEX};
+use crate::shutdown::Shutdown;
+use crate::slowlog::SlowLog;
+use crate::tracking::Tracking;
+
+// Active expiry runs this often, removing the most overdue strings in batches while full
+// batches keep turning up and the cycle stays within its time budget
+const ACTIVE_EXPIRE_PERIOD: Duration = Duration::from_millis(100);
+const ACTIVE_EXPIRE_BATCH: usize = 20;
+const ACTIVE_EXPIRE_BUDGET: Duration = Duration::from_millis(25);
+
+// The type maps a key can live in, one at a time
+#[derive(Clone, Copy, PartialEq)]
+enum KeyKind {
+    String,
+    List,
+    Set,
+    SortedSet,
+    Stream,
+    Json,
+    TimeSeries,
+    Sketch,
+}
+
+// Running totals behind used_memory and avg_ttl, kept up to date by every write so
+// INFO and /metrics don't walk the keyspace
+#[derive(Default)]
+struct Usage {
+    // Estimated bytes held by the keyspace, see the *_size functions
+    memory: AtomicUsize,
+    // Sum of the TTL deadlines in ms since startup
+    deadlines: AtomicU64,
+}
 
 #[derive(Clone)]
 pub struct Database {
-    db: Arc<RwLock<HashMap<String, String>>>,
-    expiry: Arc<RwLock<HashMap<String, Instant>>>,
+    db: Arc<RwLock<HashMap<String, Vec<u8>>>>,
+    expiry: Arc<RwLock<Expiries>>,
     list: Arc<RwLock<HashMap<String, RList>>>,
     set: Arc<RwLock<HashMap<String, RSets>>>,
     sorted_set: Arc<RwLock<HashMap<String, RSortedSet>>>,
+    stream: Arc<RwLock<HashMap<String, RStream>>>,
+    json: Arc<RwLock<HashMap<String, Value>>>,
+    timeseries: Arc<RwLock<HashMap<String, TimeSeries>>>,
+    // Bloom and Cuckoo filters, Count-Min sketches and Top-K
+    sketch: Arc<RwLock<HashMap<String, Sketch>>>,
+    // Held while a key is checked against the other type maps and created
+    claims: Arc<Mutex<()>>,
+    usage: Arc<Usage>,
+    // Wakes clients blocked in XREAD / XREADGROUP when a stream changes
+    stream_changed: Arc<Notify>,
+    stats: Arc<Stats>,
+    clients: Arc<ClientRegistry>,
+    config: Arc<RwLock<Config>>,
+    shutdown: Arc<Shutdown>,
+    replication: Arc<Replication>,
+    cluster: Arc<Cluster>,
+    scripting: Arc<Scripting>,
+    search: Arc<Search>,
+    pubsub: Arc<PubSub>,
+    tracking: Arc<Tracking>,
+    slowlog: Arc<SlowLog>,
+    latency: Arc<LatencyMonitor>,
 }
 
 impl Database {
-    pub fn new() -> Self {
-        Database { 
+    pub fn new(config: Config) -> Self {
+        let clients = Arc::new(ClientRegistry::new());
+        let pubsub = Arc::new(PubSub::new());
+        Database {
             db: Arc::new(RwLock::new(HashMap::new())), 
-            expiry: Arc::new(RwLock::new(HashMap::new())),
+            expiry: Arc::new(RwLock::new(Expiries::default())),
             list: Arc::new(RwLock::new(HashMap::new())),
             set: Arc::new(RwLock::new(HashMap::new())),
             sorted_set: Arc::new(RwLock::new(HashMap::new())), 
+            stream: Arc::new(RwLock::new(HashMap::new())),
+            json: Arc::new(RwLock::new(HashMap::new())),
+            timeseries: Arc::new(RwLock::new(HashMap::new())),
+            sketch: Arc::new(RwLock::new(HashMap::new())),
+            claims: Arc::new(Mutex::new(())),
+            usage: Arc::new(Usage::default()),
+            stream_changed: Arc::new(Notify::new()),
+            stats: Arc::new(Stats::new()),
+            tracking: Arc::new(Tracking::new(clients.clone(), pubsub.clone())),
+            clients,
+            shutdown: Arc::new(Shutdown::new()),
+            replication: Arc::new(Replication::new(config.repl_backlog_size)),
+            cluster: Arc::new(Cluster::new(
+                config.cluster_enabled,
+                config.cluster_announce_ip.clone().unwrap_or_else(|| config.bind.clone()),
+                config.port,
+            )),
+            scripting: Arc::new(Scripting::new()),
+            search: Arc::new(Search::new()),
+            pubsub,
+            slowlog: Arc::new(SlowLog::new()),
+            latency: Arc::new(LatencyMonitor::new()),
+            config: Arc::new(RwLock::new(config)),
+        }
+    }
+
+    pub fn stats(&self) -> &Arc<Stats> {
+        &self.stats
+    }
+
+    pub fn clients(&self) -> &Arc<ClientRegistry> {
+        &self.clients
+    }
+
+    pub fn config(&self) -> RwLockReadGuard<'_, Config> {
+        self.config.read().unwrap()
+    }
+
+    pub fn shutdown(&self) -> &Arc<Shutdown> {
+        &self.shutdown
+    }
+
+    pub fn replication(&self) -> &Arc<Replication> {
+        &self.replication
+    }
+
+    pub fn cluster(&self) -> &Arc<Cluster> {
+        &self.cluster
+    }
+
+    pub fn scripting(&self) -> &Arc<Scripting> {
+        &self.scripting
+    }
+
+    pub fn search(&self) -> &Arc<Search> {
+        &self.search
+    }
+
+    pub fn pubsub(&self) -> &Arc<PubSub> {
+        &self.pubsub
+    }
+
+    pub fn tracking(&self) -> &Arc<Tracking> {
+        &self.tracking
+    }
+
+    pub fn slowlog(&self) -> &Arc<SlowLog> {
+        &self.slowlog
+    }
+
+    pub fn latency(&self) -> &Arc<LatencyMonitor> {
+        &self.latency
+    }
+
+    // Samples an event for LATENCY if it took at least latency-monitor-threshold
+    pub fn record_latency(&self, event: &str, duration: Duration) {
+        let threshold = self.config().latency_monitor_threshold;
+        self.latency.record(threshold, event, duration);
+    }
+
+    // Records a write for the persistence stats and sends it to replicas. Callers
+    // still hold the lock of the map they changed, so replicas see writes in order.
+    fn changed<A: AsRef<[u8]>>(&self, command: &[A]) {
+        Stats::incr(&self.stats.dirty);
+        self.replication.propagate(command);
+        // Tracking and notifications only look at command names and keys, which are text
+        let text: Vec<Cow<str>> = command.iter().map(|arg| String::from_utf8_lossy(arg.as_ref())).collect();
+        let command: Vec<&str> = text.iter().map(|arg| arg.as_ref()).collect();
+        self.tracking.changed(&command);
+        for (class, event, key) in command_events(&command) {
+            self.notify(class, &event, key);
+        }
+    }
+
+    // Publishes a keyspace notification, if notify-keyspace-events asks for
+    // this class of event. There's only database 0.
+    fn notify(&self, class: u16, event: &str, key: &str) {
+        let (keyspace, keyevent) = self.config().notify_keyspace_events.channels(class);
+        if keyspace {
+            self.pubsub.publish(&format!("__keyspace@0__:{}", key), event);
         }
+        if keyevent {
+            self.pubsub.publish(&format!("__keyevent@0__:{}", event), key);
+        }
+    }
+
+    // A string whose TTL ran out was found and removed
+    fn expired(&self, key: &str) {
+        // Replicas don't expire keys on their own schedule, the master tells them
+        self.replication.propagate(&["DEL", key]);
+        Stats::incr(&self.stats.expired_keys);
+        self.tracking.invalidate(key);
+        self.notify(notify::EXPIRED, "expired", key);
+    }
+
+    // A list, set or sorted set that lost its last element was removed; like Redis
+    // an empty collection doesn't exist, so the key is free for any type again
+    fn emptied(&self, key: &str) {
+        self.resize(key_size(key), 0);
+        self.notify(notify::GENERIC, "del", key);
     }
 
-    pub async fn get(&self, key: &str) -> Option<String> {
+    pub async fn get(&self, key: &str) -> Option<Vec<u8>> {
         let now = Instant::now();
 
         // Check expiry - must drop guard before await
@@ -38,26 +233,82 @@ impl Database {
         };
         
         if is_expired {
-            self.delete(key).await;
+            if self.remove_string(key) {
+                self.expired(key);
+            }
+            self.stats.record_lookup(false);
             return None;
         }
 
         let db = self.db.read().unwrap();
-        db.get(key).cloned()
+        let value = db.get(key).cloned();
+        self.stats.record_lookup(value.is_some());
+        value
     }
     
-    pub async fn set(&self, key: String, value: String, ttl: Option<u64>) {
+    pub async fn set(&self, key: String, value: Vec<u8>, ttl: Option<u64>) {
+        let _claim = self.claim_replacing(KeyKind::String, &key);
         let mut db_map = self.db.write().unwrap();
-        db_map.insert(key.clone(), value);
+        match ttl {
+            Some(sec) => self.changed(&[b"SET", key.as_bytes(), &value, b"EX", sec.to_string().as_bytes()]),
+            None => self.changed(&[b"SET", key.as_bytes(), &value]),
+        }
+        let size = string_size(&key, &value);
+        let old = db_map.insert(key.clone(), value);
+        self.resize(old.map_or(0, |old| string_size(&key, &old)), size);
 
         if let Some(sec) = ttl {
             let exp_time = Instant::now() + Duration::from_secs(sec);
             let mut exp_map = self.expiry.write().unwrap();
-            exp_map.insert(key, exp_time);
+            self.set_expiry(&mut exp_map, &key, exp_time);
         }
     }
 
+    // RESTORE of a string value into a free key; `command` is what gets replicated
+    pub async fn restore_string(&self, key: &str, value: Vec<u8>, ttl: Option<Duration>, command: &[&[u8]]) {
+        let mut db_map = self.db.write().unwrap();
+        let mut exp_map = self.expiry.write().unwrap();
+        self.changed(command);
+        self.resize(0, string_size(key, &value));
+        db_map.insert(key.to_string(), value);
+        if let Some(ttl) = ttl {
+            self.set_expiry(&mut exp_map, key, Instant::now() + ttl);
+        }
+    }
 
+    // Read-modify-write of a string value under the lock, keeping its TTL. `f` gets
+    // the live value, if any, and returns the value to store (None leaves the key
+    // as it is) along with its result; `command` is replicated when it stores.
+    pub fn update_string<R>(
+        &self,
+        key: &str,
+        command: &[&str],
+        f: impl FnOnce(Option<&[u8]>) -> Result<(Option<Vec<u8>>, R), String>,
+    ) -> Result<R, String> {
+        let _claim = self.claim(KeyKind::String, &[key])?;
+        let mut db = self.db.write().unwrap();
+        let mut expiry = self.expiry.write().unwrap();
+        if expiry.get(key).is_some_and(|exp| Instant::now() > *exp) {
+            self.clear_expiry(&mut expiry, key);
+            if let Some(old) = db.remove(key) {
+                self.resize(string_size(key, &old), 0);
+                self.expired(key);
+            }
+        }
+
+        let (value, result) = f(db.get(key).map(Vec::as_slice))?;
+        if let Some(value) = value {
+            self.changed(command);
+            let size = string_size(key, &value);
+            let old = db.insert(key.to_string(), value);
+            self.resize(old.map_or(0, |old| string_size(key, &old)), size);
+        }
+        Ok(result)
+    }
+
+    // The commands check TTLs under the locks they already hold, so nothing in the
+    // server calls this one
+    #[allow(dead_code)]
     pub async fn is_expired(&self, key: &str) -> bool {
         let exp_map = self.expiry.read().unwrap();
         if let Some(exp_time) = exp_map.get(key) {
@@ -66,122 +317,998 @@ impl Database {
         false
     }
 
+    // Removes the key whatever its type
     pub async fn delete(&self, key: &str) -> bool {
+        self.remove_key(key)
+    }
+
+    fn remove_key(&self, key: &str) -> bool {
         let mut db = self.db.write().unwrap();
         let mut expiry = self.expiry.write().unwrap();
+        let mut list_map = self.list.write().unwrap();
+        let mut set_map = self.set.write().unwrap();
+        let mut ss_map = self.sorted_set.write().unwrap();
+        let mut stream_map = self.stream.write().unwrap();
+        let mut json_map = self.json.write().unwrap();
+        let mut ts_map = self.timeseries.write().unwrap();
+        let mut sketch_map = self.sketch.write().unwrap();
 
-        let existed = db.remove(key).is_some();
-        expiry.remove(key);
+        self.clear_expiry(&mut expiry, key);
+        let size = if let Some(value) = db.remove(key) {
+            string_size(key, &value)
+        } else if let Some(list) = list_map.remove(key) {
+            list_size(key, &list)
+        } else if let Some(set) = set_map.remove(key) {
+            set_size(key, &set)
+        } else if let Some(sorted_set) = ss_map.remove(key) {
+            sorted_set_size(key, &sorted_set)
+        } else if let Some(sketch) = sketch_map.remove(key) {
+            sketch_size(key, &sketch)
+        } else if let Some(doc) = json_map.remove(key) {
+            self.search.update(key, None);
+            json_size(key, &doc)
+        } else if let Some(series) = ts_map.remove(key) {
+            timeseries::unlink(&mut ts_map, &series, key);
+            series_size(key, &series)
+        } else if let Some(stream) = stream_map.remove(key) {
+            self.stream_changed.notify_waiters();
+            stream_size(key, &stream)
+        } else {
+            0
+        };
+        let existed = size > 0;
+        self.resize(size, 0);
+        if existed {
+            self.changed(&["DEL", key]);
+        }
         existed
     }
 
+    // Whether the key exists in any type, without counting as a keyspace lookup
+    pub fn exists(&self, key: &str) -> bool {
+        self.kind_of(key).is_some()
+    }
+
+    // The type map holding the key; a string whose TTL ran out doesn't count
+    fn kind_of(&self, key: &str) -> Option<KeyKind> {
+        let live_string = self.db.read().unwrap().contains_key(key)
+            && self.expiry.read().unwrap().get(key).is_none_or(|exp| *exp > Instant::now());
+        if live_string {
+            return Some(KeyKind::String);
+        }
+        let held = [
+            (KeyKind::List, self.list.read().unwrap().contains_key(key)),
+            (KeyKind::Set, self.set.read().unwrap().contains_key(key)),
+            (KeyKind::SortedSet, self.sorted_set.read().unwrap().contains_key(key)),
+            (KeyKind::Stream, self.stream.read().unwrap().contains_key(key)),
+            (KeyKind::Json, self.json.read().unwrap().contains_key(key)),
+            (KeyKind::TimeSeries, self.timeseries.read().unwrap().contains_key(key)),
+            (KeyKind::Sketch, self.sketch.read().unwrap().contains_key(key)),
+        ];
+        held.into_iter().find_map(|(kind, held)| held.then_some(kind))
+    }
+
+    // Writes that may create keys claim them first, since a key lives in one type
+    // map at a time: WRONGTYPE if another type holds one of them, otherwise a guard
+    // that keeps other writes from creating keys until the caller has stored its
+    // own. An expired string in the way is removed.
+    fn claim(&self, kind: KeyKind, keys: &[&str]) -> Result<MutexGuard<'_, ()>, String> {
+        let claims = self.claims.lock().unwrap();
+        for key in keys {
+            match self.kind_of(key) {
+                Some(held) if held != kind => return Err(WRONG_TYPE.to_string()),
+                None if kind != KeyKind::String && self.remove_string(key) => self.expired(key),
+                _ => {}
+            }
+        }
+        Ok(claims)
+    }
+
+    // Like claim for writes that overwrite the key whatever it holds, like SET
+    fn claim_replacing(&self, kind: KeyKind, key: &str) -> MutexGuard<'_, ()> {
+        let claims = self.claims.lock().unwrap();
+        if self.kind_of(key).is_some_and(|held| held != kind) {
+            self.remove_key(key);
+        }
+        claims
+    }
+
+    // Active expiry, so strings nobody reads again don't stay around forever
+    pub async fn active_expire(self) {
+        let mut interval = tokio::time::interval(ACTIVE_EXPIRE_PERIOD);
+        loop {
+            interval.tick().await;
+            // Replicas wait for the master's DEL, and a running script keeps its keys
+            if self.replication.is_replica() {
+                continue;
+            }
+            let Some(_exec) = self.scripting.enter().await else {
+                continue;
+            };
+            let start = Instant::now();
+            self.expire_cycle(start);
+            self.record_latency("expire-cycle", start.elapsed());
+        }
+    }
+
+    fn expire_cycle(&self, start: Instant) {
+        loop {
+            let batch = self.expiry.read().unwrap().due(Instant::now(), ACTIVE_EXPIRE_BATCH);
+            for key in &batch {
+                if self.remove_if_expired(key) {
+                    self.expired(key);
+                }
+            }
+            if batch.len() < ACTIVE_EXPIRE_BATCH || start.elapsed() >= ACTIVE_EXPIRE_BUDGET {
+                return;
+            }
+        }
+    }
+
+    // Checks the TTL again under the lock, in case the key was set anew meanwhile
+    fn remove_if_expired(&self, key: &str) -> bool {
+        let mut db = self.db.write().unwrap();
+        let mut expiry = self.expiry.write().unwrap();
+        if expiry.get(key).is_none_or(|exp| Instant::now() <= *exp) {
+            return false;
+        }
+        self.clear_expiry(&mut expiry, key);
+        let old = db.remove(key);
+        self.resize(old.as_ref().map_or(0, |old| string_size(key, old)), 0);
+        old.is_some()
+    }
+
+    fn remove_string(&self, key: &str) -> bool {
+        let mut db = self.db.write().unwrap();
+        let mut expiry = self.expiry.write().unwrap();
+
+        self.clear_expiry(&mut expiry, key);
+        let old = db.remove(key);
+        self.resize(old.as_ref().map_or(0, |old| string_size(key, old)), 0);
+        old.is_some()
+    }
+
+    // Adds `after - before` bytes to the memory estimate
+    fn resize(&self, before: usize, after: usize) {
+        if after >= before {
+            self.usage.memory.fetch_add(after - before, Ordering::Relaxed);
+        } else {
+            self.usage.memory.fetch_sub(before - after, Ordering::Relaxed);
+        }
+    }
+
+    // The expiry map only changes through these two, which keep the totals in step
+    fn set_expiry(&self, expiry: &mut Expiries, key: &str, deadline: Instant) {
+        let old = expiry.insert(key.to_string(), deadline);
+        self.expiry_changed(key, old, Some(deadline));
+    }
+
+    fn clear_expiry(&self, expiry: &mut Expiries, key: &str) {
+        if let Some(old) = expiry.remove(key) {
+            self.expiry_changed(key, Some(old), None);
+        }
+    }
+
+    fn expiry_changed(&self, key: &str, before: Option<Instant>, after: Option<Instant>) {
+        let size = |deadline: Option<Instant>| deadline.map_or(0, |_| expiry_size(key));
+        self.resize(size(before), size(after));
+        if let Some(before) = before {
+            self.usage.deadlines.fetch_sub(self.since_start(before), Ordering::Relaxed);
+        }
+        if let Some(after) = after {
+            self.usage.deadlines.fetch_add(self.since_start(after), Ordering::Relaxed);
+        }
+    }
+
+    fn since_start(&self, instant: Instant) -> u64 {
+        instant.saturating_duration_since(self.stats.start_time).as_millis() as u64
+    }
+
     // List operations
-    pub async fn lpush(&self, key: String, value: String) -> usize {
+    pub async fn lpush(&self, key: String, value: String) -> Result<usize, String> {
+        let _claim = self.claim(KeyKind::List, &[&key])?;
         let mut list_map = self.list.write().unwrap();
+        self.changed(&["LPUSH", &key, &value]);
+        let created = !list_map.contains_key(&key);
+        self.resize(0, list_item_size(&value) + if created { key_size(&key) } else { 0 });
         let list = list_map.entry(key).or_insert_with(RList::new);
         list.lpush(value);
-        list.list.len()
+        Ok(list.list.len())
     }
 
-    pub async fn rpush(&self, key: String, value: String) -> usize {
+    pub async fn rpush(&self, key: String, value: String) -> Result<usize, String> {
+        let _claim = self.claim(KeyKind::List, &[&key])?;
         let mut list_map = self.list.write().unwrap();
+        self.changed(&["RPUSH", &key, &value]);
+        let created = !list_map.contains_key(&key);
+        self.resize(0, list_item_size(&value) + if created { key_size(&key) } else { 0 });
         let list = list_map.entry(key).or_insert_with(RList::new);
         list.rpush(value);
-        list.list.len()
+        Ok(list.list.len())
     }
 
     pub async fn lpop(&self, key: &str) -> Option<String> {
         let mut list_map = self.list.write().unwrap();
-        if let Some(list) = list_map.get_mut(key) {
-            list.lpop()
-        } else {
-            None
+        let value = list_map.get_mut(key).and_then(|list| list.lpop());
+        if let Some(value) = &value {
+            self.changed(&["LPOP", key]);
+            self.resize(list_item_size(value), 0);
+            if list_map.get(key).is_some_and(|list| list.list.is_empty()) {
+                list_map.remove(key);
+                self.emptied(key);
+            }
         }
+        value
     }
 
     pub async fn rpop(&self, key: &str) -> Option<String> {
         let mut list_map = self.list.write().unwrap();
-        if let Some(list) = list_map.get_mut(key) {
-            list.rpop()
-        } else {
-            None
+        let value = list_map.get_mut(key).and_then(|list| list.rpop());
+        if let Some(value) = &value {
+            self.changed(&["RPOP", key]);
+            self.resize(list_item_size(value), 0);
+            if list_map.get(key).is_some_and(|list| list.list.is_empty()) {
+                list_map.remove(key);
+                self.emptied(key);
+            }
         }
+        value
     }
 
     pub async fn lrange(&self, key: &str, start: i64, end: i64) -> Option<Vec<String>> {
         let list_map = self.list.read().unwrap();
         if let Some(list) = list_map.get(key) {
+            self.stats.record_lookup(true);
             Some(list.lrange(start, end))
         } else {
+            self.stats.record_lookup(false);
             None
         }
     }
 
     // SET operations
-    pub async fn sadd(&self, key: String, value: String) -> bool {
+    pub async fn sadd(&self, key: String, value: String) -> Result<bool, String> {
+        let _claim = self.claim(KeyKind::Set, &[&key])?;
         let mut set_map = self.set.write().unwrap();
-        let set = set_map.entry(key).or_insert_with(RSets::new);
-        set.sadd(value)
+        if !set_map.contains_key(&key) {
+            self.resize(0, key_size(&key));
+        }
+        let set = set_map.entry(key.clone()).or_insert_with(RSets::new);
+        let added = set.sadd(value.clone());
+        if added {
+            self.changed(&["SADD", &key, &value]);
+            self.resize(0, set_member_size(&value));
+        }
+        Ok(added)
     }
 
     pub async fn srem(&self, key: &str, value: String) -> bool {
         let mut set_map = self.set.write().unwrap();
-        if let Some(set) = set_map.get_mut(key) {
-            set.srem(value)
-        } else {
-            false
+        let removed = set_map.get_mut(key).is_some_and(|set| set.srem(value.clone()));
+        if removed {
+            self.changed(&["SREM", key, &value]);
+            self.resize(set_member_size(&value), 0);
+            if set_map.get(key).is_some_and(|set| set.set.is_empty()) {
+                set_map.remove(key);
+                self.emptied(key);
+            }
         }
+        removed
     }
 
     pub async fn smembers(&self, key: &str) -> Option<Vec<String>> {
         let set_map = self.set.read().unwrap();
         if let Some(set) = set_map.get(key) {
+            self.stats.record_lookup(true);
             Some(set.smembers())
         } else {
+            self.stats.record_lookup(false);
             None
         }
     }
 
     pub async fn sismember(&self, key: &str, value: &str) -> bool {
         let set_map = self.set.read().unwrap();
-        if let Some(set) = set_map.get(key) {
-            set.sismember(value)
-        } else {
-            false
-        }
+        let set = set_map.get(key);
+        self.stats.record_lookup(set.is_some());
+        set.is_some_and(|set| set.sismember(value))
     }
 
     // Sorted Set operations
-    pub async fn zadd(&self, key: String, score: f64, member: String) -> bool {
+    pub async fn zadd(&self, key: String, score: f64, member: String) -> Result<bool, String> {
+        let _claim = self.claim(KeyKind::SortedSet, &[&key])?;
         let mut sorted_set_map = self.sorted_set.write().unwrap();
-        let sorted_set = sorted_set_map.entry(key).or_insert_with(RSortedSet::new);
-        sorted_set.zadd(score, member)
+        let created = !sorted_set_map.contains_key(&key);
+        let sorted_set = sorted_set_map.entry(key.clone()).or_insert_with(RSortedSet::new);
+        let members = sorted_set.members.len();
+        let added = sorted_set.zadd(score, member.clone());
+        if sorted_set.members.len() > members {
+            self.resize(0, zset_member_size(&member));
+        }
+        if created {
+            self.resize(0, key_size(&key));
+        }
+        if added {
+            self.changed(&["ZADD", &key, &score.to_string(), &member]);
+        }
+        Ok(added)
     }
 
     pub async fn zrem(&self, key: &str, member: String) -> bool {
         let mut sorted_set_map = self.sorted_set.write().unwrap();
-        if let Some(sorted_set) = sorted_set_map.get_mut(key) {
-            sorted_set.zrem(member)
-        } else {
-            false
+        let removed = sorted_set_map
+            .get_mut(key)
+            .is_some_and(|sorted_set| sorted_set.zrem(member.clone()));
+        if removed {
+            self.changed(&["ZREM", key, &member]);
+            self.resize(zset_member_size(&member), 0);
+            if sorted_set_map.get(key).is_some_and(|sorted_set| sorted_set.members.is_empty()) {
+                sorted_set_map.remove(key);
+                self.emptied(key);
+            }
         }
+        removed
     }
 
     pub async fn zrange(&self, key: &str, start: usize, end: usize) -> Option<Vec<String>> {
         let ss_map = self.sorted_set.read().unwrap();
         if let Some(sorted_set) = ss_map.get(key) {
+            self.stats.record_lookup(true);
             Some(sorted_set.zrange(start, end))
         } else {
+            self.stats.record_lookup(false);
             None
         }
     }
 
     pub async fn zscore(&self, key: &str, member: &str) -> Option<f64> {
         let ss_map = self.sorted_set.read().unwrap();
-        if let Some(sorted_set) = ss_map.get(key) {
-            sorted_set.zscore(member)
-        } else {
-            None
+        let sorted_set = ss_map.get(key);
+        self.stats.record_lookup(sorted_set.is_some());
+        sorted_set.and_then(|sorted_set| sorted_set.zscore(member))
+    }
+
+    // Adds several members under one lock. With `nx` existing members are left
+    // alone, with `xx` new ones aren't added. Returns how many members were added
+    // and how many were added or got a new score.
+    pub fn zadd_members(
+        &self,
+        key: &str,
+        members: &[(f64, String)],
+        nx: bool,
+        xx: bool,
+    ) -> Result<(usize, usize), String> {
+        let _claim = self.claim(KeyKind::SortedSet, &[key])?;
+        let mut sorted_set_map = self.sorted_set.write().unwrap();
+        let (mut added, mut changed) = (0, 0);
+        for (score, member) in members {
+            let exists = sorted_set_map.get(key).is_some_and(|sorted_set| sorted_set.members.contains_key(member));
+            if (nx && exists) || (xx && !exists) {
+                continue;
+            }
+            if !sorted_set_map.contains_key(key) {
+                self.resize(0, key_size(key));
+            }
+            let sorted_set = sorted_set_map.entry(key.to_string()).or_insert_with(RSortedSet::new);
+            if sorted_set.zadd(*score, member.clone()) {
+                self.changed(&["ZADD", key, &score.to_string(), member]);
+                if !exists {
+                    self.resize(0, zset_member_size(member));
+                }
+                added += !exists as usize;
+                changed += 1;
+            }
+        }
+        Ok((added, changed))
+    }
+
+    // Replaces the key, whatever it held, with a sorted set of the given members;
+    // none removes the key. Returns the size of the new set.
+    pub fn zstore(&self, key: &str, members: Vec<(f64, String)>) -> usize {
+        let _claim = self.claim_replacing(KeyKind::SortedSet, key);
+        let mut sorted_set_map = self.sorted_set.write().unwrap();
+        if let Some(old) = sorted_set_map.remove(key) {
+            self.changed(&["DEL", key]);
+            self.resize(sorted_set_size(key, &old), 0);
+        }
+        if members.is_empty() {
+            return 0;
         }
+        let mut sorted_set = RSortedSet::new();
+        for (score, member) in members {
+            self.changed(&["ZADD", key, &score.to_string(), &member]);
+            sorted_set.zadd(score, member);
+        }
+        let len = sorted_set.members.len();
+        self.resize(0, sorted_set_size(key, &sorted_set));
+        sorted_set_map.insert(key.to_string(), sorted_set);
+        len
     }
-}
\ No newline at end of file
+
+    pub fn sorted_set_read<R>(&self, key: &str, f: impl FnOnce(&RSortedSet) -> R) -> Option<R> {
+        let ss_map = self.sorted_set.read().unwrap();
+        let sorted_set = ss_map.get(key);
+        self.stats.record_lookup(sorted_set.is_some());
+        sorted_set.map(f)
+    }
+
+    // Stream operations. `f` runs under the stream map lock and returns its result
+    // along with the commands that replay its changes on replicas. A missing key is
+    // created only with `create`, and only kept if `f` succeeds.
+    pub fn stream_write<R>(
+        &self,
+        key: &str,
+        create: bool,
+        f: impl FnOnce(&mut RStream) -> Result<(R, Vec<Vec<String>>), String>,
+    ) -> Result<Option<R>, String> {
+        let _claim = if create { Some(self.claim(KeyKind::Stream, &[key])?) } else { None };
+        let mut stream_map = self.stream.write().unwrap();
+        let (result, commands) = match stream_map.get_mut(key) {
+            Some(stream) => {
+                let before = stream_size(key, stream);
+                let output = f(stream);
+                self.resize(before, stream_size(key, stream));
+                output?
+            }
+            None if create => {
+                let mut stream = RStream::new();
+                let output = f(&mut stream)?;
+                self.resize(0, stream_size(key, &stream));
+                stream_map.insert(key.to_string(), stream);
+                output
+            }
+            None => return Ok(None),
+        };
+
+        for command in &commands {
+            let command: Vec<&str> = command.iter().map(String::as_str).collect();
+            self.changed(&command);
+        }
+        if !commands.is_empty() {
+            self.stream_changed.notify_waiters();
+        }
+        Ok(Some(result))
+    }
+
+    pub fn stream_read<R>(&self, key: &str, f: impl FnOnce(&RStream) -> R) -> Option<R> {
+        let stream_map = self.stream.read().unwrap();
+        let stream = stream_map.get(key);
+        self.stats.record_lookup(stream.is_some());
+        stream.map(f)
+    }
+
+    pub fn stream_changed(&self) -> &Notify {
+        &self.stream_changed
+    }
+
+    // JSON operations. `f` gets the document, if any, and may replace or remove it;
+    // it returns its result and whether it changed anything, in which case
+    // `command` is replicated.
+    pub fn json_write<R>(
+        &self,
+        key: &str,
+        command: &[&str],
+        f: impl FnOnce(&mut Option<Value>) -> Result<(R, bool), String>,
+    ) -> Result<R, String> {
+        let _claim = self.claim(KeyKind::Json, &[key])?;
+        let mut json_map = self.json.write().unwrap();
+        let mut doc = json_map.remove(key);
+        let before = doc.as_ref().map_or(0, |doc| json_size(key, doc));
+        let result = f(&mut doc);
+        self.resize(before, doc.as_ref().map_or(0, |doc| json_size(key, doc)));
+        if matches!(result, Ok((_, true))) {
+            self.search.update(key, doc.as_ref());
+        }
+        if let Some(doc) = doc {
+            json_map.insert(key.to_string(), doc);
+        }
+        let (result, changed) = result?;
+        if changed {
+            self.changed(command);
+        }
+        Ok(result)
+    }
+
+    // FT.CREATE: the index starts out with the documents already there
+    pub fn create_index(&self, index: Index) -> Result<(), String> {
+        let json_map = self.json.read().unwrap();
+        let definition = index.definition.clone();
+        self.search.create(index, json_map.iter())?;
+        let command: Vec<&str> = definition.iter().map(String::as_str).collect();
+        self.changed(&command);
+        Ok(())
+    }
+
+    // FT.DROPINDEX, with `delete_docs` (DD) also deleting the documents it covered
+    pub async fn drop_index(&self, name: &str, delete_docs: bool) -> Result<(), String> {
+        let keys = self.search.drop_index(name).ok_or(UNKNOWN_INDEX)?;
+        self.changed(&["FT.DROPINDEX", name]);
+        if delete_docs {
+            for key in keys {
+                self.delete(&key).await;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn json_read<R>(&self, key: &str, f: impl FnOnce(&Value) -> R) -> Option<R> {
+        let json_map = self.json.read().unwrap();
+        let doc = json_map.get(key);
+        self.stats.record_lookup(doc.is_some());
+        doc.map(f)
+    }
+
+    // Time series operations. `f` gets every series, since compaction rules write
+    // to other keys, and returns its result with the commands to replicate. It may
+    // only create or add samples to `keys`, and to the destinations of their rules.
+    pub fn timeseries_write<R>(
+        &self,
+        keys: &[&str],
+        f: impl FnOnce(&mut HashMap<String, TimeSeries>) -> Result<(R, Vec<Vec<String>>), String>,
+    ) -> Result<R, String> {
+        let _claim = self.claim(KeyKind::TimeSeries, keys)?;
+        let mut ts_map = self.timeseries.write().unwrap();
+        let mut written: Vec<String> = keys.iter().map(|key| key.to_string()).collect();
+        for key in keys {
+            written.extend(ts_map.get(*key).into_iter().flat_map(|series| series.rules.iter().map(|rule| rule.dest.clone())));
+        }
+        written.sort();
+        written.dedup();
+        let size = |ts_map: &HashMap<String, TimeSeries>| -> usize {
+            written.iter().filter_map(|key| ts_map.get(key).map(|series| series_size(key, series))).sum()
+        };
+        let before = size(&ts_map);
+        let output = f(&mut ts_map);
+        self.resize(before, size(&ts_map));
+        let (result, commands) = output?;
+        for command in &commands {
+            let command: Vec<&str> = command.iter().map(String::as_str).collect();
+            self.changed(&command);
+        }
+        Ok(result)
+    }
+
+    pub fn timeseries_read<R>(&self, key: &str, f: impl FnOnce(&TimeSeries) -> R) -> Option<R> {
+        let ts_map = self.timeseries.read().unwrap();
+        let series = ts_map.get(key);
+        self.stats.record_lookup(series.is_some());
+        series.map(f)
+    }
+
+    // TS.MRANGE and friends, which look at every series
+    pub fn timeseries_scan<R>(&self, f: impl FnOnce(&HashMap<String, TimeSeries>) -> R) -> R {
+        f(&self.timeseries.read().unwrap())
+    }
+
+    // Sketch operations, like json_write: `f` may create, change or remove the
+    // sketch and says whether `command` needs replicating
+    pub fn sketch_write<R>(
+        &self,
+        key: &str,
+        command: &[&str],
+        f: impl FnOnce(&mut Option<Sketch>) -> Result<(R, bool), String>,
+    ) -> Result<R, String> {
+        let _claim = self.claim(KeyKind::Sketch, &[key])?;
+        let mut sketch_map = self.sketch.write().unwrap();
+        let mut sketch = sketch_map.remove(key);
+        let before = sketch.as_ref().map_or(0, |sketch| sketch_size(key, sketch));
+        let result = f(&mut sketch);
+        self.resize(before, sketch.as_ref().map_or(0, |sketch| sketch_size(key, sketch)));
+        if let Some(sketch) = sketch {
+            sketch_map.insert(key.to_string(), sketch);
+        }
+        let (result, changed) = result?;
+        if changed {
+            self.changed(command);
+        }
+        Ok(result)
+    }
+
+    // CMS.MERGE, which reads `sources` while writing `key`
+    pub fn sketches_write<R>(
+        &self,
+        key: &str,
+        sources: &[&str],
+        command: &[&str],
+        f: impl FnOnce(&mut HashMap<String, Sketch>) -> Result<(R, bool), String>,
+    ) -> Result<R, String> {
+        let keys: Vec<&str> = std::iter::once(key).chain(sources.iter().copied()).collect();
+        let _claim = self.claim(KeyKind::Sketch, &keys)?;
+        let mut sketch_map = self.sketch.write().unwrap();
+        let size = |sketch_map: &HashMap<String, Sketch>| sketch_map.get(key).map_or(0, |sketch| sketch_size(key, sketch));
+        let before = size(&sketch_map);
+        let output = f(&mut sketch_map);
+        self.resize(before, size(&sketch_map));
+        let (result, changed) = output?;
+        if changed {
+            self.changed(command);
+        }
+        Ok(result)
+    }
+
+    // WRONGTYPE if another type holds the key, otherwise None when it's missing
+    pub fn sketch_read<R>(&self, key: &str, f: impl FnOnce(&Sketch) -> R) -> Result<Option<R>, String> {
+        if self.kind_of(key).is_some_and(|held| held != KeyKind::Sketch) {
+            return Err(WRONG_TYPE.to_string());
+        }
+        let sketch_map = self.sketch.read().unwrap();
+        let sketch = sketch_map.get(key);
+        self.stats.record_lookup(sketch.is_some());
+        Ok(sketch.map(f))
+    }
+
+    // Keyspace introspection for INFO
+    pub fn key_count(&self) -> usize {
+        self.key_counts_by_type().iter().map(|(_, count)| count).sum()
+    }
+
+    // Every key name; each lives in a single type map
+    pub fn keys(&self) -> Vec<String> {
+        let now = Instant::now();
+        let db = self.db.read().unwrap();
+        let exp_map = self.expiry.read().unwrap();
+        let live = |key: &&String| exp_map.get(key).is_none_or(|exp| *exp > now);
+
+        let mut keys: Vec<String> = db.keys().filter(live).cloned().collect();
+        keys.extend(self.list.read().unwrap().keys().cloned());
+        keys.extend(self.set.read().unwrap().keys().cloned());
+        keys.extend(self.sorted_set.read().unwrap().keys().cloned());
+        keys.extend(self.stream.read().unwrap().keys().cloned());
+        keys.extend(self.json.read().unwrap().keys().cloned());
+        keys.extend(self.timeseries.read().unwrap().keys().cloned());
+        keys.extend(self.sketch.read().unwrap().keys().cloned());
+        keys
+    }
+
+    pub fn key_counts_by_type(&self) -> Vec<(&'static str, usize)> {
+        vec![
+            ("string", self.db.read().unwrap().len()),
+            ("list", self.list.read().unwrap().len()),
+            ("set", self.set.read().unwrap().len()),
+            ("zset", self.sorted_set.read().unwrap().len()),
+            ("stream", self.stream.read().unwrap().len()),
+            ("json", self.json.read().unwrap().len()),
+            ("timeseries", self.timeseries.read().unwrap().len()),
+            ("sketch", self.sketch.read().unwrap().len()),
+        ]
+    }
+
+    // Returns the number of keys with a TTL and their average remaining TTL in ms
+    pub fn expires_info(&self) -> (usize, u64) {
+        let count = self.expiry.read().unwrap().len() as u64;
+        if count == 0 {
+            return (0, 0);
+        }
+        // Keys past their deadline that active expiry hasn't got to yet count as 0
+        let remaining = self.usage.deadlines.load(Ordering::Relaxed).saturating_sub(count * self.since_start(Instant::now()));
+        (count as usize, remaining / count)
+    }
+
+    // Rough estimate of the memory held by the keyspace, including per-entry overhead
+    pub fn used_memory(&self) -> usize {
+        self.usage.memory.load(Ordering::Relaxed)
+    }
+
+    // Replication: serves a PSYNC. The keyspace stays read-locked until the replica
+    // is registered, so every write lands either in its snapshot or in its stream.
+    pub async fn psync(&self, client: &Arc<Client>, replid: &str, from: i64) {
+        let _sync = self.replication.sync_lock().await;
+        let db = self.db.read().unwrap();
+        let exp_map = self.expiry.read().unwrap();
+        let list_map = self.list.read().unwrap();
+        let set_map = self.set.read().unwrap();
+        let ss_map = self.sorted_set.read().unwrap();
+        let stream_map = self.stream.read().unwrap();
+        let json_map = self.json.read().unwrap();
+        let ts_map = self.timeseries.read().unwrap();
+        let sketch_map = self.sketch.read().unwrap();
+
+        self.replication.add_replica(client, replid, from, || {
+            // The snapshot is the keyspace written out as commands that rebuild it
+            let mut out = Vec::new();
+            let now = Instant::now();
+            let start = now;
+            for (key, value) in db.iter() {
+                dump_string(&mut out, key, value, exp_map.get(key), now);
+            }
+            for (key, list) in list_map.iter() {
+                dump_list(&mut out, key, list);
+            }
+            for (key, set) in set_map.iter() {
+                dump_set(&mut out, key, set);
+            }
+            for (key, sorted_set) in ss_map.iter() {
+                dump_sorted_set(&mut out, key, sorted_set);
+            }
+            for (key, stream) in stream_map.iter() {
+                dump_stream(&mut out, key, stream);
+            }
+            for (key, doc) in json_map.iter() {
+                dump_json(&mut out, key, doc);
+            }
+            for (key, sketch) in sketch_map.iter() {
+                dump_sketch(&mut out, key, sketch);
+            }
+            // Rules go last so that loading the samples doesn't compact them again
+            for (key, series) in ts_map.iter() {
+                dump_timeseries(&mut out, key, series);
+            }
+            for (key, series) in ts_map.iter() {
+                dump_timeseries_rules(&mut out, key, series);
+            }
+            for definition in self.search.definitions() {
+                let command: Vec<&str> = definition.iter().map(String::as_str).collect();
+                out.extend(resp::command(&command).into_bytes());
+            }
+            self.record_latency("snapshot", start.elapsed());
+            out
+        });
+    }
+
+    // The commands that rebuild one key, used by MIGRATE. None if the key doesn't exist.
+    pub fn dump_key(&self, key: &str) -> Option<Vec<u8>> {
+        let mut out = Vec::new();
+        if let Some(value) = self.db.read().unwrap().get(key) {
+            dump_string(&mut out, key, value, self.expiry.read().unwrap().get(key), Instant::now());
+        }
+        if let Some(list) = self.list.read().unwrap().get(key) {
+            dump_list(&mut out, key, list);
+        }
+        if let Some(set) = self.set.read().unwrap().get(key) {
+            dump_set(&mut out, key, set);
+        }
+        if let Some(sorted_set) = self.sorted_set.read().unwrap().get(key) {
+            dump_sorted_set(&mut out, key, sorted_set);
+        }
+        if let Some(stream) = self.stream.read().unwrap().get(key) {
+            dump_stream(&mut out, key, stream);
+        }
+        if let Some(doc) = self.json.read().unwrap().get(key) {
+            dump_json(&mut out, key, doc);
+        }
+        if let Some(series) = self.timeseries.read().unwrap().get(key) {
+            dump_timeseries(&mut out, key, series);
+        }
+        if let Some(sketch) = self.sketch.read().unwrap().get(key) {
+            dump_sketch(&mut out, key, sketch);
+        }
+        (!out.is_empty()).then_some(out)
+    }
+
+    // Drops the whole keyspace before loading a snapshot from the master. Search
+    // indexes go too, the snapshot recreates them.
+    pub fn flush(&self) {
+        self.db.write().unwrap().clear();
+        self.expiry.write().unwrap().clear();
+        self.list.write().unwrap().clear();
+        self.set.write().unwrap().clear();
+        self.sorted_set.write().unwrap().clear();
+        self.stream.write().unwrap().clear();
+        self.json.write().unwrap().clear();
+        self.timeseries.write().unwrap().clear();
+        self.sketch.write().unwrap().clear();
+        self.usage.memory.store(0, Ordering::Relaxed);
+        self.usage.deadlines.store(0, Ordering::Relaxed);
+        self.search.clear();
+        self.tracking.invalidate_all();
+    }
+}
+
+// Memory estimates for used_memory: each key costs its name plus a fixed overhead
+// on top of what its value holds
+const ENTRY_OVERHEAD: usize = 48;
+
+fn key_size(key: &str) -> usize {
+    key.len() + ENTRY_OVERHEAD
+}
+
+fn string_size(key: &str, value: &[u8]) -> usize {
+    key_size(key) + value.len()
+}
+
+// The key is held by both indexes of the expiry map
+fn expiry_size(key: &str) -> usize {
+    key_size(key) + key.len()
+}
+
+fn list_item_size(value: &str) -> usize {
+    value.len() + 24
+}
+
+fn list_size(key: &str, list: &RList) -> usize {
+    key_size(key) + list.list.iter().map(|value| list_item_size(value)).sum::<usize>()
+}
+
+fn set_member_size(member: &str) -> usize {
+    member.len() + 32
+}
+
+fn set_size(key: &str, set: &RSets) -> usize {
+    key_size(key) + set.set.iter().map(|member| set_member_size(member)).sum::<usize>()
+}
+
+// Each member is stored in both the hash map and the tree
+fn zset_member_size(member: &str) -> usize {
+    2 * (member.len() + 40)
+}
+
+fn sorted_set_size(key: &str, sorted_set: &RSortedSet) -> usize {
+    key_size(key) + sorted_set.members.keys().map(|member| zset_member_size(member)).sum::<usize>()
+}
+
+fn stream_size(key: &str, stream: &RStream) -> usize {
+    key_size(key) + stream.memory_usage()
+}
+
+// Documents are counted at their serialized size, twice for the parsed nodes
+fn json_size(key: &str, doc: &Value) -> usize {
+    key_size(key) + 2 * doc.to_string().len()
+}
+
+fn series_size(key: &str, series: &TimeSeries) -> usize {
+    key_size(key) + series.memory_usage()
+}
+
+fn sketch_size(key: &str, sketch: &Sketch) -> usize {
+    key_size(key) + sketch.memory_usage()
+}
+
+fn dump_string(out: &mut Vec<u8>, key: &str, value: &[u8], expiry: Option<&Instant>, now: Instant) {
+    match expiry {
+        Some(exp) if *exp <= now => {}
+        Some(exp) => {
+            // EX takes whole seconds, round up so the key doesn't vanish early
+            let ttl = exp.duration_since(now).as_millis().div_ceil(1000).to_string();
+            out.extend(resp::command_bytes(&[b"SET", key.as_bytes(), value, b"EX", ttl.as_bytes()]));
+        }
+        None => out.extend(resp::command_bytes(&[b"SET", key.as_bytes(), value])),
+    }
+}
+
+fn dump_list(out: &mut Vec<u8>, key: &str, list: &RList) {
+    for value in &list.list {
+        out.extend(resp::command(&["RPUSH", key, value]).into_bytes());
+    }
+}
+
+fn dump_set(out: &mut Vec<u8>, key: &str, set: &RSets) {
+    for member in &set.set {
+        out.extend(resp::command(&["SADD", key, member]).into_bytes());
+    }
+}
+
+fn dump_sorted_set(out: &mut Vec<u8>, key: &str, sorted_set: &RSortedSet) {
+    for entry in &sorted_set.sorted {
+        out.extend(resp::command(&["ZADD", key, &entry.score.to_string(), &entry.member]).into_bytes());
+    }
+}
+
+fn dump_json(out: &mut Vec<u8>, key: &str, doc: &Value) {
+    out.extend(resp::command(&["JSON.SET", key, "$", &doc.to_string()]).into_bytes());
+}
+
+// Filters are dumped as their SCANDUMP chunks, which LOADCHUNK turns back into
+// the same bits
+fn dump_sketch(out: &mut Vec<u8>, key: &str, sketch: &Sketch) {
+    for command in sketch.dump(key) {
+        let command: Vec<&str> = command.iter().map(String::as_str).collect();
+        out.extend(resp::command(&command).into_bytes());
+    }
+}
+
+// The series with its samples, in batches. Its compaction rules are left to
+// dump_timeseries_rules, and MIGRATE leaves them behind.
+fn dump_timeseries(out: &mut Vec<u8>, key: &str, series: &TimeSeries) {
+    let mut create = vec![
+        "TS.CREATE".to_string(),
+        key.to_string(),
+        "RETENTION".to_string(),
+        series.retention.to_string(),
+        "CHUNK_SIZE".to_string(),
+        series.chunk_size.to_string(),
+    ];
+    if !series.labels.is_empty() {
+        create.push("LABELS".to_string());
+        create.extend(series.labels.iter().flat_map(|(name, value)| [name.clone(), value.clone()]));
+    }
+    out.extend(resp::command(&create.iter().map(String::as_str).collect::<Vec<_>>()).into_bytes());
+
+    for batch in series.samples(i64::MIN, i64::MAX).chunks(100) {
+        let mut args = vec!["TS.MADD".to_string()];
+        for (timestamp, value) in batch {
+            args.extend([key.to_string(), timestamp.to_string(), timeseries::format_value(*value)]);
+        }
+        out.extend(resp::command(&args.iter().map(String::as_str).collect::<Vec<_>>()).into_bytes());
+    }
+}
+
+fn dump_timeseries_rules(out: &mut Vec<u8>, key: &str, series: &TimeSeries) {
+    for rule in &series.rules {
+        out.extend(resp::command(&[
+            "TS.CREATERULE",
+            key,
+            &rule.dest,
+            "AGGREGATION",
+            rule.aggregation.name(),
+            &rule.bucket.to_string(),
+        ]).into_bytes());
+    }
+}
+
+// Entries, then the groups with their pending entries and consumers, then the ID
+// state. Pending entries can outlive their message, so those IDs (or one ID if the
+// stream is empty, to create the key) get placeholder entries that are deleted
+// once claimed.
+fn dump_stream(out: &mut Vec<u8>, key: &str, stream: &RStream) {
+    let placeholder = vec![("x".to_string(), "y".to_string())];
+    let mut entries: BTreeMap<StreamId, &Vec<(String, String)>> =
+        stream.entries.iter().map(|(id, fields)| (*id, fields)).collect();
+    let mut placeholders: Vec<StreamId> = stream
+        .groups
+        .values()
+        .flat_map(|group| group.pending.keys())
+        .filter(|id| !stream.entries.contains_key(id))
+        .copied()
+        .collect();
+    if entries.is_empty() && placeholders.is_empty() {
+        placeholders.push(stream.last_id.max(StreamId::new(0, 1)));
+    }
+    placeholders.sort();
+    placeholders.dedup();
+    for id in &placeholders {
+        entries.insert(*id, &placeholder);
+    }
+
+    for (id, fields) in entries {
+        let mut args = vec!["XADD".to_string(), key.to_string(), id.to_string()];
+        args.extend(fields.iter().flat_map(|(field, value)| [field.clone(), value.clone()]));
+        out.extend(resp::command(&args.iter().map(String::as_str).collect::<Vec<_>>()).into_bytes());
+    }
+
+    for (name, group) in &stream.groups {
+        let mut create = vec!["XGROUP", "CREATE", key, name];
+        let last_id = group.last_id.to_string();
+        create.push(&last_id);
+        let entries_read = group.entries_read.map(|read| read.to_string());
+        if let Some(entries_read) = &entries_read {
+            create.extend(["ENTRIESREAD", entries_read]);
+        }
+        out.extend(resp::command(&create).into_bytes());
+
+        for (id, pending) in &group.pending {
+            out.extend(resp::command(&[
+                "XCLAIM",
+                key,
+                name,
+                &pending.consumer,
+                "0",
+                &id.to_string(),
+                "TIME",
+                &pending.delivered.to_string(),
+                "RETRYCOUNT",
+                &pending.deliveries.to_string(),
+                "FORCE",
+                "JUSTID",
+            ]).into_bytes());
+        }
+        for consumer in group.consumers.keys() {
+            out.extend(resp::command(&["XGROUP", "CREATECONSUMER", key, name, consumer]).into_bytes());
+        }
+    }
+
+    if !placeholders.is_empty() {
+        let ids: Vec<String> = placeholders.iter().map(StreamId::to_string).collect();
+        let mut args = vec!["XDEL", key];
+        args.extend(ids.iter().map(String::as_str));
+        out.extend(resp::command(&args).into_bytes());
+    }
+    out.extend(resp::command(&[
+        "XSETID",
+        key,
+        &stream.last_id.to_string(),
+        "ENTRIESADDED",
+        &stream.entries_added.to_string(),
+        "MAXDELETEDID",
+        &stream.max_deleted_id.to_string(),
+    ]).into_bytes());
+}
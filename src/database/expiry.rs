@@ -0,0 +1,52 @@
+// TTL deadlines of string keys. They're indexed by key for lookups and by deadline,
+// so active expiry takes the keys that are due without looking at the others.
+
+use std::collections::{BTreeSet, HashMap};
+use std::time::Instant;
+
+#[derive(Default)]
+pub struct Expiries {
+    by_key: HashMap<String, Instant>,
+    by_deadline: BTreeSet<(Instant, String)>,
+}
+
+impl Expiries {
+    pub fn get(&self, key: &str) -> Option<&Instant> {
+        self.by_key.get(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_key.len()
+    }
+
+    // Sets the key's deadline, returning the one it replaces
+    pub fn insert(&mut self, key: String, deadline: Instant) -> Option<Instant> {
+        let old = self.by_key.insert(key.clone(), deadline);
+        if let Some(old) = old {
+            self.by_deadline.remove(&(old, key.clone()));
+        }
+        self.by_deadline.insert((deadline, key));
+        old
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Instant> {
+        let old = self.by_key.remove(key)?;
+        self.by_deadline.remove(&(old, key.to_string()));
+        Some(old)
+    }
+
+    pub fn clear(&mut self) {
+        self.by_key.clear();
+        self.by_deadline.clear();
+    }
+
+    // Keys whose deadline has passed, the longest overdue first, at most `limit`
+    pub fn due(&self, now: Instant, limit: usize) -> Vec<String> {
+        self.by_deadline
+            .iter()
+            .take_while(|(deadline, _)| now > *deadline)
+            .take(limit)
+            .map(|(_, key)| key.clone())
+            .collect()
+    }
+}
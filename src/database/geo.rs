@@ -0,0 +1,116 @@
+// Geo positions are kept in sorted sets with a 52 bit geohash as the score, like
+// Redis: 26 bits of latitude and 26 of longitude, interleaved. The encoding,
+// decoding and distance math follow Redis' geohash.c and geohash_helper.c, so
+// scores, positions and distances come out the same.
+
+const STEP: u32 = 26;
+pub const LAT_MIN: f64 = -85.05112878;
+pub const LAT_MAX: f64 = 85.05112878;
+pub const LONG_MIN: f64 = -180.0;
+pub const LONG_MAX: f64 = 180.0;
+const EARTH_RADIUS_IN_METERS: f64 = 6372797.560856;
+const ALPHABET: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+pub fn valid(long: f64, lat: f64) -> bool {
+    (LONG_MIN..=LONG_MAX).contains(&long) && (LAT_MIN..=LAT_MAX).contains(&lat)
+}
+
+// Spreads the 32 bits of x over the even bits of a u64
+fn spread(x: u32) -> u64 {
+    let mut x = x as u64;
+    x = (x | (x << 16)) & 0x0000_FFFF_0000_FFFF;
+    x = (x | (x << 8)) & 0x00FF_00FF_00FF_00FF;
+    x = (x | (x << 4)) & 0x0F0F_0F0F_0F0F_0F0F;
+    x = (x | (x << 2)) & 0x3333_3333_3333_3333;
+    (x | (x << 1)) & 0x5555_5555_5555_5555
+}
+
+fn squash(x: u64) -> u32 {
+    let mut x = x & 0x5555_5555_5555_5555;
+    x = (x | (x >> 1)) & 0x3333_3333_3333_3333;
+    x = (x | (x >> 2)) & 0x0F0F_0F0F_0F0F_0F0F;
+    x = (x | (x >> 4)) & 0x00FF_00FF_00FF_00FF;
+    x = (x | (x >> 8)) & 0x0000_FFFF_0000_FFFF;
+    ((x | (x >> 16)) & 0x0000_0000_FFFF_FFFF) as u32
+}
+
+fn encode_in(long: f64, lat: f64, lat_min: f64, lat_max: f64) -> u64 {
+    let lat_offset = (lat - lat_min) / (lat_max - lat_min) * (1u64 << STEP) as f64;
+    let long_offset = (long - LONG_MIN) / (LONG_MAX - LONG_MIN) * (1u64 << STEP) as f64;
+    spread(lat_offset as u32) | (spread(long_offset as u32) << 1)
+}
+
+// The sorted set score for a position, None when it's outside what can be indexed
+pub fn encode(long: f64, lat: f64) -> Option<u64> {
+    valid(long, lat).then(|| encode_in(long, lat, LAT_MIN, LAT_MAX))
+}
+
+// The center of the cell a score stands for, as (longitude, latitude)
+pub fn decode(bits: u64) -> (f64, f64) {
+    let scale = (1u64 << STEP) as f64;
+    let (lat_cell, long_cell) = (squash(bits) as f64, squash(bits >> 1) as f64);
+    let lat_min = LAT_MIN + lat_cell / scale * (LAT_MAX - LAT_MIN);
+    let lat_max = LAT_MIN + (lat_cell + 1.0) / scale * (LAT_MAX - LAT_MIN);
+    let long_min = LONG_MIN + long_cell / scale * (LONG_MAX - LONG_MIN);
+    let long_max = LONG_MIN + (long_cell + 1.0) / scale * (LONG_MAX - LONG_MIN);
+    let long = ((long_min + long_max) / 2.0).clamp(LONG_MIN, LONG_MAX);
+    let lat = ((lat_min + lat_max) / 2.0).clamp(LAT_MIN, LAT_MAX);
+    (long, lat)
+}
+
+// The standard 11 character geohash. Scores use a latitude range of +-85 degrees
+// instead of +-90, so the position is encoded again; the last character only
+// has zero bits left, as in Redis.
+pub fn hash_string(bits: u64) -> String {
+    let (long, lat) = decode(bits);
+    let standard = encode_in(long, lat, -90.0, 90.0);
+    (0..11)
+        .map(|i| {
+            let index = if i == 10 { 0 } else { (standard >> (52 - (i + 1) * 5)) & 0x1f };
+            ALPHABET[index as usize] as char
+        })
+        .collect()
+}
+
+fn lat_distance(lat1: f64, lat2: f64) -> f64 {
+    EARTH_RADIUS_IN_METERS * (lat2.to_radians() - lat1.to_radians()).abs()
+}
+
+// Haversine distance in meters
+pub fn distance(long1: f64, lat1: f64, long2: f64, lat2: f64) -> f64 {
+    let v = ((long2.to_radians() - long1.to_radians()) / 2.0).sin();
+    if v == 0.0 {
+        return lat_distance(lat1, lat2);
+    }
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let u = ((lat2 - lat1) / 2.0).sin();
+    let a = u * u + lat1.cos() * lat2.cos() * v * v;
+    2.0 * EARTH_RADIUS_IN_METERS * a.sqrt().asin()
+}
+
+// A search area around a center, in meters
+#[derive(Clone, Copy)]
+pub enum Shape {
+    Radius(f64),
+    Box { width: f64, height: f64 },
+}
+
+impl Shape {
+    // The distance from the center to a point in the area, None when it's outside
+    pub fn contains(self, (long, lat): (f64, f64), (point_long, point_lat): (f64, f64)) -> Option<f64> {
+        match self {
+            Shape::Radius(radius) => {
+                let d = distance(long, lat, point_long, point_lat);
+                (d <= radius).then_some(d)
+            }
+            Shape::Box { width, height } => {
+                if lat_distance(point_lat, lat) > height / 2.0
+                    || distance(point_long, point_lat, long, point_lat) > width / 2.0
+                {
+                    return None;
+                }
+                Some(distance(long, lat, point_long, point_lat))
+            }
+        }
+    }
+}
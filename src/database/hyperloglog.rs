@@ -0,0 +1,476 @@
+// HyperLogLog in Redis' own representation, so the bytes are the ones Redis
+// stores: a 16 byte header ("HYLL", the encoding, 3 unused bytes and the cached
+// cardinality) followed by the registers, sparse or dense. The algorithms follow
+// Redis' hyperloglog.c step by step, including when and how the sparse encoding
+// is rewritten, so the same elements give the same bytes.
+
+const P: usize = 14;
+const REGISTERS: usize = 1 << P;
+const Q: usize = 64 - P;
+const BITS: usize = 6;
+const REGISTER_MAX: u8 = (1 << BITS) - 1;
+const HEADER_SIZE: usize = 16;
+const DENSE_SIZE: usize = HEADER_SIZE + (REGISTERS * BITS).div_ceil(8);
+const DENSE: u8 = 0;
+const SPARSE: u8 = 1;
+const HASH_SEED: u64 = 0xadc83b19;
+const ALPHA_INF: f64 = 0.721_347_520_444_481_7;
+
+// Sparse opcodes: ZERO 00xxxxxx, XZERO 01xxxxxx xxxxxxxx, VAL 1vvvvvxx
+const SPARSE_VAL_MAX_VALUE: u8 = 32;
+const SPARSE_VAL_MAX_LEN: usize = 4;
+const SPARSE_ZERO_MAX_LEN: usize = 64;
+const SPARSE_XZERO_MAX_LEN: usize = 16384;
+
+pub const WRONGTYPE: &str = "WRONGTYPE Key is not a valid HyperLogLog string value.";
+pub const CORRUPTED: &str = "INVALIDOBJ Corrupted HLL object detected";
+
+pub struct HyperLogLog {
+    bytes: Vec<u8>,
+}
+
+impl HyperLogLog {
+    // An empty HLL: sparse, one XZERO covering every register
+    pub fn new() -> Self {
+        let mut bytes = b"HYLL".to_vec();
+        bytes.extend([SPARSE, 0, 0, 0]);
+        bytes.extend([0; 8]);
+        let mut xzero = [0; 2];
+        xzero_set(&mut xzero, REGISTERS);
+        bytes.extend(xzero);
+        HyperLogLog { bytes }
+    }
+
+    // String values hold the representation as is. It's checked whenever it's read,
+    // so a value set by hand or restored from a dump can't be misread.
+    pub fn from_bytes(value: &[u8]) -> Result<Self, String> {
+        let valid = value.len() >= HEADER_SIZE
+            && value.starts_with(b"HYLL")
+            && value[4] <= SPARSE
+            && (value[4] != DENSE || value.len() == DENSE_SIZE);
+        if !valid {
+            return Err(WRONGTYPE.to_string());
+        }
+        if value[4] == SPARSE && !valid_sparse(&value[HEADER_SIZE..]) {
+            return Err(CORRUPTED.to_string());
+        }
+        Ok(HyperLogLog { bytes: value.to_vec() })
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    pub fn is_dense(&self) -> bool {
+        self.bytes[4] == DENSE
+    }
+
+    // Returns whether a register changed
+    pub fn add(&mut self, element: &str, sparse_max_bytes: usize) -> Result<bool, String> {
+        let (index, count) = pattern_len(element.as_bytes());
+        self.set(index, count, sparse_max_bytes)
+    }
+
+    // Cardinality estimate, from the cache when it's still valid
+    pub fn count(&self) -> Result<u64, String> {
+        if self.bytes[15] & 0x80 == 0 {
+            let mut card = [0; 8];
+            card.copy_from_slice(&self.bytes[8..16]);
+            return Ok(u64::from_le_bytes(card));
+        }
+        Ok(count_registers(&self.registers()?))
+    }
+
+    // Raises `max` to this HLL's registers, for unions
+    pub fn merge_into(&self, max: &mut [u8]) -> Result<(), String> {
+        for (max, register) in max.iter_mut().zip(self.registers()?) {
+            *max = (*max).max(register);
+        }
+        Ok(())
+    }
+
+    // Raises the registers to `max`, staying sparse unless `dense` or it doesn't fit
+    pub fn merge_from(&mut self, max: &[u8], dense: bool, sparse_max_bytes: usize) -> Result<(), String> {
+        if dense && !self.is_dense() {
+            self.make_dense()?;
+        }
+        for (index, register) in max.iter().enumerate() {
+            if *register != 0 {
+                self.set(index, *register, sparse_max_bytes)?;
+            }
+        }
+        self.invalidate_cache();
+        Ok(())
+    }
+
+    // Every register's value, whatever the encoding
+    pub fn registers(&self) -> Result<Vec<u8>, String> {
+        let data = &self.bytes[HEADER_SIZE..];
+        if self.is_dense() {
+            return Ok((0..REGISTERS).map(|index| dense_get(data, index)).collect());
+        }
+
+        let mut registers = Vec::with_capacity(REGISTERS);
+        let mut p = 0;
+        while p < data.len() {
+            let (value, len, oplen) = match data[p] {
+                op if is_zero(op) => (0, zero_len(op), 1),
+                op if is_val(op) => (val_value(op), val_len(op), 1),
+                _ if p + 1 < data.len() => (0, xzero_len(&data[p..]), 2),
+                _ => return Err(CORRUPTED.to_string()),
+            };
+            if registers.len() + len > REGISTERS {
+                return Err(CORRUPTED.to_string());
+            }
+            registers.extend(std::iter::repeat_n(value, len));
+            p += oplen;
+        }
+        if registers.len() != REGISTERS {
+            return Err(CORRUPTED.to_string());
+        }
+        Ok(registers)
+    }
+
+    fn invalidate_cache(&mut self) {
+        self.bytes[15] |= 0x80;
+    }
+
+    fn set(&mut self, index: usize, count: u8, sparse_max_bytes: usize) -> Result<bool, String> {
+        let updated = if self.is_dense() {
+            dense_set(&mut self.bytes[HEADER_SIZE..], index, count)
+        } else {
+            self.sparse_set(index, count, sparse_max_bytes)?
+        };
+        if updated {
+            self.invalidate_cache();
+        }
+        Ok(updated)
+    }
+
+    fn make_dense(&mut self) -> Result<(), String> {
+        let registers = self.registers()?;
+        let mut bytes = self.bytes[..HEADER_SIZE].to_vec();
+        bytes[4] = DENSE;
+        bytes.resize(DENSE_SIZE, 0);
+        for (index, register) in registers.into_iter().enumerate() {
+            if register != 0 {
+                dense_set(&mut bytes[HEADER_SIZE..], index, register);
+            }
+        }
+        self.bytes = bytes;
+        Ok(())
+    }
+
+    // Like hllSparseSet: finds the opcode covering `index`, splits it around the
+    // new value and merges equal neighbouring VAL opcodes. Values too big for the
+    // sparse encoding, or growing past `sparse_max_bytes`, switch to dense.
+    fn sparse_set(&mut self, index: usize, count: u8, sparse_max_bytes: usize) -> Result<bool, String> {
+        if count > SPARSE_VAL_MAX_VALUE {
+            return self.promote(index, count);
+        }
+
+        // Step 1: locate the opcode covering the register
+        let end = self.bytes.len();
+        let mut p = HEADER_SIZE;
+        let mut first = 0;
+        let mut prev = None;
+        let mut span = 0;
+        while p < end {
+            let op = self.bytes[p];
+            let oplen = if is_xzero(op) { 2 } else { 1 };
+            span = if is_zero(op) {
+                zero_len(op)
+            } else if is_val(op) {
+                val_len(op)
+            } else if p + 1 < end {
+                xzero_len(&self.bytes[p..])
+            } else {
+                return Err(CORRUPTED.to_string());
+            };
+            if index < first + span {
+                break;
+            }
+            prev = Some(p);
+            p += oplen;
+            first += span;
+        }
+        if span == 0 || p >= end {
+            return Err(CORRUPTED.to_string());
+        }
+
+        let op = self.bytes[p];
+        // Step 2: trivial updates in place, otherwise split the opcode
+        if is_val(op) {
+            if val_value(op) >= count {
+                return Ok(false);
+            }
+            if val_len(op) == 1 {
+                self.bytes[p] = val_op(count, 1);
+                self.merge_vals(prev.unwrap_or(HEADER_SIZE));
+                return Ok(true);
+            }
+        }
+        if is_zero(op) && zero_len(op) == 1 {
+            self.bytes[p] = val_op(count, 1);
+            self.merge_vals(prev.unwrap_or(HEADER_SIZE));
+            return Ok(true);
+        }
+
+        let last = first + span - 1;
+        let mut seq = Vec::with_capacity(5);
+        if is_val(op) {
+            let value = val_value(op);
+            if index != first {
+                seq.push(val_op(value, index - first));
+            }
+            seq.push(val_op(count, 1));
+            if index != last {
+                seq.push(val_op(value, last - index));
+            }
+        } else {
+            push_zeros(&mut seq, index - first);
+            seq.push(val_op(count, 1));
+            push_zeros(&mut seq, last - index);
+        }
+
+        // Step 3: put the new sequence in place of the old opcode
+        let oldlen = if is_xzero(op) { 2 } else { 1 };
+        if seq.len() > oldlen && self.bytes.len() + seq.len() - oldlen > sparse_max_bytes {
+            return self.promote(index, count);
+        }
+        self.bytes.splice(p..p + oldlen, seq);
+
+        // Step 4
+        self.merge_vals(prev.unwrap_or(HEADER_SIZE));
+        Ok(true)
+    }
+
+    // Merges adjacent VAL opcodes with the same value, scanning up to 5 opcodes
+    fn merge_vals(&mut self, mut p: usize) {
+        let mut scan = 5;
+        while p < self.bytes.len() && scan > 0 {
+            scan -= 1;
+            let op = self.bytes[p];
+            if is_xzero(op) {
+                p += 2;
+                continue;
+            }
+            if is_zero(op) {
+                p += 1;
+                continue;
+            }
+            if let Some(&next) = self.bytes.get(p + 1) {
+                if is_val(next) && val_value(op) == val_value(next) {
+                    let len = val_len(op) + val_len(next);
+                    if len <= SPARSE_VAL_MAX_LEN {
+                        self.bytes[p + 1] = val_op(val_value(op), len);
+                        self.bytes.remove(p);
+                        // Try the merged opcode against the one on its right too
+                        continue;
+                    }
+                }
+            }
+            p += 1;
+        }
+    }
+
+    fn promote(&mut self, index: usize, count: u8) -> Result<bool, String> {
+        self.make_dense()?;
+        Ok(dense_set(&mut self.bytes[HEADER_SIZE..], index, count))
+    }
+}
+
+// The register an element lands in and the length of the 000..1 run that
+// follows in its hash
+fn pattern_len(element: &[u8]) -> (usize, u8) {
+    let hash = murmurhash64a(element, HASH_SEED);
+    let index = (hash & (REGISTERS as u64 - 1)) as usize;
+    // The extra bit caps the count at Q + 1
+    let rest = (hash >> P) | (1 << Q);
+    (index, rest.trailing_zeros() as u8 + 1)
+}
+
+// Cardinality of a register array, with Otmar Ertl's estimator like Redis
+// ("New cardinality estimation algorithms for HyperLogLog sketches")
+pub fn count_registers(registers: &[u8]) -> u64 {
+    let m = REGISTERS as f64;
+    let mut histogram = [0u32; Q + 2];
+    for register in registers {
+        histogram[*register as usize] += 1;
+    }
+
+    let mut z = m * tau((m - histogram[Q + 1] as f64) / m);
+    for count in histogram[1..=Q].iter().rev() {
+        z += *count as f64;
+        z *= 0.5;
+    }
+    z += m * sigma(histogram[0] as f64 / m);
+    (ALPHA_INF * m * m / z).round() as u64
+}
+
+pub fn empty_registers() -> Vec<u8> {
+    vec![0; REGISTERS]
+}
+
+fn sigma(mut x: f64) -> f64 {
+    if x == 1.0 {
+        return f64::INFINITY;
+    }
+    let mut y = 1.0;
+    let mut z = x;
+    loop {
+        x *= x;
+        let previous = z;
+        z += x * y;
+        y += y;
+        if previous == z {
+            return z;
+        }
+    }
+}
+
+fn tau(mut x: f64) -> f64 {
+    if x == 0.0 || x == 1.0 {
+        return 0.0;
+    }
+    let mut y = 1.0;
+    let mut z = 1.0 - x;
+    loop {
+        x = x.sqrt();
+        let previous = z;
+        y *= 0.5;
+        z -= (1.0 - x).powi(2) * y;
+        if previous == z {
+            return z / 3.0;
+        }
+    }
+}
+
+// MurmurHash2, 64-bit version, as used by Redis
+fn murmurhash64a(key: &[u8], seed: u64) -> u64 {
+    const M: u64 = 0xc6a4a7935bd1e995;
+    const R: u32 = 47;
+    let mut h = seed ^ (key.len() as u64).wrapping_mul(M);
+
+    let mut chunks = key.chunks_exact(8);
+    for chunk in &mut chunks {
+        let mut k = u64::from_le_bytes(chunk.try_into().expect("chunks of 8"));
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+        h ^= k;
+        h = h.wrapping_mul(M);
+    }
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        for (i, byte) in tail.iter().enumerate() {
+            h ^= (*byte as u64) << (8 * i);
+        }
+        h = h.wrapping_mul(M);
+    }
+
+    h ^= h >> R;
+    h = h.wrapping_mul(M);
+    h ^= h >> R;
+    h
+}
+
+// Dense registers are 6 bits each, packed starting at the least significant bit
+fn dense_get(data: &[u8], index: usize) -> u8 {
+    let byte = index * BITS / 8;
+    let shift = index * BITS % 8;
+    let b0 = data[byte] as u16;
+    let b1 = data.get(byte + 1).copied().unwrap_or(0) as u16;
+    (((b0 >> shift) | (b1 << (8 - shift))) as u8) & REGISTER_MAX
+}
+
+fn dense_set(data: &mut [u8], index: usize, count: u8) -> bool {
+    if dense_get(data, index) >= count {
+        return false;
+    }
+    let byte = index * BITS / 8;
+    let shift = index * BITS % 8;
+    let value = count as u16;
+    data[byte] &= !((REGISTER_MAX as u16) << shift) as u8;
+    data[byte] |= (value << shift) as u8;
+    if let Some(next) = data.get_mut(byte + 1) {
+        *next &= !((REGISTER_MAX as u16) >> (8 - shift)) as u8;
+        *next |= (value >> (8 - shift)) as u8;
+    }
+    true
+}
+
+fn is_zero(op: u8) -> bool {
+    op & 0xc0 == 0
+}
+
+fn is_xzero(op: u8) -> bool {
+    op & 0xc0 == 0x40
+}
+
+fn is_val(op: u8) -> bool {
+    op & 0x80 != 0
+}
+
+fn zero_len(op: u8) -> usize {
+    (op & 0x3f) as usize + 1
+}
+
+fn xzero_len(ops: &[u8]) -> usize {
+    ((((ops[0] & 0x3f) as usize) << 8) | ops[1] as usize) + 1
+}
+
+fn val_value(op: u8) -> u8 {
+    ((op >> 2) & 0x1f) + 1
+}
+
+fn val_len(op: u8) -> usize {
+    (op & 0x3) as usize + 1
+}
+
+fn val_op(value: u8, len: usize) -> u8 {
+    ((value - 1) << 2) | (len as u8 - 1) | 0x80
+}
+
+fn xzero_set(ops: &mut [u8], len: usize) {
+    let len = len - 1;
+    ops[0] = (len >> 8) as u8 | 0x40;
+    ops[1] = (len & 0xff) as u8;
+}
+
+// A run of zero registers, as ZERO when it fits and XZERO otherwise
+fn push_zeros(seq: &mut Vec<u8>, len: usize) {
+    if len == 0 {
+        return;
+    }
+    if len > SPARSE_ZERO_MAX_LEN {
+        let mut xzero = [0; 2];
+        xzero_set(&mut xzero, len.min(SPARSE_XZERO_MAX_LEN));
+        seq.extend(xzero);
+    } else {
+        seq.push((len - 1) as u8);
+    }
+}
+
+// Whether the sparse opcodes cover every register exactly once
+fn valid_sparse(data: &[u8]) -> bool {
+    let mut covered = 0;
+    let mut p = 0;
+    while p < data.len() {
+        let op = data[p];
+        covered += if is_xzero(op) {
+            if p + 1 >= data.len() {
+                return false;
+            }
+            p += 2;
+            xzero_len(&data[p - 2..])
+        } else {
+            p += 1;
+            if is_zero(op) { zero_len(op) } else { val_len(op) }
+        };
+        if covered > REGISTERS {
+            return false;
+        }
+    }
+    covered == REGISTERS
+}
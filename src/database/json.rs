@@ -0,0 +1,609 @@
+// JSON documents and the paths the JSON.* commands take. Paths starting with
+// `$` are JSONPath and select any number of values: `.name`, `['name']`, `[0]`,
+// `[-1]`, `[1:5:2]`, `*`, `..name` for any depth, `[a,b]` unions and
+// `[?(@.price < 10 && @.tag == 'x')]` filters. Other paths use the older RedisJSON
+// syntax (`.`, `.a.b[0]`, `a.b`) and select at most one value.
+
+use std::cmp::Ordering;
+
+use serde_json::Value;
+
+// One step from a value to a child, so a match can be found again to change it
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Step {
+    Key(String),
+    Index(usize),
+}
+
+pub type Location = Vec<Step>;
+
+#[derive(Clone)]
+enum Selector {
+    Name(String),
+    Wildcard,
+    Index(i64),
+    Slice(Option<i64>, Option<i64>, i64),
+    Filter(Box<Filter>),
+}
+
+#[derive(Clone)]
+enum Segment {
+    Child(Vec<Selector>),
+    Descendant(Vec<Selector>),
+}
+
+#[derive(Clone)]
+enum Operand {
+    Relative(Vec<Segment>),
+    Literal(Value),
+}
+
+#[derive(Clone)]
+enum Filter {
+    Or(Vec<Filter>),
+    And(Vec<Filter>),
+    Not(Box<Filter>),
+    Exists(Vec<Segment>),
+    Compare(Operand, &'static str, Operand),
+}
+
+#[derive(Clone)]
+pub struct Path {
+    segments: Vec<Segment>,
+    legacy: bool,
+}
+
+impl Path {
+    pub fn parse(path: &str) -> Result<Path, String> {
+        let legacy = !path.starts_with('$');
+        let text = match path {
+            "." => "$".to_string(),
+            _ if !legacy => path.to_string(),
+            _ if path.starts_with('.') || path.starts_with('[') => format!("${}", path),
+            _ => format!("$.{}", path),
+        };
+        let mut parser = Parser { chars: text.chars().collect(), pos: 1, depth: 0 };
+        let segments = parser
+            .segments(false)
+            .ok()
+            .filter(|_| parser.pos == parser.chars.len())
+            .ok_or_else(|| format!("JSON Path error: path '{}' is not valid", path))?;
+        Ok(Path { segments, legacy })
+    }
+
+    pub fn root() -> Path {
+        Path { segments: Vec::new(), legacy: false }
+    }
+
+    // The same path selecting every match, as JSONPath does
+    pub fn to_jsonpath(&self) -> Path {
+        Path { segments: self.segments.clone(), legacy: false }
+    }
+
+    pub fn is_legacy(&self) -> bool {
+        self.legacy
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    // Where the path matches, in document order; legacy paths keep the first match
+    pub fn find(&self, doc: &Value) -> Vec<Location> {
+        let mut found = find(doc, &self.segments);
+        if self.legacy {
+            found.truncate(1);
+        }
+        found
+    }
+
+    // For paths ending in a plain member name: the path of the parent and the
+    // name, so that JSON.SET can add the member
+    pub fn parent_and_name(&self) -> Option<(Path, &str)> {
+        match self.segments.split_last()? {
+            (Segment::Child(selectors), parent) => match selectors.as_slice() {
+                [Selector::Name(name)] => Some((Path { segments: parent.to_vec(), legacy: self.legacy }, name)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+fn find(doc: &Value, segments: &[Segment]) -> Vec<Location> {
+    let mut current: Vec<(Location, &Value)> = vec![(Vec::new(), doc)];
+    for segment in segments {
+        let mut next = Vec::new();
+        for (location, value) in current {
+            match segment {
+                Segment::Child(selectors) => select(value, &location, selectors, &mut next),
+                Segment::Descendant(selectors) => descend(value, location, selectors, &mut next),
+            }
+        }
+        current = next;
+    }
+    current.into_iter().map(|(location, _)| location).collect()
+}
+
+// Applies the selectors to a value and then to everything below it
+fn descend<'a>(value: &'a Value, location: Location, selectors: &[Selector], out: &mut Vec<(Location, &'a Value)>) {
+    select(value, &location, selectors, out);
+    for (step, child) in children(value) {
+        let mut child_location = location.clone();
+        child_location.push(step);
+        descend(child, child_location, selectors, out);
+    }
+}
+
+fn children(value: &Value) -> Vec<(Step, &Value)> {
+    match value {
+        Value::Object(map) => map.iter().map(|(key, child)| (Step::Key(key.clone()), child)).collect(),
+        Value::Array(items) => items.iter().enumerate().map(|(i, child)| (Step::Index(i), child)).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn select<'a>(value: &'a Value, location: &Location, selectors: &[Selector], out: &mut Vec<(Location, &'a Value)>) {
+    let mut push = |step: Step, child: &'a Value| {
+        let mut child_location = location.clone();
+        child_location.push(step);
+        out.push((child_location, child));
+    };
+    for selector in selectors {
+        match (selector, value) {
+            (Selector::Name(name), Value::Object(map)) => {
+                if let Some(child) = map.get(name) {
+                    push(Step::Key(name.clone()), child);
+                }
+            }
+            (Selector::Wildcard, _) => {
+                for (step, child) in children(value) {
+                    push(step, child);
+                }
+            }
+            (Selector::Index(index), Value::Array(items)) => {
+                let index = if *index < 0 { items.len() as i64 + index } else { *index };
+                if (0..items.len() as i64).contains(&index) {
+                    push(Step::Index(index as usize), &items[index as usize]);
+                }
+            }
+            (Selector::Slice(start, end, step), Value::Array(items)) => {
+                for i in slice_indexes(items.len() as i64, *start, *end, *step) {
+                    push(Step::Index(i), &items[i]);
+                }
+            }
+            (Selector::Filter(filter), _) => {
+                for (step, child) in children(value) {
+                    if filter.matches(child) {
+                        push(step, child);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Array slices as in RFC 9535, negative steps walking backwards
+fn slice_indexes(len: i64, start: Option<i64>, end: Option<i64>, step: i64) -> Vec<usize> {
+    let normalize = |i: i64| if i < 0 { len + i } else { i };
+    let mut indexes = Vec::new();
+    match step.cmp(&0) {
+        Ordering::Greater => {
+            let lower = normalize(start.unwrap_or(0)).clamp(0, len);
+            let upper = normalize(end.unwrap_or(len)).clamp(0, len);
+            let mut i = lower;
+            while i < upper {
+                indexes.push(i as usize);
+                i += step;
+            }
+        }
+        Ordering::Less => {
+            let upper = start.map_or(len - 1, |start| normalize(start).clamp(-1, len - 1));
+            let lower = end.map_or(-1, |end| normalize(end).clamp(-1, len - 1));
+            let mut i = upper;
+            while i > lower {
+                indexes.push(i as usize);
+                i += step;
+            }
+        }
+        Ordering::Equal => {}
+    }
+    indexes
+}
+
+impl Filter {
+    fn matches(&self, value: &Value) -> bool {
+        match self {
+            Filter::Or(filters) => filters.iter().any(|filter| filter.matches(value)),
+            Filter::And(filters) => filters.iter().all(|filter| filter.matches(value)),
+            Filter::Not(filter) => !filter.matches(value),
+            Filter::Exists(segments) => !find(value, segments).is_empty(),
+            Filter::Compare(left, op, right) => {
+                let (left, right) = (left.resolve(value), right.resolve(value));
+                compare(left.as_ref(), op, right.as_ref())
+            }
+        }
+    }
+}
+
+impl Operand {
+    fn resolve(&self, value: &Value) -> Option<Value> {
+        match self {
+            Operand::Literal(literal) => Some(literal.clone()),
+            Operand::Relative(segments) => {
+                let location = find(value, segments).into_iter().next()?;
+                get(value, &location).cloned()
+            }
+        }
+    }
+}
+
+// Comparisons between values of different types are only ever unequal
+fn compare(left: Option<&Value>, op: &str, right: Option<&Value>) -> bool {
+    let ordering = match (left, right) {
+        (Some(Value::Number(a)), Some(Value::Number(b))) => a.as_f64().partial_cmp(&b.as_f64()),
+        (Some(Value::String(a)), Some(Value::String(b))) => Some(a.cmp(b)),
+        (Some(a), Some(b)) if a == b => Some(Ordering::Equal),
+        (None, None) => Some(Ordering::Equal),
+        _ => None,
+    };
+    match op {
+        "==" => ordering == Some(Ordering::Equal),
+        "!=" => ordering != Some(Ordering::Equal),
+        "<" => ordering == Some(Ordering::Less),
+        "<=" => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+        ">" => ordering == Some(Ordering::Greater),
+        ">=" => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        _ => false,
+    }
+}
+
+// Filters nest through parentheses, `!` and filters on relative paths. Parsing
+// recurses for each level, so deeper paths are a syntax error rather than a
+// stack overflow.
+const MAX_FILTER_DEPTH: usize = 128;
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    // Filter nesting at the current position
+    depth: usize,
+}
+
+type Parsed<T> = Result<T, ()>;
+
+impl Parser {
+    // Runs `parse` one filter level deeper
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Parsed<T>) -> Parsed<T> {
+        if self.depth == MAX_FILTER_DEPTH {
+            return Err(());
+        }
+        self.depth += 1;
+        let parsed = parse(self);
+        self.depth -= 1;
+        parsed
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, text: &str) -> bool {
+        let matches = text.chars().enumerate().all(|(i, c)| self.chars.get(self.pos + i) == Some(&c));
+        if matches {
+            self.pos += text.chars().count();
+        }
+        matches
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    // Segments up to the end of the path, or in a filter up to whatever follows
+    fn segments(&mut self, in_filter: bool) -> Parsed<Vec<Segment>> {
+        let mut segments = Vec::new();
+        loop {
+            if self.eat("..") {
+                let selectors = match self.peek() {
+                    Some('[') => self.bracket()?,
+                    _ => vec![self.dot_selector(in_filter)?],
+                };
+                segments.push(Segment::Descendant(selectors));
+            } else if self.eat(".") {
+                segments.push(Segment::Child(vec![self.dot_selector(in_filter)?]));
+            } else if self.peek() == Some('[') {
+                segments.push(Segment::Child(self.bracket()?));
+            } else {
+                return Ok(segments);
+            }
+        }
+    }
+
+    fn dot_selector(&mut self, in_filter: bool) -> Parsed<Selector> {
+        if self.eat("*") {
+            return Ok(Selector::Wildcard);
+        }
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            let stop = c == '.' || c == '[' || (in_filter && !(c.is_alphanumeric() || c == '_' || c == '-'));
+            if stop {
+                break;
+            }
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return Err(());
+        }
+        Ok(Selector::Name(self.chars[start..self.pos].iter().collect()))
+    }
+
+    fn bracket(&mut self) -> Parsed<Vec<Selector>> {
+        self.pos += 1;
+        let mut selectors = Vec::new();
+        loop {
+            self.skip_spaces();
+            selectors.push(self.bracket_selector()?);
+            self.skip_spaces();
+            if self.eat("]") {
+                return Ok(selectors);
+            }
+            if !self.eat(",") {
+                return Err(());
+            }
+        }
+    }
+
+    fn bracket_selector(&mut self) -> Parsed<Selector> {
+        match self.peek() {
+            Some('*') => {
+                self.pos += 1;
+                Ok(Selector::Wildcard)
+            }
+            Some('\'' | '"') => Ok(Selector::Name(self.quoted()?)),
+            Some('?') => {
+                self.pos += 1;
+                self.skip_spaces();
+                let parenthesized = self.eat("(");
+                let filter = self.nested(Self::or_filter)?;
+                if parenthesized {
+                    self.skip_spaces();
+                    if !self.eat(")") {
+                        return Err(());
+                    }
+                }
+                Ok(Selector::Filter(Box::new(filter)))
+            }
+            _ => {
+                let start = self.integer()?;
+                if !self.eat(":") {
+                    return start.map(Selector::Index).ok_or(());
+                }
+                let end = self.integer()?;
+                let step = if self.eat(":") { self.integer()?.unwrap_or(1) } else { 1 };
+                Ok(Selector::Slice(start, end, step))
+            }
+        }
+    }
+
+    fn integer(&mut self) -> Parsed<Option<i64>> {
+        self.skip_spaces();
+        let start = self.pos;
+        if self.peek() == Some('-') {
+            self.pos += 1;
+        }
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        self.skip_spaces();
+        let text: String = self.chars[start..self.pos].iter().collect();
+        match text.trim() {
+            "" => Ok(None),
+            number => number.parse().map(Some).map_err(|_| ()),
+        }
+    }
+
+    fn quoted(&mut self) -> Parsed<String> {
+        let quote = self.peek().ok_or(())?;
+        self.pos += 1;
+        let mut text = String::new();
+        loop {
+            match self.peek().ok_or(())? {
+                c if c == quote => {
+                    self.pos += 1;
+                    return Ok(text);
+                }
+                '\\' => {
+                    self.pos += 1;
+                    text.push(self.peek().ok_or(())?);
+                }
+                c => text.push(c),
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn or_filter(&mut self) -> Parsed<Filter> {
+        let mut filters = vec![self.and_filter()?];
+        loop {
+            self.skip_spaces();
+            if !self.eat("||") {
+                break;
+            }
+            filters.push(self.and_filter()?);
+        }
+        Ok(if filters.len() == 1 { filters.remove(0) } else { Filter::Or(filters) })
+    }
+
+    fn and_filter(&mut self) -> Parsed<Filter> {
+        let mut filters = vec![self.basic_filter()?];
+        loop {
+            self.skip_spaces();
+            if !self.eat("&&") {
+                break;
+            }
+            filters.push(self.basic_filter()?);
+        }
+        Ok(if filters.len() == 1 { filters.remove(0) } else { Filter::And(filters) })
+    }
+
+    fn basic_filter(&mut self) -> Parsed<Filter> {
+        self.skip_spaces();
+        if self.eat("!") {
+            return Ok(Filter::Not(Box::new(self.nested(Self::basic_filter)?)));
+        }
+        if self.eat("(") {
+            let filter = self.nested(Self::or_filter)?;
+            self.skip_spaces();
+            return if self.eat(")") { Ok(filter) } else { Err(()) };
+        }
+
+        let left = self.operand()?;
+        self.skip_spaces();
+        let op = ["==", "!=", "<=", ">=", "<", ">"].into_iter().find(|op| self.eat(op));
+        match (op, left) {
+            (Some(op), left) => Ok(Filter::Compare(left, op, self.operand()?)),
+            (None, Operand::Relative(segments)) => Ok(Filter::Exists(segments)),
+            (None, Operand::Literal(_)) => Err(()),
+        }
+    }
+
+    fn operand(&mut self) -> Parsed<Operand> {
+        self.skip_spaces();
+        if self.eat("@") {
+            return Ok(Operand::Relative(self.segments(true)?));
+        }
+        if matches!(self.peek(), Some('\'' | '"')) {
+            return Ok(Operand::Literal(Value::String(self.quoted()?)));
+        }
+        for (word, value) in [("true", Value::Bool(true)), ("false", Value::Bool(false)), ("null", Value::Null)] {
+            if self.eat(word) {
+                return Ok(Operand::Literal(value));
+            }
+        }
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit() || "-+.eE".contains(c)) {
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        serde_json::from_str::<serde_json::Number>(&text)
+            .map(|number| Operand::Literal(Value::Number(number)))
+            .map_err(|_| ())
+    }
+}
+
+pub fn get<'a>(doc: &'a Value, location: &[Step]) -> Option<&'a Value> {
+    location.iter().try_fold(doc, |value, step| match (step, value) {
+        (Step::Key(key), Value::Object(map)) => map.get(key),
+        (Step::Index(i), Value::Array(items)) => items.get(*i),
+        _ => None,
+    })
+}
+
+pub fn get_mut<'a>(doc: &'a mut Value, location: &[Step]) -> Option<&'a mut Value> {
+    location.iter().try_fold(doc, |value, step| match (step, value) {
+        (Step::Key(key), Value::Object(map)) => map.get_mut(key),
+        (Step::Index(i), Value::Array(items)) => items.get_mut(*i),
+        _ => None,
+    })
+}
+
+// Removes the values at the locations, later array elements first so the
+// indexes of the others stay valid, and values inside removed ones not at all.
+// Returns how many were removed.
+pub fn remove(doc: &mut Value, mut locations: Vec<Location>) -> usize {
+    locations.sort();
+    locations.dedup();
+    let kept: Vec<Location> = locations
+        .iter()
+        .filter(|location| !locations.iter().any(|other| other.len() < location.len() && location.starts_with(other)))
+        .cloned()
+        .collect();
+
+    let mut removed = 0;
+    for location in kept.into_iter().rev() {
+        let Some((last, parent)) = location.split_last() else {
+            continue;
+        };
+        let removed_here = match (get_mut(doc, parent), last) {
+            (Some(Value::Object(map)), Step::Key(key)) => map.shift_remove(key).is_some(),
+            (Some(Value::Array(items)), Step::Index(i)) if *i < items.len() => {
+                items.remove(*i);
+                true
+            }
+            _ => false,
+        };
+        removed += removed_here as usize;
+    }
+    removed
+}
+
+// What JSON.GET, JSON.MGET and the HTTP API return for a path: every match as
+// a JSON array, or for a legacy path the first match
+pub fn matches(doc: &Value, path: &Path) -> Option<Value> {
+    let values = path.find(doc).into_iter().filter_map(|location| get(doc, &location).cloned());
+    if path.is_legacy() {
+        values.into_iter().next()
+    } else {
+        Some(Value::Array(values.collect()))
+    }
+}
+
+pub fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(number) if number.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+// JSON.GET's formatting: INDENT per nesting level, NEWLINE after each element
+// and SPACE after each colon, all empty by default
+pub struct Format<'a> {
+    pub indent: &'a str,
+    pub newline: &'a str,
+    pub space: &'a str,
+}
+
+impl Format<'_> {
+    pub fn write(&self, value: &Value) -> String {
+        let mut out = String::new();
+        self.write_value(&mut out, value, 0);
+        out
+    }
+
+    fn write_value(&self, out: &mut String, value: &Value, depth: usize) {
+        let (open, close, items): (char, char, Vec<(Option<&String>, &Value)>) = match value {
+            Value::Object(map) if !map.is_empty() => ('{', '}', map.iter().map(|(k, v)| (Some(k), v)).collect()),
+            Value::Array(items) if !items.is_empty() => ('[', ']', items.iter().map(|v| (None, v)).collect()),
+            _ => {
+                out.push_str(&value.to_string());
+                return;
+            }
+        };
+        out.push(open);
+        for (i, (key, item)) in items.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push_str(self.newline);
+            out.push_str(&self.indent.repeat(depth + 1));
+            if let Some(key) = key {
+                out.push_str(&Value::String(key.to_string()).to_string());
+                out.push(':');
+                out.push_str(self.space);
+            }
+            self.write_value(out, item, depth + 1);
+        }
+        out.push_str(self.newline);
+        out.push_str(&self.indent.repeat(depth));
+        out.push(close);
+    }
+}
@@ -1,4 +1,17 @@
+pub mod bitmap;
+pub mod bloom;
+pub mod countmin;
+pub mod cuckoo;
 pub mod db;
+pub mod expiry;
+pub mod geo;
 pub mod data_structure;
+pub mod hyperloglog;
+pub mod json;
+pub mod sketch;
+pub mod stats;
+pub mod stream;
+pub mod timeseries;
+pub mod topk;
 
 pub use db::Database;
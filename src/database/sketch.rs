@@ -0,0 +1,219 @@
+// Probabilistic value types (Bloom and Cuckoo filters, Count-Min sketches and
+// Top-K), kept in one keyspace map. They're dumped for replicas and MIGRATE the
+// way SCANDUMP hands them out: a header chunk describing the structure, then its
+// bytes in hex chunks that LOADCHUNK writes back at the same offsets.
+
+use crate::database::bloom::ScalableBloom;
+use crate::database::countmin::CountMinSketch;
+use crate::database::cuckoo::CuckooFilter;
+use crate::database::topk::TopK;
+
+pub const WRONG_TYPE: &str = "WRONGTYPE Operation against a key holding the wrong kind of value";
+
+pub const INVALID_CHUNK: &str = "invalid chunk";
+
+pub const FILTER_TOO_LARGE: &str = "filter is too large";
+
+// Bytes per SCANDUMP chunk after the header, a multiple of the 8 byte counters
+// and 12 byte Top-K buckets so chunks never split one
+const DUMP_CHUNK: usize = 3 << 18;
+
+// Sketches are capped like string values, so huge dimensions are an error
+// instead of an allocation that fails
+pub const MAX_SKETCH_BYTES: usize = 512 * 1024 * 1024;
+
+// Number of `item_bytes` sized elements in a `rows` by `columns` table, if it fits the cap
+pub fn table_len(rows: usize, columns: usize, item_bytes: usize) -> Option<usize> {
+    let len = rows.checked_mul(columns)?;
+    (len.checked_mul(item_bytes)? <= MAX_SKETCH_BYTES).then_some(len)
+}
+
+pub enum Sketch {
+    Bloom(ScalableBloom),
+    Cuckoo(CuckooFilter),
+    CountMin(CountMinSketch),
+    TopK(TopK),
+}
+
+impl Sketch {
+    // The command prefix of the type, which SCANDUMP and LOADCHUNK go by
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            Sketch::Bloom(_) => "BF",
+            Sketch::Cuckoo(_) => "CF",
+            Sketch::CountMin(_) => "CMS",
+            Sketch::TopK(_) => "TOPK",
+        }
+    }
+
+    // An empty sketch of the prefix's type, shaped by a SCANDUMP header
+    pub fn from_header(prefix: &str, header: &str) -> Option<Sketch> {
+        match prefix {
+            "BF" => ScalableBloom::from_header(header).map(Sketch::Bloom),
+            "CF" => CuckooFilter::from_header(header).map(Sketch::Cuckoo),
+            "CMS" => CountMinSketch::from_header(header).map(Sketch::CountMin),
+            "TOPK" => TopK::from_header(header).map(Sketch::TopK),
+            _ => None,
+        }
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        let names = match self {
+            Sketch::TopK(topk) => topk.list().iter().map(|(name, _)| name.len() + 32).sum(),
+            _ => 0,
+        };
+        self.bytes_len() + names + 64
+    }
+
+    fn header(&self) -> String {
+        match self {
+            Sketch::Bloom(bloom) => bloom.header(),
+            Sketch::Cuckoo(cuckoo) => cuckoo.header(),
+            Sketch::CountMin(cms) => cms.header(),
+            Sketch::TopK(topk) => topk.header(),
+        }
+    }
+
+    fn bytes_len(&self) -> usize {
+        match self {
+            Sketch::Bloom(bloom) => bloom.bytes_len(),
+            Sketch::Cuckoo(cuckoo) => cuckoo.bytes_len(),
+            Sketch::CountMin(cms) => cms.bytes_len(),
+            Sketch::TopK(topk) => topk.bytes_len(),
+        }
+    }
+
+    fn read_bytes(&self, offset: usize, len: usize) -> Vec<u8> {
+        match self {
+            Sketch::Bloom(bloom) => bloom.read_bytes(offset, len),
+            Sketch::Cuckoo(cuckoo) => cuckoo.read_bytes(offset, len),
+            Sketch::CountMin(cms) => cms.read_bytes(offset, len),
+            Sketch::TopK(topk) => topk.read_bytes(offset, len),
+        }
+    }
+
+    fn write_bytes(&mut self, offset: usize, bytes: &[u8]) -> Result<(), String> {
+        match self {
+            Sketch::Bloom(bloom) => bloom.write_bytes(offset, bytes),
+            Sketch::Cuckoo(cuckoo) => cuckoo.write_bytes(offset, bytes),
+            Sketch::CountMin(cms) => cms.write_bytes(offset, bytes),
+            Sketch::TopK(topk) => topk.write_bytes(offset, bytes),
+        }
+    }
+
+    // SCANDUMP: iterator 0 starts with the header, every later chunk returns the
+    // next iterator, which is 0 with empty data once there is nothing left
+    pub fn scandump(&self, iter: usize) -> (usize, String) {
+        if iter == 0 {
+            return (1, self.header());
+        }
+        let offset = (iter - 1) * DUMP_CHUNK;
+        if offset >= self.bytes_len() {
+            return (0, String::new());
+        }
+        (iter + 1, to_hex(&self.read_bytes(offset, DUMP_CHUNK)))
+    }
+
+    // LOADCHUNK for a chunk after the header. It comes with the iterator
+    // SCANDUMP returned along with it, one past the one that asked for it.
+    pub fn load_chunk(&mut self, iter: usize, data: &str) -> Result<(), String> {
+        let bytes = from_hex(data).ok_or(INVALID_CHUNK)?;
+        self.write_bytes((iter - 2) * DUMP_CHUNK, &bytes)
+    }
+
+    // The commands that rebuild the value
+    pub fn dump(&self, key: &str) -> Vec<Vec<String>> {
+        let command = format!("{}.LOADCHUNK", self.prefix());
+        let mut commands = Vec::new();
+        let mut iter = 0;
+        loop {
+            let (next, data) = self.scandump(iter);
+            if next == 0 {
+                return commands;
+            }
+            commands.push(vec![command.clone(), key.to_string(), next.to_string(), data]);
+            iter = next;
+        }
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+// MurmurHash64A, so that items hash the same on every node
+pub fn murmur64(data: &[u8], seed: u64) -> u64 {
+    const M: u64 = 0xc6a4a7935bd1e995;
+    const R: u32 = 47;
+    let mut h = seed ^ (data.len() as u64).wrapping_mul(M);
+    let chunks = data.chunks_exact(8);
+    let tail = chunks.remainder();
+    for chunk in chunks {
+        let mut k = u64::from_le_bytes(chunk.try_into().unwrap());
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+        h ^= k;
+        h = h.wrapping_mul(M);
+    }
+    if !tail.is_empty() {
+        for (i, byte) in tail.iter().enumerate() {
+            h ^= (*byte as u64) << (8 * i);
+        }
+        h = h.wrapping_mul(M);
+    }
+    h ^= h >> R;
+    h = h.wrapping_mul(M);
+    h ^= h >> R;
+    h
+}
+
+// Copies `len` bytes from `offset` of the concatenation of `parts`
+pub fn read_parts(parts: &[&[u8]], mut offset: usize, mut len: usize) -> Vec<u8> {
+    let mut out = Vec::new();
+    for part in parts {
+        if len == 0 {
+            break;
+        }
+        if offset >= part.len() {
+            offset -= part.len();
+            continue;
+        }
+        let end = part.len().min(offset + len);
+        out.extend_from_slice(&part[offset..end]);
+        len -= end - offset;
+        offset = 0;
+    }
+    out
+}
+
+// Writes `bytes` at `offset` of the concatenation of `parts`
+pub fn write_parts(parts: &mut [&mut [u8]], mut offset: usize, mut bytes: &[u8]) -> Result<(), String> {
+    for part in parts.iter_mut() {
+        if bytes.is_empty() {
+            break;
+        }
+        if offset >= part.len() {
+            offset -= part.len();
+            continue;
+        }
+        let len = bytes.len().min(part.len() - offset);
+        part[offset..offset + len].copy_from_slice(&bytes[..len]);
+        bytes = &bytes[len..];
+        offset = 0;
+    }
+    match bytes.is_empty() {
+        true => Ok(()),
+        false => Err(INVALID_CHUNK.to_string()),
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// Server-wide counters reported by INFO
+pub struct Stats {
+    pub start_time: Instant,
+    pub start_unix_time: u64,
+    pub connected_clients: AtomicU64,
+    pub total_connections_received: AtomicU64,
+    pub total_commands_processed: AtomicU64,
+    pub instantaneous_ops_per_sec: AtomicU64,
+    pub keyspace_hits: AtomicU64,
+    pub keyspace_misses: AtomicU64,
+    pub expired_keys: AtomicU64,
+    pub evicted_keys: AtomicU64,
+    pub rejected_connections: AtomicU64,
+    pub client_query_buffer_limit_disconnections: AtomicU64,
+    pub client_output_buffer_limit_disconnections: AtomicU64,
+    // Writes since startup, reported as rdb_changes_since_last_save
+    pub dirty: AtomicU64,
+    pub commands: Mutex<HashMap<String, CommandStats>>,
+}
+
+// Upper bounds (in seconds) of the command latency histogram buckets
+pub const LATENCY_BUCKETS: [f64; 11] = [
+    0.00001, 0.00005, 0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0,
+];
+
+#[derive(Clone, Default)]
+pub struct CommandStats {
+    pub calls: u64,
+    pub usec: u64,
+    // Non-cumulative counts per LATENCY_BUCKETS bound; slower calls only show in `calls`
+    pub buckets: [u64; LATENCY_BUCKETS.len()],
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Stats {
+            start_time: Instant::now(),
+            start_unix_time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            connected_clients: AtomicU64::new(0),
+            total_connections_received: AtomicU64::new(0),
+            total_commands_processed: AtomicU64::new(0),
+            instantaneous_ops_per_sec: AtomicU64::new(0),
+            keyspace_hits: AtomicU64::new(0),
+            keyspace_misses: AtomicU64::new(0),
+            expired_keys: AtomicU64::new(0),
+            evicted_keys: AtomicU64::new(0),
+            rejected_connections: AtomicU64::new(0),
+            client_query_buffer_limit_disconnections: AtomicU64::new(0),
+            client_output_buffer_limit_disconnections: AtomicU64::new(0),
+            dirty: AtomicU64::new(0),
+            commands: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn incr(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn get(counter: &AtomicU64) -> u64 {
+        counter.load(Ordering::Relaxed)
+    }
+
+    pub fn record_lookup(&self, found: bool) {
+        if found {
+            Stats::incr(&self.keyspace_hits);
+        } else {
+            Stats::incr(&self.keyspace_misses);
+        }
+    }
+
+    pub fn record_command(&self, name: &str, duration: Duration) {
+        let mut commands = self.commands.lock().unwrap();
+        let entry = commands.entry(name.to_lowercase()).or_default();
+        entry.calls += 1;
+        entry.usec += duration.as_micros() as u64;
+
+        let secs = duration.as_secs_f64();
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|bound| secs <= *bound) {
+            entry.buckets[bucket] += 1;
+        }
+    }
+
+    pub fn command_stats(&self) -> Vec<(String, CommandStats)> {
+        let commands = self.commands.lock().unwrap();
+        let mut stats: Vec<_> = commands
+            .iter()
+            .map(|(name, stats)| (name.clone(), stats.clone()))
+            .collect();
+        stats.sort_by(|a, b| a.0.cmp(&b.0));
+        stats
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.start_time.elapsed()
+    }
+
+    // Samples the command counter once per second for instantaneous_ops_per_sec
+    pub async fn track_ops_per_sec(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        let mut last = Stats::get(&self.total_commands_processed);
+
+        loop {
+            interval.tick().await;
+            let current = Stats::get(&self.total_commands_processed);
+            self.instantaneous_ops_per_sec
+                .store(current - last, Ordering::Relaxed);
+            last = current;
+        }
+    }
+}
@@ -0,0 +1,492 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Bound;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const INVALID_ID: &str = "Invalid stream ID specified as stream command argument";
+
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+// Entry IDs are `<milliseconds>-<sequence>` and ordered numerically
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+pub struct StreamId {
+    pub ms: u64,
+    pub seq: u64,
+}
+
+impl StreamId {
+    pub const MIN: StreamId = StreamId { ms: 0, seq: 0 };
+    pub const MAX: StreamId = StreamId {
+        ms: u64::MAX,
+        seq: u64::MAX,
+    };
+
+    pub fn new(ms: u64, seq: u64) -> Self {
+        StreamId { ms, seq }
+    }
+
+    // `ms-seq`, or just `ms` with the sequence taken from `default_seq`
+    pub fn parse(s: &str, default_seq: u64) -> Result<StreamId, String> {
+        let invalid = || INVALID_ID.to_string();
+        match s.split_once('-') {
+            Some((ms, seq)) => Ok(StreamId::new(
+                ms.parse().map_err(|_| invalid())?,
+                seq.parse().map_err(|_| invalid())?,
+            )),
+            None => Ok(StreamId::new(s.parse().map_err(|_| invalid())?, default_seq)),
+        }
+    }
+
+    pub fn next(self) -> Option<StreamId> {
+        match self.seq.checked_add(1) {
+            Some(seq) => Some(StreamId::new(self.ms, seq)),
+            None => self.ms.checked_add(1).map(|ms| StreamId::new(ms, 0)),
+        }
+    }
+
+    pub fn prev(self) -> Option<StreamId> {
+        match self.seq.checked_sub(1) {
+            Some(seq) => Some(StreamId::new(self.ms, seq)),
+            None => self.ms.checked_sub(1).map(|ms| StreamId::new(ms, u64::MAX)),
+        }
+    }
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+// The ID argument of XADD
+pub enum IdSpec {
+    // `*`
+    Auto,
+    // `ms-*`
+    AutoSeq(u64),
+    Explicit(StreamId),
+}
+
+impl IdSpec {
+    pub fn parse(s: &str) -> Result<IdSpec, String> {
+        if s == "*" {
+            return Ok(IdSpec::Auto);
+        }
+        if let Some(ms) = s.strip_suffix("-*") {
+            return ms.parse().map(IdSpec::AutoSeq).map_err(|_| INVALID_ID.to_string());
+        }
+        StreamId::parse(s, 0).map(IdSpec::Explicit)
+    }
+}
+
+// MAXLEN / MINID of XADD and XTRIM
+#[derive(Clone, Copy)]
+pub enum Trim {
+    MaxLen(usize),
+    MinId(StreamId),
+}
+
+pub type Fields = Vec<(String, String)>;
+
+pub struct RStream {
+    pub entries: BTreeMap<StreamId, Fields>,
+    // Highest ID ever added, new IDs must be above it even after deletions
+    pub last_id: StreamId,
+    pub max_deleted_id: StreamId,
+    pub entries_added: u64,
+    pub groups: BTreeMap<String, ConsumerGroup>,
+    // Estimated size of the entries, kept as they come and go
+    entries_memory: usize,
+}
+
+pub struct ConsumerGroup {
+    pub last_id: StreamId,
+    // Entries the group has read, when it can be known; used for the lag
+    pub entries_read: Option<u64>,
+    // Pending entries list: delivered but not yet acknowledged
+    pub pending: BTreeMap<StreamId, PendingEntry>,
+    pub consumers: BTreeMap<String, Consumer>,
+}
+
+pub struct PendingEntry {
+    pub consumer: String,
+    // Unix time in ms of the last delivery
+    pub delivered: u64,
+    pub deliveries: u64,
+}
+
+pub struct Consumer {
+    // Unix times in ms of the last interaction and the last successful read
+    pub seen: u64,
+    pub active: Option<u64>,
+}
+
+// Options of XCLAIM
+pub struct ClaimOptions {
+    // Delivery time to record, from IDLE or TIME
+    pub delivered: Option<u64>,
+    pub retry_count: Option<u64>,
+    pub force: bool,
+    pub just_id: bool,
+    pub last_id: Option<StreamId>,
+}
+
+impl RStream {
+    pub fn new() -> Self {
+        RStream {
+            entries: BTreeMap::new(),
+            last_id: StreamId::MIN,
+            max_deleted_id: StreamId::MIN,
+            entries_added: 0,
+            groups: BTreeMap::new(),
+            entries_memory: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    // Picks the ID for XADD; it has to be above every ID the stream has seen
+    pub fn next_id(&self, spec: &IdSpec, now_ms: u64) -> Result<StreamId, String> {
+        let too_small = || "The ID specified in XADD is equal or smaller than the target stream top item".to_string();
+        let id = match *spec {
+            // A clock that went backwards keeps using the last millisecond
+            IdSpec::Auto if now_ms > self.last_id.ms => StreamId::new(now_ms, 0),
+            IdSpec::Auto => self.last_id.next().ok_or_else(too_small)?,
+            IdSpec::AutoSeq(ms) if ms > self.last_id.ms => StreamId::new(ms, 0),
+            IdSpec::AutoSeq(ms) if ms == self.last_id.ms => self.last_id.next().ok_or_else(too_small)?,
+            IdSpec::AutoSeq(_) => return Err(too_small()),
+            IdSpec::Explicit(id) => id,
+        };
+        if id == StreamId::MIN {
+            return Err("The ID specified in XADD must be greater than 0-0".to_string());
+        }
+        if id <= self.last_id {
+            return Err(too_small());
+        }
+        Ok(id)
+    }
+
+    // Rough estimate of the memory held: each entry is an ID plus its field/value
+    // pairs, each pending entry a fixed record
+    pub fn memory_usage(&self) -> usize {
+        let pending: usize = self.groups.values().map(|group| group.pending.len() * 64).sum();
+        self.entries_memory + pending
+    }
+
+    pub fn add(&mut self, id: StreamId, fields: Fields) {
+        self.entries_memory += entry_size(&fields);
+        self.entries.insert(id, fields);
+        self.last_id = id;
+        self.entries_added += 1;
+    }
+
+    // Removes the oldest entries, at most `limit` of them; returns how many went
+    pub fn trim(&mut self, trim: Trim, limit: Option<usize>) -> usize {
+        let mut removed = 0;
+        while limit.is_none_or(|limit| removed < limit) {
+            let first = match self.entries.keys().next() {
+                Some(first) => *first,
+                None => break,
+            };
+            let over = match trim {
+                Trim::MaxLen(max) => self.entries.len() > max,
+                Trim::MinId(min) => first < min,
+            };
+            if !over {
+                break;
+            }
+            if let Some(fields) = self.entries.remove(&first) {
+                self.entries_memory -= entry_size(&fields);
+            }
+            self.max_deleted_id = self.max_deleted_id.max(first);
+            removed += 1;
+        }
+        removed
+    }
+
+    pub fn range(&self, start: StreamId, end: StreamId, count: Option<usize>, rev: bool) -> Vec<(StreamId, Fields)> {
+        if start > end {
+            return Vec::new();
+        }
+        let entries = self
+            .entries
+            .range(start..=end)
+            .map(|(id, fields)| (*id, fields.clone()));
+        let count = count.unwrap_or(usize::MAX);
+        if rev {
+            entries.rev().take(count).collect()
+        } else {
+            entries.take(count).collect()
+        }
+    }
+
+    pub fn delete(&mut self, ids: &[StreamId]) -> usize {
+        let mut deleted = 0;
+        for id in ids {
+            if let Some(fields) = self.entries.remove(id) {
+                self.entries_memory -= entry_size(&fields);
+                self.max_deleted_id = self.max_deleted_id.max(*id);
+                deleted += 1;
+            }
+        }
+        deleted
+    }
+
+    pub fn first_id(&self) -> Option<StreamId> {
+        self.entries.keys().next().copied()
+    }
+
+    // How many entries a group starting after `id` has read, when that's known
+    pub fn entries_read_at(&self, id: StreamId) -> Option<u64> {
+        if id >= self.last_id {
+            Some(self.entries_added)
+        } else if id == StreamId::MIN && self.max_deleted_id == StreamId::MIN {
+            Some(0)
+        } else {
+            None
+        }
+    }
+
+    pub fn create_group(&mut self, name: &str, last_id: StreamId, entries_read: Option<u64>) -> bool {
+        if self.groups.contains_key(name) {
+            return false;
+        }
+        let group = ConsumerGroup {
+            last_id,
+            entries_read,
+            pending: BTreeMap::new(),
+            consumers: BTreeMap::new(),
+        };
+        self.groups.insert(name.to_string(), group);
+        true
+    }
+
+    // XREADGROUP with `>`: the next entries after the group's last delivered ID
+    pub fn read_new(
+        &mut self,
+        group: &str,
+        consumer: &str,
+        count: Option<usize>,
+        noack: bool,
+        now_ms: u64,
+    ) -> Vec<(StreamId, Fields)> {
+        let group = match self.groups.get_mut(group) {
+            Some(group) => group,
+            None => return Vec::new(),
+        };
+        let entries: Vec<(StreamId, Fields)> = self
+            .entries
+            .range((Bound::Excluded(group.last_id), Bound::Unbounded))
+            .take(count.unwrap_or(usize::MAX))
+            .map(|(id, fields)| (*id, fields.clone()))
+            .collect();
+
+        group.touch_consumer(consumer, now_ms, !entries.is_empty());
+        if let Some((last, _)) = entries.last() {
+            group.last_id = *last;
+            group.entries_read = group.entries_read.map(|read| read + entries.len() as u64);
+        }
+        // Caught up with the stream, so every entry ever added has been read
+        if group.last_id >= self.last_id {
+            group.entries_read = Some(self.entries_added);
+        }
+        if !noack {
+            for (id, _) in &entries {
+                group.pending.insert(
+                    *id,
+                    PendingEntry {
+                        consumer: consumer.to_string(),
+                        delivered: now_ms,
+                        deliveries: 1,
+                    },
+                );
+            }
+        }
+        entries
+    }
+
+    // XREADGROUP with an ID: the consumer's own pending entries after it, delivered
+    // again. Entries deleted since have no fields.
+    pub fn read_pending(
+        &mut self,
+        group: &str,
+        consumer: &str,
+        after: StreamId,
+        count: Option<usize>,
+        now_ms: u64,
+    ) -> Vec<(StreamId, Option<Fields>)> {
+        let group = match self.groups.get_mut(group) {
+            Some(group) => group,
+            None => return Vec::new(),
+        };
+        group.touch_consumer(consumer, now_ms, false);
+        let mut entries = Vec::new();
+        for (id, pending) in group.pending.range_mut((Bound::Excluded(after), Bound::Unbounded)) {
+            if entries.len() >= count.unwrap_or(usize::MAX) {
+                break;
+            }
+            if pending.consumer != consumer {
+                continue;
+            }
+            pending.delivered = now_ms;
+            pending.deliveries += 1;
+            entries.push((*id, self.entries.get(id).cloned()));
+        }
+        entries
+    }
+
+    // XCLAIM: gives pending entries idle for at least `min_idle` ms to `consumer`.
+    // Returns the claimed entries and the pending IDs dropped because their entry
+    // was deleted.
+    pub fn claim(
+        &mut self,
+        group: &str,
+        consumer: &str,
+        min_idle: u64,
+        ids: &[StreamId],
+        options: &ClaimOptions,
+        now_ms: u64,
+    ) -> (Vec<(StreamId, Fields)>, Vec<StreamId>) {
+        let group = match self.groups.get_mut(group) {
+            Some(group) => group,
+            None => return (Vec::new(), Vec::new()),
+        };
+        if let Some(last_id) = options.last_id {
+            group.last_id = group.last_id.max(last_id);
+        }
+        group.touch_consumer(consumer, now_ms, false);
+
+        let mut claimed = Vec::new();
+        let mut deleted = Vec::new();
+        for id in ids {
+            let fields = match self.entries.get(id) {
+                Some(fields) => fields.clone(),
+                None => {
+                    if group.pending.remove(id).is_some() {
+                        deleted.push(*id);
+                    }
+                    continue;
+                }
+            };
+            let pending = match group.pending.get_mut(id) {
+                Some(pending) => pending,
+                None if options.force => group.pending.entry(*id).or_insert(PendingEntry {
+                    consumer: consumer.to_string(),
+                    delivered: now_ms,
+                    deliveries: 0,
+                }),
+                None => continue,
+            };
+            if now_ms.saturating_sub(pending.delivered) < min_idle {
+                continue;
+            }
+
+            pending.consumer = consumer.to_string();
+            pending.delivered = options.delivered.unwrap_or(now_ms);
+            if let Some(retry_count) = options.retry_count {
+                pending.deliveries = retry_count;
+            } else if !options.just_id {
+                pending.deliveries += 1;
+            }
+            claimed.push((*id, fields));
+        }
+        if !claimed.is_empty() {
+            group.touch_consumer(consumer, now_ms, true);
+        }
+        (claimed, deleted)
+    }
+
+    // XAUTOCLAIM: claims up to `count` idle pending entries from `start` on.
+    // Returns the cursor to continue from, the claimed entries and the IDs that
+    // were pending but deleted from the stream.
+    pub fn auto_claim(
+        &mut self,
+        group: &str,
+        consumer: &str,
+        min_idle: u64,
+        start: StreamId,
+        count: usize,
+        just_id: bool,
+    ) -> (StreamId, Vec<(StreamId, Fields)>, Vec<StreamId>) {
+        let now_ms = now_ms();
+        let group = match self.groups.get_mut(group) {
+            Some(group) => group,
+            None => return (StreamId::MIN, Vec::new(), Vec::new()),
+        };
+        group.touch_consumer(consumer, now_ms, false);
+
+        let mut claimed = Vec::new();
+        let mut deleted = Vec::new();
+        let mut cursor = StreamId::MIN;
+        // Like Redis, look at no more than 10 times `count` entries per call
+        let mut attempts = count * 10;
+        let ids: Vec<StreamId> = group.pending.range(start..).map(|(id, _)| *id).collect();
+
+        for id in ids {
+            if claimed.len() + deleted.len() >= count || attempts == 0 {
+                cursor = id;
+                break;
+            }
+            attempts -= 1;
+
+            let fields = match self.entries.get(&id) {
+                Some(fields) => fields.clone(),
+                None => {
+                    group.pending.remove(&id);
+                    deleted.push(id);
+                    continue;
+                }
+            };
+            let pending = group.pending.get_mut(&id).expect("ID came from the pending list");
+            if now_ms.saturating_sub(pending.delivered) < min_idle {
+                continue;
+            }
+            pending.consumer = consumer.to_string();
+            pending.delivered = now_ms;
+            if !just_id {
+                pending.deliveries += 1;
+            }
+            claimed.push((id, fields));
+        }
+        if !claimed.is_empty() {
+            group.touch_consumer(consumer, now_ms, true);
+        }
+        (cursor, claimed, deleted)
+    }
+}
+
+impl ConsumerGroup {
+    // Creates the consumer on first use and records the interaction
+    pub fn touch_consumer(&mut self, name: &str, now_ms: u64, active: bool) {
+        let consumer = self.consumers.entry(name.to_string()).or_insert(Consumer {
+            seen: now_ms,
+            active: None,
+        });
+        consumer.seen = now_ms;
+        if active {
+            consumer.active = Some(now_ms);
+        }
+    }
+
+    pub fn pending_for(&self, consumer: &str) -> usize {
+        self.pending
+            .values()
+            .filter(|pending| pending.consumer == consumer)
+            .count()
+    }
+
+    pub fn ack(&mut self, ids: &[StreamId]) -> usize {
+        ids.iter().filter(|id| self.pending.remove(id).is_some()).count()
+    }
+}
+
+fn entry_size(fields: &Fields) -> usize {
+    56 + fields.iter().map(|(field, value)| field.len() + value.len() + 48).sum::<usize>()
+}
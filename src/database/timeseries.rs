@@ -0,0 +1,391 @@
+// Time series: samples in compressed chunks, labels for TS.MRANGE filters and
+// compaction rules that aggregate each finished bucket into another series.
+// Chunks use the Gorilla encoding: timestamps as delta-of-deltas, values XOR'd
+// with the previous one, so regular samples take a few bits each.
+
+use std::collections::HashMap;
+
+pub const DEFAULT_CHUNK_SIZE: usize = 4096;
+pub const NO_KEY: &str = "TSDB: the key does not exist";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Aggregation {
+    Avg,
+    Sum,
+    Min,
+    Max,
+    Count,
+    First,
+    Last,
+    Range,
+}
+
+impl Aggregation {
+    pub fn parse(name: &str) -> Option<Aggregation> {
+        match name.to_lowercase().as_str() {
+            "avg" => Some(Aggregation::Avg),
+            "sum" => Some(Aggregation::Sum),
+            "min" => Some(Aggregation::Min),
+            "max" => Some(Aggregation::Max),
+            "count" => Some(Aggregation::Count),
+            "first" => Some(Aggregation::First),
+            "last" => Some(Aggregation::Last),
+            "range" => Some(Aggregation::Range),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aggregation::Avg => "avg",
+            Aggregation::Sum => "sum",
+            Aggregation::Min => "min",
+            Aggregation::Max => "max",
+            Aggregation::Count => "count",
+            Aggregation::First => "first",
+            Aggregation::Last => "last",
+            Aggregation::Range => "range",
+        }
+    }
+
+    // The values of one bucket, never empty, in timestamp order
+    fn apply(&self, values: &[f64]) -> f64 {
+        let min = || values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = || values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        match self {
+            Aggregation::Avg => values.iter().sum::<f64>() / values.len() as f64,
+            Aggregation::Sum => values.iter().sum(),
+            Aggregation::Min => min(),
+            Aggregation::Max => max(),
+            Aggregation::Count => values.len() as f64,
+            Aggregation::First => values[0],
+            Aggregation::Last => values[values.len() - 1],
+            Aggregation::Range => max() - min(),
+        }
+    }
+}
+
+// Values as replies show them: shortest round-tripping form, in exponent
+// notation when very large or small
+pub fn format_value(value: f64) -> String {
+    let magnitude = value.abs();
+    if magnitude != 0.0 && !(1e-5..1e17).contains(&magnitude) {
+        format!("{:e}", value)
+    } else {
+        value.to_string()
+    }
+}
+
+// Buckets start at multiples of their duration
+pub fn bucket_start(timestamp: i64, bucket: i64) -> i64 {
+    timestamp - timestamp.rem_euclid(bucket)
+}
+
+// One sample per non-empty bucket, at the bucket's start
+pub fn aggregate(samples: &[(i64, f64)], aggregation: Aggregation, bucket: i64) -> Vec<(i64, f64)> {
+    let mut out = Vec::new();
+    let mut values = Vec::new();
+    let mut current = None;
+    for (timestamp, value) in samples {
+        let start = bucket_start(*timestamp, bucket);
+        if current.is_some_and(|current| current != start) {
+            out.push((current.unwrap(), aggregation.apply(&values)));
+            values.clear();
+        }
+        current = Some(start);
+        values.push(*value);
+    }
+    if let Some(current) = current {
+        out.push((current, aggregation.apply(&values)));
+    }
+    out
+}
+
+#[derive(Clone)]
+pub struct Rule {
+    pub dest: String,
+    pub aggregation: Aggregation,
+    pub bucket: i64,
+}
+
+pub struct TimeSeries {
+    // Milliseconds of history kept behind the latest sample, 0 for all of it
+    pub retention: u64,
+    pub chunk_size: usize,
+    pub labels: Vec<(String, String)>,
+    pub rules: Vec<Rule>,
+    // The series this one is a compaction of
+    pub source: Option<String>,
+    chunks: Vec<Chunk>,
+}
+
+impl TimeSeries {
+    pub fn new(retention: u64, chunk_size: usize, labels: Vec<(String, String)>) -> Self {
+        TimeSeries { retention, chunk_size, labels, rules: Vec::new(), source: None, chunks: Vec::new() }
+    }
+
+    pub fn last(&self) -> Option<(i64, f64)> {
+        self.chunks.last().map(|chunk| (chunk.last_ts, chunk.last_value))
+    }
+
+    pub fn first_timestamp(&self) -> Option<i64> {
+        self.samples(i64::MIN, i64::MAX).first().map(|(timestamp, _)| *timestamp)
+    }
+
+    pub fn label(&self, name: &str) -> Option<&str> {
+        self.labels.iter().find(|(label, _)| label == name).map(|(_, value)| value.as_str())
+    }
+
+    // Samples are appended in timestamp order; there is no way to change one
+    pub fn add(&mut self, timestamp: i64, value: f64) -> Result<(), String> {
+        match self.last() {
+            Some((last, _)) if timestamp == last => {
+                return Err(
+                    "TSDB: Error at upsert, update is not supported when DUPLICATE_POLICY is set to BLOCK mode"
+                        .to_string(),
+                )
+            }
+            Some((last, _)) if timestamp < last => {
+                return Err("TSDB: timestamp must be equal to or higher than the maximum existing timestamp".to_string())
+            }
+            _ => {}
+        }
+        if self.chunks.last().is_none_or(|chunk| chunk.data.len() >= self.chunk_size) {
+            self.chunks.push(Chunk::new());
+        }
+        self.chunks.last_mut().unwrap().push(timestamp, value);
+
+        // Chunks that are entirely past the retention window are dropped
+        let cutoff = self.cutoff();
+        let expired = self.chunks.iter().take_while(|chunk| chunk.last_ts < cutoff).count();
+        self.chunks.drain(..expired);
+        Ok(())
+    }
+
+    // The oldest timestamp still kept
+    fn cutoff(&self) -> i64 {
+        match (self.retention, self.last()) {
+            (0, _) | (_, None) => i64::MIN,
+            (retention, Some((last, _))) => last.saturating_sub(retention as i64),
+        }
+    }
+
+    // Samples with from <= timestamp <= to, oldest first
+    pub fn samples(&self, from: i64, to: i64) -> Vec<(i64, f64)> {
+        let from = from.max(self.cutoff());
+        self.chunks
+            .iter()
+            .filter(|chunk| chunk.last_ts >= from && chunk.first_ts <= to)
+            .flat_map(Chunk::decode)
+            .filter(|(timestamp, _)| (from..=to).contains(timestamp))
+            .collect()
+    }
+
+    pub fn total_samples(&self) -> usize {
+        self.samples(i64::MIN, i64::MAX).len()
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        self.chunks.iter().map(|chunk| chunk.data.capacity() + 64).sum::<usize>()
+            + self.labels.iter().map(|(name, value)| name.len() + value.len() + 48).sum::<usize>()
+    }
+}
+
+// TS.ADD and TS.MADD: adds the sample and, for each rule whose bucket the
+// sample closes, the aggregate of that bucket to the rule's destination
+pub fn add_sample(map: &mut HashMap<String, TimeSeries>, key: &str, timestamp: i64, value: f64) -> Result<(), String> {
+    let series = map.get_mut(key).ok_or(NO_KEY)?;
+    let previous = series.last();
+    series.add(timestamp, value)?;
+
+    let Some((previous, _)) = previous else {
+        return Ok(());
+    };
+    for rule in series.rules.clone() {
+        let closed = bucket_start(previous, rule.bucket);
+        if bucket_start(timestamp, rule.bucket) == closed {
+            continue;
+        }
+        let values: Vec<f64> = map[key]
+            .samples(closed, closed.saturating_add(rule.bucket - 1))
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect();
+        if !values.is_empty() && map.contains_key(&rule.dest) {
+            // A destination written to directly may already be past the bucket
+            let _ = add_sample(map, &rule.dest, closed, rule.aggregation.apply(&values));
+        }
+    }
+    Ok(())
+}
+
+// Drops the rules into and out of a deleted series
+pub fn unlink(map: &mut HashMap<String, TimeSeries>, removed: &TimeSeries, key: &str) {
+    if let Some(source) = removed.source.as_ref().and_then(|source| map.get_mut(source)) {
+        source.rules.retain(|rule| rule.dest != key);
+    }
+    for rule in &removed.rules {
+        if let Some(dest) = map.get_mut(&rule.dest) {
+            dest.source = None;
+        }
+    }
+}
+
+// Gorilla-compressed samples. The first sample is stored in full; after that a
+// timestamp takes 1 bit when its delta is the same as the previous one, and a
+// value 1 bit when it repeats.
+struct Chunk {
+    data: Vec<u8>,
+    bits: usize,
+    count: usize,
+    first_ts: i64,
+    last_ts: i64,
+    last_value: f64,
+    last_delta: i64,
+    // Leading and trailing zeros of the last XOR written in full
+    window: Option<(u32, u32)>,
+}
+
+// Delta-of-delta ranges: the prefix bits, how many bits follow, and the offset
+// that makes the value unsigned
+const DOD_CLASSES: [(u64, u32, u32, i64); 3] = [(0b10, 2, 7, 63), (0b110, 3, 9, 255), (0b1110, 4, 12, 2047)];
+
+impl Chunk {
+    fn new() -> Self {
+        Chunk {
+            data: Vec::new(),
+            bits: 0,
+            count: 0,
+            first_ts: 0,
+            last_ts: 0,
+            last_value: 0.0,
+            last_delta: 0,
+            window: None,
+        }
+    }
+
+    fn write(&mut self, value: u64, width: u32) {
+        for i in (0..width).rev() {
+            if self.bits.is_multiple_of(8) {
+                self.data.push(0);
+            }
+            if (value >> i) & 1 == 1 {
+                let last = self.data.len() - 1;
+                self.data[last] |= 0x80 >> (self.bits % 8);
+            }
+            self.bits += 1;
+        }
+    }
+
+    fn push(&mut self, timestamp: i64, value: f64) {
+        if self.count == 0 {
+            self.write(timestamp as u64, 64);
+            self.write(value.to_bits(), 64);
+            self.first_ts = timestamp;
+        } else {
+            let delta = timestamp - self.last_ts;
+            let dod = delta - self.last_delta;
+            match DOD_CLASSES.iter().find(|(_, _, width, offset)| dod >= -offset && dod <= (1 << width) - 1 - offset) {
+                _ if dod == 0 => self.write(0, 1),
+                Some((prefix, prefix_width, width, offset)) => {
+                    self.write(*prefix, *prefix_width);
+                    self.write((dod + offset) as u64, *width);
+                }
+                None => {
+                    self.write(0b1111, 4);
+                    self.write(dod as u64, 64);
+                }
+            }
+            self.last_delta = delta;
+
+            let xor = value.to_bits() ^ self.last_value.to_bits();
+            if xor == 0 {
+                self.write(0, 1);
+            } else {
+                let (leading, trailing) = (xor.leading_zeros(), xor.trailing_zeros());
+                match self.window {
+                    Some((window_leading, window_trailing))
+                        if leading >= window_leading && trailing >= window_trailing =>
+                    {
+                        self.write(0b10, 2);
+                        self.write(xor >> window_trailing, 64 - window_leading - window_trailing);
+                    }
+                    _ => {
+                        let meaningful = 64 - leading - trailing;
+                        self.write(0b11, 2);
+                        self.write(leading as u64, 6);
+                        self.write((meaningful - 1) as u64, 6);
+                        self.write(xor >> trailing, meaningful);
+                        self.window = Some((leading, trailing));
+                    }
+                }
+            }
+        }
+        self.last_ts = timestamp;
+        self.last_value = value;
+        self.count += 1;
+    }
+
+    fn decode(&self) -> Vec<(i64, f64)> {
+        let mut reader = Reader { data: &self.data, pos: 0 };
+        let mut samples = Vec::with_capacity(self.count);
+        if self.count == 0 {
+            return samples;
+        }
+        let mut timestamp = reader.read(64) as i64;
+        let mut value = reader.read(64);
+        let mut delta = 0;
+        let mut window = (0, 0);
+        samples.push((timestamp, f64::from_bits(value)));
+
+        for _ in 1..self.count {
+            let mut prefix_width = 0;
+            while prefix_width < 4 && reader.read(1) == 1 {
+                prefix_width += 1;
+            }
+            let dod = match prefix_width {
+                0 => 0,
+                4 => reader.read(64) as i64,
+                n => {
+                    let (_, _, width, offset) = DOD_CLASSES[n - 1];
+                    reader.read(width) as i64 - offset
+                }
+            };
+            delta += dod;
+            timestamp += delta;
+
+            if reader.read(1) == 1 {
+                if reader.read(1) == 1 {
+                    let leading = reader.read(6) as u32;
+                    let meaningful = reader.read(6) as u32 + 1;
+                    window = (leading, 64 - leading - meaningful);
+                }
+                let (leading, trailing) = window;
+                value ^= reader.read(64 - leading - trailing) << trailing;
+            }
+            samples.push((timestamp, f64::from_bits(value)));
+        }
+        samples
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn read(&mut self, width: u32) -> u64 {
+        let mut value = 0;
+        for _ in 0..width {
+            let bit = (self.data[self.pos / 8] >> (7 - self.pos % 8)) & 1;
+            value = (value << 1) | bit as u64;
+            self.pos += 1;
+        }
+        value
+    }
+}
@@ -0,0 +1,208 @@
+// Top-K: the HeavyKeeper algorithm. Items count in `depth` rows of `width`
+// fingerprinted buckets; an item landing in a bucket that holds another
+// fingerprint decays that count with probability decay^count, and takes the
+// bucket over once it reaches zero. The k items with the biggest counts are
+// kept by name.
+
+use crate::database::sketch::{murmur64, table_len, INVALID_CHUNK};
+
+pub const DEFAULT_WIDTH: usize = 8;
+pub const DEFAULT_DEPTH: usize = 7;
+pub const DEFAULT_DECAY: f64 = 0.9;
+pub const MAX_INCREMENT: u64 = 100_000;
+
+// Decay draws come from a generator that starts from this seed and is dumped
+// with the sketch, so replicas replaying the same commands decay alike
+const SEED: u64 = 0x9e3779b97f4a7c15;
+
+// Each bucket is a 4 byte fingerprint and an 8 byte count
+const BUCKET_BYTES: usize = 12;
+
+#[derive(Clone, Copy)]
+struct Bucket {
+    fingerprint: u32,
+    count: u64,
+}
+
+pub struct TopK {
+    k: usize,
+    width: usize,
+    depth: usize,
+    decay: f64,
+    rng: u64,
+    buckets: Vec<Bucket>,
+    // The top items and their counts, at most k
+    top: Vec<(String, u64)>,
+}
+
+impl TopK {
+    // None if the sketch would be too large
+    pub fn new(k: usize, width: usize, depth: usize, decay: f64) -> Option<Self> {
+        Some(TopK {
+            k,
+            width,
+            depth,
+            decay,
+            rng: SEED,
+            buckets: vec![Bucket { fingerprint: 0, count: 0 }; table_len(depth, width, BUCKET_BYTES)?],
+            top: Vec::new(),
+        })
+    }
+
+    // xorshift64*, as a number in [0, 1)
+    fn random(&mut self) -> f64 {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        (self.rng.wrapping_mul(0x2545f4914f6cdd1d) >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // Adds `by` to the item's count. Returns the item it pushed out of the top k, if any.
+    pub fn increment(&mut self, item: &str, by: u64) -> Option<String> {
+        let bytes = item.as_bytes();
+        let fingerprint = murmur64(bytes, SEED) as u32;
+        let mut estimate = 0;
+        for row in 0..self.depth {
+            let position = row * self.width + (murmur64(bytes, row as u64) % self.width as u64) as usize;
+            let bucket = self.buckets[position];
+            if bucket.count == 0 || bucket.fingerprint == fingerprint {
+                let count = bucket.count.saturating_add(by);
+                self.buckets[position] = Bucket { fingerprint, count };
+                estimate = estimate.max(count);
+                continue;
+            }
+            let mut remaining = by;
+            let mut count = bucket.count;
+            while remaining > 0 {
+                if self.random() < self.decay.powf(count as f64) {
+                    count -= 1;
+                    if count == 0 {
+                        break;
+                    }
+                }
+                remaining -= 1;
+            }
+            self.buckets[position] = match count {
+                0 => {
+                    estimate = estimate.max(remaining);
+                    Bucket { fingerprint, count: remaining }
+                }
+                _ => Bucket { fingerprint: bucket.fingerprint, count },
+            };
+        }
+        self.update_top(item, estimate)
+    }
+
+    fn update_top(&mut self, item: &str, estimate: u64) -> Option<String> {
+        if let Some(entry) = self.top.iter_mut().find(|(name, _)| name == item) {
+            entry.1 = entry.1.max(estimate);
+            return None;
+        }
+        if estimate == 0 {
+            return None;
+        }
+        if self.top.len() < self.k {
+            self.top.push((item.to_string(), estimate));
+            return None;
+        }
+        let (smallest, _) = self.top.iter().enumerate().min_by_key(|(_, (_, count))| *count)?;
+        if self.top[smallest].1 > estimate {
+            return None;
+        }
+        let (expelled, _) = std::mem::replace(&mut self.top[smallest], (item.to_string(), estimate));
+        Some(expelled)
+    }
+
+    pub fn contains(&self, item: &str) -> bool {
+        self.top.iter().any(|(name, _)| name == item)
+    }
+
+    // The top items, biggest count first
+    pub fn list(&self) -> Vec<(&str, u64)> {
+        let mut list: Vec<(&str, u64)> = self.top.iter().map(|(name, count)| (name.as_str(), *count)).collect();
+        list.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        list
+    }
+
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn decay(&self) -> f64 {
+        self.decay
+    }
+
+    pub fn bytes_len(&self) -> usize {
+        self.buckets.len() * BUCKET_BYTES
+    }
+
+    // `k width depth decay rng item:count ...`, the item names in hex since
+    // they may hold anything
+    pub fn header(&self) -> String {
+        let mut header = format!("{} {} {} {} {}", self.k, self.width, self.depth, self.decay, self.rng);
+        for (name, count) in &self.top {
+            let hex: String = name.bytes().map(|byte| format!("{:02x}", byte)).collect();
+            header.push_str(&format!(" {}:{}", hex, count));
+        }
+        header
+    }
+
+    pub fn from_header(header: &str) -> Option<Self> {
+        let mut fields = header.split(' ');
+        let k: usize = fields.next()?.parse().ok()?;
+        let width: usize = fields.next()?.parse().ok()?;
+        let depth: usize = fields.next()?.parse().ok()?;
+        let decay: f64 = fields.next()?.parse().ok()?;
+        let rng: u64 = fields.next()?.parse().ok()?;
+        if k == 0 || width == 0 || depth == 0 || rng == 0 {
+            return None;
+        }
+        let mut topk = TopK::new(k, width, depth, decay)?;
+        topk.rng = rng;
+        for entry in fields {
+            let (hex, count) = entry.split_once(':')?;
+            let bytes = (0..hex.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+                .collect::<Option<Vec<u8>>>()?;
+            topk.top.push((String::from_utf8(bytes).ok()?, count.parse().ok()?));
+        }
+        (topk.top.len() <= k).then_some(topk)
+    }
+
+    // Buckets as little-endian fingerprint and count
+    pub fn read_bytes(&self, offset: usize, len: usize) -> Vec<u8> {
+        let end = (offset + len).min(self.bytes_len());
+        let first = offset / BUCKET_BYTES;
+        let bytes: Vec<u8> = self.buckets[first..end.div_ceil(BUCKET_BYTES)]
+            .iter()
+            .flat_map(|bucket| bucket.fingerprint.to_le_bytes().into_iter().chain(bucket.count.to_le_bytes()))
+            .collect();
+        let skip = offset - first * BUCKET_BYTES;
+        bytes[skip..skip + (end - offset)].to_vec()
+    }
+
+    pub fn write_bytes(&mut self, offset: usize, bytes: &[u8]) -> Result<(), String> {
+        if !offset.is_multiple_of(BUCKET_BYTES)
+            || !bytes.len().is_multiple_of(BUCKET_BYTES)
+            || offset + bytes.len() > self.bytes_len()
+        {
+            return Err(INVALID_CHUNK.to_string());
+        }
+        for (i, chunk) in bytes.chunks_exact(BUCKET_BYTES).enumerate() {
+            self.buckets[offset / BUCKET_BYTES + i] = Bucket {
+                fingerprint: u32::from_le_bytes(chunk[..4].try_into().unwrap()),
+                count: u64::from_le_bytes(chunk[4..].try_into().unwrap()),
+            };
+        }
+        Ok(())
+    }
+}
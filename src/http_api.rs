@@ -1,13 +1,25 @@
 use axum::{
-    extract::{Path, State},
-    response::Json,
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Json},
     routing::{delete, get, post},
     Router,
 };
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto::Builder;
+use hyper_util::server::graceful::GracefulShutdown;
+use hyper_util::service::TowerToHyperService;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use tokio::sync::RwLockReadGuard;
+use tokio::time::timeout;
 
+use crate::command;
+use crate::database::json::{self, Path as JsonPath};
 use crate::database::Database;
+use crate::info;
+use crate::metrics;
+use crate::tls::{TlsContext, HANDSHAKE_TIMEOUT};
 
 #[derive(Serialize)]
 pub struct ApiResponse {
@@ -16,6 +28,38 @@ pub struct ApiResponse {
     message: Option<String>,
 }
 
+// A failed write comes with a status saying why
+type ApiResult = Result<Json<ApiResponse>, (StatusCode, Json<ApiResponse>)>;
+
+fn failure(status: StatusCode, message: String) -> (StatusCode, Json<ApiResponse>) {
+    let response = ApiResponse {
+        success: false,
+        data: None,
+        message: Some(message),
+    };
+    (status, Json(response))
+}
+
+// The key holds another type, so the write conflicts with it
+fn conflict(message: String) -> (StatusCode, Json<ApiResponse>) {
+    failure(StatusCode::CONFLICT, message)
+}
+
+// Writes go through the same checks as RESP writes. A read-only replica forbids
+// them, a busy script makes the server unavailable for now, and keys served by
+// another cluster node are misdirected.
+async fn admit<'a>(db: &'a Database, key: &str) -> Result<RwLockReadGuard<'a, ()>, (StatusCode, Json<ApiResponse>)> {
+    command::admit_write(db, key).await.map_err(|error| {
+        let message = error.trim_start_matches('-').trim_end().to_string();
+        let status = match message.split(' ').next() {
+            Some("READONLY") => StatusCode::FORBIDDEN,
+            Some("BUSY") => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::MISDIRECTED_REQUEST,
+        };
+        failure(status, message)
+    })
+}
+
 #[derive(Deserialize)]
 pub struct SetRequest {
     value: String,
@@ -32,13 +76,18 @@ pub struct SetAddRequest {
     value: String,
 }
 
+#[derive(Deserialize)]
+pub struct JsonQuery {
+    path: Option<String>,
+}
+
 #[derive(Deserialize)]
 pub struct ZAddRequest {
     score: f64,
     member: String,
 }
 
-pub async fn create_http_server(addr: &str, db: Arc<Database>) {
+pub async fn create_http_server(addr: &str, db: Arc<Database>, tls: Option<Arc<TlsContext>>) {
     let app = Router::new()
         // String operations
         .route("/ping", get(ping))
@@ -61,11 +110,83 @@ pub async fn create_http_server(addr: &str, db: Arc<Database>) {
         .route("/zsets/:key/remove/:member", delete(zrem))
         .route("/zsets/:key/range/:start/:end", get(zrange))
         .route("/zsets/:key/score/:member", get(zscore))
-        .with_state(db);
+        // JSON operations
+        .route("/json/:key", get(json_get))
+        .route("/json/:key", post(json_set))
+        .route("/json/:key", delete(json_del))
+        // Server introspection
+        .route("/info", get(info_all))
+        .route("/info/:section", get(info_section))
+        .route("/metrics", get(prometheus_metrics))
+        .with_state(db.clone());
 
-    println!("HTTP API server listening on {}", addr);
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+
+    match tls {
+        Some(tls) => {
+            println!("HTTPS API server listening on {}", addr);
+            serve_tls(listener, app, tls, db).await;
+        }
+        None => {
+            println!("HTTP API server listening on {}", addr);
+            // Stops accepting on shutdown and waits for in-flight requests
+            axum::serve(listener, app)
+                .with_graceful_shutdown(async move { db.shutdown().wait().await })
+                .await
+                .unwrap();
+        }
+    }
+}
+
+// axum::serve only takes plain TCP listeners, so TLS connections are
+// handed to hyper directly after the handshake. On shutdown the listener
+// stops and open connections finish their in-flight requests.
+async fn serve_tls(
+    listener: tokio::net::TcpListener,
+    app: Router,
+    tls: Arc<TlsContext>,
+    db: Arc<Database>,
+) {
+    let graceful = GracefulShutdown::new();
+    loop {
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            _ = db.shutdown().wait() => break,
+        };
+        let (socket, client_addr) = match accepted {
+            Ok(conn) => conn,
+            Err(e) => {
+                eprintln!("HTTPS accept failed: {}", e);
+                continue;
+            }
+        };
+        let app = app.clone();
+        let tls = tls.clone();
+        let watcher = graceful.watcher();
+
+        tokio::spawn(async move {
+            let stream = match timeout(HANDSHAKE_TIMEOUT, tls.accept(socket)).await {
+                Ok(Ok((stream, _))) => stream,
+                Ok(Err(e)) => {
+                    println!("TLS handshake failed for {}: {}", client_addr, e);
+                    return;
+                }
+                Err(_) => {
+                    println!("TLS handshake timed out for {}", client_addr);
+                    return;
+                }
+            };
+
+            let service = TowerToHyperService::new(app);
+            let builder = Builder::new(TokioExecutor::new());
+            let conn = builder.serve_connection_with_upgrades(TokioIo::new(stream), service);
+            if let Err(e) = watcher.watch(conn.into_owned()).await {
+                println!("HTTPS connection error from {}: {}", client_addr, e);
+            }
+        });
+    }
+
+    graceful.shutdown().await;
 }
 
 // PING
@@ -77,15 +198,19 @@ async fn ping() -> Json<ApiResponse> {
     })
 }
 
-// GET key
+// GET key. JSON documents come back as JSON, not as a quoted string.
 async fn get_key(
     State(db): State<Arc<Database>>,
     Path(key): Path<String>,
 ) -> Json<ApiResponse> {
-    match db.get(&key).await {
+    let value = match db.get(&key).await {
+        Some(value) => Some(serde_json::json!(String::from_utf8_lossy(&value))),
+        None => db.json_read(&key, |doc| doc.clone()),
+    };
+    match value {
         Some(value) => Json(ApiResponse {
             success: true,
-            data: Some(serde_json::json!(value)),
+            data: Some(value),
             message: None,
         }),
         None => Json(ApiResponse {
@@ -101,26 +226,28 @@ async fn set_key(
     State(db): State<Arc<Database>>,
     Path(key): Path<String>,
     Json(payload): Json<SetRequest>,
-) -> Json<ApiResponse> {
-    db.set(key, payload.value, payload.ttl).await;
-    Json(ApiResponse {
+) -> ApiResult {
+    let _write = admit(&db, &key).await?;
+    db.set(key, payload.value.into_bytes(), payload.ttl).await;
+    Ok(Json(ApiResponse {
         success: true,
         data: Some(serde_json::json!("OK")),
         message: None,
-    })
+    }))
 }
 
 // DELETE key
 async fn delete_key(
     State(db): State<Arc<Database>>,
     Path(key): Path<String>,
-) -> Json<ApiResponse> {
+) -> ApiResult {
+    let _write = admit(&db, &key).await?;
     let deleted = db.delete(&key).await;
-    Json(ApiResponse {
+    Ok(Json(ApiResponse {
         success: true,
         data: Some(serde_json::json!(if deleted { 1 } else { 0 })),
         message: None,
-    })
+    }))
 }
 
 // LPUSH
@@ -128,13 +255,14 @@ async fn lpush(
     State(db): State<Arc<Database>>,
     Path(key): Path<String>,
     Json(payload): Json<ListPushRequest>,
-) -> Json<ApiResponse> {
-    let len = db.lpush(key, payload.value).await;
-    Json(ApiResponse {
+) -> ApiResult {
+    let _write = admit(&db, &key).await?;
+    let len = db.lpush(key, payload.value).await.map_err(conflict)?;
+    Ok(Json(ApiResponse {
         success: true,
         data: Some(serde_json::json!(len)),
         message: None,
-    })
+    }))
 }
 
 // RPUSH
@@ -142,21 +270,23 @@ async fn rpush(
     State(db): State<Arc<Database>>,
     Path(key): Path<String>,
     Json(payload): Json<ListPushRequest>,
-) -> Json<ApiResponse> {
-    let len = db.rpush(key, payload.value).await;
-    Json(ApiResponse {
+) -> ApiResult {
+    let _write = admit(&db, &key).await?;
+    let len = db.rpush(key, payload.value).await.map_err(conflict)?;
+    Ok(Json(ApiResponse {
         success: true,
         data: Some(serde_json::json!(len)),
         message: None,
-    })
+    }))
 }
 
 // LPOP
 async fn lpop(
     State(db): State<Arc<Database>>,
     Path(key): Path<String>,
-) -> Json<ApiResponse> {
-    match db.lpop(&key).await {
+) -> ApiResult {
+    let _write = admit(&db, &key).await?;
+    Ok(match db.lpop(&key).await {
         Some(value) => Json(ApiResponse {
             success: true,
             data: Some(serde_json::json!(value)),
@@ -167,15 +297,16 @@ async fn lpop(
             data: None,
             message: Some("List empty or not found".to_string()),
         }),
-    }
+    })
 }
 
 // RPOP
 async fn rpop(
     State(db): State<Arc<Database>>,
     Path(key): Path<String>,
-) -> Json<ApiResponse> {
-    match db.rpop(&key).await {
+) -> ApiResult {
+    let _write = admit(&db, &key).await?;
+    Ok(match db.rpop(&key).await {
         Some(value) => Json(ApiResponse {
             success: true,
             data: Some(serde_json::json!(value)),
@@ -186,7 +317,7 @@ async fn rpop(
             data: None,
             message: Some("List empty or not found".to_string()),
         }),
-    }
+    })
 }
 
 // LRANGE
@@ -213,26 +344,28 @@ async fn sadd(
     State(db): State<Arc<Database>>,
     Path(key): Path<String>,
     Json(payload): Json<SetAddRequest>,
-) -> Json<ApiResponse> {
-    let added = db.sadd(key, payload.value).await;
-    Json(ApiResponse {
+) -> ApiResult {
+    let _write = admit(&db, &key).await?;
+    let added = db.sadd(key, payload.value).await.map_err(conflict)?;
+    Ok(Json(ApiResponse {
         success: true,
         data: Some(serde_json::json!(if added { 1 } else { 0 })),
         message: None,
-    })
+    }))
 }
 
 // SREM
 async fn srem(
     State(db): State<Arc<Database>>,
     Path((key, value)): Path<(String, String)>,
-) -> Json<ApiResponse> {
+) -> ApiResult {
+    let _write = admit(&db, &key).await?;
     let removed = db.srem(&key, value).await;
-    Json(ApiResponse {
+    Ok(Json(ApiResponse {
         success: true,
         data: Some(serde_json::json!(if removed { 1 } else { 0 })),
         message: None,
-    })
+    }))
 }
 
 // SMEMBERS
@@ -272,26 +405,28 @@ async fn zadd(
     State(db): State<Arc<Database>>,
     Path(key): Path<String>,
     Json(payload): Json<ZAddRequest>,
-) -> Json<ApiResponse> {
-    let added = db.zadd(key, payload.score, payload.member).await;
-    Json(ApiResponse {
+) -> ApiResult {
+    let _write = admit(&db, &key).await?;
+    let added = db.zadd(key, payload.score, payload.member).await.map_err(conflict)?;
+    Ok(Json(ApiResponse {
         success: true,
         data: Some(serde_json::json!(if added { 1 } else { 0 })),
         message: None,
-    })
+    }))
 }
 
 // ZREM
 async fn zrem(
     State(db): State<Arc<Database>>,
     Path((key, member)): Path<(String, String)>,
-) -> Json<ApiResponse> {
+) -> ApiResult {
+    let _write = admit(&db, &key).await?;
     let removed = db.zrem(&key, member).await;
-    Json(ApiResponse {
+    Ok(Json(ApiResponse {
         success: true,
         data: Some(serde_json::json!(if removed { 1 } else { 0 })),
         message: None,
-    })
+    }))
 }
 
 // ZRANGE
@@ -331,3 +466,124 @@ async fn zscore(
         }),
     }
 }
+
+// JSON.GET, the document (or what ?path= selects) as JSON
+async fn json_get(
+    State(db): State<Arc<Database>>,
+    Path(key): Path<String>,
+    Query(query): Query<JsonQuery>,
+) -> Json<ApiResponse> {
+    let path = match JsonPath::parse(query.path.as_deref().unwrap_or(".")) {
+        Ok(path) => path,
+        Err(e) => {
+            return Json(ApiResponse {
+                success: false,
+                data: None,
+                message: Some(e),
+            })
+        }
+    };
+    match db.json_read(&key, |doc| json::matches(doc, &path)).flatten() {
+        Some(value) => Json(ApiResponse {
+            success: true,
+            data: Some(value),
+            message: None,
+        }),
+        None => Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("Key or path not found".to_string()),
+        }),
+    }
+}
+
+// JSON.SET key $, the request body is the document
+async fn json_set(
+    State(db): State<Arc<Database>>,
+    Path(key): Path<String>,
+    Json(document): Json<serde_json::Value>,
+) -> ApiResult {
+    let _write = admit(&db, &key).await?;
+    let text = document.to_string();
+    db.json_write(&key, &["JSON.SET", &key, "$", &text], |doc| {
+        *doc = Some(document);
+        Ok(((), true))
+    })
+    .map_err(conflict)?;
+    Ok(Json(ApiResponse {
+        success: true,
+        data: Some(serde_json::json!("OK")),
+        message: None,
+    }))
+}
+
+// JSON.DEL
+async fn json_del(
+    State(db): State<Arc<Database>>,
+    Path(key): Path<String>,
+) -> ApiResult {
+    let _write = admit(&db, &key).await?;
+    let deleted = db.json_read(&key, |_| ()).is_some() && db.delete(&key).await;
+    Ok(Json(ApiResponse {
+        success: true,
+        data: Some(serde_json::json!(if deleted { 1 } else { 0 })),
+        message: None,
+    }))
+}
+
+// INFO
+async fn info_all(State(db): State<Arc<Database>>) -> Json<ApiResponse> {
+    Json(ApiResponse {
+        success: true,
+        data: Some(info_json(&info::collect(&db, None))),
+        message: None,
+    })
+}
+
+// INFO section
+async fn info_section(
+    State(db): State<Arc<Database>>,
+    Path(section): Path<String>,
+) -> Json<ApiResponse> {
+    let sections = info::collect(&db, Some(&section));
+    if sections.is_empty() {
+        return Json(ApiResponse {
+            success: false,
+            data: None,
+            message: Some("Unknown INFO section".to_string()),
+        });
+    }
+    Json(ApiResponse {
+        success: true,
+        data: Some(info_json(&sections)),
+        message: None,
+    })
+}
+
+// Numeric INFO fields become JSON numbers, everything else stays a string
+fn info_json(sections: &[info::Section]) -> serde_json::Value {
+    let mut out = serde_json::Map::new();
+    for (name, fields) in sections {
+        let mut section = serde_json::Map::new();
+        for (field, value) in fields {
+            let value = if let Ok(n) = value.parse::<u64>() {
+                serde_json::json!(n)
+            } else if let Ok(f) = value.parse::<f64>() {
+                serde_json::json!(f)
+            } else {
+                serde_json::json!(value)
+            };
+            section.insert(field.clone(), value);
+        }
+        out.insert(name.to_string(), serde_json::Value::Object(section));
+    }
+    serde_json::Value::Object(out)
+}
+
+// Prometheus scrape endpoint
+async fn prometheus_metrics(State(db): State<Arc<Database>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics::render(&db),
+    )
+}
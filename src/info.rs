@@ -0,0 +1,215 @@
+use std::fs;
+
+use crate::database::stats::Stats;
+use crate::database::Database;
+use crate::replication::LinkStatus;
+
+pub type Section = (&'static str, Vec<(String, String)>);
+
+const DEFAULT_SECTIONS: [&str; 8] = [
+    "server", "clients", "memory", "persistence", "stats", "replication", "cluster", "keyspace",
+];
+const ALL_SECTIONS: [&str; 9] = [
+    "server", "clients", "memory", "persistence", "stats", "replication", "commandstats", "cluster",
+    "keyspace",
+];
+
+// Builds the INFO sections selected by `section` ("default", "all", "everything"
+// or a single section name). Unknown sections yield an empty list, like Redis.
+pub fn collect(db: &Database, section: Option<&str>) -> Vec<Section> {
+    let section = section.unwrap_or("default").to_lowercase();
+    let names: Vec<&str> = match section.as_str() {
+        "default" => DEFAULT_SECTIONS.to_vec(),
+        "all" | "everything" => ALL_SECTIONS.to_vec(),
+        name => ALL_SECTIONS.iter().copied().filter(|s| *s == name).collect(),
+    };
+
+    names
+        .into_iter()
+        .map(|name| match name {
+            "server" => ("server", server_section(db)),
+            "clients" => ("clients", clients_section(db)),
+            "memory" => ("memory", memory_section(db)),
+            "persistence" => ("persistence", persistence_section(db)),
+            "stats" => ("stats", stats_section(db)),
+            "replication" => ("replication", replication_section(db)),
+            "commandstats" => ("commandstats", commandstats_section(db)),
+            "cluster" => ("cluster", vec![field("cluster_enabled", db.cluster().enabled() as u8)]),
+            _ => ("keyspace", keyspace_section(db)),
+        })
+        .collect()
+}
+
+// Renders sections in the `# Section\r\nfield:value\r\n` INFO text format
+pub fn render(sections: &[Section]) -> String {
+    let mut out = String::new();
+    for (name, fields) in sections {
+        if !out.is_empty() {
+            out.push_str("\r\n");
+        }
+        out.push_str(&format!("# {}{}\r\n", name[..1].to_uppercase(), &name[1..]));
+        for (field, value) in fields {
+            out.push_str(&format!("{}:{}\r\n", field, value));
+        }
+    }
+    out
+}
+
+fn field(name: &str, value: impl ToString) -> (String, String) {
+    (name.to_string(), value.to_string())
+}
+
+fn server_section(db: &Database) -> Vec<(String, String)> {
+    let uptime = db.stats().uptime().as_secs();
+    vec![
+        field("redis_version", env!("CARGO_PKG_VERSION")),
+        field("redis_mode", "standalone"),
+        field("os", format!("{} {}", std::env::consts::OS, std::env::consts::ARCH)),
+        field("arch_bits", usize::BITS),
+        field("process_id", std::process::id()),
+        field("uptime_in_seconds", uptime),
+        field("uptime_in_days", uptime / 86400),
+    ]
+}
+
+fn clients_section(db: &Database) -> Vec<(String, String)> {
+    vec![
+        field("connected_clients", Stats::get(&db.stats().connected_clients)),
+        field("maxclients", db.config().maxclients),
+    ]
+}
+
+fn memory_section(db: &Database) -> Vec<(String, String)> {
+    let used = db.used_memory();
+    let mut fields = vec![
+        field("used_memory", used),
+        field("used_memory_human", human_bytes(used as u64)),
+        field("number_of_cached_scripts", db.scripting().cached_scripts()),
+    ];
+    if let Some(rss) = rss_bytes() {
+        fields.push(field("used_memory_rss", rss));
+        fields.push(field("used_memory_rss_human", human_bytes(rss)));
+    }
+    fields
+}
+
+fn persistence_section(db: &Database) -> Vec<(String, String)> {
+    let stats = db.stats();
+    vec![
+        field("loading", 0),
+        field("rdb_changes_since_last_save", Stats::get(&stats.dirty)),
+        field("rdb_bgsave_in_progress", 0),
+        field("rdb_last_save_time", stats.start_unix_time),
+        field("aof_enabled", 0),
+    ]
+}
+
+fn stats_section(db: &Database) -> Vec<(String, String)> {
+    let stats = db.stats();
+    vec![
+        field("total_connections_received", Stats::get(&stats.total_connections_received)),
+        field("total_commands_processed", Stats::get(&stats.total_commands_processed)),
+        field("instantaneous_ops_per_sec", Stats::get(&stats.instantaneous_ops_per_sec)),
+        field("expired_keys", Stats::get(&stats.expired_keys)),
+        field("evicted_keys", Stats::get(&stats.evicted_keys)),
+        field("keyspace_hits", Stats::get(&stats.keyspace_hits)),
+        field("keyspace_misses", Stats::get(&stats.keyspace_misses)),
+        field("rejected_connections", Stats::get(&stats.rejected_connections)),
+        field(
+            "client_query_buffer_limit_disconnections",
+            Stats::get(&stats.client_query_buffer_limit_disconnections),
+        ),
+        field(
+            "client_output_buffer_limit_disconnections",
+            Stats::get(&stats.client_output_buffer_limit_disconnections),
+        ),
+    ]
+}
+
+fn replication_section(db: &Database) -> Vec<(String, String)> {
+    let replication = db.replication();
+    let mut fields = Vec::new();
+
+    match replication.master() {
+        Some(master) => {
+            let up = master.status == LinkStatus::Connected;
+            fields.push(field("role", "slave"));
+            fields.push(field("master_host", master.host));
+            fields.push(field("master_port", master.port));
+            fields.push(field("master_link_status", if up { "up" } else { "down" }));
+            fields.push(field("master_last_io_seconds_ago", if up { master.last_io as i64 } else { -1 }));
+            fields.push(field("master_sync_in_progress", (master.status == LinkStatus::Sync) as u8));
+            fields.push(field("slave_repl_offset", replication.offset()));
+            fields.push(field("slave_read_only", db.config().replica_read_only as u8));
+        }
+        None => fields.push(field("role", "master")),
+    }
+
+    let replicas = replication.replicas();
+    fields.push(field("connected_slaves", replicas.len()));
+    for (i, replica) in replicas.iter().enumerate() {
+        fields.push(field(
+            &format!("slave{}", i),
+            format!(
+                "ip={},port={},state=online,offset={},lag={}",
+                replica.ip, replica.port, replica.offset, replica.lag
+            ),
+        ));
+    }
+
+    let (size, first_byte, histlen) = replication.backlog_info();
+    fields.push(field("master_replid", replication.replid()));
+    fields.push(field("master_repl_offset", replication.offset()));
+    fields.push(field("repl_backlog_active", 1));
+    fields.push(field("repl_backlog_size", size));
+    fields.push(field("repl_backlog_first_byte_offset", first_byte));
+    fields.push(field("repl_backlog_histlen", histlen));
+    fields
+}
+
+fn commandstats_section(db: &Database) -> Vec<(String, String)> {
+    db.stats()
+        .command_stats()
+        .into_iter()
+        .map(|(name, stats)| {
+            let per_call = stats.usec as f64 / stats.calls as f64;
+            field(
+                &format!("cmdstat_{}", name),
+                format!("calls={},usec={},usec_per_call={:.2}", stats.calls, stats.usec, per_call),
+            )
+        })
+        .collect()
+}
+
+fn keyspace_section(db: &Database) -> Vec<(String, String)> {
+    let keys = db.key_count();
+    if keys == 0 {
+        return Vec::new();
+    }
+    let (expires, avg_ttl) = db.expires_info();
+    vec![field("db0", format!("keys={},expires={},avg_ttl={}", keys, expires, avg_ttl))]
+}
+
+fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["K", "M", "G", "T"];
+    if bytes < 1024 {
+        return format!("{}B", bytes);
+    }
+    let mut value = bytes as f64;
+    let mut unit = "B";
+    for u in UNITS {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = u;
+    }
+    format!("{:.2}{}", value, unit)
+}
+
+// Resident set size from /proc, only available on Linux
+pub fn rss_bytes() -> Option<u64> {
+    let statm = fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    Some(pages * 4096)
+}
@@ -0,0 +1,79 @@
+// The latency monitor (LATENCY). Events that took at least
+// latency-monitor-threshold milliseconds are sampled per event name: `command`
+// for commands, `expire-cycle` for active expiry and `snapshot` for the keyspace
+// dumps sent to replicas. Each event keeps its last 160 samples, at most one
+// per second, along with the worst latency seen since it was last reset.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const HISTORY_LEN: usize = 160;
+
+#[derive(Default)]
+struct EventHistory {
+    // (unix time, milliseconds), oldest first
+    samples: VecDeque<(u64, u64)>,
+    max: u64,
+}
+
+pub struct LatencyMonitor {
+    events: Mutex<BTreeMap<String, EventHistory>>,
+}
+
+impl LatencyMonitor {
+    pub fn new() -> Self {
+        LatencyMonitor { events: Mutex::new(BTreeMap::new()) }
+    }
+
+    pub fn record(&self, threshold: u64, event: &str, duration: Duration) {
+        let latency = duration.as_millis() as u64;
+        if threshold == 0 || latency < threshold {
+            return;
+        }
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+
+        let mut events = self.events.lock().unwrap();
+        let history = events.entry(event.to_string()).or_default();
+        history.max = history.max.max(latency);
+        // Spikes within the same second share a sample
+        match history.samples.back_mut() {
+            Some((time, worst)) if *time == now => *worst = (*worst).max(latency),
+            _ => {
+                history.samples.push_back((now, latency));
+                if history.samples.len() > HISTORY_LEN {
+                    history.samples.pop_front();
+                }
+            }
+        }
+    }
+
+    // (event, time of the latest sample, its latency, worst latency) per event
+    pub fn latest(&self) -> Vec<(String, u64, u64, u64)> {
+        let events = self.events.lock().unwrap();
+        events
+            .iter()
+            .filter_map(|(name, history)| {
+                let (time, latency) = history.samples.back()?;
+                Some((name.clone(), *time, *latency, history.max))
+            })
+            .collect()
+    }
+
+    // (time, latency) samples of one event, oldest first
+    pub fn history(&self, event: &str) -> Vec<(u64, u64)> {
+        let events = self.events.lock().unwrap();
+        events.get(event).map(|history| history.samples.iter().copied().collect()).unwrap_or_default()
+    }
+
+    // Drops the given events, or all of them, returning how many were dropped
+    pub fn reset(&self, events: &[&str]) -> usize {
+        let mut all = self.events.lock().unwrap();
+        if events.is_empty() {
+            let count = all.len();
+            all.clear();
+            return count;
+        }
+        events.iter().filter(|event| all.remove(**event).is_some()).count()
+    }
+}
@@ -1,28 +1,115 @@
+use std::fs;
 use std::sync::Arc;
 
+mod client;
+mod cluster;
 mod command;
+mod config;
 mod database;
 mod http_api;
+mod id;
+mod info;
+mod latency;
+mod metrics;
+mod pubsub;
+mod replication;
+mod scripting;
+mod search;
 mod server;
+mod shutdown;
+mod slowlog;
+mod tls;
+mod tracking;
 
 #[tokio::main]
 async fn main() {
-    let db = Arc::new(database::Database::new());
-    
-    // Clone for TCP server
-    let db_tcp = db.clone();
-    
-    // Start TCP Redis server in background
-    let tcp_handle = tokio::spawn(async move {
-        let addr = "127.0.0.1:6379";
-        eprintln!("Starting Redis TCP server at {}", addr);
-        server::create_server(addr, db_tcp).await;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let config = config::Config::from_args(&args).unwrap_or_else(|e| {
+        eprintln!("Invalid configuration: {}", e);
+        std::process::exit(1);
     });
-    
+
+    let tls = if config.tls_enabled() {
+        let tls = tls::TlsContext::from_config(&config).unwrap_or_else(|e| {
+            eprintln!("Failed to set up TLS: {}", e);
+            std::process::exit(1);
+        });
+        Some(Arc::new(tls))
+    } else {
+        None
+    };
+
+    if let Some(pidfile) = &config.pidfile {
+        if let Err(e) = fs::write(pidfile, format!("{}\n", std::process::id())) {
+            eprintln!("Failed to write pidfile {}: {}", pidfile, e);
+        }
+    }
+
+    let db = Arc::new(database::Database::new(config.clone()));
+    tokio::spawn(db.stats().clone().track_ops_per_sec());
+    tokio::spawn(shutdown::handle_signals(db.clone()));
+    tokio::spawn((*db).clone().active_expire());
+    if let Some((host, port)) = config.replicaof.clone() {
+        replication::replica::replicate(&db, host, port);
+    }
+    if config.cluster_enabled {
+        println!("Cluster mode enabled, node id {}", db.cluster().myself().id);
+        tokio::spawn(cluster::bus::run((*db).clone()));
+    }
+    let mut tcp_handles = Vec::new();
+
+    // Start TCP Redis server in background (port 0 disables plaintext)
+    if config.port != 0 {
+        let db_tcp = db.clone();
+        let addr = format!("{}:{}", config.bind, config.port);
+        tcp_handles.push(tokio::spawn(async move {
+            eprintln!("Starting Redis TCP server at {}", addr);
+            server::create_server(&addr, db_tcp, None).await;
+        }));
+    }
+
+    // Start TLS Redis server in background
+    if config.tls_port != 0 {
+        let db_tls = db.clone();
+        let tls = tls.clone();
+        let addr = format!("{}:{}", config.bind, config.tls_port);
+        tcp_handles.push(tokio::spawn(async move {
+            eprintln!("Starting Redis TLS server at {}", addr);
+            server::create_server(&addr, db_tls, tls).await;
+        }));
+    }
+
+    // Start Unix socket Redis server in background
+    if let Some(path) = config.unixsocket.clone() {
+        let db_unix = db.clone();
+        let perm = config.unixsocketperm;
+        tcp_handles.push(tokio::spawn(async move {
+            eprintln!("Starting Redis Unix socket server at {}", path);
+            server::create_unix_server(&path, perm, db_unix).await;
+        }));
+    }
+
     // Start HTTP API server
-    let http_addr = "127.0.0.1:3000";
+    let http_addr = format!("{}:{}", config.bind, config.http_port);
+    let http_tls = if config.tls_http { tls } else { None };
     eprintln!("Starting HTTP API server at {}", http_addr);
-    http_api::create_http_server(http_addr, db).await;
-    
-    tcp_handle.await.unwrap();
+    http_api::create_http_server(&http_addr, db.clone(), http_tls).await;
+
+    // Every server returns once shutdown was requested
+    for handle in tcp_handles {
+        handle.await.unwrap();
+    }
+
+    println!("Waiting for connections to finish...");
+    if tokio::time::timeout(shutdown::DRAIN_TIMEOUT, db.clients().wait_until_empty())
+        .await
+        .is_err()
+    {
+        eprintln!("Some connections didn't close in time, exiting anyway");
+    }
+
+    if let Some(pidfile) = &config.pidfile {
+        let _ = fs::remove_file(pidfile);
+    }
+    println!("Redis is now ready to exit, bye bye...");
 }
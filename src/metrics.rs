@@ -0,0 +1,85 @@
+use std::fmt::Write;
+
+use crate::database::stats::{Stats, LATENCY_BUCKETS};
+use crate::database::Database;
+use crate::info;
+
+// Renders all metrics in the Prometheus text exposition format (version 0.0.4)
+pub fn render(db: &Database) -> String {
+    let stats = db.stats();
+    let (expires, _) = db.expires_info();
+    let mut out = String::new();
+
+    // (name, type, help, value)
+    let mut simple: Vec<(&str, &str, &str, u64)> = vec![
+        ("redis_uptime_seconds", "gauge", "Seconds since the server started", stats.uptime().as_secs()),
+        ("redis_connected_clients", "gauge", "Number of client connections", Stats::get(&stats.connected_clients)),
+        ("redis_connections_received_total", "counter", "Total number of connections accepted", Stats::get(&stats.total_connections_received)),
+        ("redis_commands_processed_total", "counter", "Total number of commands processed", Stats::get(&stats.total_commands_processed)),
+        ("redis_keys_with_expiry", "gauge", "Number of keys with a TTL", expires as u64),
+        ("redis_keyspace_hits_total", "counter", "Successful key lookups", Stats::get(&stats.keyspace_hits)),
+        ("redis_keyspace_misses_total", "counter", "Failed key lookups", Stats::get(&stats.keyspace_misses)),
+        ("redis_expired_keys_total", "counter", "Keys removed because their TTL passed", Stats::get(&stats.expired_keys)),
+        ("redis_evicted_keys_total", "counter", "Keys evicted due to the memory limit", Stats::get(&stats.evicted_keys)),
+        ("redis_rejected_connections_total", "counter", "Connections refused because of maxclients", Stats::get(&stats.rejected_connections)),
+        ("redis_client_query_buffer_limit_disconnections_total", "counter", "Clients closed for exceeding the query buffer limit", Stats::get(&stats.client_query_buffer_limit_disconnections)),
+        ("redis_client_output_buffer_limit_disconnections_total", "counter", "Clients closed for exceeding an output buffer limit", Stats::get(&stats.client_output_buffer_limit_disconnections)),
+        ("redis_memory_used_bytes", "gauge", "Estimated memory used by the keyspace", db.used_memory() as u64),
+        ("redis_loading", "gauge", "Whether a dataset is being loaded", 0),
+        ("redis_rdb_changes_since_last_save", "gauge", "Writes since the last save", Stats::get(&stats.dirty)),
+        ("redis_aof_enabled", "gauge", "Whether the append only file is enabled", 0),
+        ("redis_connected_slaves", "gauge", "Number of connected replicas", db.replication().replicas().len() as u64),
+        ("redis_master_repl_offset", "gauge", "Replication stream offset", db.replication().offset()),
+    ];
+    if let Some(rss) = info::rss_bytes() {
+        simple.push(("redis_memory_rss_bytes", "gauge", "Resident set size of the process", rss));
+    }
+
+    for (name, kind, help, value) in simple {
+        header(&mut out, name, kind, help);
+        let _ = writeln!(out, "{} {}", name, value);
+    }
+
+    header(&mut out, "redis_keys", "gauge", "Number of keys per type");
+    for (key_type, count) in db.key_counts_by_type() {
+        let _ = writeln!(out, "redis_keys{{type=\"{}\"}} {}", key_type, count);
+    }
+
+    let commands = stats.command_stats();
+    header(&mut out, "redis_commands_total", "counter", "Calls per command");
+    for (name, cmd) in &commands {
+        let _ = writeln!(out, "redis_commands_total{{cmd=\"{}\"}} {}", name, cmd.calls);
+    }
+
+    header(&mut out, "redis_command_duration_seconds", "histogram", "Command execution latency");
+    for (name, cmd) in &commands {
+        let mut cumulative = 0;
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(cmd.buckets.iter()) {
+            cumulative += count;
+            let _ = writeln!(
+                out,
+                "redis_command_duration_seconds_bucket{{cmd=\"{}\",le=\"{}\"}} {}",
+                name, bound, cumulative
+            );
+        }
+        let _ = writeln!(
+            out,
+            "redis_command_duration_seconds_bucket{{cmd=\"{}\",le=\"+Inf\"}} {}",
+            name, cmd.calls
+        );
+        let _ = writeln!(
+            out,
+            "redis_command_duration_seconds_sum{{cmd=\"{}\"}} {}",
+            name,
+            cmd.usec as f64 / 1e6
+        );
+        let _ = writeln!(out, "redis_command_duration_seconds_count{{cmd=\"{}\"}} {}", name, cmd.calls);
+    }
+
+    out
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
@@ -0,0 +1,217 @@
+// Pub/sub: which clients are subscribed to which channels and patterns.
+// Messages go straight into the subscribers' output buffers.
+
+pub mod notify;
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, RwLock};
+
+use crate::client::Client;
+use crate::command::resp;
+
+#[derive(Default)]
+struct Subscriptions {
+    channels: HashMap<String, HashMap<u64, Arc<Client>>>,
+    patterns: HashMap<String, HashMap<u64, Arc<Client>>>,
+    // What each subscribed client listens to, as (channels, patterns)
+    clients: HashMap<u64, (BTreeSet<String>, BTreeSet<String>)>,
+}
+
+impl Subscriptions {
+    // Channels plus patterns, the count subscribe replies carry
+    fn count(&self, id: u64) -> usize {
+        self.clients.get(&id).map_or(0, |(channels, patterns)| channels.len() + patterns.len())
+    }
+
+    fn forget_if_unsubscribed(&mut self, id: u64) {
+        if self.count(id) == 0 {
+            self.clients.remove(&id);
+        }
+    }
+}
+
+pub struct PubSub {
+    subscriptions: RwLock<Subscriptions>,
+}
+
+impl PubSub {
+    pub fn new() -> Self {
+        PubSub {
+            subscriptions: RwLock::new(Subscriptions::default()),
+        }
+    }
+
+    // Returns the client's subscription count afterwards
+    pub fn subscribe(&self, client: &Arc<Client>, channel: &str) -> usize {
+        let mut subs = self.subscriptions.write().unwrap();
+        subs.channels.entry(channel.to_string()).or_default().insert(client.id, client.clone());
+        subs.clients.entry(client.id).or_default().0.insert(channel.to_string());
+        subs.count(client.id)
+    }
+
+    pub fn psubscribe(&self, client: &Arc<Client>, pattern: &str) -> usize {
+        let mut subs = self.subscriptions.write().unwrap();
+        subs.patterns.entry(pattern.to_string()).or_default().insert(client.id, client.clone());
+        subs.clients.entry(client.id).or_default().1.insert(pattern.to_string());
+        subs.count(client.id)
+    }
+
+    pub fn unsubscribe(&self, id: u64, channel: &str) -> usize {
+        let mut subs = self.subscriptions.write().unwrap();
+        if let Some(clients) = subs.channels.get_mut(channel) {
+            clients.remove(&id);
+            if clients.is_empty() {
+                subs.channels.remove(channel);
+            }
+        }
+        if let Some((channels, _)) = subs.clients.get_mut(&id) {
+            channels.remove(channel);
+        }
+        subs.forget_if_unsubscribed(id);
+        subs.count(id)
+    }
+
+    pub fn punsubscribe(&self, id: u64, pattern: &str) -> usize {
+        let mut subs = self.subscriptions.write().unwrap();
+        if let Some(clients) = subs.patterns.get_mut(pattern) {
+            clients.remove(&id);
+            if clients.is_empty() {
+                subs.patterns.remove(pattern);
+            }
+        }
+        if let Some((_, patterns)) = subs.clients.get_mut(&id) {
+            patterns.remove(pattern);
+        }
+        subs.forget_if_unsubscribed(id);
+        subs.count(id)
+    }
+
+    pub fn channels_of(&self, id: u64) -> Vec<String> {
+        let subs = self.subscriptions.read().unwrap();
+        subs.clients.get(&id).map_or_else(Vec::new, |(channels, _)| channels.iter().cloned().collect())
+    }
+
+    pub fn patterns_of(&self, id: u64) -> Vec<String> {
+        let subs = self.subscriptions.read().unwrap();
+        subs.clients.get(&id).map_or_else(Vec::new, |(_, patterns)| patterns.iter().cloned().collect())
+    }
+
+    pub fn count(&self, id: u64) -> usize {
+        self.subscriptions.read().unwrap().count(id)
+    }
+
+    // Drops every subscription of a client that disconnected
+    pub fn remove_client(&self, id: u64) {
+        for channel in self.channels_of(id) {
+            self.unsubscribe(id, &channel);
+        }
+        for pattern in self.patterns_of(id) {
+            self.punsubscribe(id, &pattern);
+        }
+    }
+
+    // Sends the message to the channel's subscribers and to every matching
+    // pattern's. Returns how many messages went out.
+    pub fn publish(&self, channel: &str, message: &str) -> usize {
+        let subs = self.subscriptions.read().unwrap();
+        let mut sent = 0;
+        if let Some(clients) = subs.channels.get(channel) {
+            let items = [resp::bulk("message"), resp::bulk(channel), resp::bulk(message)];
+            for client in clients.values() {
+                client.send(out_of_band(client, &items));
+                sent += 1;
+            }
+        }
+        for (pattern, clients) in &subs.patterns {
+            if !glob_match(pattern.as_bytes(), channel.as_bytes()) {
+                continue;
+            }
+            let items = [resp::bulk("pmessage"), resp::bulk(pattern), resp::bulk(channel), resp::bulk(message)];
+            for client in clients.values() {
+                client.send(out_of_band(client, &items));
+                sent += 1;
+            }
+        }
+        sent
+    }
+
+    pub fn is_subscribed(&self, id: u64, channel: &str) -> bool {
+        let subs = self.subscriptions.read().unwrap();
+        subs.clients.get(&id).is_some_and(|(channels, _)| channels.contains(channel))
+    }
+
+    // PUBSUB CHANNELS: active channels, optionally matching a pattern
+    pub fn channels(&self, pattern: Option<&str>) -> Vec<String> {
+        let subs = self.subscriptions.read().unwrap();
+        let mut channels: Vec<String> = subs
+            .channels
+            .keys()
+            .filter(|channel| pattern.is_none_or(|pattern| glob_match(pattern.as_bytes(), channel.as_bytes())))
+            .cloned()
+            .collect();
+        channels.sort();
+        channels
+    }
+
+    pub fn subscribers(&self, channel: &str) -> usize {
+        self.subscriptions.read().unwrap().channels.get(channel).map_or(0, HashMap::len)
+    }
+
+    pub fn pattern_count(&self) -> usize {
+        self.subscriptions.read().unwrap().patterns.len()
+    }
+}
+
+// Messages that aren't replies to a command: pushes for RESP3 clients, plain
+// arrays for RESP2 ones
+pub fn out_of_band(client: &Client, items: &[String]) -> String {
+    match client.protocol() {
+        2 => resp::array(items),
+        _ => resp::push(items),
+    }
+}
+
+// Redis glob-style matching: `*`, `?`, `[abc]`, `[^a-z]` and `\` escapes
+pub fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len()).any(|skip| glob_match(rest, &text[skip..])),
+        Some((b'?', rest)) => !text.is_empty() && glob_match(rest, &text[1..]),
+        Some((b'[', rest)) => {
+            let Some((&byte, text_rest)) = text.split_first() else {
+                return false;
+            };
+            let (negate, mut class) = match rest.split_first() {
+                Some((b'^', class)) => (true, class),
+                _ => (false, rest),
+            };
+            let mut matched = false;
+            loop {
+                match class {
+                    // An unterminated class runs to the end of the pattern
+                    [] => break,
+                    [b']', after @ ..] => {
+                        class = after;
+                        break;
+                    }
+                    [b'\\', escaped, after @ ..] => {
+                        matched |= *escaped == byte;
+                        class = after;
+                    }
+                    [start, b'-', end, after @ ..] if *end != b']' => {
+                        let (low, high) = if start <= end { (*start, *end) } else { (*end, *start) };
+                        matched |= (low..=high).contains(&byte);
+                        class = after;
+                    }
+                    [single, after @ ..] => {
+                        matched |= *single == byte;
+                        class = after;
+                    }
+                }
+            }
+            matched != negate && glob_match(class, text_rest)
+        }
+        Some((b'\\', [escaped, rest @ ..])) => text.first() == Some(escaped) && glob_match(rest, &text[1..]),
+        Some((byte, rest)) => text.first() == Some(byte) && glob_match(rest, &text[1..]),
+    }
+}
@@ -0,0 +1,90 @@
+// Keyspace notifications: writes announce themselves on
+// `__keyspace@0__:<key>` (the message is the event) and
+// `__keyevent@0__:<event>` (the message is the key), as selected by the
+// notify-keyspace-events flags.
+
+const KEYSPACE: u16 = 1 << 0;
+const KEYEVENT: u16 = 1 << 1;
+pub const GENERIC: u16 = 1 << 2;
+pub const STRING: u16 = 1 << 3;
+pub const LIST: u16 = 1 << 4;
+pub const SET: u16 = 1 << 5;
+pub const HASH: u16 = 1 << 6;
+pub const ZSET: u16 = 1 << 7;
+pub const EXPIRED: u16 = 1 << 8;
+pub const EVICTED: u16 = 1 << 9;
+pub const STREAM: u16 = 1 << 10;
+pub const MODULE: u16 = 1 << 11;
+pub const KEY_MISS: u16 = 1 << 12;
+pub const NEW: u16 = 1 << 13;
+
+// What `A` stands for; key misses and new keys have to be asked for by name
+const ALL: u16 = GENERIC | STRING | LIST | SET | HASH | ZSET | EXPIRED | EVICTED | STREAM | MODULE;
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct NotifyFlags(u16);
+
+impl NotifyFlags {
+    // Parses a flags string such as "KEA" or "Elx"; unknown letters are an error
+    pub fn parse(flags: &str) -> Option<Self> {
+        let mut bits = 0;
+        for flag in flags.chars() {
+            bits |= match flag {
+                'K' => KEYSPACE,
+                'E' => KEYEVENT,
+                'g' => GENERIC,
+                '$' => STRING,
+                'l' => LIST,
+                's' => SET,
+                'h' => HASH,
+                'z' => ZSET,
+                'x' => EXPIRED,
+                'e' => EVICTED,
+                't' => STREAM,
+                'd' => MODULE,
+                'm' => KEY_MISS,
+                'n' => NEW,
+                'A' => ALL,
+                _ => return None,
+            };
+        }
+        Some(NotifyFlags(bits))
+    }
+
+    // Which of the two channels an event of this class goes to
+    pub fn channels(&self, class: u16) -> (bool, bool) {
+        let wanted = self.0 & class != 0;
+        (wanted && self.0 & KEYSPACE != 0, wanted && self.0 & KEYEVENT != 0)
+    }
+}
+
+// The class, event name and keys a replicated write command notifies about.
+// Every write goes through Database::changed as the command replicas replay,
+// so this is where those commands map to events.
+pub fn command_events<'a>(command: &[&'a str]) -> Vec<(u16, String, &'a str)> {
+    let event = |class: u16, name: &str, key: &'a str| vec![(class, name.to_string(), key)];
+    match command {
+        ["SET", key, ..] => event(STRING, "set", key),
+        ["SETBIT" | "BITFIELD", key, ..] => event(STRING, "setbit", key),
+        ["PFADD" | "PFMERGE", key, ..] => event(STRING, "pfadd", key),
+        ["DEL", key] => event(GENERIC, "del", key),
+        ["RESTORE", key, ..] => event(GENERIC, "restore", key),
+        [name @ ("LPUSH" | "RPUSH" | "LPOP" | "RPOP"), key, ..] => event(LIST, &name.to_lowercase(), key),
+        [name @ ("SADD" | "SREM"), key, ..] => event(SET, &name.to_lowercase(), key),
+        [name @ ("ZADD" | "ZREM"), key, ..] => event(ZSET, &name.to_lowercase(), key),
+        [name @ ("XADD" | "XTRIM" | "XDEL" | "XSETID" | "XCLAIM"), key, ..] => {
+            event(STREAM, &name.to_lowercase(), key)
+        }
+        ["XGROUP", subcommand, key, ..] => event(STREAM, &format!("xgroup-{}", subcommand.to_lowercase()), key),
+        ["JSON.FORGET", key, ..] => event(MODULE, "json.del", key),
+        ["TS.MADD", args @ ..] => args.iter().step_by(3).map(|key| (MODULE, "ts.add".to_string(), *key)).collect(),
+        [name @ ("TS.CREATERULE" | "TS.DELETERULE"), source, dest, ..] => {
+            let name = name.to_lowercase();
+            vec![(MODULE, name.clone(), *source), (MODULE, name, *dest)]
+        }
+        // Indexes aren't keys
+        [name, ..] if name.starts_with("FT.") => Vec::new(),
+        [name, key, ..] if name.contains('.') => event(MODULE, &name.to_lowercase(), key),
+        _ => Vec::new(),
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::VecDeque;
+
+// The most recent bytes of the replication stream, so a replica that reconnects
+// after a short break can continue where it left off instead of a full sync
+pub struct Backlog {
+    buf: VecDeque<u8>,
+    size: usize,
+    // Replication offset of the last byte in the buffer
+    offset: u64,
+}
+
+impl Backlog {
+    pub fn new(size: usize, offset: u64) -> Self {
+        Backlog {
+            buf: VecDeque::new(),
+            size,
+            offset,
+        }
+    }
+
+    pub fn feed(&mut self, data: &[u8]) {
+        self.buf.extend(data);
+        self.offset += data.len() as u64;
+        if self.buf.len() > self.size {
+            let excess = self.buf.len() - self.size;
+            self.buf.drain(..excess);
+        }
+    }
+
+    // Forgets the history, e.g. after a full resync from a new master
+    pub fn reset(&mut self, offset: u64) {
+        self.buf.clear();
+        self.offset = offset;
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn histlen(&self) -> usize {
+        self.buf.len()
+    }
+
+    // Offset of the oldest byte still held
+    pub fn first_byte_offset(&self) -> u64 {
+        self.offset - self.buf.len() as u64 + 1
+    }
+
+    // Everything from `from` (the first offset the replica is missing) to the end,
+    // or None when that part of the stream is no longer in the backlog
+    pub fn read_from(&self, from: u64) -> Option<Vec<u8>> {
+        if from < self.first_byte_offset() || from > self.offset + 1 {
+            return None;
+        }
+        let skip = (from - self.first_byte_offset()) as usize;
+        Some(self.buf.iter().skip(skip).copied().collect())
+    }
+}
@@ -0,0 +1,312 @@
+mod backlog;
+pub mod replica;
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::{MutexGuard, Notify};
+use tokio::task::AbortHandle;
+
+use crate::client::{Client, ClientClass};
+use crate::command::resp;
+use crate::id;
+use backlog::Backlog;
+
+// Replication state shared by both roles. A master feeds every write into the
+// backlog and to its replicas; a replica feeds the stream it receives from its
+// master the same way, so offsets line up and replicas can be chained.
+pub struct Replication {
+    state: Mutex<ReplicationState>,
+    // Held while a replica applies its master's stream or a PSYNC takes a snapshot,
+    // so every write ends up in exactly one of the snapshot and the stream
+    sync_lock: tokio::sync::Mutex<()>,
+    ack_notify: Notify,
+}
+
+struct ReplicationState {
+    replid: String,
+    backlog: Backlog,
+    replicas: Vec<ReplicaInfo>,
+    master: Option<MasterLink>,
+}
+
+struct ReplicaInfo {
+    client: Arc<Client>,
+    ack_offset: u64,
+    last_ack: Instant,
+}
+
+struct MasterLink {
+    host: String,
+    port: u16,
+    status: LinkStatus,
+    last_io: Instant,
+    task: AbortHandle,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum LinkStatus {
+    // Waiting to (re)connect
+    Connect,
+    Connecting,
+    // Receiving the snapshot of a full resync
+    Sync,
+    Connected,
+}
+
+impl LinkStatus {
+    // Name used by ROLE
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkStatus::Connect => "connect",
+            LinkStatus::Connecting => "connecting",
+            LinkStatus::Sync => "sync",
+            LinkStatus::Connected => "connected",
+        }
+    }
+}
+
+// A connected replica as reported by ROLE and INFO replication
+pub struct ReplicaSummary {
+    pub ip: String,
+    pub port: u16,
+    pub offset: u64,
+    pub lag: u64,
+}
+
+pub struct MasterSummary {
+    pub host: String,
+    pub port: u16,
+    pub status: LinkStatus,
+    pub last_io: u64,
+}
+
+impl Replication {
+    pub fn new(backlog_size: usize) -> Self {
+        Replication {
+            state: Mutex::new(ReplicationState {
+                replid: id::random_hex_id(),
+                backlog: Backlog::new(backlog_size, 0),
+                replicas: Vec::new(),
+                master: None,
+            }),
+            sync_lock: tokio::sync::Mutex::new(()),
+            ack_notify: Notify::new(),
+        }
+    }
+
+    pub fn is_replica(&self) -> bool {
+        self.state.lock().unwrap().master.is_some()
+    }
+
+    pub fn replid(&self) -> String {
+        self.state.lock().unwrap().replid.clone()
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.state.lock().unwrap().backlog.offset()
+    }
+
+    pub async fn sync_lock(&self) -> MutexGuard<'_, ()> {
+        self.sync_lock.lock().await
+    }
+
+    // Sends a write executed on this server to the replicas. Replicas don't
+    // propagate their own writes, only what their master sends them.
+    pub fn propagate<A: AsRef<[u8]>>(&self, args: &[A]) {
+        if !self.is_replica() {
+            self.feed(&resp::command_bytes(args));
+        }
+    }
+
+    // Appends raw stream data to the backlog and passes it on to every replica
+    pub fn feed(&self, data: &[u8]) {
+        let mut state = self.state.lock().unwrap();
+        state.backlog.feed(data);
+        // Replicas that went over their output buffer limit or disconnected are dropped
+        state.replicas.retain(|replica| replica.client.send(data.to_vec()));
+    }
+
+    // Answers a PSYNC and turns the client into a replica. Continues from the backlog
+    // when the replica asks for our history and we still have it, otherwise sends
+    // `snapshot` for a full resync. The caller keeps writes out until this returns.
+    pub fn add_replica(
+        &self,
+        client: &Arc<Client>,
+        replid: &str,
+        from: i64,
+        snapshot: impl FnOnce() -> Vec<u8>,
+    ) {
+        let mut state = self.state.lock().unwrap();
+
+        let history = match u64::try_from(from) {
+            Ok(from) if replid == state.replid => state.backlog.read_from(from),
+            _ => None,
+        };
+        match history {
+            Some(data) => {
+                println!("Partial resync of replica {} from offset {}", client.addr, from);
+                client.send(format!("+CONTINUE {}\r\n", state.replid));
+                client.send(data);
+            }
+            None => {
+                println!("Full resync of replica {}", client.addr);
+                let snapshot = snapshot();
+                client.send(format!("+FULLRESYNC {} {}\r\n", state.replid, state.backlog.offset()));
+                // Like an RDB transfer: a bulk length, then the payload without a trailing CRLF
+                client.send(format!("${}\r\n", snapshot.len()));
+                client.send(snapshot);
+            }
+        }
+
+        client.set_class(ClientClass::Replica);
+        state.replicas.push(ReplicaInfo {
+            client: client.clone(),
+            ack_offset: 0,
+            last_ack: Instant::now(),
+        });
+    }
+
+    pub fn remove_replica(&self, client_id: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.replicas.retain(|replica| replica.client.id != client_id);
+    }
+
+    // REPLCONF ACK from a replica
+    pub fn ack(&self, client_id: u64, offset: u64) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(replica) = state.replicas.iter_mut().find(|r| r.client.id == client_id) {
+            replica.ack_offset = replica.ack_offset.max(offset);
+            replica.last_ack = Instant::now();
+        }
+        drop(state);
+        self.ack_notify.notify_waiters();
+    }
+
+    fn acked_replicas(&self, offset: u64) -> usize {
+        let state = self.state.lock().unwrap();
+        state.replicas.iter().filter(|r| r.ack_offset >= offset).count()
+    }
+
+    // WAIT: blocks until `numreplicas` replicas acknowledged every write made so far
+    // or the timeout passes, and returns how many did
+    pub async fn wait(&self, numreplicas: usize, timeout: Option<Duration>) -> usize {
+        let target = self.offset();
+        let deadline = timeout.map(|timeout| tokio::time::Instant::now() + timeout);
+        let mut asked = false;
+
+        loop {
+            let notified = self.ack_notify.notified();
+            let acked = self.acked_replicas(target);
+            if acked >= numreplicas {
+                return acked;
+            }
+            if !asked {
+                // Replicas otherwise only report their offset once a second
+                self.feed(resp::command(&["REPLCONF", "GETACK", "*"]).as_bytes());
+                asked = true;
+            }
+
+            match deadline {
+                Some(deadline) => tokio::select! {
+                    _ = notified => {}
+                    _ = tokio::time::sleep_until(deadline) => return self.acked_replicas(target),
+                },
+                None => notified.await,
+            }
+        }
+    }
+
+    pub fn replicas(&self) -> Vec<ReplicaSummary> {
+        let state = self.state.lock().unwrap();
+        state
+            .replicas
+            .iter()
+            .map(|replica| ReplicaSummary {
+                ip: replica
+                    .client
+                    .addr
+                    .rsplit_once(':')
+                    .map(|(ip, _)| ip.to_string())
+                    .unwrap_or_default(),
+                port: replica.client.listening_port().unwrap_or(0),
+                offset: replica.ack_offset,
+                lag: replica.last_ack.elapsed().as_secs(),
+            })
+            .collect()
+    }
+
+    pub fn master(&self) -> Option<MasterSummary> {
+        let state = self.state.lock().unwrap();
+        state.master.as_ref().map(|master| MasterSummary {
+            host: master.host.clone(),
+            port: master.port,
+            status: master.status,
+            last_io: master.last_io.elapsed().as_secs(),
+        })
+    }
+
+    // Backlog details for INFO replication: (size, first byte offset, histlen)
+    pub fn backlog_info(&self) -> (usize, u64, usize) {
+        let state = self.state.lock().unwrap();
+        let backlog = &state.backlog;
+        (backlog.size(), backlog.first_byte_offset(), backlog.histlen())
+    }
+
+    // Starts following a new master; `start` spawns the link task. Any previous
+    // link is stopped first.
+    pub fn set_master(&self, host: String, port: u16, start: impl FnOnce() -> AbortHandle) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(old) = state.master.take() {
+            old.task.abort();
+        }
+        state.master = Some(MasterLink {
+            host,
+            port,
+            status: LinkStatus::Connect,
+            last_io: Instant::now(),
+            task: start(),
+        });
+    }
+
+    // REPLICAOF NO ONE. The data and offset are kept, but under a new replication
+    // id since our history now diverges from the old master's.
+    pub fn promote(&self) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(old) = state.master.take() {
+            old.task.abort();
+            state.replid = id::random_hex_id();
+        }
+    }
+
+    pub fn set_link_status(&self, status: LinkStatus) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(master) = state.master.as_mut() {
+            master.status = status;
+            master.last_io = Instant::now();
+        }
+    }
+
+    // Records that something arrived from the master
+    pub fn touch_link(&self) {
+        if let Some(master) = self.state.lock().unwrap().master.as_mut() {
+            master.last_io = Instant::now();
+        }
+    }
+
+    // Adopts the master's history after a full resync. Our own replicas have a
+    // stream that no longer matches, so they are disconnected to resync as well.
+    pub fn full_resync(&self, replid: String, offset: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.replid = replid;
+        state.backlog.reset(offset);
+        for replica in state.replicas.drain(..) {
+            replica.client.kill();
+        }
+    }
+
+    // After +CONTINUE the master may announce a new id (e.g. it was promoted)
+    pub fn continue_as(&self, replid: String) {
+        self.state.lock().unwrap().replid = replid;
+    }
+}
@@ -0,0 +1,221 @@
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncBufRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+
+use crate::client::{Client, ClientClass};
+use crate::command::{command_parser, resp};
+use crate::database::Database;
+use crate::replication::LinkStatus;
+use crate::server::protocol::{read_command, read_line, ConnectionError, RequestLimits, INLINE_MAX_SIZE};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const ACK_INTERVAL: Duration = Duration::from_secs(1);
+
+// The master is trusted, its stream isn't held to client request limits
+const MASTER_LIMITS: RequestLimits = RequestLimits {
+    query_buffer: usize::MAX,
+    max_bulk_len: usize::MAX,
+};
+
+// REPLICAOF host port: replaces the current master link (if any) with one to
+// host:port that keeps reconnecting until REPLICAOF NO ONE
+pub fn replicate(db: &Database, host: String, port: u16) {
+    let link_db = db.clone();
+    let link_host = host.clone();
+    db.replication().set_master(host, port, move || {
+        tokio::spawn(run(link_db, link_host, port)).abort_handle()
+    });
+}
+
+async fn run(db: Database, host: String, port: u16) {
+    loop {
+        db.replication().set_link_status(LinkStatus::Connecting);
+        println!("Connecting to master {}:{}", host, port);
+        // Shutdown drops the link wherever it is: connecting, syncing or streaming
+        tokio::select! {
+            result = sync_with_master(&db, &host, port) => {
+                if let Err(e) = result {
+                    println!("Lost connection to master {}:{}: {}", host, port, e);
+                }
+            }
+            _ = db.shutdown().wait() => {
+                println!("Closing the link to master {}:{} for shutdown", host, port);
+                return;
+            }
+        }
+        db.replication().set_link_status(LinkStatus::Connect);
+        tokio::select! {
+            _ = tokio::time::sleep(RECONNECT_DELAY) => {}
+            _ = db.shutdown().wait() => return,
+        }
+    }
+}
+
+// The master link shows up in CLIENT LIST (flag M) for as long as it's connected
+struct MasterClient<'a> {
+    db: &'a Database,
+    client: Arc<Client>,
+}
+
+impl Drop for MasterClient<'_> {
+    fn drop(&mut self) {
+        self.db.clients().unregister(self.client.id);
+    }
+}
+
+// One connection to the master: handshake, PSYNC, then applying the stream until
+// the link breaks
+async fn sync_with_master(db: &Database, host: &str, port: u16) -> Result<(), ConnectionError> {
+    let stream = TcpStream::connect((host, port)).await?;
+    let laddr = stream.local_addr().map(|a| a.to_string()).unwrap_or_default();
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+
+    let listening_port = db.config().port.to_string();
+    request(&mut reader, &mut writer, &["PING"]).await?;
+    request(&mut reader, &mut writer, &["REPLCONF", "listening-port", &listening_port]).await?;
+    request(&mut reader, &mut writer, &["REPLCONF", "capa", "psync2"]).await?;
+
+    let output_limits = db.config().client_output_buffer_limit;
+    let (client, _) = db.clients().register(format!("{}:{}", host, port), laddr, None, output_limits);
+    client.set_class(ClientClass::Master);
+    let master = MasterClient { db, client };
+
+    // Always ask to continue our own history; the master decides if it can
+    let replication = db.replication();
+    let next = (replication.offset() + 1).to_string();
+    let reply = request(&mut reader, &mut writer, &["PSYNC", &replication.replid(), &next]).await?;
+
+    if let Some(rest) = reply.strip_prefix("+FULLRESYNC ") {
+        let (replid, offset) = rest
+            .split_once(' ')
+            .and_then(|(replid, offset)| Some((replid.to_string(), offset.parse::<u64>().ok()?)))
+            .ok_or_else(|| ConnectionError::Protocol(format!("bad PSYNC reply '{}'", reply)))?;
+
+        replication.set_link_status(LinkStatus::Sync);
+        let snapshot = read_snapshot(&mut reader).await?;
+
+        let _sync = replication.sync_lock().await;
+        db.flush();
+        let mut snapshot = snapshot.as_slice();
+        while let Some(args) = read_command(&mut snapshot, MASTER_LIMITS).await? {
+            let _ = command_parser(db, &master.client, &args).await;
+        }
+        replication.full_resync(replid, offset);
+        println!("Full resync from master {}:{} finished", host, port);
+    } else if let Some(rest) = reply.strip_prefix("+CONTINUE") {
+        if !rest.trim().is_empty() {
+            replication.continue_as(rest.trim().to_string());
+        }
+        println!("Partial resync from master {}:{} accepted", host, port);
+    } else {
+        return Err(ConnectionError::Protocol(format!("unexpected PSYNC reply '{}'", reply)));
+    }
+    replication.set_link_status(LinkStatus::Connected);
+
+    // Reading the stream and the periodic ACKs run side by side, since read_command
+    // can't be interrupted without losing what it has read so far
+    let writer = Mutex::new(writer);
+    tokio::try_join!(
+        apply_stream(db, &master.client, &mut reader, &writer),
+        send_acks(db, &writer),
+    )?;
+    Ok(())
+}
+
+async fn apply_stream<R, W>(
+    db: &Database,
+    master: &Arc<Client>,
+    reader: &mut R,
+    writer: &Mutex<W>,
+) -> Result<(), ConnectionError>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    loop {
+        let args = read_command(reader, MASTER_LIMITS)
+            .await?
+            .ok_or_else(|| ConnectionError::Io(io::ErrorKind::UnexpectedEof.into()))?;
+        db.replication().touch_link();
+
+        let _sync = db.replication().sync_lock().await;
+        match args.as_slice() {
+            [] => continue,
+            // The ACK reports the offset before the GETACK itself, which is what WAIT waits for
+            [cmd, sub, ..] if cmd.eq_ignore_ascii_case(b"REPLCONF") && sub.eq_ignore_ascii_case(b"GETACK") => {
+                send_ack(db, writer).await?;
+            }
+            [name, ..] => {
+                master.touch(&String::from_utf8_lossy(name));
+                // Replies go nowhere, the master doesn't read them
+                let _ = command_parser(db, master, &args).await;
+            }
+        }
+        db.replication().feed(&resp::command_bytes(&args));
+    }
+}
+
+async fn send_acks<W>(db: &Database, writer: &Mutex<W>) -> Result<(), ConnectionError>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut interval = tokio::time::interval(ACK_INTERVAL);
+    loop {
+        interval.tick().await;
+        send_ack(db, writer).await?;
+    }
+}
+
+async fn send_ack<W>(db: &Database, writer: &Mutex<W>) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let offset = db.replication().offset().to_string();
+    let ack = resp::command(&["REPLCONF", "ACK", &offset]);
+    writer.lock().await.write_all(ack.as_bytes()).await
+}
+
+// Sends a handshake command and returns the master's single-line reply
+async fn request<R, W>(reader: &mut R, writer: &mut W, args: &[&str]) -> Result<String, ConnectionError>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    writer.write_all(resp::command(args).as_bytes()).await?;
+    let reply = read_reply_line(reader).await?;
+    if reply.starts_with('-') {
+        return Err(ConnectionError::Protocol(format!("master replied '{}' to {}", reply, args[0])));
+    }
+    Ok(reply)
+}
+
+async fn read_reply_line<R>(reader: &mut R) -> Result<String, ConnectionError>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let line = read_line(reader, INLINE_MAX_SIZE)
+        .await?
+        .ok_or_else(|| ConnectionError::Io(io::ErrorKind::UnexpectedEof.into()))?;
+    String::from_utf8(line).map_err(|_| ConnectionError::Protocol("invalid UTF-8 from master".to_string()))
+}
+
+// The full resync payload: `$<len>\r\n` followed by len bytes of commands
+async fn read_snapshot<R>(reader: &mut R) -> Result<Vec<u8>, ConnectionError>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let header = read_reply_line(reader).await?;
+    let len = header
+        .strip_prefix('$')
+        .and_then(|len| len.parse::<usize>().ok())
+        .ok_or_else(|| ConnectionError::Protocol(format!("bad snapshot header '{}'", header)))?;
+
+    let mut data = vec![0; len];
+    reader.read_exact(&mut data).await?;
+    Ok(data)
+}
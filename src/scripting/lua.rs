@@ -0,0 +1,303 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use mlua::{HookTriggers, Lua, LuaOptions, MultiValue, StdLib, Table, Value, VmState};
+
+use crate::client::{Client, ClientClass};
+use crate::command::{self, is_write_command, resp};
+use crate::database::Database;
+
+// How often a running script checks whether it was killed
+const KILL_CHECK_INSTRUCTIONS: u32 = 100_000;
+
+const KILLED: &str = "Script killed by user with SCRIPT KILL...";
+
+// Commands a script can't run: scripting itself, connection and server
+// management, anything that waits on other clients or servers, and anything
+// that would switch the script's client into pub/sub, monitor or RESP3 mode
+const DENIED_COMMANDS: [&str; 20] = [
+    "EVAL", "EVALSHA", "EVAL_RO", "EVALSHA_RO", "SCRIPT", "SHUTDOWN", "CLIENT", "WAIT", "PSYNC",
+    "REPLCONF", "REPLICAOF", "SLAVEOF", "MIGRATE", "ASKING", "SUBSCRIBE", "UNSUBSCRIBE", "PSUBSCRIBE",
+    "PUNSUBSCRIBE", "MONITOR", "HELLO",
+];
+
+// One script invocation, run on a blocking thread
+pub struct Script {
+    pub db: Database,
+    pub client: Arc<Client>,
+    pub sha: String,
+    pub keys: Vec<String>,
+    pub argv: Vec<String>,
+    pub read_only: bool,
+    pub killed: Arc<AtomicBool>,
+    pub wrote: Arc<AtomicBool>,
+    pub runtime: tokio::runtime::Handle,
+}
+
+// An error reply from redis.call, raised through Lua so the script stops and the
+// caller gets the original error
+#[derive(Debug)]
+struct CallError(String);
+
+impl fmt::Display for CallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for CallError {}
+
+impl Script {
+    // Returns the script's reply encoded as RESP
+    pub fn run(self, body: &str) -> Result<Vec<u8>, String> {
+        let killed = self.killed.clone();
+        let sha = self.sha.clone();
+        let lua = Lua::new_with(StdLib::TABLE | StdLib::STRING | StdLib::MATH, LuaOptions::default())
+            .map_err(|e| e.to_string())?;
+        self.setup(&lua).map_err(|e| e.to_string())?;
+
+        let function = lua
+            .load(body)
+            .set_name("=user_script")
+            .into_function()
+            .map_err(|e| single_line(&format!("Error compiling script (new function): {}", message(&e))))?;
+
+        match function.call::<Value>(()) {
+            Ok(value) => Ok(to_reply(&value)),
+            Err(_) if killed.load(Ordering::Relaxed) => Err(KILLED.to_string()),
+            Err(e) => match call_error(&e) {
+                Some(reply) => Ok(error_reply(&reply).into_bytes()),
+                None => Err(single_line(&format!("Error running script (call to f_{}): {}", sha, message(&e)))),
+            },
+        }
+    }
+
+    fn setup(self, lua: &Lua) -> mlua::Result<()> {
+        let globals = lua.globals();
+        globals.set("KEYS", lua.create_sequence_from(self.keys.iter().map(String::as_str))?)?;
+        globals.set("ARGV", lua.create_sequence_from(self.argv.iter().map(String::as_str))?)?;
+        // No file access from scripts
+        globals.set("loadfile", Value::Nil)?;
+        globals.set("dofile", Value::Nil)?;
+
+        let killed = self.killed.clone();
+        lua.set_hook(HookTriggers::new().every_nth_instruction(KILL_CHECK_INSTRUCTIONS), move |_, _| {
+            if killed.load(Ordering::Relaxed) {
+                Err(mlua::Error::RuntimeError(KILLED.to_string()))
+            } else {
+                Ok(VmState::Continue)
+            }
+        })?;
+
+        let redis = lua.create_table()?;
+        let script = Arc::new(self);
+        let call_script = script.clone();
+        redis.set(
+            "call",
+            lua.create_function(move |lua, args: MultiValue| call_script.call(lua, args, false))?,
+        )?;
+        redis.set(
+            "pcall",
+            lua.create_function(move |lua, args: MultiValue| script.call(lua, args, true))?,
+        )?;
+        redis.set(
+            "error_reply",
+            lua.create_function(|lua, msg: String| lua.create_table_from([("err", msg)]))?,
+        )?;
+        redis.set(
+            "status_reply",
+            lua.create_function(|lua, msg: String| lua.create_table_from([("ok", msg)]))?,
+        )?;
+        redis.set("sha1hex", lua.create_function(|_, data: String| Ok(super::sha1_hex(&data)))?)?;
+        redis.set(
+            "log",
+            lua.create_function(|_, (_level, msg): (i64, String)| {
+                println!("Script log: {}", msg);
+                Ok(())
+            })?,
+        )?;
+        for (level, name) in ["LOG_DEBUG", "LOG_VERBOSE", "LOG_NOTICE", "LOG_WARNING"].iter().enumerate() {
+            redis.set(*name, level)?;
+        }
+        globals.set("redis", redis)?;
+
+        // Scripts must keep their state in keys, not in globals
+        lua.load(
+            r#"
+            setmetatable(_G, {
+                __newindex = function(_, name)
+                    error("Script attempted to create global variable '" .. tostring(name) .. "'", 2)
+                end,
+                __index = function(_, name)
+                    error("Script attempted to access nonexistent global variable '" .. tostring(name) .. "'", 2)
+                end,
+            })
+            "#,
+        )
+        .exec()
+    }
+
+    // redis.call / redis.pcall: runs a command through the normal command layer.
+    // Error replies are raised by call and returned as {err=...} by pcall.
+    fn call(&self, lua: &Lua, args: MultiValue, protected: bool) -> mlua::Result<Value> {
+        let mut command = Vec::with_capacity(args.len());
+        for arg in args {
+            match arg {
+                Value::String(s) => command.push(s.as_bytes().to_vec()),
+                Value::Integer(n) => command.push(n.to_string().into_bytes()),
+                Value::Number(n) => command.push(n.to_string().into_bytes()),
+                _ => {
+                    return self.fail(lua, "Lua redis lib command arguments must be strings or integers", protected)
+                }
+            }
+        }
+        let name = match command.first_mut() {
+            Some(name) => {
+                name.make_ascii_uppercase();
+                String::from_utf8_lossy(name).into_owned()
+            }
+            None => return self.fail(lua, "Please specify at least one argument for this redis lib call", protected),
+        };
+
+        if DENIED_COMMANDS.contains(&name.as_str()) {
+            return self.fail(lua, "This Redis command is not allowed from script", protected);
+        }
+        if is_write_command(&name) {
+            if self.read_only {
+                return self.fail(lua, "Write commands are not allowed from read-only scripts.", protected);
+            }
+            let replica = self.db.replication().is_replica() && self.db.config().replica_read_only;
+            if replica && self.client.class() != ClientClass::Master {
+                let error = "READONLY You can't write against a read only replica.".to_string();
+                return self.fail_with(lua, error, protected);
+            }
+            self.wrote.store(true, Ordering::Relaxed);
+        }
+
+        let reply = self
+            .runtime
+            .block_on(command::script_call(&self.db, &self.client, &command))
+            .unwrap_or_else(|e| format!("-ERR {}\r\n", e).into_bytes());
+        match reply.strip_prefix(b"-") {
+            Some(error) => self.fail_with(lua, String::from_utf8_lossy(error).trim_end().to_string(), protected),
+            None => from_reply(lua, &mut reply.as_slice()),
+        }
+    }
+
+    fn fail(&self, lua: &Lua, msg: &str, protected: bool) -> mlua::Result<Value> {
+        self.fail_with(lua, format!("ERR {}", msg), protected)
+    }
+
+    fn fail_with(&self, lua: &Lua, error: String, protected: bool) -> mlua::Result<Value> {
+        if protected {
+            Ok(Value::Table(lua.create_table_from([("err", error)])?))
+        } else {
+            Err(mlua::Error::external(CallError(error)))
+        }
+    }
+}
+
+// Converts a RESP reply from the command layer to what redis.call returns:
+// integers to numbers, bulk strings to strings, arrays to tables, nil to false,
+// and status/error replies to {ok=...}/{err=...} tables
+fn from_reply(lua: &Lua, input: &mut &[u8]) -> mlua::Result<Value> {
+    let bad_reply = || mlua::Error::RuntimeError("malformed reply from command".to_string());
+    let end = input.windows(2).position(|w| w == b"\r\n").ok_or_else(bad_reply)?;
+    let line = std::str::from_utf8(&input[..end]).map_err(|_| bad_reply())?;
+    *input = &input[end + 2..];
+    if line.is_empty() {
+        return Err(bad_reply());
+    }
+
+    let (kind, body) = line.split_at(1);
+    match kind {
+        "+" => Ok(Value::Table(lua.create_table_from([("ok", body)])?)),
+        "-" => Ok(Value::Table(lua.create_table_from([("err", body)])?)),
+        ":" => Ok(Value::Integer(body.parse().map_err(|_| bad_reply())?)),
+        "$" => {
+            let len = body.parse::<i64>().map_err(|_| bad_reply())?;
+            if len < 0 {
+                return Ok(Value::Boolean(false));
+            }
+            let len = len as usize;
+            let value = input.get(..len).ok_or_else(bad_reply)?;
+            *input = input.get(len + 2..).ok_or_else(bad_reply)?;
+            Ok(Value::String(lua.create_string(value)?))
+        }
+        "*" => {
+            let len = body.parse::<i64>().map_err(|_| bad_reply())?;
+            if len < 0 {
+                return Ok(Value::Boolean(false));
+            }
+            let table = lua.create_table()?;
+            for i in 1..=len {
+                table.raw_set(i, from_reply(lua, input)?)?;
+            }
+            Ok(Value::Table(table))
+        }
+        _ => Err(bad_reply()),
+    }
+}
+
+// Converts a script's return value to RESP, the reverse of from_reply. Numbers
+// are truncated to integers and arrays stop at the first nil, like in Redis.
+// Lua strings are bytes, so they go out unchanged.
+fn to_reply(value: &Value) -> Vec<u8> {
+    match value {
+        Value::Boolean(true) => resp::integer(1).into_bytes(),
+        Value::Integer(n) => resp::integer(*n).into_bytes(),
+        Value::Number(n) => resp::integer(*n as i64).into_bytes(),
+        Value::String(s) => resp::bulk_bytes(&s.as_bytes()),
+        Value::Table(table) => table_reply(table),
+        Value::Error(e) => error_reply(&e.to_string()).into_bytes(),
+        _ => resp::null_bulk().into_bytes(),
+    }
+}
+
+fn table_reply(table: &Table) -> Vec<u8> {
+    if let Ok(Value::String(err)) = table.raw_get::<Value>("err") {
+        return error_reply(&err.to_string_lossy()).into_bytes();
+    }
+    if let Ok(Value::String(ok)) = table.raw_get::<Value>("ok") {
+        return format!("+{}\r\n", single_line(&ok.to_string_lossy())).into_bytes();
+    }
+
+    let mut items = Vec::new();
+    for i in 1.. {
+        match table.raw_get::<Value>(i) {
+            Ok(Value::Nil) | Err(_) => break,
+            Ok(value) => items.push(to_reply(&value)),
+        }
+    }
+    let mut out = format!("*{}\r\n", items.len()).into_bytes();
+    out.extend(items.concat());
+    out
+}
+
+fn error_reply(error: &str) -> String {
+    format!("-{}\r\n", single_line(error))
+}
+
+fn single_line(text: &str) -> String {
+    text.replace(['\r', '\n'], " ")
+}
+
+// The error reply a failed redis.call raised, if that's what stopped the script
+fn call_error(error: &mlua::Error) -> Option<String> {
+    match error {
+        mlua::Error::ExternalError(e) => e.downcast_ref::<CallError>().map(|e| e.0.clone()),
+        mlua::Error::CallbackError { cause, .. } => call_error(cause),
+        _ => None,
+    }
+}
+
+// The Lua error message without mlua's decorations and tracebacks
+fn message(error: &mlua::Error) -> String {
+    match error {
+        mlua::Error::RuntimeError(msg) => msg.split("\nstack traceback:").next().unwrap_or(msg).to_string(),
+        mlua::Error::SyntaxError { message, .. } => message.clone(),
+        mlua::Error::CallbackError { cause, .. } => message(cause),
+        other => other.to_string(),
+    }
+}
@@ -0,0 +1,186 @@
+mod lua;
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use sha1::{Digest, Sha1};
+use tokio::sync::{Notify, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::client::Client;
+use crate::database::Database;
+
+pub const BUSY: &str =
+    "-BUSY Redis is busy running a script. You can only call SCRIPT KILL or SHUTDOWN NOSAVE.\r\n";
+
+// Script cache and execution state. Scripts run one at a time with the keyspace
+// to themselves: commands hold `exec` for reading while they run and a script
+// holds it for writing. Once a script has run longer than busy-reply-threshold,
+// commands waiting for it are answered with -BUSY instead.
+pub struct Scripting {
+    scripts: Mutex<HashMap<String, String>>,
+    exec: RwLock<()>,
+    running: Mutex<Option<RunningScript>>,
+    busy: AtomicBool,
+    busy_notify: Notify,
+}
+
+// EVAL/EVALSHA arguments, with the body already looked up
+pub struct Invocation {
+    pub sha: String,
+    pub body: String,
+    pub keys: Vec<String>,
+    pub argv: Vec<String>,
+    pub read_only: bool,
+}
+
+#[derive(Clone)]
+struct RunningScript {
+    killed: Arc<AtomicBool>,
+    wrote: Arc<AtomicBool>,
+}
+
+impl Scripting {
+    pub fn new() -> Self {
+        Scripting {
+            scripts: Mutex::new(HashMap::new()),
+            exec: RwLock::new(()),
+            running: Mutex::new(None),
+            busy: AtomicBool::new(false),
+            busy_notify: Notify::new(),
+        }
+    }
+
+    // SCRIPT LOAD (and every EVAL): caches the body under its SHA1
+    pub fn load(&self, body: &str) -> String {
+        let sha = sha1_hex(body);
+        self.scripts.lock().unwrap().insert(sha.clone(), body.to_string());
+        sha
+    }
+
+    pub fn get(&self, sha: &str) -> Option<String> {
+        self.scripts.lock().unwrap().get(&sha.to_lowercase()).cloned()
+    }
+
+    pub fn exists(&self, sha: &str) -> bool {
+        self.scripts.lock().unwrap().contains_key(&sha.to_lowercase())
+    }
+
+    pub fn flush(&self) {
+        self.scripts.lock().unwrap().clear();
+    }
+
+    pub fn cached_scripts(&self) -> usize {
+        self.scripts.lock().unwrap().len()
+    }
+
+    // Waits for `fut` unless a script is (or becomes) busy
+    async fn unless_busy<F: Future>(&self, fut: F) -> Option<F::Output> {
+        let notified = self.busy_notify.notified();
+        if self.busy.load(Ordering::Relaxed) {
+            return None;
+        }
+        tokio::select! {
+            output = fut => Some(output),
+            _ = notified => None,
+        }
+    }
+
+    // Taken by every command; None means a busy script is running
+    pub async fn enter(&self) -> Option<RwLockReadGuard<'_, ()>> {
+        self.unless_busy(self.exec.read()).await
+    }
+
+    // Keeps every other command out, for MIGRATE between dumping its keys and
+    // deleting them; None means a busy script is running
+    pub async fn exclusive(&self) -> Option<RwLockWriteGuard<'_, ()>> {
+        self.unless_busy(self.exec.write()).await
+    }
+
+    // Runs a script with exclusive access to the keyspace and returns its reply.
+    // It runs in a task of its own, so a caller that disconnects can't release the
+    // keyspace while Lua is still running.
+    pub async fn run(db: &Database, client: &Arc<Client>, invocation: Invocation) -> Result<Vec<u8>, String> {
+        let (db, client) = (db.clone(), client.clone());
+        tokio::spawn(async move { db.scripting().execute(&db, &client, invocation).await })
+            .await
+            .unwrap_or_else(|e| Err(format!("Error running script: {}", e)))
+    }
+
+    async fn execute(&self, db: &Database, client: &Arc<Client>, invocation: Invocation) -> Result<Vec<u8>, String> {
+        let _exec = match self.unless_busy(self.exec.write()).await {
+            Some(exec) => exec,
+            None => return Ok(BUSY.as_bytes().to_vec()),
+        };
+
+        let running = RunningScript {
+            killed: Arc::new(AtomicBool::new(false)),
+            wrote: Arc::new(AtomicBool::new(false)),
+        };
+        *self.running.lock().unwrap() = Some(running.clone());
+
+        let threshold = Duration::from_millis(db.config().busy_reply_threshold);
+        let script = lua::Script {
+            db: db.clone(),
+            client: client.clone(),
+            sha: invocation.sha,
+            keys: invocation.keys,
+            argv: invocation.argv,
+            read_only: invocation.read_only,
+            killed: running.killed,
+            wrote: running.wrote,
+            runtime: tokio::runtime::Handle::current(),
+        };
+        // Lua runs synchronously, so it gets a thread of its own while this task
+        // watches the clock
+        let body = invocation.body;
+        let mut task = tokio::task::spawn_blocking(move || script.run(&body));
+        let result = tokio::select! {
+            result = &mut task => result,
+            _ = tokio::time::sleep(threshold) => {
+                println!("Slow script detected: still running after {} ms", threshold.as_millis());
+                self.busy.store(true, Ordering::Relaxed);
+                self.busy_notify.notify_waiters();
+                task.await
+            }
+        };
+
+        self.busy.store(false, Ordering::Relaxed);
+        *self.running.lock().unwrap() = None;
+        result.unwrap_or_else(|e| Err(format!("Error running script: {}", e)))
+    }
+
+    // SCRIPT KILL. A script that already wrote can't be stopped without leaving
+    // its changes half done.
+    pub fn kill(&self) -> String {
+        match self.running.lock().unwrap().as_ref() {
+            None => "-NOTBUSY No scripts in execution right now.\r\n".to_string(),
+            Some(script) if script.wrote.load(Ordering::Relaxed) => {
+                "-UNKILLABLE Sorry the script already executed write commands against the dataset. \
+                 You can either wait the script termination or kill the server in a hard way using \
+                 the SHUTDOWN NOSAVE command.\r\n"
+                    .to_string()
+            }
+            Some(script) => {
+                script.killed.store(true, Ordering::Relaxed);
+                "+OK\r\n".to_string()
+            }
+        }
+    }
+
+    // Stops the running script whatever it did, so shutdown doesn't wait for it
+    pub fn abort(&self) {
+        if let Some(script) = self.running.lock().unwrap().as_ref() {
+            script.killed.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+pub fn sha1_hex(data: &str) -> String {
+    Sha1::digest(data.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
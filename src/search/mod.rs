@@ -0,0 +1,420 @@
+pub mod query;
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::RwLock;
+
+use ordered_float::OrderedFloat;
+use serde_json::Value;
+
+use crate::database::json::{self, Path};
+use query::{Bound, Query};
+
+pub const UNKNOWN_INDEX: &str = "Unknown index name";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum FieldType {
+    Text,
+    Tag,
+    Numeric,
+}
+
+impl FieldType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FieldType::Text => "TEXT",
+            FieldType::Tag => "TAG",
+            FieldType::Numeric => "NUMERIC",
+        }
+    }
+}
+
+// A schema field: the JSON path it's read from and the name queries use
+pub struct Field {
+    pub path: String,
+    pub name: String,
+    pub kind: FieldType,
+    pub weight: f64,
+    pub separator: char,
+    pub sortable: bool,
+    parsed: Path,
+}
+
+impl Field {
+    pub fn new(path: &str, name: &str, kind: FieldType) -> Result<Field, String> {
+        Ok(Field {
+            path: path.to_string(),
+            name: name.to_string(),
+            kind,
+            weight: 1.0,
+            separator: ',',
+            sortable: false,
+            parsed: Path::parse(path)?.to_jsonpath(),
+        })
+    }
+
+    // What the document holds at the field's path, as this field indexes it
+    fn extract(&self, doc: &Value) -> Extracted {
+        let mut values = Vec::new();
+        for location in self.parsed.find(doc) {
+            match json::get(doc, &location) {
+                Some(Value::Array(items)) => values.extend(items.iter()),
+                Some(value) => values.push(value),
+                None => {}
+            }
+        }
+        match self.kind {
+            FieldType::Text => {
+                let texts: Vec<&str> = values.iter().filter_map(|value| value.as_str()).collect();
+                let mut terms = HashMap::new();
+                for term in texts.iter().flat_map(|text| query::tokenize(text)) {
+                    *terms.entry(term).or_insert(0) += 1;
+                }
+                Extracted::Text { terms, text: texts.join(" ") }
+            }
+            FieldType::Tag => {
+                let mut tags: Vec<String> = values
+                    .iter()
+                    .filter_map(|value| value.as_str())
+                    .flat_map(|text| text.split(self.separator))
+                    .map(|tag| tag.trim().to_lowercase())
+                    .filter(|tag| !tag.is_empty())
+                    .collect();
+                tags.sort();
+                tags.dedup();
+                Extracted::Tag(tags)
+            }
+            FieldType::Numeric => Extracted::Numeric(values.iter().filter_map(|value| value.as_f64()).collect()),
+        }
+    }
+}
+
+// One field of one document, kept so it can be taken out of the postings again
+enum Extracted {
+    Text { terms: HashMap<String, u32>, text: String },
+    Tag(Vec<String>),
+    Numeric(Vec<f64>),
+}
+
+impl Extracted {
+    fn sort_key(&self) -> Option<SortKey> {
+        match self {
+            Extracted::Text { text, .. } if !text.is_empty() => Some(SortKey::Text(text.to_lowercase())),
+            Extracted::Tag(tags) if !tags.is_empty() => Some(SortKey::Text(tags.join(","))),
+            Extracted::Numeric(numbers) => numbers.first().map(|n| SortKey::Number(OrderedFloat(*n))),
+            _ => None,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum SortKey {
+    Number(OrderedFloat<f64>),
+    Text(String),
+}
+
+// Per field: term -> key -> term frequency, tag -> keys, or (number, key) pairs
+enum Postings {
+    Text(BTreeMap<String, HashMap<String, u32>>),
+    Tag(HashMap<String, HashSet<String>>),
+    Numeric(BTreeSet<(OrderedFloat<f64>, String)>),
+}
+
+pub struct Index {
+    pub name: String,
+    pub prefixes: Vec<String>,
+    pub fields: Vec<Field>,
+    // The FT.CREATE arguments, replayed on replicas
+    pub definition: Vec<String>,
+    docs: HashMap<String, Vec<Extracted>>,
+    postings: Vec<Postings>,
+}
+
+impl Index {
+    pub fn new(name: &str, prefixes: Vec<String>, fields: Vec<Field>, definition: Vec<String>) -> Index {
+        let postings = fields
+            .iter()
+            .map(|field| match field.kind {
+                FieldType::Text => Postings::Text(BTreeMap::new()),
+                FieldType::Tag => Postings::Tag(HashMap::new()),
+                FieldType::Numeric => Postings::Numeric(BTreeSet::new()),
+            })
+            .collect();
+        Index { name: name.to_string(), prefixes, fields, definition, docs: HashMap::new(), postings }
+    }
+
+    pub fn covers(&self, key: &str) -> bool {
+        self.prefixes.is_empty() || self.prefixes.iter().any(|prefix| key.starts_with(prefix.as_str()))
+    }
+
+    pub fn num_docs(&self) -> usize {
+        self.docs.len()
+    }
+
+    pub fn num_terms(&self) -> usize {
+        self.postings
+            .iter()
+            .map(|postings| match postings {
+                Postings::Text(terms) => terms.len(),
+                _ => 0,
+            })
+            .sum()
+    }
+
+    pub fn keys(&self) -> Vec<String> {
+        self.docs.keys().cloned().collect()
+    }
+
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    // Indexes the document under `key`, replacing what was indexed for it before
+    fn update(&mut self, key: &str, doc: Option<&Value>) {
+        self.remove(key);
+        let Some(doc) = doc else { return };
+
+        let values: Vec<Extracted> = self.fields.iter().map(|field| field.extract(doc)).collect();
+        for (value, postings) in values.iter().zip(self.postings.iter_mut()) {
+            match (value, postings) {
+                (Extracted::Text { terms, .. }, Postings::Text(index)) => {
+                    for (term, count) in terms {
+                        index.entry(term.clone()).or_default().insert(key.to_string(), *count);
+                    }
+                }
+                (Extracted::Tag(tags), Postings::Tag(index)) => {
+                    for tag in tags {
+                        index.entry(tag.clone()).or_default().insert(key.to_string());
+                    }
+                }
+                (Extracted::Numeric(numbers), Postings::Numeric(index)) => {
+                    for n in numbers {
+                        index.insert((OrderedFloat(*n), key.to_string()));
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.docs.insert(key.to_string(), values);
+    }
+
+    fn remove(&mut self, key: &str) {
+        let Some(values) = self.docs.remove(key) else { return };
+        for (value, postings) in values.iter().zip(self.postings.iter_mut()) {
+            match (value, postings) {
+                (Extracted::Text { terms, .. }, Postings::Text(index)) => {
+                    for term in terms.keys() {
+                        if let Some(keys) = index.get_mut(term) {
+                            keys.remove(key);
+                            if keys.is_empty() {
+                                index.remove(term);
+                            }
+                        }
+                    }
+                }
+                (Extracted::Tag(tags), Postings::Tag(index)) => {
+                    for tag in tags {
+                        if let Some(keys) = index.get_mut(tag) {
+                            keys.remove(key);
+                            if keys.is_empty() {
+                                index.remove(tag);
+                            }
+                        }
+                    }
+                }
+                (Extracted::Numeric(numbers), Postings::Numeric(index)) => {
+                    for n in numbers {
+                        index.remove(&(OrderedFloat(*n), key.to_string()));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // The documents matching the query with their scores. Text matches score
+    // TF-IDF times the field weight, everything else scores nothing.
+    pub fn evaluate(&self, query: &Query) -> Result<HashMap<String, f64>, String> {
+        match query {
+            Query::All => Ok(self.docs.keys().map(|key| (key.clone(), 0.0)).collect()),
+            Query::Term { field, term, prefix } => {
+                let fields: Vec<usize> = match field {
+                    Some(name) => vec![self.position(name, FieldType::Text)?],
+                    None => (0..self.fields.len()).filter(|i| self.fields[*i].kind == FieldType::Text).collect(),
+                };
+                let mut scores = HashMap::new();
+                for i in fields {
+                    let Postings::Text(index) = &self.postings[i] else { continue };
+                    let matching: Vec<&HashMap<String, u32>> = if *prefix {
+                        index
+                            .range(term.clone()..)
+                            .take_while(|(candidate, _)| candidate.starts_with(term.as_str()))
+                            .map(|(_, keys)| keys)
+                            .collect()
+                    } else {
+                        index.get(term).into_iter().collect()
+                    };
+                    for keys in matching {
+                        let idf = (1.0 + self.docs.len() as f64 / keys.len() as f64).ln();
+                        for (key, count) in keys {
+                            *scores.entry(key.clone()).or_insert(0.0) += *count as f64 * idf * self.fields[i].weight;
+                        }
+                    }
+                }
+                Ok(scores)
+            }
+            Query::Range { field, min, max } => {
+                let i = self.position(field, FieldType::Numeric)?;
+                let Postings::Numeric(index) = &self.postings[i] else { unreachable!() };
+                Ok(in_range(index, *min, *max).map(|key| (key.clone(), 0.0)).collect())
+            }
+            Query::Tags { field, tags } => {
+                let i = self.position(field, FieldType::Tag)?;
+                let Postings::Tag(index) = &self.postings[i] else { unreachable!() };
+                Ok(tags
+                    .iter()
+                    .filter_map(|tag| index.get(tag))
+                    .flatten()
+                    .map(|key| (key.clone(), 0.0))
+                    .collect())
+            }
+            Query::And(queries) => {
+                let mut result: Option<HashMap<String, f64>> = None;
+                for query in queries {
+                    let scores = self.evaluate(query)?;
+                    result = Some(match result {
+                        None => scores,
+                        Some(result) => result
+                            .into_iter()
+                            .filter_map(|(key, score)| scores.get(&key).map(|other| (key, score + other)))
+                            .collect(),
+                    });
+                }
+                Ok(result.unwrap_or_default())
+            }
+            Query::Or(queries) => {
+                let mut result = HashMap::new();
+                for query in queries {
+                    for (key, score) in self.evaluate(query)? {
+                        *result.entry(key).or_insert(0.0) += score;
+                    }
+                }
+                Ok(result)
+            }
+            Query::Not(query) => {
+                let excluded = self.evaluate(query)?;
+                Ok(self
+                    .docs
+                    .keys()
+                    .filter(|key| !excluded.contains_key(*key))
+                    .map(|key| (key.clone(), 0.0))
+                    .collect())
+            }
+        }
+    }
+
+    fn position(&self, name: &str, kind: FieldType) -> Result<usize, String> {
+        let i = self
+            .fields
+            .iter()
+            .position(|field| field.name == name)
+            .ok_or_else(|| format!("Unknown field `{}`", name))?;
+        if self.fields[i].kind != kind {
+            return Err(format!("Field `{}` is not a {} field", name, kind.name()));
+        }
+        Ok(i)
+    }
+
+    // Orders matches by a field, documents without it last, or by score, best
+    // first. Ties go by key so results are stable.
+    pub fn sort(&self, matches: HashMap<String, f64>, by: Option<(&str, bool)>) -> Result<Vec<(String, f64)>, String> {
+        let mut matches: Vec<(String, f64)> = matches.into_iter().collect();
+        match by {
+            Some((name, ascending)) => {
+                let i = self
+                    .fields
+                    .iter()
+                    .position(|field| field.name == name)
+                    .ok_or_else(|| format!("Property `{}` not loaded nor in schema", name))?;
+                let sort_key = |key: &str| self.docs.get(key).and_then(|values| values[i].sort_key());
+                matches.sort_by(|(a, _), (b, _)| {
+                    let ordering = match (sort_key(a), sort_key(b)) {
+                        (Some(x), Some(y)) if ascending => x.cmp(&y),
+                        (Some(x), Some(y)) => y.cmp(&x),
+                        (Some(_), None) => Ordering::Less,
+                        (None, Some(_)) => Ordering::Greater,
+                        (None, None) => Ordering::Equal,
+                    };
+                    ordering.then_with(|| a.cmp(b))
+                });
+            }
+            None => matches.sort_by(|(a, x), (b, y)| y.total_cmp(x).then_with(|| a.cmp(b))),
+        }
+        Ok(matches)
+    }
+}
+
+fn in_range(index: &BTreeSet<(OrderedFloat<f64>, String)>, min: Bound, max: Bound) -> impl Iterator<Item = &String> {
+    let start = (OrderedFloat(min.value), String::new());
+    index
+        .range(start..)
+        .take_while(move |(n, _)| max.above(n.0))
+        .filter(move |(n, _)| min.below(n.0))
+        .map(|(_, key)| key)
+}
+
+// The indexes, by name. `Database` calls `update` whenever a JSON document is
+// written or deleted, holding the JSON map's lock so indexes see writes in order.
+pub struct Search {
+    indexes: RwLock<BTreeMap<String, Index>>,
+}
+
+impl Search {
+    pub fn new() -> Self {
+        Search { indexes: RwLock::new(BTreeMap::new()) }
+    }
+
+    // FT.CREATE: adds the index and indexes the documents it covers
+    pub fn create<'a>(&self, mut index: Index, docs: impl Iterator<Item = (&'a String, &'a Value)>) -> Result<(), String> {
+        let mut indexes = self.indexes.write().unwrap();
+        if indexes.contains_key(&index.name) {
+            return Err("Index already exists".to_string());
+        }
+        for (key, doc) in docs {
+            if index.covers(key) {
+                index.update(key, Some(doc));
+            }
+        }
+        indexes.insert(index.name.clone(), index);
+        Ok(())
+    }
+
+    // FT.DROPINDEX: returns the keys the index covered
+    pub fn drop_index(&self, name: &str) -> Option<Vec<String>> {
+        self.indexes.write().unwrap().remove(name).map(|index| index.keys())
+    }
+
+    pub fn update(&self, key: &str, doc: Option<&Value>) {
+        for index in self.indexes.write().unwrap().values_mut() {
+            if index.covers(key) {
+                index.update(key, doc);
+            }
+        }
+    }
+
+    pub fn read<R>(&self, name: &str, f: impl FnOnce(&Index) -> R) -> Option<R> {
+        self.indexes.read().unwrap().get(name).map(f)
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.indexes.read().unwrap().keys().cloned().collect()
+    }
+
+    pub fn definitions(&self) -> Vec<Vec<String>> {
+        self.indexes.read().unwrap().values().map(|index| index.definition.clone()).collect()
+    }
+
+    pub fn clear(&self) {
+        self.indexes.write().unwrap().clear();
+    }
+}
@@ -0,0 +1,270 @@
+// The FT.SEARCH query language, a subset of RediSearch's:
+//   hello world          documents with both words in their TEXT fields
+//   hello | world        either word
+//   -hello               documents without the word
+//   hel*                 words starting with "hel"
+//   "hello world"        both words (positions aren't indexed, so not as a phrase)
+//   @title:hello         the word in one field, `@title:(a | b)` for a group
+//   @price:[10 (20]      numbers in a range, `(` excluding the bound, -inf/+inf
+//   @tags:{red | blue}   documents with any of the tags
+//   *                    every document
+// Parentheses group, and intersection binds tighter than union.
+
+pub enum Query {
+    All,
+    Term { field: Option<String>, term: String, prefix: bool },
+    Range { field: String, min: Bound, max: Bound },
+    Tags { field: String, tags: Vec<String> },
+    And(Vec<Query>),
+    Or(Vec<Query>),
+    Not(Box<Query>),
+}
+
+#[derive(Clone, Copy)]
+pub struct Bound {
+    pub value: f64,
+    pub inclusive: bool,
+}
+
+impl Bound {
+    pub fn below(&self, n: f64) -> bool {
+        self.value < n || (self.inclusive && self.value == n)
+    }
+
+    pub fn above(&self, n: f64) -> bool {
+        self.value > n || (self.inclusive && self.value == n)
+    }
+}
+
+pub fn parse(text: &str) -> Result<Query, String> {
+    let mut parser = Parser { chars: text.chars().collect(), pos: 0, depth: 0 };
+    let query = parser.union(None)?;
+    parser.skip_spaces();
+    match parser.peek() {
+        None => Ok(query),
+        Some(_) => Err(parser.error()),
+    }
+}
+
+// Lowercased words, the same way documents are split into terms
+pub fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+// Parsing recurses for each `(` and `-`, so deeper queries are rejected rather
+// than overflowing the stack
+const MAX_DEPTH: usize = 128;
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    // Groups and negations around the current position
+    depth: usize,
+}
+
+impl Parser {
+    fn error(&self) -> String {
+        format!("Syntax error at offset {} near '{}'", self.pos, self.rest())
+    }
+
+    fn rest(&self) -> String {
+        self.chars[self.pos.min(self.chars.len())..].iter().take(16).collect()
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let matches = self.peek() == Some(c);
+        if matches {
+            self.pos += 1;
+        }
+        matches
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.error())
+        }
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn union(&mut self, field: Option<&str>) -> Result<Query, String> {
+        let mut alternatives = vec![self.intersection(field)?];
+        loop {
+            self.skip_spaces();
+            if !self.eat('|') {
+                break;
+            }
+            alternatives.push(self.intersection(field)?);
+        }
+        Ok(if alternatives.len() == 1 { alternatives.remove(0) } else { Query::Or(alternatives) })
+    }
+
+    fn intersection(&mut self, field: Option<&str>) -> Result<Query, String> {
+        let mut items = Vec::new();
+        loop {
+            self.skip_spaces();
+            match self.peek() {
+                None | Some(')' | '|') => break,
+                Some(_) => items.push(self.unary(field)?),
+            }
+        }
+        match items.len() {
+            0 => Err(self.error()),
+            1 => Ok(items.remove(0)),
+            _ => Ok(Query::And(items)),
+        }
+    }
+
+    fn unary(&mut self, field: Option<&str>) -> Result<Query, String> {
+        if self.depth == MAX_DEPTH {
+            return Err(format!("Query nested deeper than {} levels at offset {}", MAX_DEPTH, self.pos));
+        }
+        self.depth += 1;
+        let query = self.unary_inner(field);
+        self.depth -= 1;
+        query
+    }
+
+    fn unary_inner(&mut self, field: Option<&str>) -> Result<Query, String> {
+        if self.eat('-') {
+            return Ok(Query::Not(Box::new(self.unary(field)?)));
+        }
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let query = self.union(field)?;
+                self.skip_spaces();
+                self.expect(')')?;
+                Ok(query)
+            }
+            Some('@') if field.is_none() => {
+                self.pos += 1;
+                let name = self.word();
+                if name.is_empty() {
+                    return Err(self.error());
+                }
+                self.expect(':')?;
+                self.skip_spaces();
+                self.field_query(&name)
+            }
+            Some('"') => self.phrase(field),
+            Some('*') if field.is_none() => {
+                self.pos += 1;
+                Ok(Query::All)
+            }
+            _ => self.term(field),
+        }
+    }
+
+    fn field_query(&mut self, field: &str) -> Result<Query, String> {
+        match self.peek() {
+            Some('[') => self.range(field),
+            Some('{') => self.tags(field),
+            _ => self.unary(Some(field)),
+        }
+    }
+
+    fn word(&mut self) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_') {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    fn term(&mut self, field: Option<&str>) -> Result<Query, String> {
+        let term = self.word().to_lowercase();
+        if term.is_empty() {
+            return Err(self.error());
+        }
+        let prefix = self.eat('*');
+        Ok(Query::Term { field: field.map(str::to_string), term, prefix })
+    }
+
+    fn phrase(&mut self, field: Option<&str>) -> Result<Query, String> {
+        self.pos += 1;
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c != '"') {
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        self.expect('"')?;
+        let mut terms: Vec<Query> = tokenize(&text)
+            .map(|term| Query::Term { field: field.map(str::to_string), term, prefix: false })
+            .collect();
+        match terms.len() {
+            0 => Err(self.error()),
+            1 => Ok(terms.remove(0)),
+            _ => Ok(Query::And(terms)),
+        }
+    }
+
+    // [min max], each optionally prefixed with `(` to exclude it
+    fn range(&mut self, field: &str) -> Result<Query, String> {
+        self.pos += 1;
+        let min = self.bound()?;
+        let max = self.bound()?;
+        self.skip_spaces();
+        self.expect(']')?;
+        Ok(Query::Range { field: field.to_string(), min, max })
+    }
+
+    fn bound(&mut self) -> Result<Bound, String> {
+        self.skip_spaces();
+        let inclusive = !self.eat('(');
+        let start = self.pos;
+        while self.peek().is_some_and(|c| !c.is_whitespace() && c != ']' && c != ',') {
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        self.eat(',');
+        let value = match text.to_lowercase().as_str() {
+            "-inf" => f64::NEG_INFINITY,
+            "inf" | "+inf" => f64::INFINITY,
+            number => number.parse::<f64>().map_err(|_| format!("Bad numeric range bound: {}", text))?,
+        };
+        Ok(Bound { value, inclusive })
+    }
+
+    // {a | b c | d\,e}. Tags are matched case-insensitively; a backslash escapes
+    // the next character.
+    fn tags(&mut self, field: &str) -> Result<Query, String> {
+        self.pos += 1;
+        let mut tags = Vec::new();
+        let mut tag = String::new();
+        loop {
+            match self.peek().ok_or_else(|| self.error())? {
+                '\\' => {
+                    self.pos += 1;
+                    tag.push(self.peek().ok_or_else(|| self.error())?);
+                }
+                c @ ('|' | '}') => {
+                    let trimmed = tag.trim().to_lowercase();
+                    if trimmed.is_empty() {
+                        return Err(self.error());
+                    }
+                    tags.push(trimmed);
+                    tag.clear();
+                    if c == '}' {
+                        self.pos += 1;
+                        return Ok(Query::Tags { field: field.to_string(), tags });
+                    }
+                }
+                c => tag.push(c),
+            }
+            self.pos += 1;
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use std::io;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, WriteHalf};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use crate::client::{Client, ClientClass};
+use crate::command::{command_parser, is_paused_by_write};
+use crate::database::stats::Stats;
+use crate::database::Database;
+use crate::server::protocol::{read_command, ConnectionError, RequestLimits};
+
+// A connection's place among maxclients. It's taken as soon as the connection is
+// accepted, so clients still in their TLS handshake count too, and given back
+// when dropped.
+pub struct ClientSlot {
+    stats: Arc<Stats>,
+}
+
+impl ClientSlot {
+    // None once maxclients connections are open
+    pub fn take(db: &Database, client_addr: &str) -> Option<ClientSlot> {
+        let stats = db.stats().clone();
+        let maxclients = db.config().maxclients;
+        if stats.connected_clients.fetch_add(1, Ordering::Relaxed) >= maxclients {
+            stats.connected_clients.fetch_sub(1, Ordering::Relaxed);
+            Stats::incr(&stats.rejected_connections);
+            println!("Rejecting {}: max number of clients reached", client_addr);
+            return None;
+        }
+        Some(ClientSlot { stats })
+    }
+}
+
+impl Drop for ClientSlot {
+    fn drop(&mut self) {
+        self.stats.connected_clients.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// Tells a plain text client that got no slot why it's being closed
+pub async fn reject<S>(mut stream: S)
+where
+    S: AsyncWrite + Unpin,
+{
+    // The connection is being dropped anyway, a failed write changes nothing
+    let _ = stream.write_all(b"-ERR max number of clients reached\r\n").await;
+    let _ = stream.shutdown().await;
+}
+
+// Serves one client until it disconnects, is killed or misbehaves. Every error ends
+// up here as a logged close of this connection only.
+pub async fn handle_connection<S>(
+    stream: S,
+    client_addr: String,
+    laddr: String,
+    user: Option<String>,
+    _slot: ClientSlot,
+    db: Arc<Database>,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let stats = db.stats().clone();
+    let (reader, writer) = tokio::io::split(stream);
+    let mut reader = BufReader::new(reader);
+
+    let (limits, output_limits) = {
+        let config = db.config();
+        let limits = RequestLimits {
+            query_buffer: config.client_query_buffer_limit,
+            max_bulk_len: config.proto_max_bulk_len,
+        };
+        (limits, config.client_output_buffer_limit)
+    };
+
+    Stats::incr(&stats.total_connections_received);
+    let (client, output) = db.clients().register(client_addr.clone(), laddr, user, output_limits);
+    let _registration = Registration { db: &db, client_id: client.id, addr: &client_addr };
+
+    // Commands are read and executed while the writer drains the output buffer, so a
+    // slow reader shows up as a growing buffer instead of blocking the server
+    let read_side = async {
+        match serve(&mut reader, &client, &db, limits).await {
+            Ok(()) => {}
+            Err(ConnectionError::Protocol(msg)) => {
+                println!("Protocol error from {}: {}", client_addr, msg);
+                client.send(format!("-ERR Protocol error: {}\r\n", msg));
+            }
+            Err(ConnectionError::QueryBufferLimit) => {
+                println!("Closing {}: client-query-buffer-limit reached", client_addr);
+                Stats::incr(&stats.client_query_buffer_limit_disconnections);
+            }
+            Err(ConnectionError::Io(e)) if is_disconnect(&e) => {}
+            Err(ConnectionError::Io(e)) => println!("Connection error from {}: {}", client_addr, e),
+        }
+        client.close_output();
+    };
+    let (_, written) = tokio::join!(read_side, write_replies(writer, output, &client));
+
+    match written {
+        Err(e) if !is_disconnect(&e) => println!("Connection error from {}: {}", client_addr, e),
+        _ => {}
+    }
+    if client.output_limit_exceeded() {
+        println!("Closing {}: client-output-buffer-limit reached", client_addr);
+        Stats::incr(&stats.client_output_buffer_limit_disconnections);
+    }
+}
+
+// Forgets a registered client however its connection ends, a panicking command
+// included, so it never lingers in CLIENT LIST
+struct Registration<'a> {
+    db: &'a Database,
+    client_id: u64,
+    addr: &'a str,
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        self.db.clients().unregister(self.client_id);
+        self.db.pubsub().remove_client(self.client_id);
+        self.db.tracking().disable(self.client_id);
+        self.db.replication().remove_replica(self.client_id);
+        println!("Connection closed: {}", self.addr);
+    }
+}
+
+async fn serve<R>(
+    reader: &mut R,
+    client: &Arc<Client>,
+    db: &Database,
+    limits: RequestLimits,
+) -> Result<(), ConnectionError>
+where
+    R: tokio::io::AsyncBufRead + Unpin,
+{
+    loop {
+        let args = tokio::select! {
+            args = read_command(reader, limits) => args?,
+            _ = idle_timeout(client, db.config().timeout) => {
+                println!("Closing {}: idle for more than the configured timeout", client.addr);
+                return Ok(());
+            }
+            _ = client.killed() => return Ok(()),
+            _ = db.shutdown().wait() => return Ok(()),
+        };
+        let args = match args {
+            Some(args) => args,
+            None => return Ok(()),
+        };
+        // Empty requests (blank inline lines, `*0`) are ignored like Redis does
+        let name = match args.first() {
+            Some(name) => String::from_utf8_lossy(name),
+            None => continue,
+        };
+
+        // CLIENT commands are never paused so UNPAUSE can always get through
+        if !name.eq_ignore_ascii_case("CLIENT") {
+            tokio::select! {
+                _ = db.clients().wait_if_paused(is_paused_by_write(&name)) => {}
+                _ = client.killed() => return Ok(()),
+                _ = db.shutdown().wait() => return Ok(()),
+            }
+        }
+
+        client.touch(&name);
+        let response = command_parser(db, client, &args)
+            .await
+            .unwrap_or_else(|e| format!("-ERR {}\r\n", e).into_bytes());
+
+        if client.should_reply() {
+            client.send(response);
+        }
+
+        // CLIENT KILL on ourselves closes the connection once the reply is queued,
+        // and going over the output buffer limit kills the client
+        if client.is_killed() || client.closes_after_reply() {
+            return Ok(());
+        }
+    }
+}
+
+// Completes once the client has waited `timeout` seconds for its next command.
+// Subscribers and monitors legitimately sit idle, so they never time out.
+async fn idle_timeout(client: &Client, timeout: u64) {
+    if timeout == 0 || client.class() == ClientClass::Pubsub || client.is_monitor() {
+        return std::future::pending().await;
+    }
+    tokio::time::sleep(Duration::from_secs(timeout)).await;
+}
+
+// Writes queued replies until the output buffer is closed. A write error kills the
+// client so the read side stops as well.
+async fn write_replies<S>(
+    mut writer: WriteHalf<S>,
+    mut output: UnboundedReceiver<Vec<u8>>,
+    client: &Client,
+) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite,
+{
+    let result = async {
+        while let Some(reply) = output.recv().await {
+            writer.write_all(&reply).await?;
+            writer.flush().await?;
+            client.output_written(reply.len());
+        }
+        Ok(())
+    }
+    .await;
+
+    if result.is_err() {
+        client.kill();
+    }
+    // The connection is being dropped anyway, a failed shutdown changes nothing
+    let _ = writer.shutdown().await;
+    result
+}
+
+// Errors that just mean the peer went away
+fn is_disconnect(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe
+    )
+}
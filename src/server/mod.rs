@@ -1,38 +1,180 @@
+mod connection;
+pub mod protocol;
+
+use std::fs;
+use std::io;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
 use std::sync::Arc;
-use tokio::net::TcpListener;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use std::time::Duration;
+use socket2::{SockRef, TcpKeepalive};
+use tokio::net::{TcpListener, TcpStream, UnixListener};
 use tokio::spawn;
+use tokio::time::timeout;
 
 use crate::database::Database;
-use crate::command::command_parser;
+use crate::tls::{TlsContext, HANDSHAKE_TIMEOUT};
+use connection::{handle_connection, reject, ClientSlot};
+
+// Bounds for the accept loop's sleep when the process runs out of resources
+const ACCEPT_BACKOFF_MIN: Duration = Duration::from_millis(10);
+const ACCEPT_BACKOFF_MAX: Duration = Duration::from_secs(1);
 
-pub async fn create_server(addr: &str, db: Arc<Database>) {
+pub async fn create_server(addr: &str, db: Arc<Database>, tls: Option<Arc<TlsContext>>) {
     let listener = TcpListener::bind(addr).await.unwrap();
-    
-    println!("Redis TCP server listening on {}", addr);
 
+    if tls.is_some() {
+        println!("Redis TLS server listening on {}", addr);
+    } else {
+        println!("Redis TCP server listening on {}", addr);
+    }
+
+    let mut backoff = ACCEPT_BACKOFF_MIN;
     loop {
-        // Accept incoming connections
-        let (socket, client_addr) = listener.accept().await.unwrap();
+        // Accept incoming connections until shutdown
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            _ = db.shutdown().wait() => break,
+        };
+        let (socket, client_addr) = match accepted {
+            Ok(conn) => {
+                backoff = ACCEPT_BACKOFF_MIN;
+                conn
+            }
+            Err(e) => {
+                accept_failed(e, &mut backoff).await;
+                continue;
+            }
+        };
+        let keepalive = db.config().tcp_keepalive;
+        if keepalive > 0 {
+            if let Err(e) = set_keepalive(&socket, keepalive) {
+                eprintln!("Failed to enable TCP keepalive for {}: {}", client_addr, e);
+            }
+        }
+
         let db = db.clone();
-        
+        let tls = tls.clone();
+        let laddr = socket.local_addr().map(|a| a.to_string()).unwrap_or_default();
+
         println!("New connection from: {}", client_addr);
-        
+
         spawn(async move {
-            let (reader, mut writer) = socket.into_split();
-            let mut reader = BufReader::new(reader);
-            let mut buffer = String::new();
+            let Some(slot) = ClientSlot::take(&db, &client_addr.to_string()) else {
+                // A TLS client can't be told why before its handshake, it's just closed
+                if tls.is_none() {
+                    reject(socket).await;
+                }
+                return;
+            };
+            match tls {
+                Some(tls) => match timeout(HANDSHAKE_TIMEOUT, tls.accept(socket)).await {
+                    Ok(Ok((stream, user))) => {
+                        if let Some(user) = &user {
+                            println!("Client {} presented a certificate for '{}'", client_addr, user);
+                        }
+                        handle_connection(stream, client_addr.to_string(), laddr, user, slot, db).await;
+                    }
+                    Ok(Err(e)) => println!("TLS handshake failed for {}: {}", client_addr, e),
+                    Err(_) => println!("TLS handshake timed out for {}", client_addr),
+                },
+                None => handle_connection(socket, client_addr.to_string(), laddr, None, slot, db).await,
+            }
+        });
+    }
+}
+
+pub async fn create_unix_server(path: &str, perm: Option<u32>, db: Arc<Database>) {
+    // A socket file left behind by a previous run would make bind fail. Anything
+    // else at that path isn't ours to remove.
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            eprintln!("Not listening on {}: the path exists and isn't a socket", path);
+            return;
+        }
+        if let Err(e) = fs::remove_file(path) {
+            eprintln!("Failed to remove stale Unix socket {}: {}", path, e);
+            return;
+        }
+    }
 
-            while reader.read_line(&mut buffer).await.unwrap() > 0 {
-                let response = command_parser(&db, buffer.trim())
-                    .await
-                    .unwrap_or_else(|e| format!("-ERR {}\r\n", e));
+    let listener = match UnixListener::bind(path) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Failed to listen on Unix socket {}: {}", path, e);
+            return;
+        }
+    };
+    if let Some(perm) = perm {
+        if let Err(e) = fs::set_permissions(path, fs::Permissions::from_mode(perm)) {
+            eprintln!("Failed to set permissions {:o} on Unix socket {}: {}", perm, path, e);
+            let _ = fs::remove_file(path);
+            return;
+        }
+    }
+
+    println!("Redis Unix socket server listening on {}", path);
 
-                writer.write_all(response.as_bytes()).await.unwrap();
-                buffer.clear();
+    let mut backoff = ACCEPT_BACKOFF_MIN;
+    loop {
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            _ = db.shutdown().wait() => break,
+        };
+        let socket = match accepted {
+            Ok((socket, _)) => {
+                backoff = ACCEPT_BACKOFF_MIN;
+                socket
+            }
+            Err(e) => {
+                accept_failed(e, &mut backoff).await;
+                continue;
             }
-            
-            println!("Connection closed: {}", client_addr);
+        };
+        let db = db.clone();
+
+        // Unix socket peers have no address, report them like Redis does
+        let client_addr = format!("{}:0", path);
+        println!("New connection from: {}", client_addr);
+
+        spawn(async move {
+            let Some(slot) = ClientSlot::take(&db, &client_addr) else {
+                reject(socket).await;
+                return;
+            };
+            let laddr = client_addr.clone();
+            handle_connection(socket, client_addr, laddr, None, slot, db).await;
         });
     }
-}
\ No newline at end of file
+
+    if let Err(e) = fs::remove_file(path) {
+        eprintln!("Failed to remove Unix socket {}: {}", path, e);
+    }
+}
+
+// Enables TCP keepalive probes after `secs` idle seconds. Like Redis, probes are
+// then sent every secs/3 and the peer is dropped after 3 unanswered ones.
+fn set_keepalive(socket: &TcpStream, secs: u64) -> io::Result<()> {
+    let idle = Duration::from_secs(secs);
+    let keepalive = TcpKeepalive::new()
+        .with_time(idle)
+        .with_interval((idle / 3).max(Duration::from_secs(1)))
+        .with_retries(3);
+    SockRef::from(socket).set_tcp_keepalive(&keepalive)
+}
+
+// Logs a failed accept. Running out of file descriptors or memory won't fix itself
+// on the next call, so those back off exponentially instead of spinning.
+async fn accept_failed(e: io::Error, backoff: &mut Duration) {
+    let out_of_resources = matches!(
+        e.raw_os_error(),
+        Some(libc::EMFILE | libc::ENFILE | libc::ENOBUFS | libc::ENOMEM)
+    );
+
+    if out_of_resources {
+        eprintln!("Accept failed: {} (retrying in {:?})", e, backoff);
+        tokio::time::sleep(*backoff).await;
+        *backoff = (*backoff * 2).min(ACCEPT_BACKOFF_MAX);
+    } else {
+        eprintln!("Accept failed: {}", e);
+    }
+}
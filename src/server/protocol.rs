@@ -0,0 +1,256 @@
+use std::fmt;
+use std::io;
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt};
+
+// Redis' limits for requests that aren't configurable yet
+pub const INLINE_MAX_SIZE: usize = 64 * 1024;
+const MULTIBULK_MAX_LEN: i64 = 1024 * 1024;
+
+// Configurable request limits, from client-query-buffer-limit and proto-max-bulk-len
+#[derive(Clone, Copy)]
+pub struct RequestLimits {
+    pub query_buffer: usize,
+    pub max_bulk_len: usize,
+}
+
+pub enum ConnectionError {
+    Io(io::Error),
+    // The client sent something that isn't valid RESP; it gets an error reply before closing
+    Protocol(String),
+    // A single request grew past client-query-buffer-limit
+    QueryBufferLimit,
+}
+
+impl fmt::Display for ConnectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectionError::Io(e) => write!(f, "I/O error: {}", e),
+            ConnectionError::Protocol(msg) => write!(f, "Protocol error: {}", msg),
+            ConnectionError::QueryBufferLimit => write!(f, "query buffer limit reached"),
+        }
+    }
+}
+
+impl From<io::Error> for ConnectionError {
+    fn from(e: io::Error) -> Self {
+        ConnectionError::Io(e)
+    }
+}
+
+fn protocol_error(msg: &str) -> ConnectionError {
+    ConnectionError::Protocol(msg.to_string())
+}
+
+// Reads one request, either a RESP multibulk array (`*2\r\n$3\r\nGET\r\n...`) or an
+// inline command (`GET key\r\n`). Returns Ok(None) on a clean EOF between requests.
+// Arguments are raw bytes: values may be binary, commands decide what must be text.
+pub async fn read_command<R>(
+    reader: &mut R,
+    limits: RequestLimits,
+) -> Result<Option<Vec<Vec<u8>>>, ConnectionError>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let first = match reader.fill_buf().await? {
+        [] => return Ok(None),
+        buf => buf[0],
+    };
+
+    if first == b'*' {
+        read_multibulk(reader, limits).await.map(Some)
+    } else {
+        read_inline(reader, limits).await
+    }
+}
+
+async fn read_inline<R>(
+    reader: &mut R,
+    limits: RequestLimits,
+) -> Result<Option<Vec<Vec<u8>>>, ConnectionError>
+where
+    R: AsyncBufRead + Unpin,
+{
+    // A tiny query buffer limit can be stricter than the inline request limit
+    let max = INLINE_MAX_SIZE.min(limits.query_buffer);
+    let line = match read_line(reader, max).await {
+        Err(ConnectionError::Protocol(_)) if max < INLINE_MAX_SIZE => {
+            return Err(ConnectionError::QueryBufferLimit)
+        }
+        line => match line? {
+            Some(line) => line,
+            None => return Ok(None),
+        },
+    };
+    let args = line
+        .split(|b| b.is_ascii_whitespace())
+        .filter(|arg| !arg.is_empty())
+        .map(<[u8]>::to_vec)
+        .collect();
+    Ok(Some(args))
+}
+
+async fn read_multibulk<R>(reader: &mut R, limits: RequestLimits) -> Result<Vec<Vec<u8>>, ConnectionError>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let count = read_length(reader, b'*', "multibulk").await?;
+    if count > MULTIBULK_MAX_LEN {
+        return Err(protocol_error("invalid multibulk length"));
+    }
+
+    // Bytes of argument data buffered for this request so far
+    let mut buffered = 0usize;
+    // The announced count comes from an unauthenticated header, so only part of it
+    // is reserved up front
+    let mut args = Vec::with_capacity(count.clamp(0, 1024) as usize);
+    for _ in 0..count {
+        let len = read_length(reader, b'$', "bulk").await?;
+        if len < 0 || len as u64 > limits.max_bulk_len as u64 {
+            return Err(protocol_error("invalid bulk length"));
+        }
+        buffered = buffered.saturating_add(len as usize);
+        if buffered > limits.query_buffer {
+            return Err(ConnectionError::QueryBufferLimit);
+        }
+
+        // The payload is followed by CRLF. Reading through `take` grows the buffer
+        // as data arrives instead of trusting the announced length up front.
+        let expected = len as u64 + 2;
+        let mut data = Vec::new();
+        if (&mut *reader).take(expected).read_to_end(&mut data).await? as u64 != expected {
+            return Err(ConnectionError::Io(io::ErrorKind::UnexpectedEof.into()));
+        }
+        if !data.ends_with(b"\r\n") {
+            return Err(protocol_error("expected CRLF after bulk string"));
+        }
+        data.truncate(len as usize);
+        args.push(data);
+    }
+    Ok(args)
+}
+
+// Parses a `<prefix><number>\r\n` header line
+async fn read_length<R>(reader: &mut R, prefix: u8, kind: &str) -> Result<i64, ConnectionError>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let line = read_line(reader, INLINE_MAX_SIZE)
+        .await?
+        .ok_or_else(|| ConnectionError::Io(io::ErrorKind::UnexpectedEof.into()))?;
+
+    match line.split_first() {
+        Some((p, digits)) if *p == prefix => std::str::from_utf8(digits)
+            .ok()
+            .and_then(|digits| digits.parse::<i64>().ok())
+            .ok_or_else(|| ConnectionError::Protocol(format!("invalid {} length", kind))),
+        _ => Err(ConnectionError::Protocol(format!(
+            "expected '{}', got '{}'",
+            prefix as char,
+            line.first().map(|b| *b as char).unwrap_or(' ')
+        ))),
+    }
+}
+
+// Reads up to and excluding the next `\n` (and a preceding `\r`), refusing lines
+// longer than `max` so a client can't make us buffer an endless line
+pub async fn read_line<R>(reader: &mut R, max: usize) -> Result<Option<Vec<u8>>, ConnectionError>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut line = Vec::new();
+    loop {
+        let buf = reader.fill_buf().await?;
+        if buf.is_empty() {
+            // EOF: a trailing line without newline still counts
+            if line.is_empty() {
+                return Ok(None);
+            }
+            break;
+        }
+
+        match buf.iter().position(|b| *b == b'\n') {
+            Some(pos) => {
+                line.extend_from_slice(&buf[..pos]);
+                reader.consume(pos + 1);
+                break;
+            }
+            None => {
+                let len = buf.len();
+                line.extend_from_slice(buf);
+                reader.consume(len);
+            }
+        }
+
+        if line.len() > max {
+            return Err(protocol_error("too big inline request"));
+        }
+    }
+
+    if line.len() > max {
+        return Err(protocol_error("too big inline request"));
+    }
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    Ok(Some(line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMITS: RequestLimits = RequestLimits {
+        query_buffer: 1024,
+        max_bulk_len: 16,
+    };
+
+    async fn read(input: &[u8], limits: RequestLimits) -> Result<Option<Vec<Vec<u8>>>, ConnectionError> {
+        let mut reader = input;
+        read_command(&mut reader, limits).await
+    }
+
+    fn args(args: &[&str]) -> Option<Vec<Vec<u8>>> {
+        Some(args.iter().map(|arg| arg.as_bytes().to_vec()).collect())
+    }
+
+    #[tokio::test]
+    async fn reads_inline_commands() {
+        let expected = args(&["SET", "key", "value"]);
+        assert_eq!(read(b"SET  key value\r\n", LIMITS).await.ok(), Some(expected));
+        assert_eq!(read(b"PING\n", LIMITS).await.ok(), Some(args(&["PING"])));
+        assert_eq!(read(b"", LIMITS).await.ok(), Some(None));
+    }
+
+    #[tokio::test]
+    async fn reads_multibulk_commands() {
+        let input = b"*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$5\r\na\r\nb\0\r\n";
+        let mut reader = &input[..];
+        let expected = Some(vec![b"SET".to_vec(), b"key".to_vec(), b"a\r\nb\0".to_vec()]);
+        assert_eq!(read_command(&mut reader, LIMITS).await.ok(), Some(expected));
+        assert!(reader.is_empty());
+    }
+
+    #[tokio::test]
+    async fn refuses_oversized_bulks() {
+        let input = b"*2\r\n$3\r\nGET\r\n$17\r\n";
+        let result = read(input, LIMITS).await;
+        assert!(matches!(result, Err(ConnectionError::Protocol(msg)) if msg == "invalid bulk length"));
+
+        let limits = RequestLimits { query_buffer: 8, ..LIMITS };
+        let input = b"*2\r\n$3\r\nSET\r\n$10\r\n";
+        assert!(matches!(read(input, limits).await, Err(ConnectionError::QueryBufferLimit)));
+        assert!(matches!(read(&[b'x'; 16], limits).await, Err(ConnectionError::QueryBufferLimit)));
+    }
+
+    #[tokio::test]
+    async fn refuses_bad_headers() {
+        let result = read(b"*x\r\n", LIMITS).await;
+        assert!(matches!(result, Err(ConnectionError::Protocol(msg)) if msg == "invalid multibulk length"));
+        let result = read(b"*1\r\n:3\r\n", LIMITS).await;
+        assert!(matches!(result, Err(ConnectionError::Protocol(msg)) if msg == "expected '$', got ':'"));
+        let result = read(b"*1\r\n$3\r\nGETX\r\n", LIMITS).await;
+        assert!(matches!(result, Err(ConnectionError::Protocol(msg)) if msg == "expected CRLF after bulk string"));
+        assert!(matches!(read(b"*1\r\n$3\r\nGE", LIMITS).await, Err(ConnectionError::Io(_))));
+    }
+}
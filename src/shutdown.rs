@@ -0,0 +1,71 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::watch;
+
+use crate::client::PauseMode;
+use crate::database::Database;
+
+// How long connections get to finish their in-flight commands and flush replies
+pub const DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+// How long SHUTDOWN waits for replicas to acknowledge the last writes, like
+// Redis' shutdown-timeout
+pub const REPLICA_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Server-wide shutdown flag. Listeners stop accepting and connections close once
+// it is set, by a signal or the SHUTDOWN command.
+pub struct Shutdown {
+    requested: watch::Sender<bool>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Shutdown {
+            requested: watch::Sender::new(false),
+        }
+    }
+
+    pub fn trigger(&self) {
+        self.requested.send_replace(true);
+    }
+
+    // Completes once shutdown has been requested, immediately if it already was
+    pub async fn wait(&self) {
+        let mut requested = self.requested.subscribe();
+        // The sender lives as long as self, so this can't fail
+        let _ = requested.wait_for(|requested| *requested).await;
+    }
+}
+
+// Starts a graceful shutdown. Unless `now` is set, writes are paused first and
+// connected replicas get up to REPLICA_TIMEOUT to acknowledge everything so far.
+pub async fn request(db: &Database, now: bool) {
+    // A running script would keep the process alive
+    db.scripting().abort();
+
+    let replicas = db.replication().replicas().len();
+    if !now && replicas > 0 {
+        db.clients().pause(REPLICA_TIMEOUT, PauseMode::Write);
+        println!("Waiting for {} replica(s) to catch up before exiting", replicas);
+        let acked = db.replication().wait(replicas, Some(REPLICA_TIMEOUT)).await;
+        if acked < replicas {
+            println!("{} of {} replica(s) caught up, exiting anyway", acked, replicas);
+        }
+    }
+    db.shutdown().trigger();
+}
+
+// Turns SIGINT and SIGTERM into a graceful shutdown
+pub async fn handle_signals(db: Arc<Database>) {
+    let mut sigint = signal(SignalKind::interrupt()).expect("failed to install SIGINT handler");
+    let mut sigterm = signal(SignalKind::terminate()).expect("failed to install SIGTERM handler");
+
+    let name = tokio::select! {
+        _ = sigint.recv() => "SIGINT",
+        _ = sigterm.recv() => "SIGTERM",
+    };
+    println!("Received {}, scheduling shutdown...", name);
+    request(&db, false).await;
+}
@@ -0,0 +1,90 @@
+// The slow log (SLOWLOG): commands that took at least slowlog-log-slower-than
+// microseconds, newest first, keeping the last slowlog-max-len of them. Long
+// commands are shortened like in Redis so a huge SADD can't fill the memory.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::client::Client;
+
+const MAX_ARGS: usize = 32;
+const MAX_ARG_LEN: usize = 128;
+
+#[derive(Clone)]
+pub struct SlowLogEntry {
+    pub id: u64,
+    // Unix time the command was logged at
+    pub timestamp: u64,
+    pub micros: u64,
+    pub args: Vec<String>,
+    pub addr: String,
+    pub name: String,
+}
+
+pub struct SlowLog {
+    entries: Mutex<VecDeque<SlowLogEntry>>,
+    // Ids keep counting across SLOWLOG RESET
+    next_id: AtomicU64,
+}
+
+impl SlowLog {
+    pub fn new() -> Self {
+        SlowLog {
+            entries: Mutex::new(VecDeque::new()),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    pub fn push(&self, max_len: usize, args: &[&[u8]], micros: u64, client: &Client) {
+        let entry = SlowLogEntry {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            micros,
+            args: shortened(args),
+            addr: client.addr.clone(),
+            name: client.name(),
+        };
+        let mut entries = self.entries.lock().unwrap();
+        entries.push_front(entry);
+        entries.truncate(max_len);
+    }
+
+    // The newest `count` entries
+    pub fn get(&self, count: usize) -> Vec<SlowLogEntry> {
+        self.entries.lock().unwrap().iter().take(count).cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn reset(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+// At most MAX_ARGS arguments of at most MAX_ARG_LEN bytes, saying how much was left out
+fn shortened(args: &[&[u8]]) -> Vec<String> {
+    let kept = if args.len() > MAX_ARGS { MAX_ARGS - 1 } else { args.len() };
+    let mut out: Vec<String> = args[..kept]
+        .iter()
+        .map(|arg| {
+            if arg.len() <= MAX_ARG_LEN {
+                return String::from_utf8_lossy(arg).into_owned();
+            }
+            let mut end = MAX_ARG_LEN;
+            if let Ok(text) = std::str::from_utf8(arg) {
+                while !text.is_char_boundary(end) {
+                    end -= 1;
+                }
+            }
+            format!("{}... ({} more bytes)", String::from_utf8_lossy(&arg[..end]), arg.len() - end)
+        })
+        .collect();
+    if kept < args.len() {
+        out.push(format!("... ({} more arguments)", args.len() - kept));
+    }
+    out
+}
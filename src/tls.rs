@@ -0,0 +1,124 @@
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::rustls::crypto::ring;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{RootCertStore, ServerConfig};
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+use crate::config::{Config, TlsAuthClients};
+
+// How long a client gets to finish its handshake before it's dropped
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Shared TLS state for the RESP and HTTP listeners
+pub struct TlsContext {
+    acceptor: TlsAcceptor,
+    auth_clients_user: bool,
+}
+
+impl TlsContext {
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        let cert_file = config.tls_cert_file.as_deref().ok_or("tls-cert-file is not set")?;
+        let key_file = config.tls_key_file.as_deref().ok_or("tls-key-file is not set")?;
+
+        let certs = load_certs(cert_file)?;
+        let key = load_key(key_file)?;
+        let provider = Arc::new(ring::default_provider());
+
+        let builder = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| format!("TLS setup failed: {}", e))?;
+
+        let builder = match config.tls_auth_clients {
+            TlsAuthClients::No => builder.with_no_client_auth(),
+            auth => {
+                let ca_file = config
+                    .tls_ca_cert_file
+                    .as_deref()
+                    .ok_or("tls-ca-cert-file is not set")?;
+                let mut roots = RootCertStore::empty();
+                for cert in load_certs(ca_file)? {
+                    roots
+                        .add(cert)
+                        .map_err(|e| format!("Invalid CA certificate in '{}': {}", ca_file, e))?;
+                }
+
+                let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+                let verifier = if auth == TlsAuthClients::Optional {
+                    verifier.allow_unauthenticated()
+                } else {
+                    verifier
+                };
+                let verifier = verifier
+                    .build()
+                    .map_err(|e| format!("TLS client verifier setup failed: {}", e))?;
+                builder.with_client_cert_verifier(verifier)
+            }
+        };
+
+        let mut server_config = builder
+            .with_single_cert(certs, key)
+            .map_err(|e| format!("Invalid TLS certificate or key: {}", e))?;
+        server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+        Ok(TlsContext {
+            acceptor: TlsAcceptor::from(Arc::new(server_config)),
+            auth_clients_user: config.tls_auth_clients_user,
+        })
+    }
+
+    // Performs the handshake. Returns the stream and, when tls-auth-clients-user
+    // is CN, the client certificate's Common Name. It only labels the connection:
+    // there are no ACLs, so it grants nothing.
+    pub async fn accept<S>(&self, stream: S) -> io::Result<(TlsStream<S>, Option<String>)>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let stream = self.acceptor.accept(stream).await?;
+
+        let user = if self.auth_clients_user {
+            stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .and_then(|cert| common_name(cert))
+        } else {
+            None
+        };
+
+        Ok((stream, user))
+    }
+}
+
+fn common_name(cert: &CertificateDer<'_>) -> Option<String> {
+    let (_, cert) = X509Certificate::from_der(cert.as_ref()).ok()?;
+    let cn = cert.subject().iter_common_name().next()?;
+    cn.as_str().ok().map(|cn| cn.to_string())
+}
+
+fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>, String> {
+    let file = File::open(path).map_err(|e| format!("Can't open '{}': {}", path, e))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Can't parse certificates in '{}': {}", path, e))?;
+
+    if certs.is_empty() {
+        return Err(format!("No certificates found in '{}'", path));
+    }
+    Ok(certs)
+}
+
+fn load_key(path: &str) -> Result<PrivateKeyDer<'static>, String> {
+    let file = File::open(path).map_err(|e| format!("Can't open '{}': {}", path, e))?;
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .map_err(|e| format!("Can't parse private key in '{}': {}", path, e))?
+        .ok_or_else(|| format!("No private key found in '{}'", path))
+}
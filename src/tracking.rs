@@ -0,0 +1,296 @@
+// Client-side caching (CLIENT TRACKING). Tracking clients are told when keys
+// they may have cached change: by default the keys they read since the last
+// change, in BCAST mode every key under their prefixes. Invalidations are
+// RESP3 `invalidate` pushes, or messages on __redis__:invalidate for RESP2
+// clients that redirect them to a subscribed connection.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::client::{Client, ClientRegistry};
+use crate::command::{command_keys, resp};
+use crate::pubsub::PubSub;
+
+pub const INVALIDATE_CHANNEL: &str = "__redis__:invalidate";
+
+tokio::task_local! {
+    // The client whose command is running, so NOLOOP clients aren't told
+    // about their own writes
+    pub static CURRENT_CLIENT: u64;
+}
+
+#[derive(Clone, Default)]
+pub struct TrackingOptions {
+    // Client id that receives the invalidations instead
+    pub redirect: Option<u64>,
+    pub bcast: bool,
+    pub prefixes: Vec<String>,
+    // Only track reads after CLIENT CACHING YES
+    pub optin: bool,
+    // Track reads except after CLIENT CACHING NO
+    pub optout: bool,
+    pub noloop: bool,
+}
+
+struct Tracked {
+    client: Arc<Client>,
+    options: TrackingOptions,
+    // CLIENT CACHING for the next command
+    caching: Option<bool>,
+}
+
+#[derive(Default)]
+struct TrackingState {
+    clients: HashMap<u64, Tracked>,
+    // Default mode: the clients that read each key since it last changed
+    keys: HashMap<String, HashSet<u64>>,
+}
+
+// What CLIENT TRACKINGINFO shows
+pub struct TrackingInfo {
+    pub flags: Vec<&'static str>,
+    pub redirect: i64,
+    pub prefixes: Vec<String>,
+}
+
+pub struct Tracking {
+    state: Mutex<TrackingState>,
+    // Tracking clients, so commands skip the lock while nobody tracks
+    count: AtomicUsize,
+    clients: Arc<ClientRegistry>,
+    pubsub: Arc<PubSub>,
+}
+
+impl Tracking {
+    pub fn new(clients: Arc<ClientRegistry>, pubsub: Arc<PubSub>) -> Self {
+        Tracking {
+            state: Mutex::new(TrackingState::default()),
+            count: AtomicUsize::new(0),
+            clients,
+            pubsub,
+        }
+    }
+
+    // CLIENT TRACKING ON. Turning it on again keeps the mode, adds prefixes and
+    // replaces the other options.
+    pub fn enable(&self, client: &Arc<Client>, mut options: TrackingOptions) -> Result<(), String> {
+        if !options.bcast && !options.prefixes.is_empty() {
+            return Err("PREFIX option requires BCAST mode to be enabled".to_string());
+        }
+        if options.optin && options.optout {
+            return Err("You can't use both OPTIN and OPTOUT".to_string());
+        }
+        if options.bcast && (options.optin || options.optout) {
+            return Err("OPTIN and OPTOUT are not compatible with BCAST".to_string());
+        }
+        if let Some(id) = options.redirect {
+            if self.clients.get(id).is_none() {
+                return Err("The client ID you want redirect to does not exist".to_string());
+            }
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if let Some(tracked) = state.clients.get(&client.id) {
+            if tracked.options.bcast != options.bcast {
+                return Err("You can't switch BCAST mode on/off before disabling tracking for this client, and then \
+                            re-enabling it with a different mode."
+                    .to_string());
+            }
+            options.prefixes.extend(tracked.options.prefixes.iter().cloned());
+        }
+        options.prefixes.sort();
+        options.prefixes.dedup();
+        for (i, prefix) in options.prefixes.iter().enumerate() {
+            if let Some(other) = options.prefixes[i + 1..].iter().find(|other| other.starts_with(prefix.as_str())) {
+                return Err(format!(
+                    "Prefix '{}' overlaps with another provided prefix '{}'. Prefixes for a single client must not \
+                     overlap.",
+                    other, prefix
+                ));
+            }
+        }
+
+        let tracked = Tracked {
+            client: client.clone(),
+            options,
+            caching: None,
+        };
+        if state.clients.insert(client.id, tracked).is_none() {
+            self.count.fetch_add(1, Ordering::SeqCst);
+        }
+        Ok(())
+    }
+
+    // CLIENT TRACKING OFF, and disconnects
+    pub fn disable(&self, id: u64) {
+        let mut state = self.state.lock().unwrap();
+        if state.clients.remove(&id).is_none() {
+            return;
+        }
+        // Ids left in the key table are skipped until their keys change, or
+        // dropped all at once when nobody tracks anymore
+        if self.count.fetch_sub(1, Ordering::SeqCst) == 1 {
+            state.keys.clear();
+        }
+    }
+
+    // CLIENT CACHING YES|NO, for the next command
+    pub fn set_caching(&self, id: u64, caching: bool) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        let tracked = state
+            .clients
+            .get_mut(&id)
+            .filter(|tracked| tracked.options.optin || tracked.options.optout)
+            .ok_or("CLIENT CACHING can be called only when the client is in tracking mode with OPTIN or OPTOUT mode \
+                    enabled")?;
+        if caching && !tracked.options.optin {
+            return Err("CLIENT CACHING YES is only valid when tracking is enabled in OPTIN mode.".to_string());
+        }
+        if !caching && !tracked.options.optout {
+            return Err("CLIENT CACHING NO is only valid when tracking is enabled in OPTOUT mode.".to_string());
+        }
+        tracked.caching = Some(caching);
+        Ok(())
+    }
+
+    // CLIENT GETREDIR: -1 when not tracking, 0 without a redirect
+    pub fn redirect(&self, id: u64) -> i64 {
+        let state = self.state.lock().unwrap();
+        state
+            .clients
+            .get(&id)
+            .map_or(-1, |tracked| tracked.options.redirect.map_or(0, |redirect| redirect as i64))
+    }
+
+    pub fn info(&self, id: u64) -> TrackingInfo {
+        let state = self.state.lock().unwrap();
+        let Some(tracked) = state.clients.get(&id) else {
+            return TrackingInfo {
+                flags: vec!["off"],
+                redirect: -1,
+                prefixes: Vec::new(),
+            };
+        };
+        let options = &tracked.options;
+        let mut flags = vec!["on"];
+        for (set, flag) in [
+            (options.bcast, "bcast"),
+            (options.optin, "optin"),
+            (options.optout, "optout"),
+            (tracked.caching == Some(true), "caching-yes"),
+            (tracked.caching == Some(false), "caching-no"),
+            (options.noloop, "noloop"),
+            (options.redirect.is_some_and(|redirect| self.clients.get(redirect).is_none()), "broken_redirect"),
+        ] {
+            if set {
+                flags.push(flag);
+            }
+        }
+        TrackingInfo {
+            flags,
+            redirect: options.redirect.map_or(0, |redirect| redirect as i64),
+            prefixes: options.prefixes.clone(),
+        }
+    }
+
+    // Called before each of the client's commands except CLIENT CACHING, with the
+    // keys it is about to read. Default mode clients remember them, as OPTIN/OPTOUT
+    // allow. Doing it before the read means a concurrent write can only cause an
+    // extra invalidation, never a missed one.
+    pub fn track_reads(&self, id: u64, read_keys: &[&str]) {
+        if self.count.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+        let state = &mut *self.state.lock().unwrap();
+        let Some(tracked) = state.clients.get_mut(&id) else {
+            return;
+        };
+        let caching = tracked.caching.take();
+        let options = &tracked.options;
+        let track = match caching {
+            _ if options.bcast => false,
+            Some(caching) => caching,
+            None => !options.optin,
+        };
+        if track {
+            for key in read_keys {
+                state.keys.entry(key.to_string()).or_default().insert(id);
+            }
+        }
+    }
+
+    // A replicated write went through: the keys it changed are invalidated
+    pub fn changed(&self, command: &[&str]) {
+        if self.count.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+        for key in written_keys(command) {
+            self.invalidate(key);
+        }
+    }
+
+    pub fn invalidate(&self, key: &str) {
+        if self.count.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+        let origin = CURRENT_CLIENT.try_with(|id| *id).ok();
+        let mut state = self.state.lock().unwrap();
+        let readers = state.keys.remove(key).unwrap_or_default();
+        for (id, tracked) in &state.clients {
+            let options = &tracked.options;
+            let interested = match options.bcast {
+                true => options.prefixes.is_empty() || options.prefixes.iter().any(|prefix| key.starts_with(prefix)),
+                false => readers.contains(id),
+            };
+            if interested && !(options.noloop && origin == Some(*id)) {
+                self.send(tracked, resp::array(&[resp::bulk(key)]));
+            }
+        }
+    }
+
+    // The whole keyspace went away: every tracking client gets a null invalidation
+    pub fn invalidate_all(&self) {
+        if self.count.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.keys.clear();
+        for tracked in state.clients.values() {
+            self.send(tracked, String::new());
+        }
+    }
+
+    // `keys` is the encoded array of keys, empty for all of them
+    fn send(&self, tracked: &Tracked, keys: String) {
+        let target = match tracked.options.redirect {
+            None => tracked.client.clone(),
+            Some(id) => match self.clients.get(id) {
+                Some(target) => target,
+                None => {
+                    if tracked.client.protocol() != 2 {
+                        let broken = [resp::bulk("tracking-redir-broken"), resp::integer(id as i64)];
+                        tracked.client.send(resp::push(&broken));
+                    }
+                    return;
+                }
+            },
+        };
+        if target.protocol() != 2 {
+            let keys = if keys.is_empty() { resp::null() } else { keys };
+            target.send(resp::push(&[resp::bulk("invalidate"), keys]));
+        } else if self.pubsub.is_subscribed(target.id, INVALIDATE_CHANNEL) {
+            // RESP2 clients can't take pushes, only a subscriber can be told
+            let keys = if keys.is_empty() { resp::null_array() } else { keys };
+            target.send(resp::array(&[resp::bulk("message"), resp::bulk(INVALIDATE_CHANNEL), keys]));
+        }
+    }
+}
+
+// The keys a replicated write changes; the merges only read their sources
+fn written_keys<'a>(command: &[&'a str]) -> Vec<&'a str> {
+    match command {
+        ["PFMERGE" | "CMS.MERGE", dest, ..] => vec![dest],
+        _ => command_keys(command),
+    }
+}
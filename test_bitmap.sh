@@ -0,0 +1,73 @@
+#!/bin/bash
+
+# Redis-Rust Bitmap Test Script
+# Starts a server and checks that:
+#   - SETBIT/GETBIT grow and read strings bit by bit
+#   - BITCOUNT and BITPOS see the same bits as Redis, in byte and bit ranges
+#   - BITOP combines strings
+#   - BITFIELD reads and writes integer fields with each overflow mode
+#   - Values are binary safe: bitmaps read back as raw bytes and SET takes any bytes
+
+HOST="127.0.0.1"
+PORT="16450"
+. "$(dirname "$0")/tests/lib.sh"
+
+# Sends a raw request, given with printf escapes, and shows the reply as hex bytes
+send_hex() {
+    printf "%b" "$1" | nc -w "${NC_WAIT:-1}" $HOST "$PORT" | od -An -tx1 | tr -s ' \n' ' '
+}
+
+echo "=== Redis-Rust Bitmap Test Suite ==="
+echo ""
+
+echo "--- Starting server ---"
+cargo build --quiet || exit 1
+./target/debug/redis-rust --port "$PORT" --http-port 0 > "$LOG_DIR/server.log" 2>&1 &
+SERVER_PID=$!
+sleep 1
+echo ""
+
+echo "--- Single bits ---"
+check "SETBIT returns the old bit" ":0" "$(send SETBIT flags 7 1)"
+check "GETBIT reads it back" ":1" "$(send GETBIT flags 7)"
+check "Bits past the end are clear" ":0" "$(send GETBIT flags 100)"
+check "Offsets can't be negative" "bit offset is not an integer or out of range" "$(send SETBIT flags -1 1)"
+check "Bits are 0 or 1" "bit is not an integer or out of range" "$(send SETBIT flags 1 2)"
+echo ""
+
+echo "--- Counting and searching ---"
+send SET mykey foobar > /dev/null
+check "BITCOUNT counts the whole string" ":26" "$(send BITCOUNT mykey)"
+check "BITCOUNT in a byte range" ":6" "$(send BITCOUNT mykey 1 1)"
+check "BITCOUNT in a bit range" ":17" "$(send BITCOUNT mykey 5 30 BIT)"
+check "BITPOS finds the first set bit" ":1" "$(send BITPOS mykey 1)"
+check "BITPOS in a bit range" ":9" "$(send BITPOS mykey 1 8 -1 BIT)"
+check "BITPOS on a missing key" ":-1" "$(send BITPOS nokey 1)"
+echo ""
+
+echo "--- BITOP ---"
+send SET key2 abcdef > /dev/null
+check "BITOP returns the result length" ":6" "$(send BITOP AND dest mykey key2)"
+check "BITOP AND combines the bytes" "\`bc\`ab" "$(send GET dest)"
+check "NOT takes a single key" "BITOP NOT must be called with a single source key." "$(send BITOP NOT dest mykey key2)"
+echo ""
+
+echo "--- BITFIELD ---"
+check "INCRBY and GET" "$(printf '*2\n:1\n:0')" "$(send BITFIELD counters INCRBY i5 100 1 GET u4 0)"
+check "WRAP wraps around" ":44" "$(send BITFIELD wrapped SET u8 0 300 GET u8 0)"
+check "SAT saturates" ":255" "$(send BITFIELD saturated OVERFLOW SAT SET u8 0 300 GET u8 0)"
+check "FAIL leaves the field alone" "$(printf '*2\n$-1\n:0')" "$(send BITFIELD failed OVERFLOW FAIL SET u8 0 300 GET u8 0)"
+check "Fields can be addressed by index" ":-1" "$(send BITFIELD indexed SET i8 '#1' -1 GET i8 8)"
+check "BITFIELD_RO only reads" "BITFIELD_RO only supports the GET subcommand" "$(send BITFIELD_RO counters SET u8 0 1)"
+echo ""
+
+echo "--- Binary values ---"
+send SETBIT high 0 1 > /dev/null
+check "GET returns the raw byte of a bitmap" "24 31 0d 0a 80 0d 0a" "$(send_hex '*2\r\n$3\r\nGET\r\n$4\r\nhigh\r\n')"
+check "SET takes any bytes" "+OK" "$(printf '*3\r\n$3\r\nSET\r\n$3\r\nraw\r\n$2\r\n\xff\x00\r\n' | nc -w 1 $HOST "$PORT")"
+check "They come back unchanged" "24 32 0d 0a ff 00 0d 0a" "$(send_hex '*2\r\n$3\r\nGET\r\n$3\r\nraw\r\n')"
+check "Bitmap commands see the same bytes" ":8" "$(send BITCOUNT raw)"
+check "Other arguments must be text" "invalid UTF-8" "$(printf '*2\r\n$3\r\nGET\r\n$1\r\n\xff\r\n' | nc -w 1 $HOST "$PORT")"
+echo ""
+
+finish "bitmap"
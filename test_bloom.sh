@@ -0,0 +1,117 @@
+#!/bin/bash
+
+# Redis-Rust Bloom / Cuckoo Filter Test Script
+# Starts a server and checks that:
+#   - BF.ADD/BF.MADD/BF.EXISTS never miss an added item and scale past capacity
+#   - NONSCALING filters refuse items once full
+#   - CF.ADD/CF.DEL/CF.COUNT track duplicates and deletions
+#   - filter reads on keys of other types get WRONGTYPE
+#   - BF.SCANDUMP/BF.LOADCHUNK copy a filter, and a replica's snapshot holds it
+
+HOST="127.0.0.1"
+PORT="16491"
+REPLICA_PORT="16492"
+. "$(dirname "$0")/tests/lib.sh"
+
+echo "=== Redis-Rust Bloom / Cuckoo Filter Test Suite ==="
+echo ""
+
+echo "--- Starting server ---"
+cargo build --quiet || exit 1
+./target/debug/redis-rust --port "$PORT" --http-port 0 > "$LOG_DIR/server.log" 2>&1 &
+SERVER_PID=$!
+sleep 1
+echo ""
+
+echo "--- Bloom filters ---"
+check "BF.RESERVE" "+OK" "$(send BF.RESERVE users 0.01 100)"
+check "Keys are reserved once" "item exists" "$(send BF.RESERVE users 0.01 100)"
+check "Error rates are checked" "error rate" "$(send BF.RESERVE bad 1.5 100)"
+check "Huge capacities are refused" "filter is too large" "$(send BF.RESERVE bad 0.01 18446744073709551615)"
+check "Huge Cuckoo capacities are refused" "filter is too large" "$(send CF.RESERVE bad 18446744073709551615)"
+check "BF.ADD of a new item" ":1" "$(send BF.ADD users alice)"
+check "BF.ADD of a known item" ":0" "$(send BF.ADD users alice)"
+check "BF.EXISTS" ":1" "$(send BF.EXISTS users alice)"
+check "BF.EXISTS on a missing key" ":0" "$(send BF.EXISTS nobody alice)"
+
+# Well past the capacity, so the filter has to scale
+items=()
+for i in $(seq 1 500); do
+    items+=("user:$i")
+done
+send BF.MADD users "${items[@]}" > /dev/null
+check "No added item is missed" "" "$(send BF.MEXISTS users "${items[@]}" | grep -v '^\*' | grep -v '^:1$')"
+check "The filter scaled" "Number of filters" "$(send BF.INFO users | grep -A1 'Number of filters' | tail -1 | grep -qv '^:1$' && echo 'Number of filters')"
+missing=()
+for i in $(seq 1 500); do
+    missing+=("other:$i")
+done
+false_positives=$(send BF.MEXISTS users "${missing[@]}" | grep -c '^:1$')
+check "False positives stay rare" "ok" "$([ "$false_positives" -le 15 ] && echo ok || echo "$false_positives")"
+items_added=$(send BF.INFO users ITEMS | tail -1 | tr -d :)
+check "BF.INFO ITEMS leaves out false positives" "ok" "$([ "$items_added" -ge 480 ] && [ "$items_added" -le 501 ] && echo ok || echo "$items_added")"
+
+send BF.RESERVE small 0.01 2 NONSCALING > /dev/null
+send BF.MADD small a b > /dev/null
+check "NONSCALING filters fill up" "non scaling filter is full" "$(send BF.ADD small c)"
+check "BF.ADD creates keys" ":1" "$(send BF.ADD fresh x)"
+check "Types are kept apart" "WRONGTYPE" "$(send CF.ADD fresh x)"
+send SET plain value > /dev/null
+check "BF.EXISTS on a string" "WRONGTYPE" "$(send BF.EXISTS plain x)"
+check "BF.MEXISTS on a string" "WRONGTYPE" "$(send BF.MEXISTS plain x y)"
+check "CF.EXISTS on a string" "WRONGTYPE" "$(send CF.EXISTS plain x)"
+check "CF.COUNT on a string" "WRONGTYPE" "$(send CF.COUNT plain x)"
+check "BF.EXISTS on a missing key" ":0" "$(send BF.EXISTS nothing x)"
+echo ""
+
+echo "--- Cuckoo filters ---"
+check "CF.RESERVE" "+OK" "$(send CF.RESERVE seen 64 BUCKETSIZE 4)"
+check "CF.ADD" ":1" "$(send CF.ADD seen apple)"
+send CF.ADD seen apple > /dev/null
+check "CF.ADDNX of a known item" ":0" "$(send CF.ADDNX seen apple)"
+check "CF.COUNT counts duplicates" ":2" "$(send CF.COUNT seen apple)"
+check "CF.DEL" ":1" "$(send CF.DEL seen apple)"
+check "One copy is left" ":1" "$(send CF.EXISTS seen apple)"
+send CF.DEL seen apple > /dev/null
+check "Deleted items are gone" ":0" "$(send CF.EXISTS seen apple)"
+check "CF.DEL of a missing item" ":0" "$(send CF.DEL seen pear)"
+check "CF.DEL on a missing key" "Not found" "$(send CF.DEL nothing pear)"
+
+items=()
+for i in $(seq 1 300); do
+    items+=("fruit:$i")
+done
+for item in "${items[@]}"; do
+    printf '*3\r\n$6\r\nCF.ADD\r\n$4\r\nseen\r\n$%d\r\n%s\r\n' "${#item}" "$item"
+done | nc -w 1 $HOST "$PORT" > /dev/null
+check "No added item is missed" "" "$(send CF.MEXISTS seen "${items[@]}" | grep -v '^\*' | grep -v '^:1$')"
+check "The filter grew" "Number of filters" "$(send CF.INFO seen | grep -A1 'Number of filters' | tail -1 | grep -qv '^:1$' && echo 'Number of filters')"
+check "CF.INFO counts deletions" "$(printf 'Number of items deleted\n:2')" "$(send CF.INFO seen)"
+echo ""
+
+echo "--- Dumps ---"
+header=$(send BF.SCANDUMP users 0 | sed -n 4p)
+chunk=$(send BF.SCANDUMP users 1 | sed -n 4p)
+check "BF.SCANDUMP ends with iterator 0" ":0" "$(send BF.SCANDUMP users 2 | sed -n 2p)"
+send BF.LOADCHUNK copy 1 "$header" > /dev/null
+check "BF.LOADCHUNK" "+OK" "$(send BF.LOADCHUNK copy 2 "$chunk")"
+check "The copy holds the items" "" "$(send BF.MEXISTS copy user:1 user:250 user:500 | grep -v '^\*' | grep -v '^:1$')"
+check "Bad chunks are refused" "received bad data" "$(send BF.LOADCHUNK copy 1 garbage)"
+check "Headers must match their filters" "received bad data" "$(send BF.LOADCHUNK copy 1 "0.01 2 100:999999999999999:7:0")"
+check "Cuckoo headers are capped" "received bad data" "$(send CF.LOADCHUNK copy 1 "1080 2 20 1 0 0 4611686018427387904")"
+check "The server survives them" "+PONG" "$(send PING)"
+
+./target/debug/redis-rust --port "$REPLICA_PORT" --http-port 0 \
+    --replicaof "127.0.0.1 $PORT" > "$LOG_DIR/replica.log" 2>&1 &
+REPLICA_PID=$!
+sleep 2
+check "The replica's snapshot has the Bloom filter" "" \
+    "$(send_to "$REPLICA_PORT" BF.MEXISTS users user:1 user:250 user:500 alice | grep -v '^\*' | grep -v '^:1$')"
+check "The replica's snapshot has the Cuckoo filter" "$(send CF.INFO seen)" "$(send_to "$REPLICA_PORT" CF.INFO seen)"
+check "Counts match the master's" "$(send CF.COUNT seen fruit:17)" "$(send_to "$REPLICA_PORT" CF.COUNT seen fruit:17)"
+send CF.DEL seen fruit:17 > /dev/null
+sleep 0.5
+check "Later writes are replicated" "$(printf 'Number of items deleted\n:3')" "$(send_to "$REPLICA_PORT" CF.INFO seen)"
+echo ""
+
+finish "Bloom / Cuckoo filter"
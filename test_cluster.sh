@@ -0,0 +1,81 @@
+#!/bin/bash
+
+# Redis-Rust Cluster Test Script
+# Starts two cluster nodes on localhost, splits the slots between them and checks that:
+#   - the nodes discover each other after CLUSTER MEET
+#   - keys in slots served by the other node are redirected with MOVED
+#   - multi-key commands across slots fail with CROSSSLOT
+#   - a slot can be moved with SETSLOT and MIGRATE, answering ASK in between
+#   - writes arriving during MIGRATE wait for it instead of landing mid-transfer
+
+HOST="127.0.0.1"
+PORT_A="16400"
+PORT_B="16401"
+. "$(dirname "$0")/tests/lib.sh"
+
+echo "=== Redis-Rust Cluster Test Suite ==="
+echo ""
+
+echo "--- Starting nodes ---"
+cargo build --quiet || exit 1
+./target/debug/redis-rust --port "$PORT_A" --http-port 0 --cluster-enabled yes > "$LOG_DIR/a.log" 2>&1 &
+PID_A=$!
+./target/debug/redis-rust --port "$PORT_B" --http-port 0 --cluster-enabled yes > "$LOG_DIR/b.log" 2>&1 &
+PID_B=$!
+sleep 1
+ID_A=$(send_inline "$PORT_A" "CLUSTER MYID" | tail -n 1)
+ID_B=$(send_inline "$PORT_B" "CLUSTER MYID" | tail -n 1)
+send_inline "$PORT_A" "CLUSTER ADDSLOTSRANGE 0 8191" > /dev/null
+send_inline "$PORT_B" "CLUSTER ADDSLOTSRANGE 8192 16383" > /dev/null
+send_inline "$PORT_A" "CLUSTER MEET $HOST $PORT_B" > /dev/null
+sleep 3
+echo ""
+
+echo "--- Topology ---"
+check "Node A sees all slots served" "cluster_state:ok" "$(send_inline "$PORT_A" "CLUSTER INFO")"
+check "Node B learned about node A" "cluster_known_nodes:2" "$(send_inline "$PORT_B" "CLUSTER INFO")"
+check "Node B lists A's slots" "$ID_A $HOST:$PORT_A@" "$(send_inline "$PORT_B" "CLUSTER NODES")"
+check "Key slot of foo" ":12182" "$(send_inline "$PORT_A" "CLUSTER KEYSLOT foo")"
+check "Hash tags share a slot" \
+    "$(send_inline "$PORT_A" "CLUSTER KEYSLOT user1000")" \
+    "$(send_inline "$PORT_A" "CLUSTER KEYSLOT {user1000}.following")"
+echo ""
+
+echo "--- Redirects ---"
+check "Write to the wrong node is redirected" "MOVED 12182 $HOST:$PORT_B" "$(send_inline "$PORT_A" "SET foo bar")"
+check "Write to the owner succeeds" "OK" "$(send_inline "$PORT_B" "SET foo bar")"
+check "Keys in different slots are refused" "CROSSSLOT" "$(send_inline "$PORT_B" "DEL foo bar")"
+check "Key counted in its slot" ":1" "$(send_inline "$PORT_B" "CLUSTER COUNTKEYSINSLOT 12182")"
+echo ""
+
+echo "--- Slot migration ---"
+send_inline "$PORT_A" "CLUSTER SETSLOT 12182 IMPORTING $ID_B" > /dev/null
+send_inline "$PORT_B" "CLUSTER SETSLOT 12182 MIGRATING $ID_A" > /dev/null
+check "Key moved to node A" "OK" "$(send_inline "$PORT_B" "MIGRATE $HOST $PORT_A foo 0 1000")"
+check "Migrated key is answered with ASK" "ASK 12182 $HOST:$PORT_A" "$(send_inline "$PORT_B" "GET foo")"
+check "Importing node redirects without ASKING" "MOVED 12182" "$(send_inline "$PORT_A" "GET foo")"
+check "Importing node serves after ASKING" "bar" "$(send_inline "$PORT_A" "ASKING\nGET foo")"
+send_inline "$PORT_A" "CLUSTER SETSLOT 12182 NODE $ID_A" > /dev/null
+send_inline "$PORT_B" "CLUSTER SETSLOT 12182 NODE $ID_A" > /dev/null
+check "New owner serves the key" "bar" "$(send_inline "$PORT_A" "GET foo")"
+check "Old owner redirects to the new one" "MOVED 12182 $HOST:$PORT_A" "$(send_inline "$PORT_B" "GET foo")"
+sleep 2
+check "Node B still agrees after the next poll" "MOVED 12182 $HOST:$PORT_A" "$(send_inline "$PORT_B" "GET foo")"
+echo ""
+
+echo "--- Writes during MIGRATE ---"
+# Pausing node A stalls the transfer, so the SET arrives while MIGRATE is running
+send_inline "$PORT_B" "SET race old" > /dev/null
+send_inline "$PORT_A" "CLUSTER SETSLOT 8508 IMPORTING $ID_B" > /dev/null
+send_inline "$PORT_A" "CLIENT PAUSE 1500 ALL" > /dev/null
+printf "MIGRATE $HOST $PORT_A race 0 5000\n" | nc -w 3 $HOST "$PORT_B" > "$LOG_DIR/migrate.out" &
+MIGRATE_PID=$!
+sleep 0.3
+check "A write to a key being migrated waits for MIGRATE" "OK" "$(printf "SET race new\n" | nc -w 3 $HOST "$PORT_B")"
+wait "$MIGRATE_PID"
+check "MIGRATE finished" "OK" "$(cat "$LOG_DIR/migrate.out")"
+check "The target got the value from before the write" "old" "$(send_inline "$PORT_A" "ASKING\nGET race")"
+check "The write wasn't lost" "new" "$(send_inline "$PORT_B" "GET race")"
+echo ""
+
+finish "cluster"
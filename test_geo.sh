@@ -0,0 +1,49 @@
+#!/bin/bash
+
+# Redis-Rust Geo Test Script
+# Starts a server and checks that:
+#   - GEOADD stores positions as geohash scores that ZRANGE/ZSCORE can see
+#   - GEOPOS, GEODIST and GEOHASH give the same answers as Redis
+#   - GEOSEARCH finds members by radius or box, sorted and limited
+#   - GEOSEARCHSTORE stores the result
+
+HOST="127.0.0.1"
+PORT="16460"
+. "$(dirname "$0")/tests/lib.sh"
+
+echo "=== Redis-Rust Geo Test Suite ==="
+echo ""
+
+echo "--- Starting server ---"
+cargo build --quiet || exit 1
+./target/debug/redis-rust --port "$PORT" --http-port 0 > "$LOG_DIR/server.log" 2>&1 &
+SERVER_PID=$!
+sleep 1
+echo ""
+
+echo "--- Positions ---"
+check "GEOADD adds members" ":2" "$(send GEOADD Sicily 13.361389 38.115556 Palermo 15.087269 37.502669 Catania)"
+check "Scores are 52 bit geohashes" "3479099956230698" "$(send ZSCORE Sicily Palermo)"
+check "ZRANGE works on geo keys" "Palermo" "$(send ZRANGE Sicily 0 1)"
+check "GEOPOS decodes the position" "13.36138933897018433" "$(send GEOPOS Sicily Palermo)"
+check "GEODIST in meters" "166274.1516" "$(send GEODIST Sicily Palermo Catania)"
+check "GEODIST in kilometers" "166.2742" "$(send GEODIST Sicily Palermo Catania km)"
+check "GEOHASH returns standard geohashes" "sqc8b49rny0" "$(send GEOHASH Sicily Palermo)"
+check "Positions are validated" "invalid longitude,latitude pair 181.000000,10.000000" "$(send GEOADD Sicily 181 10 x)"
+echo ""
+
+echo "--- Searching ---"
+send GEOADD Sicily 12.758489 38.788135 edge1 17.241510 38.788135 edge2 > /dev/null
+check "BYRADIUS sorts by distance" "$(printf '*2\n$7\nCatania\n$7\nPalermo')" \
+    "$(send GEOSEARCH Sicily FROMLONLAT 15 37 BYRADIUS 200 km ASC)"
+check "BYBOX finds the edges too" "*4" "$(send GEOSEARCH Sicily FROMLONLAT 15 37 BYBOX 400 400 km ASC)"
+check "WITHDIST adds distances" "56.4413" "$(send GEOSEARCH Sicily FROMLONLAT 15 37 BYRADIUS 200 km WITHDIST)"
+check "COUNT with DESC takes the farthest" "edge1" "$(send GEOSEARCH Sicily FROMLONLAT 15 37 BYRADIUS 500 km COUNT 1 DESC)"
+check "FROMMEMBER searches around a member" "edge1" "$(send GEOSEARCH Sicily FROMMEMBER Palermo BYRADIUS 100 km)"
+check "Unknown members" "could not decode requested zset member" \
+    "$(send GEOSEARCH Sicily FROMMEMBER nobody BYRADIUS 100 km)"
+check "GEOSEARCHSTORE stores the matches" ":2" "$(send GEOSEARCHSTORE near Sicily FROMLONLAT 15 37 BYRADIUS 200 km)"
+check "The stored set is a geo set" "sqdtr74hyu0" "$(send GEOHASH near Catania)"
+echo ""
+
+finish "geo"
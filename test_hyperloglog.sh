@@ -0,0 +1,93 @@
+#!/bin/bash
+
+# Redis-Rust HyperLogLog Test Script
+# Starts a server and checks that:
+#   - PFADD reports register changes and PFCOUNT estimates the cardinality
+#   - sparse HyperLogLogs turn dense past hll-sparse-max-bytes
+#   - PFMERGE and multi-key PFCOUNT count the union
+#   - values use the Redis "HYLL" representation and other strings are rejected
+#   - corrupted encodings are caught and DUMP/RESTORE carry HyperLogLogs over
+
+HOST="127.0.0.1"
+PORT="16440"
+. "$(dirname "$0")/tests/lib.sh"
+
+# The payload DUMP returns for a key, as hex
+dump_hex() {
+    local hex
+    hex=$(printf "*2\r\n\$4\r\nDUMP\r\n\$${#1}\r\n%s\r\n" "$1" | nc -w 1 $HOST "$PORT" | od -An -v -tx1 | tr -d ' \n')
+    hex=${hex#*0d0a}
+    echo "${hex%0d0a}"
+}
+
+# RESTORE key ttl payload [option], with the payload given as hex
+restore_hex() {
+    local escaped
+    escaped=$(echo "$3" | sed 's/../\\x&/g')
+    printf "%b" "*$(($# + 1))\r\n\$7\r\nRESTORE\r\n\$${#1}\r\n$1\r\n\$${#2}\r\n$2\r\n\$$((${#3} / 2))\r\n$escaped\r\n${4:+\$${#4}\r\n$4\r\n}" \
+        | nc -w 1 $HOST "$PORT" | tr -d '\r'
+}
+
+echo "=== Redis-Rust HyperLogLog Test Suite ==="
+echo ""
+
+echo "--- Starting server ---"
+cargo build --quiet || exit 1
+./target/debug/redis-rust --port "$PORT" --http-port 0 --hll-sparse-max-bytes 200 > "$LOG_DIR/server.log" 2>&1 &
+SERVER_PID=$!
+sleep 1
+echo ""
+
+echo "--- Counting ---"
+check "PFADD changes registers" ":1" "$(send PFADD visitors a b c d e f g)"
+check "PFCOUNT estimates the cardinality" ":7" "$(send PFCOUNT visitors)"
+check "Adding a known element changes nothing" ":0" "$(send PFADD visitors a)"
+check "PFADD without elements creates the key" ":1" "$(send PFADD empty)"
+check "Empty HyperLogLogs count zero" ":0" "$(send PFCOUNT empty)"
+check "Small HyperLogLogs are sparse" "48 59 4c 4c 01" "$(send GET visitors | od -An -tx1 | tr -s ' \n' ' ')"
+echo ""
+
+echo "--- Dense encoding ---"
+# shellcheck disable=SC2046
+send PFADD many $(seq 1 1000) > /dev/null
+check "Large HyperLogLogs turn dense" "48 59 4c 4c 00" "$(send GET many | od -An -tx1 | tr -s ' \n' ' ')"
+COUNT=$(send PFCOUNT many | tr -d ':')
+if [ "$COUNT" -ge 980 ] && [ "$COUNT" -le 1020 ]; then
+    echo "PASS: Dense count is within 2% ($COUNT)"
+else
+    echo "FAIL: Dense count is off ($COUNT)"
+    FAILED=1
+fi
+echo ""
+
+echo "--- Merging ---"
+send PFADD more x y z a > /dev/null
+check "PFMERGE stores the union" "OK" "$(send PFMERGE union visitors more)"
+check "The merged HyperLogLog counts it" ":10" "$(send PFCOUNT union)"
+check "PFCOUNT over several keys counts the union" ":10" "$(send PFCOUNT visitors more)"
+send SET plain text > /dev/null
+check "Other strings are rejected" "WRONGTYPE Key is not a valid HyperLogLog string value." "$(send PFADD plain a)"
+echo ""
+
+echo "--- Encoding checks ---"
+# A sparse HLL whose only opcode covers one register instead of all of them
+printf '*3\r\n$3\r\nSET\r\n$7\r\ncorrupt\r\n$17\r\nHYLL\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x80\x00\r\n' \
+    | nc -w 1 $HOST "$PORT" > /dev/null
+check "PFADD rejects a corrupted sparse HLL" "INVALIDOBJ Corrupted HLL object detected" "$(send PFADD corrupt a)"
+check "PFCOUNT rejects it too" "INVALIDOBJ Corrupted HLL object detected" "$(send PFCOUNT corrupt)"
+check "PFMERGE won't read it" "INVALIDOBJ Corrupted HLL object detected" "$(send PFMERGE merged corrupt)"
+echo ""
+
+echo "--- DUMP and RESTORE ---"
+PAYLOAD=$(dump_hex visitors)
+check "DUMP ends with the RDB version and checksum" "0b00" "${PAYLOAD: -20:4}"
+check "RESTORE loads the payload" "OK" "$(restore_hex copy 0 "$PAYLOAD")"
+check "The restored HyperLogLog counts the same" ":7" "$(send PFCOUNT copy)"
+check "RESTORE keeps existing keys" "BUSYKEY Target key name already exists." "$(restore_hex copy 0 "$PAYLOAD")"
+check "unless REPLACE is given" "OK" "$(restore_hex copy 0 "$PAYLOAD" REPLACE)"
+check "Bad checksums are rejected" "DUMP payload version or checksum are wrong" "$(restore_hex other 0 "${PAYLOAD%??}00")"
+check "Missing keys dump as nil" "\$-1" "$(send DUMP nokey)"
+check "Only string values can be dumped" "DUMP is only supported for string values" "$(send RPUSH list a > /dev/null; send DUMP list)"
+echo ""
+
+finish "HyperLogLog"
@@ -0,0 +1,82 @@
+#!/bin/bash
+
+# Redis-Rust JSON Test Script
+# Starts a server and checks that:
+#   - JSON.SET/JSON.GET store and return documents, with JSONPath and legacy paths
+#   - JSON.DEL, JSON.NUMINCRBY, JSON.ARRAPPEND and JSON.ARRPOP change documents in place
+#   - JSON.OBJKEYS, JSON.TYPE and JSON.MGET inspect them
+#   - the HTTP API serves documents as JSON
+
+HOST="127.0.0.1"
+PORT="16470"
+HTTP_PORT="16471"
+. "$(dirname "$0")/tests/lib.sh"
+
+echo "=== Redis-Rust JSON Test Suite ==="
+echo ""
+
+echo "--- Starting server ---"
+cargo build --quiet || exit 1
+./target/debug/redis-rust --port "$PORT" --http-port "$HTTP_PORT" > "$LOG_DIR/server.log" 2>&1 &
+SERVER_PID=$!
+sleep 1
+echo ""
+
+DOC='{"name":"shop","items":[{"title":"pen","price":2},{"title":"book","price":12}],"open":true}'
+
+echo "--- Setting and getting ---"
+check "JSON.SET creates a document" "+OK" "$(send JSON.SET shop '$' "$DOC")"
+check "JSON.GET returns the document" '{"name":"shop","items"' "$(send JSON.GET shop)"
+check "JSONPath returns every match" '["pen","book"]' "$(send JSON.GET shop '$..title')"
+check "Filters select by value" '[{"title":"pen","price":2}]' "$(send JSON.GET shop '$.items[?(@.price < 10)]')"
+check "Legacy paths return the value" '"shop"' "$(send JSON.GET shop .name)"
+check "Several paths give an object" '{"$.name":["shop"],"$.open":[true]}' "$(send JSON.GET shop '$.name' '$.open')"
+check "Formatting options" "$(printf '{\n  "a": 1\n}')" \
+    "$(send JSON.SET fmt '$' '{"a":1}' > /dev/null; send JSON.GET fmt INDENT '  ' NEWLINE $'\n' SPACE ' ')"
+check "JSON.SET adds members" "+OK" "$(send JSON.SET shop '$.city' '"Rome"')"
+check "NX leaves existing paths alone" "\$-1" "$(send JSON.SET shop '$.city' '"Oslo"' NX)"
+check "New keys start at the root" "new objects must be created at the root" "$(send JSON.SET other '$.a' 1)"
+check "Invalid paths are rejected" "JSON Path error" "$(send JSON.GET shop '$.[')"
+check "Nested filters parse" '[{"title":"pen","price":2}]' "$(send JSON.GET shop '$.items[?(!(!((@.price < 10))))]')"
+deep="\$[?$(printf '(%.0s' {1..50000})"
+check "Deeply nested filters are rejected" "JSON Path error" "$(send JSON.GET shop "$deep")"
+check "The server survives them" "+PONG" "$(send PING)"
+echo ""
+
+echo "--- Changing documents ---"
+check "JSON.NUMINCRBY adds to every match" '[3,13]' "$(send JSON.NUMINCRBY shop '$.items[*].price' 1)"
+check "JSON.NUMINCRBY on non-numbers" "WRONGTYPE" "$(send JSON.NUMINCRBY shop .name 1)"
+send JSON.SET shop '$.tags' '["a"]' > /dev/null
+check "JSON.ARRAPPEND returns the new length" ":3" "$(send JSON.ARRAPPEND shop .tags '"b"' '"c"')"
+check "JSON.ARRPOP pops the last element" '"c"' "$(send JSON.ARRPOP shop .tags)"
+check "JSON.ARRPOP with an index" '"a"' "$(send JSON.ARRPOP shop .tags 0)"
+check "JSON.DEL removes every match" ":2" "$(send JSON.DEL shop '$.items[*].price')"
+check "Deleted paths are gone" '[]' "$(send JSON.GET shop '$..price')"
+echo ""
+
+echo "--- Inspecting ---"
+check "JSON.OBJKEYS lists members" "$(printf '$4\nname\n$5\nitems')" "$(send JSON.OBJKEYS shop)"
+check "JSON.TYPE names the type" "object" "$(send JSON.TYPE shop)"
+check "JSON.TYPE tells integers apart" "integer" "$(send JSON.TYPE fmt .a)"
+check "JSON.MGET reads several keys" "$(printf '*3\n$8\n["shop"]\n$-1\n$2\n[]')" \
+    "$(send JSON.MGET shop missing fmt '$.name')"
+echo ""
+
+echo "--- HTTP API ---"
+check "GET /json returns the document" '"data":{"name":"shop"' "$(curl -s "http://$HOST:$HTTP_PORT/json/shop")"
+check "GET /json with a path" '"data":["Rome"]' "$(curl -s "http://$HOST:$HTTP_PORT/json/shop?path=\$.city")"
+check "GET /keys serves documents as JSON" '"data":{"a":1}' "$(curl -s "http://$HOST:$HTTP_PORT/keys/fmt")"
+curl -s -X POST -H 'Content-Type: application/json' -d '{"n":[1,2]}' "http://$HOST:$HTTP_PORT/json/posted" > /dev/null
+check "POST /json stores a document" '[1,2]' "$(send JSON.GET posted .n)"
+check "DELETE /json deletes it" '"data":1' "$(curl -s -X DELETE "http://$HOST:$HTTP_PORT/json/posted")"
+send SET plain text > /dev/null
+check "POST /json over another type is a conflict" "409" \
+    "$(curl -s -o /dev/null -w '%{http_code}' -X POST -H 'Content-Type: application/json' -d '{}' "http://$HOST:$HTTP_PORT/json/plain")"
+check "and says why" "WRONGTYPE" \
+    "$(curl -s -X POST -H 'Content-Type: application/json' -d '{}' "http://$HOST:$HTTP_PORT/json/plain")"
+check "POST /lists over another type is a conflict" "409" \
+    "$(curl -s -o /dev/null -w '%{http_code}' -X POST -H 'Content-Type: application/json' -d '{"value":"a"}' "http://$HOST:$HTTP_PORT/lists/plain/lpush")"
+check "DEL works on JSON keys" ":1" "$(send DEL fmt)"
+echo ""
+
+finish "JSON"
@@ -0,0 +1,51 @@
+#!/bin/bash
+
+# Redis-Rust MONITOR Test Script
+# Starts a server and checks that:
+#   - MONITOR streams every command with a timestamp, the db and the client address
+#   - arguments are quoted and escaped, and commands run by scripts show up as lua
+#   - credentials given to AUTH and HELLO are redacted
+
+HOST="127.0.0.1"
+PORT="16498"
+. "$(dirname "$0")/tests/lib.sh"
+
+echo "=== Redis-Rust MONITOR Test Suite ==="
+echo ""
+
+echo "--- Starting server ---"
+cargo build --quiet || exit 1
+./target/debug/redis-rust --port "$PORT" --http-port 0 > "$LOG_DIR/server.log" 2>&1 &
+SERVER_PID=$!
+sleep 1
+echo ""
+
+echo "--- Monitoring ---"
+NC_WAIT=3 send MONITOR > "$LOG_DIR/monitor.out" &
+MONITOR_PID=$!
+sleep 0.5
+check "Monitors are flagged in CLIENT LIST" "flags=O" "$(send CLIENT LIST)"
+send SET greeting 'hello "world"' > /dev/null
+send GET greeting > /dev/null
+send EVAL "return redis.call('GET', KEYS[1])" 1 greeting > /dev/null
+send HELLO 3 AUTH default s3cret SETNAME app > /dev/null
+send AUTH default s3cret > /dev/null
+wait "$MONITOR_PID"
+monitor=$(cat "$LOG_DIR/monitor.out")
+echo ""
+
+check "MONITOR replies OK" "+OK" "$monitor"
+if grep -qE '^\+[0-9]+\.[0-9]{6} \[0 127\.0\.0\.1:[0-9]+\] "GET" "greeting"$' "$LOG_DIR/monitor.out"; then
+    echo "PASS: Lines carry the time, db and client address"
+else
+    echo "FAIL: Lines carry the time, db and client address (got '$monitor')"
+    FAILED=1
+fi
+check "Arguments are quoted and escaped" '"SET" "greeting" "hello \"world\""' "$monitor"
+check "Commands from scripts show up as lua" '[0 lua] "GET" "greeting"' "$monitor"
+check "HELLO credentials are redacted" '"HELLO" "3" "AUTH" "(redacted)" "(redacted)" "SETNAME" "app"' "$monitor"
+check "AUTH is redacted" '"AUTH" "(redacted)" "(redacted)"' "$monitor"
+check_absent "Passwords never reach monitors" "s3cret" "$monitor"
+echo ""
+
+finish "MONITOR"
@@ -0,0 +1,121 @@
+#!/bin/bash
+
+# Redis-Rust Pub/Sub Test Script
+# Starts a server and checks that:
+#   - SUBSCRIBE/PSUBSCRIBE receive what PUBLISH sends, and subscribed clients
+#     can only manage their subscriptions
+#   - writes and expirations publish keyspace notifications as selected by
+#     notify-keyspace-events
+#   - a replica notifies its own subscribers about the writes it replays
+#   - CLIENT PAUSE WRITE holds back PUBLISH
+
+HOST="127.0.0.1"
+PORT="16495"
+REPLICA_PORT="16496"
+. "$(dirname "$0")/tests/lib.sh"
+
+# Subscribes in the background until nothing arrives for a few seconds, writing what arrives to a file
+LISTENERS=()
+listen_to() {
+    local port=$1
+    local out=$2
+    shift 2
+    NC_WAIT=5 send_to "$port" "$@" > "$out" &
+    LISTENERS+=($!)
+    sleep 0.5
+}
+
+wait_listeners() {
+    wait "${LISTENERS[@]}"
+    LISTENERS=()
+}
+
+echo "=== Redis-Rust Pub/Sub Test Suite ==="
+echo ""
+
+echo "--- Starting server ---"
+cargo build --quiet || exit 1
+check "Unknown notification flags are refused" "Invalid value 'KEq'" \
+    "$(./target/debug/redis-rust --port "$PORT" --notify-keyspace-events KEq 2>&1)"
+./target/debug/redis-rust --port "$PORT" --http-port 0 --notify-keyspace-events KEA \
+    > "$LOG_DIR/server.log" 2>&1 &
+SERVER_PID=$!
+sleep 1
+echo ""
+
+echo "--- Publish and subscribe ---"
+check "PUBLISH without subscribers reaches nobody" ":0" "$(send PUBLISH news hello)"
+listen_to "$PORT" "$LOG_DIR/subscribe.out" SUBSCRIBE news sports
+listen_to "$PORT" "$LOG_DIR/psubscribe.out" PSUBSCRIBE 'n[eo]*'
+check "PUBSUB NUMSUB" "$(printf 'news\n:1')" "$(send PUBSUB NUMSUB news)"
+check "PUBSUB NUMPAT" ":1" "$(send PUBSUB NUMPAT)"
+check "PUBLISH counts channel and pattern subscribers" ":2" "$(send PUBLISH news hello)"
+check "Patterns only get matching channels" ":1" "$(send PUBLISH sports goal)"
+wait_listeners
+check "SUBSCRIBE confirms each channel" "$(printf 'subscribe\n$6\nsports\n:2')" "$(cat "$LOG_DIR/subscribe.out")"
+check "Subscribers get messages" "$(printf 'message\n$4\nnews\n$5\nhello')" "$(cat "$LOG_DIR/subscribe.out")"
+check "Pattern subscribers get pmessages" "$(printf 'pmessage\n$6\nn[eo]*\n$4\nnews\n$5\nhello')" \
+    "$(cat "$LOG_DIR/psubscribe.out")"
+check "Subscriptions end with the connection" ":0" "$(send PUBLISH news again)"
+
+subscribed=$(printf "%b" "$(request SUBSCRIBE news)$(request GET key)$(request UNSUBSCRIBE)$(request GET key)" \
+    | nc -w 1 $HOST "$PORT" | tr -d '\r')
+check "Subscribed clients can't run other commands" "Can't execute 'get'" "$subscribed"
+check "UNSUBSCRIBE without arguments drops every channel" "$(printf 'unsubscribe\n$4\nnews\n:0\n$-1')" "$subscribed"
+echo ""
+
+echo "--- Keyspace notifications ---"
+listen_to "$PORT" "$LOG_DIR/keyspace.out" PSUBSCRIBE '__keyspace@0__:*'
+listen_to "$PORT" "$LOG_DIR/keyevent.out" PSUBSCRIBE '__keyevent@0__:*'
+send SET greeting hello > /dev/null
+send LPUSH queue job > /dev/null
+send SADD tags rust > /dev/null
+send ZADD board 1 alice > /dev/null
+send DEL greeting > /dev/null
+send SET session token EX 1 > /dev/null
+sleep 1.2
+send GET session > /dev/null
+wait_listeners
+keyspace=$(cat "$LOG_DIR/keyspace.out")
+keyevent=$(cat "$LOG_DIR/keyevent.out")
+check "SET on the keyspace channel" "$(printf '__keyspace@0__:greeting\n$3\nset')" "$keyspace"
+check "LPUSH on the keyspace channel" "$(printf '__keyspace@0__:queue\n$5\nlpush')" "$keyspace"
+check "DEL on the keyevent channel" "$(printf '__keyevent@0__:del\n$8\ngreeting')" "$keyevent"
+check "SADD on the keyevent channel" "$(printf '__keyevent@0__:sadd\n$4\ntags')" "$keyevent"
+check "ZADD on the keyevent channel" "$(printf '__keyevent@0__:zadd\n$5\nboard')" "$keyevent"
+check "Expired keys are announced" "$(printf '__keyevent@0__:expired\n$7\nsession')" "$keyevent"
+echo ""
+
+echo "--- Replication ---"
+./target/debug/redis-rust --port "$REPLICA_PORT" --http-port 0 --replicaof "127.0.0.1 $PORT" \
+    --notify-keyspace-events Elx > "$LOG_DIR/replica.log" 2>&1 &
+REPLICA_PID=$!
+sleep 2
+listen_to "$REPLICA_PORT" "$LOG_DIR/replica.out" PSUBSCRIBE '__key*'
+send RPUSH queue job2 > /dev/null
+send SADD tags go > /dev/null
+wait_listeners
+replica=$(cat "$LOG_DIR/replica.out")
+check "Replicas notify about replayed writes" "$(printf '__keyevent@0__:rpush\n$5\nqueue')" "$replica"
+if [[ "$replica" == *"keyspace@"* || "$replica" == *"sadd"* ]]; then
+    echo "FAIL: Only the selected channels and classes are published (got '$replica')"
+    FAILED=1
+else
+    echo "PASS: Only the selected channels and classes are published"
+fi
+echo ""
+
+echo "--- Client pause ---"
+send CLIENT PAUSE 3000 WRITE > /dev/null
+reply=$(send PUBLISH news paused)
+if [ -z "$reply" ]; then
+    echo "PASS: CLIENT PAUSE WRITE holds back PUBLISH"
+else
+    echo "FAIL: CLIENT PAUSE WRITE holds back PUBLISH (got '$reply')"
+    FAILED=1
+fi
+send CLIENT UNPAUSE > /dev/null
+check "PUBLISH works again after UNPAUSE" ":0" "$(send PUBLISH news resumed)"
+echo ""
+
+finish "pub/sub"
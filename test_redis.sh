@@ -1,56 +1,122 @@
 #!/bin/bash
 
 # Redis-Rust Test Script
-# Tests all implemented Redis commands
+# Starts a server and checks the core commands:
+#   - strings, lists, sets and sorted sets
+#   - the CLIENT command family
+#   - INFO sections and the counters they report
+#   - the HTTP API's /info and /metrics endpoints
 
-HOST="localhost"
-PORT="6379"
+HOST="127.0.0.1"
+PORT="16502"
+HTTP_PORT="16503"
+. "$(dirname "$0")/tests/lib.sh"
 
 echo "=== Redis-Rust Test Suite ==="
 echo ""
 
-# Helper function to send command
-send_cmd() {
-    echo ">>> $1"
-    echo "$1" | nc -w 1 $HOST $PORT
-    echo ""
-}
+echo "--- Starting server ---"
+cargo build --quiet || exit 1
+./target/debug/redis-rust --port "$PORT" --http-port "$HTTP_PORT" --maxclients 50 > "$LOG_DIR/server.log" 2>&1 &
+SERVER_PID=$!
+sleep 1
+echo ""
 
 echo "--- String Commands ---"
-send_cmd "PING"
-send_cmd "SET testkey testvalue"
-send_cmd "GET testkey"
-send_cmd "SET expkey expvalue EX 60"
-send_cmd "GET expkey"
-send_cmd "DEL testkey"
-send_cmd "GET testkey"
+check "PING" "+PONG" "$(send PING)"
+check "SET" "+OK" "$(send SET testkey testvalue)"
+check "GET" "testvalue" "$(send GET testkey)"
+check "SET with EX" "+OK" "$(send SET expkey expvalue EX 60)"
+check "GET of a key with a TTL" "expvalue" "$(send GET expkey)"
+check "DEL" ":1" "$(send DEL testkey)"
+check "GET of a deleted key" "\$-1" "$(send GET testkey)"
+echo ""
 
 echo "--- List Commands ---"
-send_cmd "LPUSH mylist first"
-send_cmd "RPUSH mylist second"
-send_cmd "LPUSH mylist zeroth"
-send_cmd "LRANGE mylist 0 10"
-send_cmd "LPOP mylist"
-send_cmd "RPOP mylist"
-send_cmd "LRANGE mylist 0 10"
+send LPUSH mylist first > /dev/null
+check "RPUSH" ":2" "$(send RPUSH mylist second)"
+check "LPUSH" ":3" "$(send LPUSH mylist zeroth)"
+check "LRANGE" "$(printf '*3\n$6\nzeroth\n$5\nfirst\n$6\nsecond')" "$(send LRANGE mylist 0 10)"
+check "LPOP" "zeroth" "$(send LPOP mylist)"
+check "RPOP" "second" "$(send RPOP mylist)"
+check "LRANGE after popping" "$(printf '*1\n$5\nfirst')" "$(send LRANGE mylist 0 10)"
+echo ""
 
 echo "--- Set Commands ---"
-send_cmd "SADD myset member1"
-send_cmd "SADD myset member2"
-send_cmd "SADD myset member1"
-send_cmd "SISMEMBER myset member1"
-send_cmd "SISMEMBER myset nonexistent"
-send_cmd "SMEMBERS myset"
-send_cmd "SREM myset member1"
-send_cmd "SMEMBERS myset"
+check "SADD" ":1" "$(send SADD myset member1)"
+send SADD myset member2 > /dev/null
+check "SADD of an existing member" ":0" "$(send SADD myset member1)"
+check "SISMEMBER" ":1" "$(send SISMEMBER myset member1)"
+check "SISMEMBER of a missing member" ":0" "$(send SISMEMBER myset nonexistent)"
+check "SMEMBERS" "*2" "$(send SMEMBERS myset)"
+check "SREM" ":1" "$(send SREM myset member1)"
+check "SMEMBERS after SREM" "$(printf '*1\n$7\nmember2')" "$(send SMEMBERS myset)"
+echo ""
 
 echo "--- Sorted Set Commands ---"
-send_cmd "ZADD scores 100 alice"
-send_cmd "ZADD scores 85 bob"
-send_cmd "ZADD scores 95 charlie"
-send_cmd "ZRANGE scores 0 10"
-send_cmd "ZSCORE scores alice"
-send_cmd "ZREM scores bob"
-send_cmd "ZRANGE scores 0 10"
-
-echo "=== All tests completed! ==="
+send ZADD scores 100 alice > /dev/null
+send ZADD scores 85 bob > /dev/null
+check "ZADD" ":1" "$(send ZADD scores 95 charlie)"
+check "ZRANGE orders by score" "$(printf 'bob\n$7\ncharlie\n$5\nalice')" "$(send ZRANGE scores 0 10)"
+check "ZSCORE" "100" "$(send ZSCORE scores alice)"
+check "ZREM" ":1" "$(send ZREM scores bob)"
+check "ZRANGE after ZREM" "*2" "$(send ZRANGE scores 0 10)"
+echo ""
+
+echo "--- Connection Commands ---"
+check "CLIENT ID" ":" "$(send CLIENT ID)"
+named=$(send_raw "$PORT" 'CLIENT SETNAME test-script\r\nCLIENT GETNAME\r\nCLIENT INFO\r\nCLIENT LIST\r\n')
+check "CLIENT SETNAME" "+OK" "$named"
+check "CLIENT GETNAME" "test-script" "$named"
+check "CLIENT INFO shows the name" "name=test-script" "$named"
+check "CLIENT LIST shows the command" "cmd=client" "$named"
+check "CLIENT LIST shows the address" "addr=127.0.0.1:" "$(send CLIENT LIST)"
+check "Names can't contain spaces" "Client names cannot contain spaces" "$(send CLIENT SETNAME 'a b')"
+check "CLIENT KILL of an unknown client" "No such client" "$(send CLIENT KILL 127.0.0.1:1)"
+exec 5<>/dev/tcp/$HOST/$PORT
+printf 'CLIENT ID\r\n' >&5
+read -r id <&5
+id=${id#:}
+printf 'CLIENT KILL ID %s SKIPME no\r\nPING\r\n' "${id%$'\r'}" >&5
+killed=$(timeout 2 cat <&5 | tr -d '\r')
+exec 5>&-
+check "CLIENT KILL of ourselves replies first" ":1" "$killed"
+check_absent "then closes the connection" "+PONG" "$killed"
+NC_WAIT=2 send SUBSCRIBE news > /dev/null &
+SUBSCRIBER_PID=$!
+sleep 0.3
+check "Subscribers are flagged in CLIENT LIST" "flags=P" "$(send CLIENT LIST)"
+wait $SUBSCRIBER_PID
+echo ""
+
+echo "--- Server Commands ---"
+info=$(send INFO)
+for section in Server Clients Memory Persistence Stats Replication Keyspace; do
+    check "INFO has the $section section" "# $section" "$info"
+done
+check "INFO server reports the mode" "redis_mode:standalone" "$(send INFO server)"
+check "INFO server reports the pid" "process_id:$SERVER_PID" "$(send INFO server)"
+check "INFO clients counts this client" "connected_clients:1" "$(send INFO clients)"
+check "INFO clients reports maxclients" "maxclients:50" "$(send INFO clients)"
+check "INFO memory reports usage" "used_memory_human:" "$(send INFO memory)"
+check "INFO stats counts connections" "total_connections_received:" "$(send INFO stats)"
+check "INFO stats counts hits" "keyspace_hits:" "$(send INFO stats)"
+check "INFO keyspace counts keys and expires" "db0:keys=4,expires=1" "$(send INFO keyspace)"
+check_absent "INFO keyspace leaves out the other sections" "# Server" "$(send INFO keyspace)"
+send DEL expkey mylist myset scores > /dev/null
+check "INFO keyspace is kept without keys" "# Keyspace" "$(send INFO keyspace)"
+check_absent "INFO keyspace leaves out empty databases" "db0:" "$(send INFO keyspace)"
+echo ""
+
+echo "--- Monitoring ---"
+check "GET /info serves INFO as JSON" '"redis_mode":"standalone"' "$(curl -s "http://$HOST:$HTTP_PORT/info")"
+check "GET /info/clients serves one section" '"maxclients":50' "$(curl -s "http://$HOST:$HTTP_PORT/info/clients")"
+metrics=$(curl -s "http://$HOST:$HTTP_PORT/metrics")
+check "/metrics counts calls per command" 'redis_commands_total{cmd="set"}' "$metrics"
+check "/metrics has a latency histogram per command" 'redis_command_duration_seconds_bucket{cmd="set",le="+Inf"}' "$metrics"
+check "and its count" 'redis_command_duration_seconds_count{cmd="set"}' "$metrics"
+check "/metrics reports memory" "redis_memory_used_bytes" "$metrics"
+check_absent "/metrics has no save time without persistence" "rdb_last_save" "$metrics"
+echo ""
+
+finish
@@ -0,0 +1,136 @@
+#!/bin/bash
+
+# Redis-Rust Replication Test Script
+# Starts a master and a replica on localhost and checks that:
+#   - data written before the replica connects arrives with the full sync
+#   - later writes are streamed to the replica
+#   - the replica rejects writes from clients (replica-read-only), HTTP ones too
+#   - HTTP writes on the master wait out CLIENT PAUSE WRITE
+#   - WAIT sees the replica's acknowledgement and doesn't hold up scripts while parked
+#   - a dropped link resumes with a partial resync
+#   - SHUTDOWN on the replica closes the master link instead of waiting on it
+#   - SHUTDOWN on the master lets its replicas catch up before exiting
+
+HOST="localhost"
+MASTER_PORT="16390"
+REPLICA_PORT="16391"
+MASTER_HTTP_PORT="16393"
+REPLICA_HTTP_PORT="16394"
+SECOND_REPLICA_PORT="16395"
+. "$(dirname "$0")/tests/lib.sh"
+
+echo "=== Redis-Rust Replication Test Suite ==="
+echo ""
+
+echo "--- Starting master ---"
+cargo build --quiet || exit 1
+./target/debug/redis-rust --port "$MASTER_PORT" --http-port "$MASTER_HTTP_PORT" > "$LOG_DIR/master.log" 2>&1 &
+MASTER_PID=$!
+sleep 1
+send_inline "$MASTER_PORT" "SET before-sync 1" > /dev/null
+send_inline "$MASTER_PORT" "RPUSH list a" > /dev/null
+send_inline "$MASTER_PORT" "SETBIT snapshot-bits 0 1" > /dev/null
+echo ""
+
+echo "--- Starting replica ---"
+./target/debug/redis-rust --port "$REPLICA_PORT" --http-port "$REPLICA_HTTP_PORT" \
+    --replicaof "127.0.0.1 $MASTER_PORT" > "$LOG_DIR/replica.log" 2>&1 &
+REPLICA_PID=$!
+sleep 2
+echo ""
+
+echo "--- Full sync ---"
+check "Snapshot string arrived" "1" "$(send_inline "$REPLICA_PORT" "GET before-sync")"
+check "Snapshot list arrived" "a" "$(send_inline "$REPLICA_PORT" "LRANGE list 0 -1")"
+check "Binary values survive the snapshot" "80" "$(send_inline "$REPLICA_PORT" "GET snapshot-bits" | od -An -tx1)"
+check "Replica reports its role" "slave" "$(send_inline "$REPLICA_PORT" "ROLE")"
+check "Link is up" "master_link_status:up" "$(send_inline "$REPLICA_PORT" "INFO replication")"
+echo ""
+
+echo "--- Command stream ---"
+send_inline "$MASTER_PORT" "SET after-sync 2" > /dev/null
+sleep 0.5
+check "Streamed write arrived" "2" "$(send_inline "$REPLICA_PORT" "GET after-sync")"
+send_inline "$MASTER_PORT" "BITOP NOT stream-bits after-sync" > /dev/null
+sleep 0.5
+check "Binary values survive the stream" "cd" "$(send_inline "$REPLICA_PORT" "GET stream-bits" | od -An -tx1)"
+check "Replica is read-only" "READONLY" "$(send_inline "$REPLICA_PORT" "SET nope 1")"
+check "HTTP writes to the replica are forbidden" "403" \
+    "$(curl -s -o /dev/null -w '%{http_code}' -X POST -H 'Content-Type: application/json' \
+        -d '{"value":"z"}' "http://$HOST:$REPLICA_HTTP_PORT/keys/hx")"
+check "and leave the key alone" "\$-1" "$(send_inline "$REPLICA_PORT" "GET hx")"
+send_inline "$MASTER_PORT" "CLIENT PAUSE 3000 WRITE" > /dev/null
+took=$(curl -s -o /dev/null -w '%{time_total}' -X POST -H 'Content-Type: application/json' \
+    -d '{"value":"z"}' "http://$HOST:$MASTER_HTTP_PORT/keys/hx")
+if [ "${took%%.*}" -ge 1 ]; then
+    echo "PASS: HTTP writes wait out CLIENT PAUSE WRITE"
+else
+    echo "FAIL: HTTP writes wait out CLIENT PAUSE WRITE (took ${took}s)"
+    FAILED=1
+fi
+sleep 0.5
+check "The paused write reaches the replica" "z" "$(send_inline "$REPLICA_PORT" "GET hx")"
+check "WAIT counts the replica" ":1" "$(send_inline "$MASTER_PORT" "WAIT 1 1000")"
+NC_WAIT=6 send_inline "$MASTER_PORT" "WAIT 2 4000" > /dev/null &
+WAIT_PID=$!
+sleep 0.3
+check "Scripts run while a WAIT is parked" ":1" "$(send_to "$MASTER_PORT" EVAL "return 1" 0)"
+check "Other commands too" "+PONG" "$(send_inline "$MASTER_PORT" "PING")"
+if kill -0 "$WAIT_PID" 2>/dev/null; then
+    echo "PASS: The WAIT was still parked"
+else
+    echo "FAIL: The WAIT was still parked"
+    FAILED=1
+fi
+wait "$WAIT_PID"
+check "Master lists the replica" "connected_slaves:1" "$(send_inline "$MASTER_PORT" "INFO replication")"
+echo ""
+
+echo "--- Partial resync ---"
+send_inline "$MASTER_PORT" "CLIENT KILL TYPE replica" > /dev/null
+send_inline "$MASTER_PORT" "SET while-down 3" > /dev/null
+sleep 2
+check "Write made while disconnected arrived" "3" "$(send_inline "$REPLICA_PORT" "GET while-down")"
+check "Link resumed from the backlog" "Partial resync" "$(cat "$LOG_DIR/master.log")"
+echo ""
+
+echo "--- Shutdown ---"
+send_inline "$REPLICA_PORT" "SHUTDOWN" > /dev/null
+# The master link must not hold the replica up until the drain timeout
+for _ in $(seq 1 30); do
+    kill -0 "$REPLICA_PID" 2>/dev/null || break
+    sleep 0.1
+done
+if kill -0 "$REPLICA_PID" 2>/dev/null; then
+    echo "FAIL: Replica exits promptly on SHUTDOWN"
+    FAILED=1
+else
+    echo "PASS: Replica exits promptly on SHUTDOWN"
+fi
+check "Replica closed its master link" "Closing the link to master" "$(cat "$LOG_DIR/replica.log")"
+echo ""
+
+echo "--- Master shutdown ---"
+./target/debug/redis-rust --port "$SECOND_REPLICA_PORT" --http-port 0 \
+    --replicaof "127.0.0.1 $MASTER_PORT" > "$LOG_DIR/second-replica.log" 2>&1 &
+sleep 2
+check "SHUTDOWN SAVE is refused without persistence" "-ERR SAVE is not supported" "$(send_inline "$MASTER_PORT" "SHUTDOWN SAVE")"
+check "So is FORCE" "-ERR FORCE is not supported" "$(send_inline "$MASTER_PORT" "SHUTDOWN NOSAVE FORCE")"
+send_inline "$MASTER_PORT" "SET last-write 4" > /dev/null
+send_inline "$MASTER_PORT" "SHUTDOWN" > /dev/null
+for _ in $(seq 1 30); do
+    kill -0 "$MASTER_PID" 2>/dev/null || break
+    sleep 0.1
+done
+if kill -0 "$MASTER_PID" 2>/dev/null; then
+    echo "FAIL: Master exits once the replica caught up"
+    FAILED=1
+else
+    echo "PASS: Master exits once the replica caught up"
+fi
+check "Master waited for the replica" "Waiting for 1 replica(s) to catch up" "$(cat "$LOG_DIR/master.log")"
+check_absent "and the replica acknowledged" "exiting anyway" "$(cat "$LOG_DIR/master.log")"
+check "The last write reached the replica" "4" "$(send_inline "$SECOND_REPLICA_PORT" "GET last-write")"
+echo ""
+
+finish "replication"
@@ -0,0 +1,86 @@
+#!/bin/bash
+
+# Redis-Rust Scripting Test Script
+# Starts a server and checks that:
+#   - EVAL runs Lua with KEYS/ARGV and redis.call against the keyspace
+#   - replies convert between Lua and RESP like in Redis, binary ones included
+#   - scripts can't subscribe, monitor or switch protocols
+#   - SCRIPT LOAD/EXISTS and EVALSHA use the script cache
+#   - a long script makes other clients get -BUSY and can be stopped with SCRIPT KILL
+#   - CLIENT PAUSE WRITE holds back EVAL and EVALSHA but not EVAL_RO
+
+HOST="127.0.0.1"
+PORT="16420"
+. "$(dirname "$0")/tests/lib.sh"
+
+echo "=== Redis-Rust Scripting Test Suite ==="
+echo ""
+
+echo "--- Starting server ---"
+cargo build --quiet || exit 1
+./target/debug/redis-rust --port "$PORT" --http-port 0 --busy-reply-threshold 500 > "$LOG_DIR/server.log" 2>&1 &
+SERVER_PID=$!
+sleep 1
+echo ""
+
+echo "--- EVAL ---"
+check "Script writes through redis.call" "OK" "$(send EVAL "return redis.call('SET', KEYS[1], ARGV[1])" 1 greeting hello)"
+check "Script reads the value back" "hello" "$(send EVAL "return redis.call('get', KEYS[1])" 1 greeting)"
+check "Tables become arrays" "*3" "$(send EVAL "return {1, 'two', 3}" 0)"
+check "Numbers are truncated to integers" ":3" "$(send EVAL "return 3.9" 0)"
+check "Status replies pass through" "+FINE" "$(send EVAL "return redis.status_reply('FINE')" 0)"
+check "pcall returns errors as tables" "-ERR Unknown command" "$(send EVAL "return redis.pcall('NOSUCH')" 0)"
+check "Globals are rejected" "Script attempted to create global variable 'x'" "$(send EVAL "x = 1" 0)"
+check "Read-only scripts can't write" "not allowed from read-only scripts" \
+    "$(send EVAL_RO "return redis.call('set', 'k', 'v')" 0)"
+check "Binary strings are returned as is" "ff 00 78" \
+    "$(send EVAL "return string.char(255, 0, 120)" 0 | od -An -tx1)"
+send SETBIT bits 0 1 > /dev/null
+check "Binary values read by redis.call too" "80" \
+    "$(send EVAL "return redis.call('GET', KEYS[1])" 1 bits | od -An -tx1)"
+for command in SUBSCRIBE PSUBSCRIBE MONITOR HELLO; do
+    check "$command is not allowed from scripts" "not allowed from script" \
+        "$(send EVAL "return redis.call('$command', 'news')" 0)"
+done
+check "The caller's connection is unchanged" "+PONG" \
+    "$(send_inline "$PORT" "EVAL \"return redis.pcall('SUBSCRIBE', 'news')\" 0\nPING")"
+echo ""
+
+echo "--- Script cache ---"
+SHA=$(send SCRIPT LOAD "return ARGV[1]" | tail -n 1)
+check "EVALSHA runs a loaded script" "cached" "$(send EVALSHA "$SHA" 0 cached)"
+check "SCRIPT EXISTS finds it" ":1" "$(send SCRIPT EXISTS "$SHA")"
+send SCRIPT FLUSH > /dev/null
+check "EVALSHA after SCRIPT FLUSH" "NOSCRIPT" "$(send EVALSHA "$SHA" 0 cached)"
+echo ""
+
+echo "--- Busy scripts ---"
+NC_WAIT=3 send EVAL "while true do end" 0 > "$LOG_DIR/busy.out" &
+BUSY_PID=$!
+sleep 1
+check "Other clients get BUSY" "BUSY" "$(send PING)"
+check "SCRIPT KILL stops the script" "OK" "$(send SCRIPT KILL)"
+wait "$BUSY_PID"
+check "Killed script reports it" "Script killed by user" "$(cat "$LOG_DIR/busy.out")"
+check "Server answers again" "PONG" "$(send PING)"
+echo ""
+
+echo "--- Client pause ---"
+SHA=$(send SCRIPT LOAD "return 1" | tail -n 1)
+send CLIENT PAUSE 5000 WRITE > /dev/null
+for command in EVAL EVALSHA; do
+    script=$([ "$command" = EVAL ] && echo "return 1" || echo "$SHA")
+    reply=$(send "$command" "$script" 0)
+    if [ -z "$reply" ]; then
+        echo "PASS: CLIENT PAUSE WRITE holds back $command"
+    else
+        echo "FAIL: CLIENT PAUSE WRITE holds back $command (got '$reply')"
+        FAILED=1
+    fi
+done
+check "Read-only scripts still run" ":1" "$(send EVAL_RO "return 1" 0)"
+send CLIENT UNPAUSE > /dev/null
+check "Scripts run again after UNPAUSE" ":1" "$(send EVAL "return 1" 0)"
+echo ""
+
+finish "scripting"
@@ -0,0 +1,83 @@
+#!/bin/bash
+
+# Redis-Rust Search Test Script
+# Starts a server and checks that:
+#   - FT.CREATE indexes the JSON documents already under its prefixes
+#   - JSON.SET, JSON.DEL and DEL keep the index up to date
+#   - FT.SEARCH handles words, prefixes, field filters, numeric ranges, tags,
+#     negation, unions, SORTBY, LIMIT and RETURN
+#   - FT.INFO, FT._LIST and FT.DROPINDEX
+
+HOST="127.0.0.1"
+PORT="16480"
+. "$(dirname "$0")/tests/lib.sh"
+
+# Only the keys of an FT.SEARCH NOCONTENT reply, space separated
+keys() {
+    send "$@" | grep -v '^[*$:]' | tr '\n' ' ' | sed 's/ $//'
+}
+
+echo "=== Redis-Rust Search Test Suite ==="
+echo ""
+
+echo "--- Starting server ---"
+cargo build --quiet || exit 1
+./target/debug/redis-rust --port "$PORT" --http-port 0 > "$LOG_DIR/server.log" 2>&1 &
+SERVER_PID=$!
+sleep 1
+echo ""
+
+send JSON.SET item:1 '$' '{"title":"Red running shoes","price":80,"tags":"sport,shoes"}' > /dev/null
+send JSON.SET item:2 '$' '{"title":"Blue running jacket","price":120,"tags":"sport,outerwear"}' > /dev/null
+send JSON.SET other:1 '$' '{"title":"Red wine","price":15,"tags":"drink"}' > /dev/null
+
+echo "--- Creating ---"
+check "FT.CREATE builds the index" "+OK" \
+    "$(send FT.CREATE items ON JSON PREFIX 1 item: SCHEMA '$.title' AS title TEXT WEIGHT 2 '$.price' AS price NUMERIC SORTABLE '$.tags' AS tags TAG)"
+check "Existing documents are indexed" "num_docs
+:2" "$(send FT.INFO items)"
+check "Names are unique" "Index already exists" "$(send FT.CREATE items SCHEMA '$.a' TEXT)"
+check "Only JSON is supported" "only ON JSON indexes are supported" "$(send FT.CREATE h ON HASH SCHEMA a TEXT)"
+check "FT._LIST names the indexes" "items" "$(send FT._LIST)"
+echo ""
+
+echo "--- Searching ---"
+check "Words match across TEXT fields" "item:1" "$(keys FT.SEARCH items red NOCONTENT)"
+check "Prefixes outside the index don't match" "$(printf '*2\n:1\n')" "$(send FT.SEARCH items red NOCONTENT)"
+check "Several words intersect" "item:2" "$(keys FT.SEARCH items 'running jacket' NOCONTENT)"
+check "Unions" "item:1 item:2" "$(keys FT.SEARCH items 'shoes | jacket' NOCONTENT SORTBY price)"
+check "Word prefixes" "item:1 item:2" "$(keys FT.SEARCH items 'run*' NOCONTENT SORTBY price)"
+check "Negation" "item:2" "$(keys FT.SEARCH items 'running -red' NOCONTENT)"
+check "Numeric ranges" "item:2" "$(keys FT.SEARCH items '@price:[(80 +inf]' NOCONTENT)"
+check "Tags" "item:1" "$(keys FT.SEARCH items '@tags:{shoes | hats}' NOCONTENT)"
+check "Field filters combine" "item:1" "$(keys FT.SEARCH items '@title:running @price:[0 100]' NOCONTENT)"
+check "SORTBY DESC" "item:2 item:1" "$(keys FT.SEARCH items '*' NOCONTENT SORTBY price DESC)"
+check "LIMIT pages the results" "$(printf '*2\n:2\n$6\nitem:2')" "$(send FT.SEARCH items '*' NOCONTENT SORTBY price DESC LIMIT 0 1)"
+check "RETURN picks fields" "$(printf '$5\ntitle\n$17\nRed running shoes')" "$(send FT.SEARCH items shoes RETURN 1 title)"
+check "Documents come back whole" '{"title":"Red running shoes"' "$(send FT.SEARCH items shoes)"
+check "Unknown fields" "Unknown field" "$(send FT.SEARCH items '@color:red')"
+check "Syntax errors" "Syntax error" "$(send FT.SEARCH items '(red')"
+deep="$(printf '(%.0s' {1..50000})red"
+check "Deeply nested queries are rejected" "nested deeper than 128 levels" "$(send FT.SEARCH items "$deep")"
+check "The server survives them" "+PONG" "$(send PING)"
+echo ""
+
+echo "--- Keeping up with writes ---"
+send JSON.SET item:3 '$' '{"title":"Green hat","price":25,"tags":"hats"}' > /dev/null
+check "New documents are indexed" "item:3" "$(keys FT.SEARCH items '@tags:{hats}' NOCONTENT)"
+send JSON.SET item:1 '$.price' 60 > /dev/null
+check "Changed fields are reindexed" "item:1" "$(keys FT.SEARCH items '@price:[50 70]' NOCONTENT)"
+send JSON.DEL item:1 '$.title' > /dev/null
+check "Deleted fields are unindexed" ":0" "$(send FT.SEARCH items shoes NOCONTENT)"
+send DEL item:2 > /dev/null
+check "Deleted keys are unindexed" ":0" "$(send FT.SEARCH items jacket NOCONTENT)"
+echo ""
+
+echo "--- Dropping ---"
+check "FT.DROPINDEX DD deletes the documents" "+OK" "$(send FT.DROPINDEX items DD)"
+check "The documents are gone" ":0" "$(send EXISTS item:1 item:3)"
+check "Other keys stay" ":1" "$(send EXISTS other:1)"
+check "The index is gone" "Unknown index name" "$(send FT.SEARCH items '*')"
+echo ""
+
+finish "search"
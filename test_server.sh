@@ -0,0 +1,133 @@
+#!/bin/bash
+
+# Redis-Rust Server Test Script
+# Starts a server with small limits on its connections and checks that:
+#   - the Unix socket listener serves clients with the configured permissions
+#   - malformed requests get a protocol error before the connection closes
+#   - client-query-buffer-limit and client-output-buffer-limit close the client,
+#     and bulks over proto-max-bulk-len are refused
+#   - tcp-keepalive, timeout and maxclients apply to client connections
+#   - SHUTDOWN lets in-flight commands finish, then removes the pidfile and exits
+
+HOST="127.0.0.1"
+PORT="16501"
+. "$(dirname "$0")/tests/lib.sh"
+SOCKET="$LOG_DIR/redis.sock"
+PIDFILE="$LOG_DIR/redis.pid"
+
+echo "=== Redis-Rust Server Test Suite ==="
+echo ""
+
+echo "--- Starting server ---"
+cargo build --quiet || exit 1
+./target/debug/redis-rust --port "$PORT" --http-port 0 \
+    --unixsocket "$SOCKET" --unixsocketperm 700 --pidfile "$PIDFILE" \
+    --client-query-buffer-limit 4kb --proto-max-bulk-len 2kb \
+    --client-output-buffer-limit "normal 16kb 0 0" \
+    --timeout 3 --tcp-keepalive 60 --maxclients 3 \
+    > "$LOG_DIR/server.log" 2>&1 &
+SERVER_PID=$!
+sleep 1
+echo ""
+
+echo "--- Unix socket ---"
+check "Clients are served on the socket" "+PONG" "$(printf 'PING\r\n' | nc -w 1 -U "$SOCKET" | tr -d '\r')"
+check "unixsocketperm sets the socket mode" "700" "$(stat -c %a "$SOCKET")"
+echo ""
+
+echo "--- Protocol errors ---"
+check "A bad bulk header is a protocol error" "-ERR Protocol error: expected '\$', got ':'" \
+    "$(send_raw "$PORT" '*1\r\n:3\r\n')"
+check "A bad multibulk length is a protocol error" "-ERR Protocol error: invalid multibulk length" \
+    "$(send_raw "$PORT" '*x\r\n')"
+check "A missing CRLF after a bulk is a protocol error" "-ERR Protocol error: expected CRLF after bulk string" \
+    "$(send_raw "$PORT" '*1\r\n$4\r\nPINGxx\r\n')"
+check "Commands before the error are still answered" "+PONG" "$(send_raw "$PORT" 'PING\r\n*x\r\n')"
+echo ""
+
+echo "--- Buffer limits ---"
+check "Bulks over proto-max-bulk-len are refused" "-ERR Protocol error: invalid bulk length" \
+    "$(send_raw "$PORT" '*2\r\n$3\r\nGET\r\n$3000\r\n')"
+reply=$(send_raw "$PORT" "SET key $(head -c 5000 /dev/zero | tr '\0' x)\r\n")
+if [ -z "$reply" ]; then
+    echo "PASS: Requests over client-query-buffer-limit close the client"
+else
+    echo "FAIL: Requests over client-query-buffer-limit close the client (got '$reply')"
+    FAILED=1
+fi
+check "and are counted" "client_query_buffer_limit_disconnections:1" "$(send INFO stats)"
+value=$(head -c 100 /dev/zero | tr '\0' v)
+for i in $(seq 300); do echo "RPUSH big $value"; done | nc -w 1 $HOST "$PORT" > /dev/null
+check "The list is built" "$value" "$(send LRANGE big 299 299)"
+reply=$(send LRANGE big 0 -1)
+if [ -z "$reply" ]; then
+    echo "PASS: Replies over client-output-buffer-limit close the client"
+else
+    echo "FAIL: Replies over client-output-buffer-limit close the client (got ${#reply} bytes)"
+    FAILED=1
+fi
+check "and are counted" "client_output_buffer_limit_disconnections:1" "$(send INFO stats)"
+echo ""
+
+echo "--- Keepalive and maxclients ---"
+exec 5<>/dev/tcp/$HOST/$PORT 6<>/dev/tcp/$HOST/$PORT 7<>/dev/tcp/$HOST/$PORT
+sleep 0.5
+if command -v ss > /dev/null; then
+    check "tcp-keepalive arms a keepalive timer" "keepalive" "$(ss -tno state established "( sport = :$PORT )")"
+else
+    echo "SKIP: tcp-keepalive arms a keepalive timer (ss not found)"
+fi
+check "Clients past maxclients are rejected" "-ERR max number of clients reached" "$(send PING)"
+exec 5>&- 6>&- 7>&-
+sleep 0.5
+check "and counted" "rejected_connections:1" "$(send INFO stats)"
+check "Closed clients free their place" "+PONG" "$(send PING)"
+echo ""
+
+echo "--- Idle timeout ---"
+exec 5<>/dev/tcp/$HOST/$PORT
+start=$SECONDS
+timeout 10 cat <&5 > /dev/null
+elapsed=$((SECONDS - start))
+exec 5>&-
+if [ $elapsed -ge 2 ] && [ $elapsed -le 6 ]; then
+    echo "PASS: Idle clients are closed after timeout"
+else
+    echo "FAIL: Idle clients are closed after timeout (closed after ${elapsed}s)"
+    FAILED=1
+fi
+echo ""
+
+echo "--- Shutdown ---"
+check "The pidfile holds the server's pid" "$SERVER_PID" "$(cat "$PIDFILE")"
+send EVAL "local i = 0 while i < 20000000 do i = i + 1 end return i" 0 > "$LOG_DIR/eval.out" &
+EVAL_PID=$!
+sleep 0.3
+reply=$(send SHUTDOWN)
+if [ -z "$reply" ]; then
+    echo "PASS: SHUTDOWN has no reply"
+else
+    echo "FAIL: SHUTDOWN has no reply (got '$reply')"
+    FAILED=1
+fi
+wait $EVAL_PID
+check "In-flight commands finish first" ":20000000" "$(cat "$LOG_DIR/eval.out")"
+for i in 1 2 3 4 5; do
+    kill -0 "$SERVER_PID" 2>/dev/null || break
+    sleep 1
+done
+if kill -0 "$SERVER_PID" 2>/dev/null; then
+    echo "FAIL: The server exits"
+    FAILED=1
+else
+    echo "PASS: The server exits"
+fi
+if [ -e "$PIDFILE" ] || [ -e "$SOCKET" ]; then
+    echo "FAIL: The pidfile and socket are removed"
+    FAILED=1
+else
+    echo "PASS: The pidfile and socket are removed"
+fi
+echo ""
+
+finish "server"
@@ -0,0 +1,95 @@
+#!/bin/bash
+
+# Redis-Rust Count-Min Sketch / Top-K Test Script
+# Starts a server and checks that:
+#   - CMS.INCRBY/CMS.QUERY never undercount and CMS.MERGE sums with weights
+#   - TOPK.ADD/TOPK.INCRBY find the heavy hitters among many light items
+#   - sketch reads on keys of other types get WRONGTYPE
+#   - a replica's snapshot and later commands leave it with the same sketches
+
+HOST="127.0.0.1"
+PORT="16493"
+REPLICA_PORT="16494"
+. "$(dirname "$0")/tests/lib.sh"
+
+echo "=== Redis-Rust Count-Min Sketch / Top-K Test Suite ==="
+echo ""
+
+echo "--- Starting server ---"
+cargo build --quiet || exit 1
+./target/debug/redis-rust --port "$PORT" --http-port 0 > "$LOG_DIR/server.log" 2>&1 &
+SERVER_PID=$!
+sleep 1
+echo ""
+
+echo "--- Count-Min sketches ---"
+check "CMS.INITBYDIM" "+OK" "$(send CMS.INITBYDIM words 2000 5)"
+check "Keys are created once" "CMS: key already exists" "$(send CMS.INITBYDIM words 10 10)"
+check "CMS.INITBYPROB" "+OK" "$(send CMS.INITBYPROB sized 0.001 0.01)"
+check "INITBYPROB sizes the sketch" "$(printf 'width\n:2000\n$5\ndepth\n:7')" "$(send CMS.INFO sized)"
+check "CMS.INCRBY replies with estimates" "$(printf '*2\n:3\n:7')" "$(send CMS.INCRBY words apple 3 pear 7)"
+check "Increments add up" ":5" "$(send CMS.INCRBY words apple 2 | tail -1)"
+check "CMS.QUERY" "$(printf '*3\n:5\n:7\n:0')" "$(send CMS.QUERY words apple pear plum)"
+check "CMS.INCRBY needs the key" "CMS: key does not exist" "$(send CMS.INCRBY nothing a 1)"
+check "Counts must be numbers" "CMS: Cannot parse number" "$(send CMS.INCRBY words a lots)"
+
+send CMS.INITBYDIM other 2000 5 > /dev/null
+send CMS.INCRBY other apple 10 > /dev/null
+send CMS.INITBYDIM total 2000 5 > /dev/null
+check "CMS.MERGE" "+OK" "$(send CMS.MERGE total 2 words other WEIGHTS 1 3)"
+check "Merged counts are weighted" "$(printf '*2\n:35\n:7')" "$(send CMS.QUERY total apple pear)"
+check "CMS.INFO counts every increment" "$(printf 'count\n:12')" "$(send CMS.INFO words)"
+check "Dimensions must match" "CMS: width/depth is not equal" "$(send CMS.MERGE total 1 sized)"
+check "Types are kept apart" "WRONGTYPE" "$(send TOPK.ADD words x)"
+send SET plain value > /dev/null
+check "CMS.QUERY on a string" "WRONGTYPE" "$(send CMS.QUERY plain x)"
+check "CMS.INFO on a string" "WRONGTYPE" "$(send CMS.INFO plain)"
+check "TOPK.QUERY on a string" "WRONGTYPE" "$(send TOPK.QUERY plain x)"
+check "CMS.MERGE from a string" "WRONGTYPE" "$(send CMS.MERGE total 1 plain)"
+check "Huge dimensions are refused" "CMS: sketch is too large" "$(send CMS.INITBYDIM huge 4294967295 4294967295)"
+check "Tiny error rates are refused" "CMS: sketch is too large" "$(send CMS.INITBYPROB huge 1e-300 1e-300)"
+check "The server survives them" ":0" "$(send EXISTS huge)"
+echo ""
+
+echo "--- Top-K ---"
+check "TOPK.RESERVE" "+OK" "$(send TOPK.RESERVE trending 3 50 4 0.9)"
+check "Keys are reserved once" "TopK: key already exists" "$(send TOPK.RESERVE trending 3)"
+check "TOPK.ADD replies nil for each item that expels nothing" "$(printf '*2\n$-1\n$-1')" "$(send TOPK.ADD trending a b)"
+
+# Three heavy items among a hundred light ones
+items=()
+for i in $(seq 1 100); do
+    items+=("light:$i")
+done
+send TOPK.ADD trending "${items[@]}" > /dev/null
+send TOPK.INCRBY trending rust 500 go 300 zig 200 > /dev/null
+send TOPK.ADD trending "${items[@]}" > /dev/null
+check "TOPK.LIST holds the heavy hitters, biggest first" "$(printf '*3\n$4\nrust\n$2\ngo\n$3\nzig')" "$(send TOPK.LIST trending)"
+check "WITHCOUNT" "$(printf 'rust\n:500')" "$(send TOPK.LIST trending WITHCOUNT)"
+check "TOPK.QUERY" "$(printf '*2\n:1\n:0')" "$(send TOPK.QUERY trending go light:5)"
+check "TOPK.INCRBY expels the smallest" "$(printf '*1\n$3\nzig')" "$(send TOPK.INCRBY trending c 1000)"
+check "Increments are bounded" "TopK: increment must be" "$(send TOPK.INCRBY trending c 0)"
+check "TOPK.INFO" "$(printf 'k\n:3\n$5\nwidth\n:50')" "$(send TOPK.INFO trending)"
+check "Huge Top-K dimensions are refused" "TopK: sketch is too large" "$(send TOPK.RESERVE huge 10 4294967295 4294967295 0.9)"
+check "The keyspace still works after them" ":1" "$(send EXISTS trending)"
+echo ""
+
+echo "--- Replication ---"
+./target/debug/redis-rust --port "$REPLICA_PORT" --http-port 0 \
+    --replicaof "127.0.0.1 $PORT" > "$LOG_DIR/replica.log" 2>&1 &
+REPLICA_PID=$!
+sleep 2
+check "The snapshot has the Count-Min sketch" "$(send CMS.QUERY total apple pear plum)" \
+    "$(send_to "$REPLICA_PORT" CMS.QUERY total apple pear plum)"
+check "The snapshot has the Top-K" "$(send TOPK.LIST trending WITHCOUNT)" \
+    "$(send_to "$REPLICA_PORT" TOPK.LIST trending WITHCOUNT)"
+send TOPK.ADD trending "${items[@]}" > /dev/null
+send TOPK.INCRBY trending light:9 2000 > /dev/null
+sleep 0.5
+check "Replicas decay alike" "$(send TOPK.SCANDUMP trending 0; send TOPK.SCANDUMP trending 1)" \
+    "$(send_to "$REPLICA_PORT" TOPK.SCANDUMP trending 0; send_to "$REPLICA_PORT" TOPK.SCANDUMP trending 1)"
+check "Later writes are replicated" "$(send TOPK.LIST trending WITHCOUNT)" \
+    "$(send_to "$REPLICA_PORT" TOPK.LIST trending WITHCOUNT)"
+echo ""
+
+finish "Count-Min sketch / Top-K"
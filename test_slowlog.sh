@@ -0,0 +1,65 @@
+#!/bin/bash
+
+# Redis-Rust SLOWLOG and LATENCY Test Script
+# Starts a server that logs every command and samples every event, then checks:
+#   - SLOWLOG GET/LEN/RESET with durations and client info, and redacted AUTH
+#   - slowlog-max-len bounds the log
+#   - LATENCY LATEST/HISTORY/RESET/HISTOGRAM on a slow script
+
+HOST="127.0.0.1"
+PORT="16499"
+. "$(dirname "$0")/tests/lib.sh"
+
+echo "=== Redis-Rust SLOWLOG and LATENCY Test Suite ==="
+echo ""
+
+echo "--- Starting server ---"
+cargo build --quiet || exit 1
+./target/debug/redis-rust --port "$PORT" --http-port 0 \
+    --slowlog-log-slower-than 0 --slowlog-max-len 5 --latency-monitor-threshold 1 \
+    > "$LOG_DIR/server.log" 2>&1 &
+SERVER_PID=$!
+sleep 1
+echo ""
+
+echo "--- Slow log ---"
+send SLOWLOG RESET > /dev/null
+send CLIENT SETNAME app > /dev/null
+send SADD big member > /dev/null
+send SMEMBERS big > /dev/null
+slowlog=$(send SLOWLOG GET 1)
+check "The newest entry comes first" "SLOWLOG" "$(send SLOWLOG GET 1)"
+check "Entries carry the arguments" "SMEMBERS" "$(send SLOWLOG GET 3)"
+if grep -qE '^127\.0\.0\.1:[0-9]+$' <<< "$slowlog"; then
+    echo "PASS: Entries carry the client address"
+else
+    echo "FAIL: Entries carry the client address (got '$slowlog')"
+    FAILED=1
+fi
+send AUTH default s3cret > /dev/null
+auth=$(send SLOWLOG GET 1)
+check "AUTH is redacted" "(redacted)" "$auth"
+check_absent "Passwords never reach the slow log" "s3cret" "$auth"
+for i in 1 2 3 4 5 6; do send PING > /dev/null; done
+check "slowlog-max-len bounds the log" ":5" "$(send SLOWLOG LEN)"
+check "SLOWLOG GET -1 returns everything" "*5" "$(send SLOWLOG GET -1)"
+send SLOWLOG RESET > /dev/null
+check "SLOWLOG RESET leaves only itself" ":1" "$(send SLOWLOG LEN)"
+check "Bad counts are rejected" "greater than or equal to -1" "$(send SLOWLOG GET -2)"
+echo ""
+
+echo "--- Latency monitor ---"
+send LATENCY RESET > /dev/null
+send EVAL "local i = 0 while i < 5000000 do i = i + 1 end return i" 0 > /dev/null
+check "LATENCY LATEST reports the slow command" "command" "$(send LATENCY LATEST)"
+check "LATENCY HISTORY has its sample" "*1" "$(send LATENCY HISTORY command)"
+check "LATENCY HISTOGRAM reports calls" "calls" "$(send LATENCY HISTOGRAM sadd)"
+check "LATENCY HISTOGRAM reports buckets" "histogram_usec" "$(send LATENCY HISTOGRAM sadd)"
+check "Unknown events have no history" "*0" "$(send LATENCY HISTORY no-such-event)"
+check "LATENCY RESET of an unknown event resets nothing" ":0" "$(send LATENCY RESET no-such-event)"
+check "LATENCY RESET drops the event" ":1" "$(send LATENCY RESET command)"
+check "Reset events have no history" "*0" "$(send LATENCY HISTORY command)"
+check "Unknown subcommands are rejected" "Try LATENCY HELP" "$(send LATENCY NOPE)"
+echo ""
+
+finish "SLOWLOG and LATENCY"
@@ -0,0 +1,91 @@
+#!/bin/bash
+
+# Redis-Rust Streams Test Script
+# Starts a server and checks that:
+#   - XADD generates growing IDs and XRANGE/XREVRANGE/XLEN/XTRIM see the entries
+#   - XREAD BLOCK wakes up when another client adds an entry
+#   - consumer groups deliver, track, acknowledge and hand over pending entries
+#   - a key holds one type: other types' writes get WRONGTYPE, SET replaces it,
+#     and emptying a list, set or sorted set frees the key
+
+HOST="127.0.0.1"
+PORT="16430"
+. "$(dirname "$0")/tests/lib.sh"
+
+echo "=== Redis-Rust Streams Test Suite ==="
+echo ""
+
+echo "--- Starting server ---"
+cargo build --quiet || exit 1
+./target/debug/redis-rust --port "$PORT" --http-port 0 > "$LOG_DIR/server.log" 2>&1 &
+SERVER_PID=$!
+sleep 1
+echo ""
+
+echo "--- Entries ---"
+check "XADD with an explicit ID" "1-1" "$(send XADD events 1-1 type login)"
+check "XADD with * picks a later ID" "\$" "$(send XADD events '*' type logout)"
+check "IDs must grow" "equal or smaller than the target stream top item" "$(send XADD events 1-1 type x)"
+check "XLEN counts entries" ":2" "$(send XLEN events)"
+check "XRANGE returns fields" "login" "$(send XRANGE events - + COUNT 1)"
+check "XREVRANGE starts at the end" "logout" "$(send XREVRANGE events + - COUNT 1)"
+send XADD events MAXLEN 2 '*' type view > /dev/null
+check "MAXLEN trims the oldest entry" "*0" "$(send XRANGE events 1-1 1-1)"
+check "XTRIM removes down to the length" ":1" "$(send XTRIM events MAXLEN 1)"
+echo ""
+
+echo "--- Blocking reads ---"
+NC_WAIT=3 send XREAD BLOCK 2000 STREAMS feed '$' > "$LOG_DIR/block.out" &
+BLOCK_PID=$!
+sleep 1
+send XADD feed '*' msg hello > /dev/null
+wait "$BLOCK_PID"
+check "XREAD BLOCK gets the new entry" "hello" "$(cat "$LOG_DIR/block.out")"
+check "XREAD BLOCK times out with nil" "*-1" "$(send XREAD BLOCK 100 STREAMS feed '$')"
+echo ""
+
+echo "--- Consumer groups ---"
+send XADD jobs 1-0 task a > /dev/null
+send XADD jobs 2-0 task b > /dev/null
+check "XGROUP CREATE" "OK" "$(send XGROUP CREATE jobs workers 0)"
+check "Duplicate groups are refused" "BUSYGROUP" "$(send XGROUP CREATE jobs workers 0)"
+check "XREADGROUP delivers new entries" "1-0" "$(send XREADGROUP GROUP workers alice COUNT 1 STREAMS jobs '>')"
+check "Each entry goes to one consumer" "2-0" "$(send XREADGROUP GROUP workers bob STREAMS jobs '>')"
+check "XPENDING counts unacknowledged entries" ":2" "$(send XPENDING jobs workers)"
+check "XACK acknowledges" ":1" "$(send XACK jobs workers 1-0)"
+check "XCLAIM hands over an entry" "2-0" "$(send XCLAIM jobs workers alice 0 2-0 JUSTID)"
+check "XAUTOCLAIM scans the pending list" "2-0" "$(send XAUTOCLAIM jobs workers carol 0 0)"
+check "XINFO GROUPS shows the group" "workers" "$(send XINFO GROUPS jobs)"
+check "Unknown groups" "NOGROUP" "$(send XREADGROUP GROUP nobody x STREAMS jobs '>')"
+echo ""
+
+echo "--- Key types ---"
+send XADD typed '*' field value > /dev/null
+check "LPUSH on a stream" "WRONGTYPE" "$(send LPUSH typed item)"
+check "JSON.SET on a stream" "WRONGTYPE" "$(send JSON.SET typed '$' '{}')"
+check "TS.ADD on a stream" "WRONGTYPE" "$(send TS.ADD typed 1 1)"
+check "BF.ADD on a stream" "WRONGTYPE" "$(send BF.ADD typed item)"
+check "SETBIT on a stream" "WRONGTYPE" "$(send SETBIT typed 1 1)"
+check "The stream is untouched" ":1" "$(send XLEN typed)"
+send SET text hello > /dev/null
+check "XADD on a string" "WRONGTYPE" "$(send XADD text '*' field value)"
+check "SET replaces the stream" "OK" "$(send SET typed hello)"
+check "The key is a string now" "hello" "$(send GET typed)"
+check "The stream is gone" ":0" "$(send XLEN typed)"
+check "A string past its TTL doesn't get in the way" "OK" \
+    "$(send SET fleeting hello EX 1 > /dev/null; sleep 2; send XGROUP CREATE fleeting workers '$' MKSTREAM)"
+keys=$(send INFO keyspace | grep -o 'keys=[0-9]*')
+send LPUSH retyped item > /dev/null
+send LPOP retyped > /dev/null
+check "Popping the last item removes the list" ":0" "$(send EXISTS retyped)"
+check "The key takes a set then" ":1" "$(send SADD retyped member)"
+send SREM retyped member > /dev/null
+check "Removing the last member removes the set" ":0" "$(send EXISTS retyped)"
+check "The key takes a sorted set then" ":1" "$(send ZADD retyped 1 member)"
+send ZREM retyped member > /dev/null
+check "Removing the last member removes the sorted set" ":0" "$(send EXISTS retyped)"
+check "Emptied keys aren't counted" "$keys," "$(send INFO keyspace)"
+check "The key takes a stream then" "-" "$(send XADD retyped '*' field value)"
+echo ""
+
+finish "streams"
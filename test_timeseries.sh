@@ -0,0 +1,87 @@
+#!/bin/bash
+
+# Redis-Rust Time Series Test Script
+# Starts a server and checks that:
+#   - TS.CREATE/TS.ADD/TS.MADD store samples that TS.RANGE and TS.GET read back
+#     exactly, across several compressed chunks
+#   - RETENTION drops old samples
+#   - AGGREGATION buckets samples, TS.MRANGE filters series by label
+#   - TS.CREATERULE compacts finished buckets into another series
+
+HOST="127.0.0.1"
+PORT="16490"
+. "$(dirname "$0")/tests/lib.sh"
+
+# Samples of a range reply as "ts=value" pairs on one line
+samples() {
+    send "$@" | grep -v '^[*$]' | paste -d= - - | tr -d ':' | tr '\n' ' ' | sed 's/ $//'
+}
+
+echo "=== Redis-Rust Time Series Test Suite ==="
+echo ""
+
+echo "--- Starting server ---"
+cargo build --quiet || exit 1
+./target/debug/redis-rust --port "$PORT" --http-port 0 > "$LOG_DIR/server.log" 2>&1 &
+SERVER_PID=$!
+sleep 1
+echo ""
+
+echo "--- Samples ---"
+check "TS.CREATE" "+OK" "$(send TS.CREATE temp:1 LABELS sensor temp room kitchen)"
+check "Keys exist once" "TSDB: key already exists" "$(send TS.CREATE temp:1)"
+check "TS.ADD returns the timestamp" ":1000" "$(send TS.ADD temp:1 1000 21.5)"
+check "Older timestamps are rejected" "TSDB: timestamp must be equal to or higher" "$(send TS.ADD temp:1 999 1)"
+check "Duplicate timestamps are rejected" "DUPLICATE_POLICY" "$(send TS.ADD temp:1 1000 1)"
+send TS.MADD temp:1 2000 22 temp:1 3000 22 temp:1 4500 -3.25 temp:1 4501 1e300 temp:1 99999999999 0.1 > /dev/null
+check "Values round-trip through compression" "1000=21.5 2000=22 3000=22 4500=-3.25 4501=1e300 99999999999=0.1" \
+    "$(samples TS.RANGE temp:1 - +)"
+check "TS.REVRANGE with COUNT" "99999999999=0.1 4501=1e300" "$(samples TS.REVRANGE temp:1 - + COUNT 2)"
+check "FILTER_BY_VALUE" "2000=22 3000=22" "$(samples TS.RANGE temp:1 - + FILTER_BY_VALUE 21.9 22.1)"
+check "TS.GET returns the latest sample" "$(printf ':99999999999\n$3\n0.1')" "$(send TS.GET temp:1)"
+check "TS.ADD creates keys" ":5" "$(send TS.ADD auto 5 1 LABELS sensor temp room hall)"
+
+# Enough samples for several 48 byte chunks
+args=()
+for i in $(seq 1 300); do
+    args+=(big "$((i * 1000 + (i % 7)))" "$((i % 13)).5")
+done
+send TS.CREATE big CHUNK_SIZE 48 > /dev/null
+send TS.MADD "${args[@]}" > /dev/null
+check "Samples span several chunks" "chunkCount" "$(send TS.INFO big | grep -A1 chunkCount | tail -1 | grep -qv '^:1$' && echo chunkCount)"
+check "Every sample survives" "totalSamples
+:300" "$(send TS.INFO big)"
+check "Samples in later chunks decode" "250005=3.5" "$(samples TS.RANGE big 250000 250010)"
+echo ""
+
+echo "--- Retention ---"
+send TS.CREATE short RETENTION 100 > /dev/null
+send TS.MADD short 1000 1 short 1050 2 short 1120 3 > /dev/null
+check "Samples older than RETENTION are gone" "1050=2 1120=3" "$(samples TS.RANGE short - +)"
+echo ""
+
+echo "--- Aggregation ---"
+check "avg" "0=21.5 2000=22 4000=-3.25" "$(samples TS.RANGE temp:1 0 4500 AGGREGATION avg 2000)"
+check "count" "0=1 2000=2 4000=2" "$(samples TS.RANGE temp:1 0 5000 AGGREGATION count 2000)"
+check "max" "4000=1e300" "$(samples TS.RANGE temp:1 4000 5000 AGGREGATION max 1000)"
+check "Unknown aggregations" "TSDB: Unknown aggregation type" "$(send TS.RANGE temp:1 - + AGGREGATION median 10)"
+check "TS.MRANGE filters by label" "$(printf '$4\nauto\n*0\n*1\n*2\n:5')" \
+    "$(send TS.MRANGE - + FILTER sensor=temp room!=kitchen)"
+check "WITHLABELS" "kitchen" "$(send TS.MRANGE - + WITHLABELS FILTER room=kitchen)"
+check "Value lists" "temp:1" "$(send TS.MRANGE - + FILTER 'room=(hall,kitchen)')"
+check "A positive matcher is required" "at least one matcher" "$(send TS.MRANGE - + FILTER room!=x)"
+echo ""
+
+echo "--- Compaction ---"
+send TS.CREATE raw > /dev/null
+send TS.CREATE raw:sum > /dev/null
+check "TS.CREATERULE" "+OK" "$(send TS.CREATERULE raw raw:sum AGGREGATION sum 10)"
+send TS.MADD raw 1 1 raw 5 2 raw 12 3 raw 18 4 raw 25 5 > /dev/null
+check "Finished buckets are compacted" "0=3 10=7" "$(samples TS.RANGE raw:sum - +)"
+check "TS.INFO lists the rule" "raw:sum" "$(send TS.INFO raw)"
+check "TS.DELETERULE" "+OK" "$(send TS.DELETERULE raw raw:sum)"
+send TS.ADD raw 31 6 > /dev/null
+check "Deleted rules stop compacting" "0=3 10=7" "$(samples TS.RANGE raw:sum - +)"
+echo ""
+
+finish "time series"
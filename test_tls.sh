@@ -0,0 +1,105 @@
+#!/bin/bash
+
+# Redis-Rust TLS Test Script
+# Generates a throwaway CA plus server and client certificates, starts the
+# server with TLS on the RESP and HTTP listeners and checks that:
+#   - clients presenting a certificate signed by the CA are served
+#   - clients without a certificate are rejected
+#   - the client certificate CN labels the connection in CLIENT LIST
+
+HOST="localhost"
+TLS_PORT="16380"
+HTTP_PORT="13443"
+. "$(dirname "$0")/tests/lib.sh"
+LOG_FILE="$LOG_DIR/server.log"
+
+# Helper function to create a certificate signed by the test CA
+gen_cert() {
+    local name=$1
+    local cn=$2
+    local ext=$3
+
+    openssl genrsa -out "$LOG_DIR/$name.key" 2048 2>/dev/null
+    openssl req -new -key "$LOG_DIR/$name.key" -subj "/CN=$cn" \
+        -out "$LOG_DIR/$name.csr" 2>/dev/null
+    openssl x509 -req -in "$LOG_DIR/$name.csr" -days 1 \
+        -CA "$LOG_DIR/ca.crt" -CAkey "$LOG_DIR/ca.key" -CAcreateserial \
+        -extfile <(printf "%s" "$ext") -out "$LOG_DIR/$name.crt" 2>/dev/null
+}
+
+# Helper function to send a command over TLS
+send_tls() {
+    local cmd=$1
+    shift
+    # -nocommands keeps s_client from treating lines like CLIENT as its own commands
+    (echo "$cmd"; sleep 1) | openssl s_client -connect "$HOST:$TLS_PORT" \
+        -CAfile "$LOG_DIR/ca.crt" -quiet -nocommands -no_ign_eof "$@" 2>/dev/null | tr -d '\r'
+}
+
+echo "=== Redis-Rust TLS Test Suite ==="
+echo ""
+
+echo "--- Generating certificates in $LOG_DIR ---"
+openssl genrsa -out "$LOG_DIR/ca.key" 2048 2>/dev/null
+openssl req -x509 -new -key "$LOG_DIR/ca.key" -days 1 -subj "/CN=Redis-Rust Test CA" \
+    -out "$LOG_DIR/ca.crt" 2>/dev/null
+gen_cert server localhost "subjectAltName=DNS:localhost,IP:127.0.0.1"
+gen_cert client alice "extendedKeyUsage=clientAuth"
+echo ""
+
+echo "--- Starting server ---"
+cargo build --quiet || exit 1
+./target/debug/redis-rust \
+    --port 0 \
+    --tls-port "$TLS_PORT" \
+    --http-port "$HTTP_PORT" \
+    --tls-http yes \
+    --tls-cert-file "$LOG_DIR/server.crt" \
+    --tls-key-file "$LOG_DIR/server.key" \
+    --tls-ca-cert-file "$LOG_DIR/ca.crt" \
+    --tls-auth-clients yes \
+    --tls-auth-clients-user CN > "$LOG_FILE" 2>&1 &
+SERVER_PID=$!
+sleep 1
+echo ""
+
+echo "--- RESP over TLS ---"
+check "PING with client certificate" "+PONG" \
+    "$(send_tls PING -cert "$LOG_DIR/client.crt" -key "$LOG_DIR/client.key")"
+check "SET with client certificate" "+OK" \
+    "$(send_tls "SET tlskey tlsvalue" -cert "$LOG_DIR/client.crt" -key "$LOG_DIR/client.key")"
+check "GET with client certificate" "tlsvalue" \
+    "$(send_tls "GET tlskey" -cert "$LOG_DIR/client.crt" -key "$LOG_DIR/client.key")"
+if [[ "$(send_tls PING)" == *"PONG"* ]]; then
+    echo "FAIL: connection without client certificate was served"
+    FAILED=1
+else
+    echo "PASS: connection without client certificate is rejected"
+fi
+check "Client certificate CN labels the connection" "user=alice" \
+    "$(send_tls "CLIENT INFO" -cert "$LOG_DIR/client.crt" -key "$LOG_DIR/client.key")"
+check "and is logged" "presented a certificate for 'alice'" "$(cat "$LOG_FILE")"
+echo ""
+
+echo "--- HTTP over TLS ---"
+check "GET /ping with client certificate" '"PONG"' \
+    "$(curl -s --cacert "$LOG_DIR/ca.crt" --cert "$LOG_DIR/client.crt" \
+        --key "$LOG_DIR/client.key" "https://$HOST:$HTTP_PORT/ping")"
+check "GET /keys/tlskey with client certificate" '"tlsvalue"' \
+    "$(curl -s --cacert "$LOG_DIR/ca.crt" --cert "$LOG_DIR/client.crt" \
+        --key "$LOG_DIR/client.key" "https://$HOST:$HTTP_PORT/keys/tlskey")"
+if curl -s --cacert "$LOG_DIR/ca.crt" "https://$HOST:$HTTP_PORT/ping" > /dev/null; then
+    echo "FAIL: HTTPS request without client certificate was served"
+    FAILED=1
+else
+    echo "PASS: HTTPS request without client certificate is rejected"
+fi
+if curl -s "http://$HOST:$HTTP_PORT/ping" | grep -q PONG; then
+    echo "FAIL: plaintext HTTP request was served"
+    FAILED=1
+else
+    echo "PASS: plaintext HTTP request is rejected"
+fi
+echo ""
+
+finish "TLS"
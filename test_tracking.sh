@@ -0,0 +1,110 @@
+#!/bin/bash
+
+# Redis-Rust Client Tracking Test Script
+# Starts a server and checks that:
+#   - HELLO switches a connection to RESP3
+#   - CLIENT TRACKING ON sends invalidate pushes for keys the client read
+#   - BCAST, OPTIN and NOLOOP pick which changes are announced
+#   - RESP2 clients can REDIRECT invalidations to a __redis__:invalidate subscriber
+
+HOST="127.0.0.1"
+PORT="16497"
+. "$(dirname "$0")/tests/lib.sh"
+
+# Sends already encoded commands on one connection that stays open in the
+# background, writing everything that arrives to a file
+SESSIONS=()
+session() {
+    local out=$1
+    local requests=$2
+    printf "%b" "$requests" | nc -w 3 $HOST "$PORT" | tr -d '\r' > "$out" &
+    SESSIONS+=($!)
+    sleep 0.5
+}
+
+wait_sessions() {
+    wait "${SESSIONS[@]}"
+    SESSIONS=()
+}
+
+echo "=== Redis-Rust Client Tracking Test Suite ==="
+echo ""
+
+echo "--- Starting server ---"
+cargo build --quiet || exit 1
+./target/debug/redis-rust --port "$PORT" --http-port 0 > "$LOG_DIR/server.log" 2>&1 &
+SERVER_PID=$!
+sleep 1
+echo ""
+
+echo "--- HELLO ---"
+check "HELLO 3 replies with a map" "$(printf '%%7\n$6\nserver')" "$(send HELLO 3)"
+check "HELLO 2 replies with an array" "$(printf '*14\n$6\nserver')" "$(send HELLO 2)"
+check "Unknown versions are refused" "NOPROTO" "$(send HELLO 4)"
+check "CLIENT INFO shows the protocol" "resp=3" "$(printf "%b" "$(request HELLO 3)$(request CLIENT INFO)" \
+    | nc -w 1 $HOST "$PORT" | tr -d '\r')"
+echo ""
+
+echo "--- Default mode ---"
+send SET user:1 alice > /dev/null
+send SET user:2 bob > /dev/null
+session "$LOG_DIR/default.out" "$(request HELLO 3)$(request CLIENT TRACKING ON)$(request GET user:1)"
+send SET user:2 carol > /dev/null
+send SET user:1 dave > /dev/null
+send SET user:1 erin > /dev/null
+wait_sessions
+default=$(cat "$LOG_DIR/default.out")
+check "Keys read are invalidated when they change" "$(printf '>2\n$10\ninvalidate\n*1\n$6\nuser:1')" "$default"
+check_absent "Keys not read aren't" "user:2" "$default"
+check "Each read is invalidated once" "1" "$(grep -c invalidate "$LOG_DIR/default.out")"
+echo ""
+
+echo "--- BCAST ---"
+session "$LOG_DIR/bcast.out" "$(request HELLO 3)$(request CLIENT TRACKING ON BCAST PREFIX user: PREFIX cart:)"
+send SET user:3 frank > /dev/null
+send DEL cart:9 > /dev/null
+send LPUSH cart:9 apple > /dev/null
+send SET order:1 shipped > /dev/null
+wait_sessions
+bcast=$(cat "$LOG_DIR/bcast.out")
+check "Writes under a prefix are broadcast without reads" "$(printf '$6\nuser:3')" "$bcast"
+check "Any type of write counts" "$(printf '$6\ncart:9')" "$bcast"
+check_absent "Other prefixes are left out" "order:1" "$bcast"
+check "PREFIX needs BCAST" "PREFIX option requires BCAST" "$(send CLIENT TRACKING ON PREFIX user:)"
+check "Prefixes may not overlap" "overlaps" "$(send CLIENT TRACKING ON BCAST PREFIX a PREFIX ab)"
+echo ""
+
+echo "--- OPTIN and NOLOOP ---"
+session "$LOG_DIR/optin.out" "$(request HELLO 3)$(request CLIENT TRACKING ON OPTIN)$(request GET user:1)\
+$(request CLIENT CACHING YES)$(request GET user:2)$(request CLIENT TRACKINGINFO)"
+send SET user:1 gina > /dev/null
+send SET user:2 hank > /dev/null
+wait_sessions
+optin=$(cat "$LOG_DIR/optin.out")
+check "OPTIN tracks reads after CLIENT CACHING YES" "$(printf 'invalidate\n*1\n$6\nuser:2')" "$optin"
+check_absent "OPTIN skips other reads" "$(printf '$6\nuser:1\n>')" "$optin"
+check "CLIENT TRACKINGINFO" "$(printf 'flags\n*2\n$2\non\n$5\noptin')" "$optin"
+check "CLIENT CACHING needs OPTIN or OPTOUT" "CLIENT CACHING can be called only" "$(send CLIENT CACHING YES)"
+
+session "$LOG_DIR/noloop.out" "$(request HELLO 3)$(request CLIENT TRACKING ON BCAST NOLOOP)$(request SET mine 1)"
+send SET theirs 1 > /dev/null
+wait_sessions
+noloop=$(cat "$LOG_DIR/noloop.out")
+check "NOLOOP still hears about other clients' writes" "$(printf '$6\ntheirs')" "$noloop"
+check_absent "NOLOOP skips the client's own writes" "mine" "$noloop"
+echo ""
+
+echo "--- REDIRECT ---"
+session "$LOG_DIR/listener.out" "$(request SUBSCRIBE __redis__:invalidate)"
+listener_id=$(send CLIENT LIST | grep "cmd=subscribe" | sed 's/^id=\([0-9]*\) .*/\1/')
+session "$LOG_DIR/redirect.out" "$(request CLIENT TRACKING ON REDIRECT "$listener_id")$(request GET user:1)\
+$(request CLIENT GETREDIR)"
+send SET user:1 ivan > /dev/null
+wait_sessions
+check "RESP2 invalidations go to the redirect as messages" \
+    "$(printf 'message\n$20\n__redis__:invalidate\n*1\n$6\nuser:1')" "$(cat "$LOG_DIR/listener.out")"
+check "CLIENT GETREDIR" ":$listener_id" "$(cat "$LOG_DIR/redirect.out")"
+check "Redirects must exist" "does not exist" "$(send CLIENT TRACKING ON REDIRECT 999999)"
+echo ""
+
+finish "client tracking"
@@ -0,0 +1,90 @@
+# Helpers shared by the test_*.sh suites. A suite sets HOST and PORT, sources this
+# file and starts its servers in the background; they're stopped when it exits.
+# Servers log to $LOG_DIR, which is removed on exit too.
+
+LOG_DIR=$(mktemp -d)
+FAILED=0
+
+cleanup() {
+    local pids
+    pids=$(jobs -p)
+    if [ -n "$pids" ]; then
+        kill $pids 2>/dev/null
+        wait $pids 2>/dev/null
+    fi
+    rm -rf "$LOG_DIR"
+}
+trap cleanup EXIT
+
+# Helper function to check a result
+check() {
+    local description=$1
+    local expected=$2
+    local actual=$3
+
+    if [[ "$actual" == *"$expected"* ]]; then
+        echo "PASS: $description"
+    else
+        echo "FAIL: $description (expected '$expected', got '$actual')"
+        FAILED=1
+    fi
+}
+
+# Helper function to check that a result doesn't contain something
+check_absent() {
+    local description=$1
+    local unexpected=$2
+    local actual=$3
+
+    if [[ "$actual" == *"$unexpected"* ]]; then
+        echo "FAIL: $description (did not expect '$unexpected' in '$actual')"
+        FAILED=1
+    else
+        echo "PASS: $description"
+    fi
+}
+
+# Encodes one command as a RESP array, so arguments may contain spaces
+request() {
+    local out="*$#\r\n"
+    for arg in "$@"; do
+        out+="\$${#arg}\r\n$arg\r\n"
+    done
+    printf "%s" "$out"
+}
+
+# Helper function to send one command to the server on a port
+send_to() {
+    local port=$1
+    shift
+    printf "%b" "$(request "$@")" | nc -w "${NC_WAIT:-1}" $HOST "$port" | tr -d '\r'
+}
+
+# Helper function to send one command to the server on $PORT
+send() {
+    send_to "$PORT" "$@"
+}
+
+# Helper function to send raw bytes, for requests send can't produce
+send_raw() {
+    local port=$1
+    local data=$2
+    printf "%b" "$data" | nc -w "${NC_WAIT:-1}" $HOST "$port" | tr -d '\r'
+}
+
+# Helper function to send one or more inline commands (separated by \n)
+send_inline() {
+    send_raw "$1" "$2\n"
+}
+
+# Prints the suite's result and fails the script if any check failed
+finish() {
+    local name=${1:+$1 }
+
+    if [ $FAILED -eq 0 ]; then
+        echo "=== All ${name}tests passed! ==="
+    else
+        echo "=== Some ${name}tests failed ==="
+        exit 1
+    fi
+}